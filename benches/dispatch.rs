@@ -0,0 +1,81 @@
+//! Measures how many instructions per second `Cpu::step` executes. Run
+//! with `cargo bench --features reference-dispatch` to also time the
+//! mnemonic dispatch `step` used before the dispatch table, on the same
+//! program, and print the two side by side.
+//!
+//! The figures depend on the machine and its load, so compare the two
+//! from one run rather than with figures from another machine.
+
+extern crate rs6502;
+
+use std::time::Instant;
+
+use rs6502::{Assembler, Cpu};
+
+const INSTRUCTIONS: u64 = 5_000_000;
+
+// A mix of loads, stores, arithmetic, stack operations and branches
+const PROGRAM: &str = "
+    START   LDX #$00
+    LOOP    LDA $0200,X
+            CLC
+            ADC #$01
+            STA $0200,X
+            PHA
+            PLA
+            ASL $10
+            INX
+            BNE LOOP
+            JSR SUB
+            JMP START
+    SUB     LDY $10
+            RTS
+";
+
+fn main() {
+    let mut assembler = Assembler::new();
+    let segments = assembler.assemble_string(PROGRAM, 0xC000).unwrap();
+
+    let table = time(Cpu::new(), &segments[0].code);
+    report("dispatch table", table);
+
+    #[cfg(feature = "reference-dispatch")]
+    {
+        let mut cpu = Cpu::new();
+        cpu.enable_mnemonic_dispatch();
+        let mnemonic = time(cpu, &segments[0].code);
+        report("mnemonic dispatch", mnemonic);
+
+        // Both ran the same instructions, so they must agree on the cycles
+        assert_eq!(table.1, mnemonic.1);
+        println!("The dispatch table is {:.1}x as fast", mnemonic.0 / table.0);
+    }
+
+    #[cfg(not(feature = "reference-dispatch"))]
+    println!("Run with --features reference-dispatch to compare with the mnemonic dispatch");
+}
+
+/// Runs the program for `INSTRUCTIONS` steps, returning the seconds it
+/// took and the cycles it counted
+fn time(mut cpu: Cpu, code: &[u8]) -> (f64, u64) {
+    cpu.load(code, None).unwrap();
+    cpu.reset();
+
+    let start = Instant::now();
+    let mut cycles = 0u64;
+    for _ in 0..INSTRUCTIONS {
+        cycles += cpu.step().unwrap() as u64;
+    }
+    let elapsed = start.elapsed();
+
+    (elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0, cycles)
+}
+
+fn report(name: &str, (seconds, cycles): (f64, u64)) {
+    println!("{:>17}: {} instructions ({} cycles) in {:.3}s: {:.2} million instructions per second",
+             name,
+             INSTRUCTIONS,
+             cycles,
+             seconds,
+             INSTRUCTIONS as f64 / seconds / 1_000_000.0);
+}
@@ -1,20 +1,31 @@
 use std;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::path::Path;
 
-use ::opcodes::{AddressingMode, OpCode};
+use ::control_flow::{self, UnreachableRegion};
+use ::cpu::Symbols;
+use ::opcodes::{AddressingMode, CpuVariant};
+use assembler::directive::{DirectiveHandler, DirectiveOutput};
+use assembler::export;
+use assembler::ines::{INesHeader, INES_HEADER_SIZE};
 use assembler::lexer::{Lexer, LexerError};
+use assembler::map;
+use assembler::options::AssemblerOptions;
 use assembler::parser::{Parser, ParserError};
+use assembler::peephole::{self, Optimization};
+use assembler::pseudo::{self, Expansion};
+use assembler::relocation::RelocatableCode;
 use assembler::token::{LexerToken, ParserToken};
+use assembler::xref::{self, CrossReference};
 
 #[derive(Debug, PartialEq)]
 pub struct Label(u16);
 
 #[derive(Debug)]
 pub struct AssemblerError {
-    message: String,
+    pub message: String,
 }
 
 impl AssemblerError {
@@ -24,10 +35,61 @@ impl AssemblerError {
         AssemblerError::from(format!("Unknown label: '{}'", label))
     }
 
-    fn relative_offset_too_large<S>(context: S) -> AssemblerError
+    fn relative_offset_too_large(label: &str, branch_addr: u16, label_addr: u16) -> AssemblerError {
+        AssemblerError::from(format!("Branch too far: branch at {:04X} to {} at {:04X}",
+                                     branch_addr,
+                                     label,
+                                     label_addr))
+    }
+
+    fn branch_leaves_segment(label: &str, branch_addr: u16, label_addr: u16) -> AssemblerError {
+        AssemblerError::from(format!("Branch at {:04X} to {} at {:04X} leaves its segment",
+                                     branch_addr,
+                                     label,
+                                     label_addr))
+    }
+
+    fn code_past_end_of_memory(addr: u32) -> AssemblerError {
+        AssemblerError::from(format!("Code at {:04X} runs past FFFF", addr))
+    }
+
+    fn label_past_end_of_memory(label: &str) -> AssemblerError {
+        AssemblerError::from(format!("Label {} is past the end of memory", label))
+    }
+
+    fn directive_failed<S>(directive: &str, reason: S) -> AssemblerError
         where S: Into<String> + Display
     {
-        AssemblerError::from(format!("Branch too far: {}", context))
+        AssemblerError::from(format!("Directive .{} failed: {}", directive, reason))
+    }
+
+    fn segment_too_large(segment: &CodeSegment, size: u16) -> AssemblerError {
+        AssemblerError::from(format!("Segment at {:04X} is {} bytes, larger than its declared size of {} bytes",
+                                     segment.address,
+                                     segment.code.len(),
+                                     size))
+    }
+
+    fn org_in_relocatable_code() -> AssemblerError {
+        AssemblerError::from("Relocatable code cannot contain .ORG directives".to_string())
+    }
+
+    fn no_prg_banks() -> AssemblerError {
+        AssemblerError::from("An iNES file needs at least one bank of PRG ROM".to_string())
+    }
+
+    fn segment_outside_prg_rom(segment: &CodeSegment, prg_start: u16) -> AssemblerError {
+        AssemblerError::from(format!("Segment at {:04X} is outside the PRG ROM at {:04X}-FFFF",
+                                     segment.address,
+                                     prg_start))
+    }
+
+    fn chr_too_large(size: usize, chr_size: usize) -> AssemblerError {
+        AssemblerError::from(format!("CHR data is {} bytes, larger than the {} bytes of CHR ROM", size, chr_size))
+    }
+
+    fn binary_too_large(start: u16) -> AssemblerError {
+        AssemblerError::from(format!("Code from {:04X} to FFFF is too large for an Apple II binary file", start))
     }
 }
 
@@ -49,6 +111,37 @@ impl From<ParserError> for AssemblerError {
     }
 }
 
+/// A non-fatal diagnostic raised while assembling
+#[derive(Debug, PartialEq)]
+pub struct AssemblerWarning {
+    pub message: String,
+}
+
+impl AssemblerWarning {
+    fn branch_crosses_page<S>(label: S, branch_addr: u16, label_addr: u16) -> AssemblerWarning
+        where S: Into<String> + Display
+    {
+        AssemblerWarning {
+            message: format!("Branch at {:04X} to {} at {:04X} crosses a page boundary",
+                             branch_addr,
+                             label,
+                             label_addr),
+        }
+    }
+
+    fn loop_straddles_page<S>(label: S, label_addr: u16, branch_addr: u16) -> AssemblerWarning
+        where S: Into<String> + Display
+    {
+        AssemblerWarning {
+            message: format!("Loop {} from {:04X} to {:04X} straddles a page boundary",
+                             label,
+                             label_addr,
+                             branch_addr),
+        }
+    }
+}
+
+/// Assembled code and the address it is loaded at
 #[derive(Debug)]
 pub struct CodeSegment {
     pub address: u16,
@@ -56,17 +149,553 @@ pub struct CodeSegment {
 }
 
 pub struct Assembler {
+    options: AssemblerOptions,
     symbol_table: HashMap<String, Label>,
+    line_addresses: BTreeMap<u32, u16>,
+    directives: HashMap<String, DirectiveHandler>,
+    cross_references: Vec<CrossReference>,
+    page_crossing_warnings: bool,
+    warnings: Vec<AssemblerWarning>,
+    relocations: Vec<u16>,
+    peephole_optimizations: bool,
+    optimizations: Vec<Optimization>,
+    pseudo_ops: bool,
+    expansions: Vec<Expansion>,
+    variant: Option<CpuVariant>,
+}
+
+impl Default for Assembler {
+    fn default() -> Assembler {
+        Assembler::new()
+    }
 }
 
 impl Assembler {
     pub fn new() -> Assembler {
-        Assembler { symbol_table: HashMap::new() }
+        Assembler::with_options(AssemblerOptions::new())
+    }
+
+    pub fn with_options(options: AssemblerOptions) -> Assembler {
+        Assembler {
+            options,
+            symbol_table: HashMap::new(),
+            line_addresses: BTreeMap::new(),
+            directives: HashMap::new(),
+            cross_references: Vec::new(),
+            page_crossing_warnings: false,
+            warnings: Vec::new(),
+            relocations: Vec::new(),
+            peephole_optimizations: false,
+            optimizations: Vec::new(),
+            pseudo_ops: false,
+            expansions: Vec::new(),
+            variant: None,
+        }
+    }
+
+    pub fn options(&self) -> &AssemblerOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: AssemblerOptions) {
+        self.options = options;
+    }
+
+    /// Warns about branches whose target is across a page boundary, which
+    /// costs an extra cycle each time the branch is taken. Backward
+    /// branches are reported as loops straddling a page.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.enable_page_crossing_warnings();
+    /// assembler.assemble_string("
+    ///     .ORG $C0FC
+    ///     LOOP DEX
+    ///          NOP
+    ///          NOP
+    ///          BNE LOOP
+    /// ", None).unwrap();
+    ///
+    /// assert_eq!("Loop LOOP from C0FC to C0FF straddles a page boundary",
+    ///            assembler.warnings()[0].message);
+    /// ```
+    pub fn enable_page_crossing_warnings(&mut self) {
+        self.page_crossing_warnings = true;
+    }
+
+    pub fn disable_page_crossing_warnings(&mut self) {
+        self.page_crossing_warnings = false;
+    }
+
+    /// Returns the warnings raised while assembling the most recent source
+    pub fn warnings(&self) -> &[AssemblerWarning] {
+        &self.warnings
+    }
+
+    /// Removes instructions that have no effect before the code is
+    /// assembled: a compare with zero straight after a load of the same
+    /// register (assuming the carry it sets is not relied on), a flag
+    /// instruction that repeats the one before it, and a `JMP` to the
+    /// next instruction. Instructions that a label points at are left
+    /// alone, and every removal is reported by `optimizations`.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.enable_peephole_optimizations();
+    /// let segments = assembler.assemble_string("
+    ///     CLC
+    ///     CLC
+    ///     ADC #$01
+    /// ", None).unwrap();
+    ///
+    /// assert_eq!(vec![0x18, 0x69, 0x01], segments[0].code);
+    /// assert_eq!("Removed repeated CLC at 0001", assembler.optimizations()[0].message);
+    /// ```
+    pub fn enable_peephole_optimizations(&mut self) {
+        self.peephole_optimizations = true;
+    }
+
+    pub fn disable_peephole_optimizations(&mut self) {
+        self.peephole_optimizations = false;
+    }
+
+    /// Returns the changes the peephole optimizer made to the most recent
+    /// source
+    pub fn optimizations(&self) -> &[Optimization] {
+        &self.optimizations
+    }
+
+    /// Accepts the 16-bit pseudo-instructions `LDAX` and `STAX`. `LDAX`
+    /// loads a value into A (low byte) and X (high byte), from an immediate
+    /// value or an address and the one after it, and `STAX` stores A and X
+    /// the same way. Each is expanded into the pair of real instructions it
+    /// stands for, which `expansion_listing` shows.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.enable_pseudo_ops();
+    /// let segments = assembler.assemble_string("
+    ///     LDAX #$1234
+    ///     STAX $10
+    /// ", None).unwrap();
+    ///
+    /// assert_eq!(vec![0xA9, 0x34, 0xA2, 0x12, 0x85, 0x10, 0x86, 0x11], segments[0].code);
+    /// ```
+    pub fn enable_pseudo_ops(&mut self) {
+        self.pseudo_ops = true;
+    }
+
+    pub fn disable_pseudo_ops(&mut self) {
+        self.pseudo_ops = false;
     }
 
+    /// Assembles for the given processor, rejecting the instructions it
+    /// doesn't have. Without a variant the 65C02 instructions are accepted
+    /// alongside the NMOS ones.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Assembler, CpuVariant};
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.set_variant(CpuVariant::Nmos6502);
+    ///
+    /// let error = assembler.assemble_string("LDA #$01\nSTZ $10", None).unwrap_err();
+    /// assert_eq!("Instruction 'STZ' is not available on the selected CPU (NMOS 6502). Line 2",
+    ///            error.message);
+    /// ```
+    pub fn set_variant(&mut self, variant: CpuVariant) {
+        self.variant = Some(variant);
+    }
+
+    pub fn clear_variant(&mut self) {
+        self.variant = None;
+    }
+
+    /// Returns the processor being assembled for, if one is set
+    pub fn variant(&self) -> Option<CpuVariant> {
+        self.variant
+    }
+
+    /// Returns every pseudo-instruction expanded in the most recent source
+    pub fn expansions(&self) -> &[Expansion] {
+        &self.expansions
+    }
+
+    /// Returns a printable section listing each pseudo-instruction in the
+    /// most recent source alongside the instructions it was expanded into
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.enable_pseudo_ops();
+    /// assembler.assemble_string("STAX $0200", None).unwrap();
+    ///
+    /// assert_eq!("LINE  PSEUDO-OP   EXPANSION\n\
+    ///             \x20  1  STAX $0200  STA $0200\n\
+    ///             \x20                 STX $0201\n",
+    ///            assembler.expansion_listing());
+    /// ```
+    pub fn expansion_listing(&self) -> String {
+        pseudo::format_expansions(&self.expansions)
+    }
+
+    /// Registers a handler for a custom directive, allowing `.NAME` to be
+    /// used in source code. The handler receives the remaining tokens on the
+    /// line and the current location counter, and returns the bytes to emit
+    /// at that location along with any symbols it defines.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Assembler, DirectiveOutput};
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.register_directive("HERE", |_, addr| {
+    ///     Ok(DirectiveOutput::with_bytes(vec![addr as u8, (addr >> 8) as u8]))
+    /// });
+    ///
+    /// let segments = assembler.assemble_string(".ORG $C000\n.HERE", None).unwrap();
+    ///
+    /// assert_eq!(&[0x00, 0xC0], &segments[0].code[..]);
+    /// ```
+    pub fn register_directive<S, F>(&mut self, name: S, handler: F)
+        where S: Into<String>,
+              F: Fn(&[LexerToken], u16) -> Result<DirectiveOutput, String> + 'static
+    {
+        self.directives.insert(name.into().to_uppercase(), Box::new(handler));
+    }
+
+    /// Returns, for every symbol in the most recently assembled source, the
+    /// line it was defined on and every line that references it
+    pub fn cross_references(&self) -> &[CrossReference] {
+        &self.cross_references
+    }
+
+    /// Returns a printable cross-reference section for the most recently
+    /// assembled source
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.assemble_string("MAIN\nJMP MAIN", None).unwrap();
+    ///
+    /// assert_eq!("SYMBOL  DEFINED  REFERENCED\n\
+    ///             MAIN          1  2\n",
+    ///            assembler.cross_reference_listing());
+    /// ```
+    pub fn cross_reference_listing(&self) -> String {
+        xref::format_cross_references(&self.cross_references)
+    }
+
+    /// Returns the address of a label in the assembled source, so that
+    /// breakpoints can be set by name. Looking the label up again after
+    /// re-assembling gives its new address.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.assemble_string("
+    ///     MAIN      LDX #$05
+    ///     MAIN_LOOP DEX
+    ///               BNE MAIN_LOOP
+    /// ", 0xC000).unwrap();
+    ///
+    /// assert_eq!(Some(0xC002), assembler.label_address("MAIN_LOOP"));
+    /// assert_eq!(None, assembler.label_address("MISSING"));
+    /// ```
+    pub fn label_address(&self, label: &str) -> Option<u16> {
+        self.symbol_table.get(label).map(|&Label(addr)| addr)
+    }
+
+    /// Returns the address of the first instruction assembled from a line
+    /// of the source, or None if no instruction came from it. Instructions
+    /// from an `.INCLUDE`d file count as being on the line of the
+    /// `.INCLUDE`.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.assemble_string("
+    ///     MAIN      LDX #$05
+    ///     MAIN_LOOP DEX
+    ///               BNE MAIN_LOOP
+    /// ", 0xC000).unwrap();
+    ///
+    /// assert_eq!(Some(0xC002), assembler.line_address(3));
+    /// assert_eq!(None, assembler.line_address(1));
+    /// ```
+    pub fn line_address(&self, line: u32) -> Option<u16> {
+        self.line_addresses.get(&line).cloned()
+    }
+
+    /// Returns a Rust source file declaring a `pub const` for the address
+    /// of every label, so host code that pokes guest memory can share one
+    /// source of truth with the assembly
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// assembler.assemble_string("
+    ///     MAIN LDX #$05
+    ///     LOOP DEX
+    ///          BNE LOOP
+    /// ", 0xC000).unwrap();
+    ///
+    /// assert_eq!("// Generated from assembled 6502 source. Do not edit.\n\n\
+    ///             pub const LOOP: u16 = 0xC002;\n\
+    ///             pub const MAIN: u16 = 0xC000;\n",
+    ///            assembler.rust_constants());
+    /// ```
+    pub fn rust_constants(&self) -> String {
+        let symbols: Vec<(&str, u16)> = self.symbol_table
+            .iter()
+            .map(|(symbol, &Label(addr))| (&symbol[..], addr))
+            .collect();
+
+        export::format_rust_constants(&symbols)
+    }
+
+    /// Returns a map of where assembled segments ended up, like a linker's
+    /// map file: each segment's address range and size, the gaps between
+    /// segments and every label of the most recently assembled source, all
+    /// sorted by address
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let segments = assembler.assemble_string("
+    ///     .ORG $C000
+    ///     MAIN JSR PRINT
+    ///          RTS
+    ///     .ORG $C010
+    ///     PRINT RTS
+    /// ", None).unwrap();
+    ///
+    /// assert_eq!("SEGMENTS\n\
+    ///             $C000-$C003  4 bytes\n\
+    ///             $C010-$C010  1 byte\n\
+    ///             \n\
+    ///             GAPS\n\
+    ///             $C004-$C00F  12 bytes\n\
+    ///             \n\
+    ///             LABELS\n\
+    ///             $C000  MAIN\n\
+    ///             $C010  PRINT\n",
+    ///            assembler.map_file(&segments));
+    /// ```
+    pub fn map_file(&self, segments: &[CodeSegment]) -> String {
+        let symbols: Vec<(&str, u16)> = self.symbol_table
+            .iter()
+            .map(|(symbol, &Label(addr))| (&symbol[..], addr))
+            .collect();
+
+        map::format_map(segments, &symbols)
+    }
+
+    /// Returns the address of every label as Symbols, for naming addresses
+    /// in the Cpu's trace lines and halt reports. When labels share an
+    /// address, the first in alphabetical order names it.
+    pub fn symbols(&self) -> Symbols {
+        let mut labels: Vec<(&String, &Label)> = self.symbol_table.iter().collect();
+        labels.sort_by_key(|&(label, _)| label);
+
+        let mut symbols = Symbols::new();
+        for (label, &Label(addr)) in labels {
+            symbols.insert(label.clone(), addr);
+        }
+
+        symbols
+    }
+
+    /// Builds an Apple II binary (`B`) file from assembled segments, ready to
+    /// be written to a disk image by an external tool. The file holds the
+    /// load address and length followed by one contiguous block of code
+    /// running from the lowest segment address to the end of the highest
+    /// segment. Gaps between segments are filled with zeroes.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let segments = assembler.assemble_string("
+    ///     .ORG $0300
+    ///     JSR $FDED
+    ///     RTS
+    /// ", None).unwrap();
+    ///
+    /// assert_eq!(vec![0x00, 0x03, 0x04, 0x00, 0x20, 0xED, 0xFD, 0x60],
+    ///            assembler.apple_binary(&segments).unwrap());
+    /// ```
+    pub fn apple_binary(&self, segments: &[CodeSegment]) -> Result<Vec<u8>, AssemblerError> {
+        let segments: Vec<&CodeSegment> = segments.iter().filter(|s| !s.code.is_empty()).collect();
+        let start = segments.iter().map(|s| s.address as usize).min().unwrap_or(0);
+        let end = segments.iter().map(|s| s.address as usize + s.code.len()).max().unwrap_or(0);
+
+        if end - start > 0xFFFF {
+            return Err(AssemblerError::binary_too_large(start as u16));
+        }
+
+        let mut code = vec![0x00; end - start];
+        for segment in segments {
+            let offset = segment.address as usize - start;
+            code[offset..offset + segment.code.len()].copy_from_slice(&segment.code);
+        }
+
+        Ok(export::format_apple_binary(start as u16, &code))
+    }
+
+    /// Builds an iNES (`.nes`) file from assembled PRG segments and the
+    /// caller's CHR data, ready to run in an NES emulator. The PRG ROM the
+    /// segments are placed in ends at `$FFFF`, so with one bank it starts at
+    /// `$C000` and with two or more at `$8000`. When there are more than two
+    /// banks, as with bank switching mappers, the segments fill the last two.
+    /// Unused PRG and CHR ROM is zero filled.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Assembler, INesHeader, Mirroring};
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let segments = assembler.assemble_string("
+    ///     .ORG $C000
+    ///     MAIN JMP MAIN
+    ///     .ORG $FFFA
+    ///     .BYTE #$00, #$C0, #$00, #$C0, #$00, #$C0
+    /// ", None).unwrap();
+    ///
+    /// let header = INesHeader {
+    ///     chr_banks: 1,
+    ///     mirroring: Mirroring::Vertical,
+    ///     ..Default::default()
+    /// };
+    /// let rom = assembler.ines(&segments, &header, &[]).unwrap();
+    ///
+    /// assert_eq!(16 + 0x4000 + 0x2000, rom.len());
+    /// assert_eq!(&[0x4C, 0x00, 0xC0], &rom[16..19]);
+    /// ```
+    pub fn ines(&self, segments: &[CodeSegment], header: &INesHeader, chr: &[u8]) -> Result<Vec<u8>, AssemblerError> {
+        if header.prg_banks == 0 {
+            return Err(AssemblerError::no_prg_banks());
+        }
+
+        if chr.len() > header.chr_size() {
+            return Err(AssemblerError::chr_too_large(chr.len(), header.chr_size()));
+        }
+
+        // The segments fill the last 32kb of PRG ROM at most, which is all
+        // the Cpu can see at once
+        let window = header.prg_size().min(0x8000);
+        let window_start = 0x10000 - window;
+        let mut prg = vec![0x00; header.prg_size()];
+        let window_offset = prg.len() - window;
+
+        for segment in segments.iter().filter(|s| !s.code.is_empty()) {
+            let start = segment.address as usize;
+            if start < window_start {
+                return Err(AssemblerError::segment_outside_prg_rom(segment, window_start as u16));
+            }
+
+            let offset = window_offset + start - window_start;
+            prg[offset..offset + segment.code.len()].copy_from_slice(&segment.code);
+        }
+
+        let mut result = header.to_bytes().to_vec();
+        result.extend(prg);
+        result.extend_from_slice(chr);
+        result.resize(INES_HEADER_SIZE + header.prg_size() + header.chr_size(), 0x00);
+
+        Ok(result)
+    }
+
+    /// Returns the regions of the assembled segments that can never be
+    /// executed when starting from the given entry point labels
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Assembler, UnreachableRegion};
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let segments = assembler.assemble_string("
+    ///     MAIN LDA #$20
+    ///          RTS
+    ///          LDA #$40
+    /// ", 0xC000).unwrap();
+    ///
+    /// assert_eq!(vec![UnreachableRegion { start: 0xC003, end: 0xC004 }],
+    ///            assembler.unreachable_code(&segments, &["MAIN"]).unwrap());
+    /// ```
+    pub fn unreachable_code(&self,
+                            segments: &[CodeSegment],
+                            entry_points: &[&str])
+                            -> Result<Vec<UnreachableRegion>, AssemblerError> {
+        let mut addresses = Vec::new();
+        for entry_point in entry_points {
+            if let Some(&Label(addr)) = self.symbol_table.get(*entry_point) {
+                addresses.push(addr);
+            } else {
+                return Err(AssemblerError::unknown_label(*entry_point));
+            }
+        }
+
+        Ok(control_flow::find_unreachable_code(segments, &addresses))
+    }
+
+    fn create_parser(&self) -> Parser {
+        let mut parser = Parser::with_custom_directives(self.directives.keys().cloned().collect());
+        if self.pseudo_ops {
+            parser.enable_pseudo_ops();
+        }
+        if let Some(variant) = self.variant {
+            parser.set_variant(variant);
+        }
+
+        parser
+    }
+
+    /// Assembles source code into one segment per `.ORG` directive. Code
+    /// before the first `.ORG` is placed at `origin`, or at the origin in
+    /// the options if that is `None`, and forms a leading segment of its
+    /// own. Each segment's `address` is where it should be loaded.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let segments = assembler.assemble_string("
+    ///     NOP
+    ///     .ORG $E000
+    ///     NOP
+    /// ", 0xC000).unwrap();
+    ///
+    /// assert_eq!(0xC000, segments[0].address);
+    /// assert_eq!(0xE000, segments[1].address);
+    /// ```
     pub fn assemble_string<S, O>(&mut self,
                                  code: S,
-                                 offset: O)
+                                 origin: O)
                                  -> Result<Vec<CodeSegment>, AssemblerError>
         where S: Into<String>,
               O: Into<Option<u16>>
@@ -74,62 +703,120 @@ impl Assembler {
         let code = code.into();
         let mut lexer = Lexer::new();
         let tokens = lexer.lex_string(code)?;
-        let mut parser = Parser::new();
+        let mut parser = self.create_parser();
         let tokens = parser.parse(tokens)?;
+        self.cross_references = parser.cross_references();
+        self.expansions = parser.expansions();
+        let lines = Self::source_lines(&lexer, &parser);
 
-        Ok(self.assemble(tokens, offset)?)
+        self.assemble(tokens, lines, origin)
     }
 
+    /// Assembles a source file, placing code before the first `.ORG` the
+    /// same way as `assemble_string`
     pub fn assemble_file<P, O>(&mut self,
                                path: P,
-                               offset: O)
+                               origin: O)
                                -> Result<Vec<CodeSegment>, AssemblerError>
         where P: AsRef<Path>,
               O: Into<Option<u16>>
     {
         let mut lexer = Lexer::new();
         let tokens = lexer.lex_file(path)?;
-        let mut parser = Parser::new();
+        let mut parser = self.create_parser();
         let tokens = parser.parse(tokens)?;
+        self.cross_references = parser.cross_references();
+        self.expansions = parser.expansions();
+        let lines = Self::source_lines(&lexer, &parser);
+
+        self.assemble(tokens, lines, origin)
+    }
+
+    /// Assembles code that can be loaded at any address. The code is
+    /// assembled as though it starts at `$0000` and every label address it
+    /// contains is listed in the relocation table, so it cannot contain any
+    /// `.ORG` directives.
+    pub fn assemble_relocatable<S>(&mut self, code: S) -> Result<RelocatableCode, AssemblerError>
+        where S: Into<String>
+    {
+        let mut segments = self.assemble_string(code, 0)?;
+        if segments.len() > 1 || segments[0].address != 0 {
+            return Err(AssemblerError::org_in_relocatable_code());
+        }
 
-        Ok(self.assemble(tokens, offset)?)
+        Ok(RelocatableCode {
+            code: segments.remove(0).code,
+            relocations: self.relocations.clone(),
+        })
+    }
+
+    /// Works out the source line each parsed token came from
+    fn source_lines(lexer: &Lexer, parser: &Parser) -> Vec<u32> {
+        parser.token_lines().iter().map(|&line| lexer.source_lines()[line as usize - 1]).collect()
     }
 
     fn assemble<O>(&mut self,
                    tokens: Vec<ParserToken>,
-                   offset: O)
+                   lines: Vec<u32>,
+                   origin: O)
                    -> Result<Vec<CodeSegment>, AssemblerError>
         where O: Into<Option<u16>>
     {
-        let mut addr: u16 = offset.into().unwrap_or(0);
+        let origin = origin.into().unwrap_or(self.options.origin);
+        self.warnings.clear();
+        self.relocations.clear();
+        self.line_addresses.clear();
+
+        // Expand any custom directives into raw bytes before anything
+        // else, so that labels following them are indexed correctly. Each
+        // directive becomes one token, so the lines still line up.
+        let tokens = self.expand_custom_directives(tokens, origin)?;
+
+        self.optimizations.clear();
+        let (tokens, lines) = if self.peephole_optimizations {
+            let (tokens, lines, optimizations) = peephole::optimize(tokens, &lines, origin);
+            self.optimizations = optimizations;
+            (tokens, lines)
+        } else {
+            (tokens, lines)
+        };
 
         // First, index the labels so we have addresses for them
-        self.index_labels(&tokens, addr);
+        let labels = self.index_labels(&tokens, origin);
 
         // Now assemble the code
         let mut result = Vec::new();
         let mut last_addressing_mode = AddressingMode::Absolute;
+        let mut checksums = Vec::new();
+        let mut segment_size = None;
         let mut current_segment = CodeSegment {
-            address: addr,
+            address: origin,
             code: Vec::new(),
         };
 
-        for token in tokens {
+        // The location counter is wider than an address so that code
+        // running past the end of memory can be caught
+        let mut addr = origin as u32;
+        let mut segment_index = 0;
+
+        for (token, line) in tokens.into_iter().zip(lines) {
             // Push an opcode into the output and increment our address
             // offset
             if let ParserToken::OpCode(opcode) = token {
+                self.line_addresses.entry(line).or_insert(addr as u16);
                 current_segment.code.push(opcode.code);
-                addr += opcode.length as u16;
+                addr = Self::advance(addr, opcode.length as usize)?;
                 last_addressing_mode = opcode.mode;
             } else if let ParserToken::OrgDirective(org_addr) = token {
-                if current_segment.code.len() > 0 {
-                    result.push(current_segment);
+                if !current_segment.code.is_empty() || segment_size.is_some() {
+                    result.push(Self::pad_segment(current_segment, segment_size.take())?);
                 }
                 current_segment = CodeSegment {
                     address: org_addr,
                     code: Vec::new(),
                 };
-                addr = org_addr;
+                addr = org_addr as u32;
+                segment_index += 1;
             } else if let ParserToken::RawByte(byte) = token {
                 // Push raw bytes directly into the output
                 current_segment.code.push(byte);
@@ -138,30 +825,60 @@ impl Assembler {
                 for b in &bytes {
                     current_segment.code.push(*b);
                 }
+                addr = Self::advance(addr, bytes.len())?;
+            } else if let ParserToken::SegmentSize(size, fill) = token {
+                segment_size = Some((size, fill));
+            } else if let ParserToken::Checksum(algorithm, start, end) = token {
+                // Reserve room for the checksum - it can only be calculated
+                // once the rest of the image has been assembled
+                checksums.push((result.len(), current_segment.code.len(), algorithm, start, end));
+                for _ in 0..algorithm.size() {
+                    current_segment.code.push(0x00);
+                }
+                addr = Self::advance(addr, algorithm.size() as usize)?;
             } else if let ParserToken::LabelArg(ref label) = token {
                 // Labels as arguments should be in the symbol table, look
                 // it up and calculate the address direction/location
+                if let Some(&(_, label_addr)) = labels.get(label) {
+                    if label_addr > 0xFFFF {
+                        return Err(AssemblerError::label_past_end_of_memory(label));
+                    }
+                }
+
                 if let Some(&Label(label_addr)) = self.symbol_table.get(label) {
-                    if last_addressing_mode == AddressingMode::Absolute {
+                    if last_addressing_mode == AddressingMode::Absolute ||
+                       last_addressing_mode == AddressingMode::Indirect ||
+                       last_addressing_mode == AddressingMode::AbsoluteIndexedIndirect {
                         let low_byte = (label_addr & 0xFF) as u8;
                         let high_byte = ((label_addr >> 8) & 0xFF) as u8;
 
+                        // Label addresses move with the code when it is relocated
+                        self.relocations.push(current_segment.code.len() as u16);
                         current_segment.code.push(low_byte);
                         current_segment.code.push(high_byte);
                     } else {
-                        // Its relative.. lets generate a relative branch
-                        if addr > label_addr {
-                            let distance = (label_addr as i16 - addr as i16) as i8;
-                            if distance < -128 || distance > 127 {
-                                return Err(AssemblerError::relative_offset_too_large(format!("Attempted jump to {} at {:04X}", label, addr)));
-                            }
-                            current_segment.code.push(distance as u8);
-                        } else {
-                            let distance = label_addr - addr;
-                            if distance > 127 {
-                                return Err(AssemblerError::relative_offset_too_large(format!("Attempted jump to {} at {:04X}", label, addr)));
+                        // Its relative.. lets generate a relative branch,
+                        // which can't leave the segment it is in
+                        let branch_addr = (addr - 2) as u16;
+                        if let Some(&(label_segment, _)) = labels.get(label) {
+                            if label_segment != segment_index {
+                                return Err(AssemblerError::branch_leaves_segment(label, branch_addr, label_addr));
                             }
-                            current_segment.code.push(distance as u8);
+                        }
+
+                        let distance = label_addr as i32 - addr as i32;
+                        if !(-128..=127).contains(&distance) {
+                            return Err(AssemblerError::relative_offset_too_large(label, branch_addr, label_addr));
+                        }
+                        current_segment.code.push(distance as u8);
+
+                        // Taking a branch to another page costs an extra cycle
+                        if self.page_crossing_warnings && addr & 0xFF00 != label_addr as u32 & 0xFF00 {
+                            self.warnings.push(if label_addr <= branch_addr {
+                                AssemblerWarning::loop_straddles_page(label.clone(), label_addr, branch_addr)
+                            } else {
+                                AssemblerWarning::branch_crosses_page(label.clone(), branch_addr, label_addr)
+                            });
                         }
                     }
                 } else {
@@ -170,36 +887,193 @@ impl Assembler {
             }
         }
 
-        result.push(current_segment);
+        result.push(Self::pad_segment(current_segment, segment_size)?);
+
+        for (segment, offset, algorithm, start, end) in checksums {
+            let checksum = algorithm.calculate(&Self::image_bytes(&result, start, end));
+            for (i, b) in checksum.into_iter().enumerate() {
+                result[segment].code[offset + i] = b;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Pads a segment with its fill byte up to the size declared with `.SIZE`
+    fn pad_segment(mut segment: CodeSegment, size: Option<(u16, u8)>) -> Result<CodeSegment, AssemblerError> {
+        if let Some((size, fill)) = size {
+            if segment.code.len() > size as usize {
+                return Err(AssemblerError::segment_too_large(&segment, size));
+            }
+            segment.code.resize(size as usize, fill);
+        }
+
+        Ok(segment)
+    }
+
+    /// Collects the assembled bytes between two addresses (inclusive), in
+    /// address order. Addresses that no segment covers are skipped.
+    fn image_bytes(segments: &[CodeSegment], start: u16, end: u16) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for segment in segments {
+            for (i, b) in segment.code.iter().enumerate() {
+                let addr = segment.address as usize + i;
+                if addr >= start as usize && addr <= end as usize {
+                    bytes.push((addr, *b));
+                }
+            }
+        }
+        bytes.sort_by_key(|&(addr, _)| addr);
+
+        bytes.into_iter().map(|(_, b)| b).collect()
+    }
+
+    /// Runs the registered handler for each custom directive, replacing the
+    /// directive with the bytes it emits and storing any symbols it defines
+    fn expand_custom_directives(&mut self,
+                                tokens: Vec<ParserToken>,
+                                origin: u16)
+                                -> Result<Vec<ParserToken>, AssemblerError> {
+        let mut addr: u16 = origin;
+        let mut result = Vec::new();
+
+        for token in tokens {
+            if let ParserToken::CustomDirective(name, args) = token {
+                let output = {
+                    let handler = &self.directives[&name];
+                    handler(&args[..], addr)
+                        .map_err(|reason| AssemblerError::directive_failed(&name, reason))?
+                };
+
+                for (symbol, value) in output.symbols {
+                    self.symbol_table.insert(symbol, Label(value));
+                }
+
+                addr = addr.wrapping_add(output.bytes.len() as u16);
+                result.push(ParserToken::RawBytes(output.bytes));
+                continue;
+            }
+
+            if let ParserToken::OpCode(opcode) = token {
+                addr = addr.wrapping_add(opcode.length as u16);
+            } else if let ParserToken::OrgDirective(new_addr) = token {
+                addr = new_addr;
+            } else if let ParserToken::RawBytes(ref bytes) = token {
+                addr = addr.wrapping_add(bytes.len() as u16);
+            } else if let ParserToken::Checksum(algorithm, _, _) = token {
+                addr = addr.wrapping_add(algorithm.size());
+            }
+
+            result.push(token);
+        }
 
         Ok(result)
     }
 
     /// Stores all labels in the code in a Symbol table for lookup later
-    fn index_labels(&mut self, tokens: &[ParserToken], offset: u16) {
-        let mut addr: u16 = offset;
-        let mut last_addressing_mode = AddressingMode::Absolute;
+    /// Adds the address of every label to the symbol table, returning
+    /// the segment each label is in and its unwrapped address
+    fn index_labels(&mut self, tokens: &[ParserToken], origin: u16) -> HashMap<String, (usize, u32)> {
+        let mut addr = origin as u32;
+        let mut segment_index = 0;
+        let mut labels = HashMap::new();
 
         for token in tokens {
-            if let &ParserToken::Label(ref label) = token {
+            if let ParserToken::Label(label) = token {
                 // Insert a label with the specified memory address
                 // as its offset
-                self.symbol_table.insert(label.clone(), Label(addr));
+                self.symbol_table.insert(label.clone(), Label(addr as u16));
+                labels.insert(label.clone(), (segment_index, addr));
             } else if let &ParserToken::OpCode(opcode) = token {
                 // Add the length of this opcode to our
                 // address offset
-                addr += opcode.length as u16;
-                last_addressing_mode = opcode.mode;
+                addr += opcode.length as u32;
             } else if let &ParserToken::OrgDirective(new_addr) = token {
-                addr = new_addr
+                addr = new_addr as u32;
+                segment_index += 1;
+            } else if let ParserToken::RawBytes(bytes) = token {
+                // Data from .BYTE and custom directives takes up room too,
+                // so labels after it are pushed along by its length
+                addr += bytes.len() as u32;
+            } else if let &ParserToken::Checksum(algorithm, _, _) = token {
+                addr += algorithm.size() as u32;
             }
         }
+
+        labels
+    }
+
+    /// Moves the location counter past `size` bytes, which must all fit
+    /// in memory
+    fn advance(addr: u32, size: usize) -> Result<u32, AssemblerError> {
+        let end = addr + size as u32;
+        if end > 0x10000 {
+            return Err(AssemblerError::code_past_end_of_memory(addr));
+        }
+
+        Ok(end)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ::disassembler::Disassembler;
+    use assembler::checksum::ChecksumAlgorithm;
+
+    #[test]
+    fn apple_binary_fills_the_gaps_between_segments() {
+        let assembler = Assembler::new();
+        let segments = vec![CodeSegment { address: 0x0803, code: vec![0x60] },
+                            CodeSegment { address: 0x0800, code: vec![0x4C, 0x03, 0x08] },
+                            CodeSegment { address: 0x0806, code: vec![0xEA] }];
+
+        assert_eq!(vec![0x00, 0x08, 0x07, 0x00, 0x4C, 0x03, 0x08, 0x60, 0x00, 0x00, 0xEA],
+                   assembler.apple_binary(&segments).unwrap());
+    }
+
+    #[test]
+    fn ines_places_segments_in_the_last_prg_banks() {
+        let assembler = Assembler::new();
+        let header = INesHeader { prg_banks: 4, chr_banks: 1, ..Default::default() };
+        let segments = vec![CodeSegment { address: 0x8000, code: vec![0xEA] },
+                            CodeSegment { address: 0xFFFC, code: vec![0x00, 0x80] }];
+
+        let rom = assembler.ines(&segments, &header, &[0x01, 0x02]).unwrap();
+
+        assert_eq!(16 + 4 * 0x4000 + 0x2000, rom.len());
+        assert_eq!(0xEA, rom[16 + 0x8000]);
+        assert_eq!(&[0x00, 0x80], &rom[16 + 0xFFFC..16 + 0xFFFE]);
+        assert_eq!(&[0x01, 0x02, 0x00], &rom[16 + 0x10000..16 + 0x10003]);
+    }
+
+    #[test]
+    fn ines_rejects_segments_outside_the_prg_rom() {
+        let assembler = Assembler::new();
+        let segments = vec![CodeSegment { address: 0x8000, code: vec![0xEA] }];
+
+        assert!(assembler.ines(&segments, &INesHeader::new(), &[]).is_err());
+    }
+
+    #[test]
+    fn variant_limits_the_instructions_accepted() {
+        let mut assembler = Assembler::new();
+        assembler.set_variant(CpuVariant::Ricoh2A03);
+        assembler.enable_pseudo_ops();
+
+        let error = assembler.assemble_string("
+            LOOP NOP
+            BRA LOOP
+        ",
+                             None)
+            .unwrap_err();
+        assert_eq!("Instruction 'BRA' is not available on the selected CPU (Ricoh 2A03). Line 3",
+                   error.message);
+
+        assembler.set_variant(CpuVariant::Cmos65C02);
+        let segments = assembler.assemble_string("STZ $10\nLDAX #$1234", None).unwrap();
+        assert_eq!(&[0x64, 0x10, 0xA9, 0x34, 0xA2, 0x12], &segments[0].code[..]);
+    }
 
     #[test]
     fn can_assemble_basic_code() {
@@ -293,6 +1167,322 @@ mod tests {
         assert_eq!(&[0xA2, 0x0F, 0x4C, 0x00, 0x00], &segments[0].code[..]);
     }
 
+    #[test]
+    fn can_jump_indirectly_through_labels() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            ALIAS = VECTOR
+            JMP (VECTOR)
+            JMP (ALIAS)
+            VECTOR:
+            .BYTE #$00, #$C0
+        ",
+                             0xC000)
+            .unwrap();
+
+        assert_eq!(&[0x6C, 0x06, 0xC0, 0x6C, 0x06, 0xC0, 0x00, 0xC0],
+                   &segments[0].code[..]);
+    }
+
+    #[test]
+    fn can_jump_through_variables_assigned_to_labels() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            ENTRY = MAIN
+            MAIN:
+            LDX #15
+            JMP ENTRY
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xA2, 0x0F, 0x4C, 0x00, 0x00], &segments[0].code[..]);
+    }
+
+    #[test]
+    fn can_assemble_zero_page_symbols() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ZEROPAGE
+            POINTER = $0010
+            .CODE
+            COUNTER = $0020
+            ADDRESS = $0030
+            .ZP COUNTER, ADDRESS
+            LDA POINTER
+            STA COUNTER,X
+            LDX ADDRESS,Y
+            LDA (POINTER),Y
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xA5, 0x10, 0x95, 0x20, 0xB6, 0x30, 0xB1, 0x10],
+                   &segments[0].code[..]);
+    }
+
+    #[test]
+    fn does_not_treat_variables_after_zero_page_segment_as_zero_page() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ZEROPAGE
+            POINTER = $0010
+            .ORG $C000
+            ADDRESS = $0020
+            LDA ADDRESS
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xAD, 0x20, 0x00], &segments[0].code[..]);
+    }
+
+    #[test]
+    fn errors_on_zero_page_symbols_declared_above_zero_page() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            ADDRESS = $C000
+            .ZP ADDRESS
+        ",
+                                               None);
+
+        assert_eq!("Address too large. Line 3", result.unwrap_err().message);
+    }
+
+    #[test]
+    fn warns_on_branches_across_page_boundaries() {
+        let mut assembler = Assembler::new();
+        assembler.enable_page_crossing_warnings();
+        assembler.assemble_string("
+            .ORG $C0F0
+            LOOP DEX
+                 BNE LOOP
+                 BEQ DONE
+                 .BYTE #$EA, #$EA, #$EA, #$EA, #$EA, #$EA, #$EA, #$EA, #$EA, #$EA, #$EA, #$EA
+            DONE RTS
+        ",
+                                  None)
+            .unwrap();
+
+        assert_eq!(&[AssemblerWarning::branch_crosses_page("DONE", 0xC0F3, 0xC101)], assembler.warnings());
+
+        assembler.assemble_string("
+            .ORG $C0FE
+            LOOP DEX
+                 BNE LOOP
+        ",
+                                  None)
+            .unwrap();
+
+        assert_eq!(&[AssemblerWarning::loop_straddles_page("LOOP", 0xC0FE, 0xC0FF)], assembler.warnings());
+    }
+
+    #[test]
+    fn does_not_warn_on_page_crossings_by_default() {
+        let mut assembler = Assembler::new();
+        assembler.assemble_string("
+            .ORG $C0FE
+            LOOP DEX
+                 BNE LOOP
+        ",
+                                  None)
+            .unwrap();
+
+        assert_eq!(0, assembler.warnings().len());
+    }
+
+    #[test]
+    fn can_report_unreachable_code_from_labels() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            MAIN JSR HELPER
+                 RTS
+            DEAD LDA #$FF
+            HELPER LDX #$01
+                 RTS
+            .ORG $D000
+            IRQ  RTI
+                 NOP
+        ",
+                                                 None)
+            .unwrap();
+
+        assert_eq!(vec![UnreachableRegion { start: 0xC004, end: 0xC005 },
+                        UnreachableRegion { start: 0xD001, end: 0xD001 }],
+                   assembler.unreachable_code(&segments, &["MAIN", "IRQ"]).unwrap());
+        assert_eq!("Unknown label: 'RESET'",
+                   assembler.unreachable_code(&segments, &["RESET"]).unwrap_err().message);
+    }
+
+    #[test]
+    fn can_assemble_relocatable_code() {
+        let mut assembler = Assembler::new();
+        let relocatable = assembler.assemble_relocatable("
+            MAIN LDX #$05
+            LOOP DEX
+                 BNE LOOP
+                 JSR DONE
+                 JMP (VECTOR)
+                 STA $4400
+            DONE RTS
+            VECTOR:
+            .BYTE #$00, #$00
+        ")
+            .unwrap();
+
+        assert_eq!(vec![0x06, 0x09], relocatable.relocations);
+        assert_eq!(vec![0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x20, 0x0E, 0xC0, 0x6C, 0x0F, 0xC0, 0x8D, 0x00,
+                        0x44, 0x60, 0x00, 0x00],
+                   relocatable.relocated_to(0xC000));
+    }
+
+    #[test]
+    fn errors_on_org_in_relocatable_code() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_relocatable("
+            .ORG $C000
+            NOP
+        ");
+
+        assert_eq!("Relocatable code cannot contain .ORG directives", result.unwrap_err().message);
+    }
+
+    #[test]
+    fn places_code_before_the_first_org_at_the_origin() {
+        let mut assembler = Assembler::with_options(AssemblerOptions { origin: 0x8000 });
+        let code = "
+            MAIN JMP MAIN
+            .ORG $E000
+            NOP
+        ";
+
+        let segments = assembler.assemble_string(code, None).unwrap();
+        assert_eq!(vec![0x8000, 0xE000], segments.iter().map(|s| s.address).collect::<Vec<_>>());
+        assert_eq!(&[0x4C, 0x00, 0x80], &segments[0].code[..]);
+
+        // An origin given to the call overrides the options
+        let segments = assembler.assemble_string(code, 0xC000).unwrap();
+        assert_eq!(vec![0xC000, 0xE000], segments.iter().map(|s| s.address).collect::<Vec<_>>());
+        assert_eq!(&[0x4C, 0x00, 0xC0], &segments[0].code[..]);
+
+        // Relocatable code is always assembled at $0000
+        assert_eq!(vec![0x01],
+                   assembler.assemble_relocatable("MAIN JMP MAIN").unwrap().relocations);
+    }
+
+    #[test]
+    fn org_before_any_code_replaces_the_leading_segment() {
+        let mut assembler = Assembler::with_options(AssemblerOptions { origin: 0x8000 });
+        let segments = assembler.assemble_string("
+            .ORG $E000
+            NOP
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(1, segments.len());
+        assert_eq!(0xE000, segments[0].address);
+    }
+
+    #[test]
+    fn can_patch_checksums_into_the_image() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            MAIN LDA #$01
+                 JMP MAIN
+            .ORG $FFF0
+            .CHECKSUM $C000, $C004
+            .CRC $C000, $FFFF
+            .BYTE #$AA
+        ",
+                                                 None)
+            .unwrap();
+
+        let code = &[0xA9, 0x01, 0x4C, 0x00, 0xC0];
+        let sum = code.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        let mut image = code.to_vec();
+        image.extend_from_slice(&[sum, 0x00, 0x00, 0xAA]);
+        let crc = ChecksumAlgorithm::Crc16.calculate(&image);
+
+        assert_eq!(&[sum, crc[0], crc[1], 0xAA], &segments[1].code[..]);
+    }
+
+    #[test]
+    fn checksum_directives_advance_the_address() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            .CRC $C000, $C000
+            MAIN JMP MAIN
+        ",
+                                                 None)
+            .unwrap();
+
+        assert_eq!(&[0x4C, 0x02, 0xC0], &segments[0].code[2..]);
+    }
+
+    #[test]
+    fn errors_on_reversed_checksum_ranges() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            .CHECKSUM $FFFF, $C000
+        ",
+                                               None);
+
+        assert_eq!("Invalid address range. Line 2", result.unwrap_err().message);
+    }
+
+    #[test]
+    fn can_pad_segments_to_their_declared_size() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            .SIZE $0008
+            MAIN JMP MAIN
+            .ORG $D000
+            .SIZE $0004, #$EA
+            RTS
+            .ORG $E000
+            RTS
+        ",
+                                                 None)
+            .unwrap();
+
+        assert_eq!(&[0x4C, 0x00, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], &segments[0].code[..]);
+        assert_eq!(&[0x60, 0xEA, 0xEA, 0xEA], &segments[1].code[..]);
+        assert_eq!(&[0x60], &segments[2].code[..]);
+    }
+
+    #[test]
+    fn checksums_include_segment_padding() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            .SIZE $0004, #$01
+            .CHECKSUM $C000, $C003
+        ",
+                                                 None)
+            .unwrap();
+
+        assert_eq!(&[0x03, 0x01, 0x01, 0x01], &segments[0].code[..]);
+    }
+
+    #[test]
+    fn errors_when_segment_exceeds_declared_size() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            .ORG $C000
+            .SIZE $0002
+            JMP $C000
+        ",
+                                               None);
+
+        assert_eq!("Segment at C000 is 3 bytes, larger than its declared size of 2 bytes",
+                   result.unwrap_err().message);
+    }
+
     #[test]
     fn can_assemble_clearmem_implementation() {
         let mut assembler = Assembler::new();
@@ -377,7 +1567,7 @@ mod tests {
                              None)
             .unwrap();
 
-        assert_eq!(&[0xA2, 0x0F, 0xB1, 0x00, 0x00], &segments[0].code[..]);
+        assert_eq!(&[0xA2, 0x0F, 0xB1, 0x00], &segments[0].code[..]);
     }
 
     #[test]
@@ -451,6 +1641,22 @@ mod tests {
         assert_eq!(&[255], &segments[0].code[..]);
     }
 
+    #[test]
+    fn labels_after_byte_data_are_moved_along_by_it() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            JMP AFTER
+            .BYTE #$01, #$02, #$03
+            AFTER
+            RTS
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0x4C, 0x06, 0xC0, 0x01, 0x02, 0x03, 0x60], &segments[0].code[..]);
+    }
+
     #[test]
     fn can_dump_bytes_with_other_code() {
         let mut assembler = Assembler::new();
@@ -476,7 +1682,239 @@ mod tests {
         assert_eq!(0xC000, segments[0].address);
         assert_eq!(0x2000, segments[1].address);
 
-        assert_eq!(0x05, segments[0].code[0x01]);
+        assert_eq!(0x07, segments[0].code[0x01]);
         assert_eq!(0x20, segments[0].code[0x02]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn can_use_custom_directives() {
+        let mut assembler = Assembler::new();
+        assembler.register_directive("FILL", |args, _| {
+            if let Some(LexerToken::Immediate(count, _)) = args.first() {
+                let count = count.parse::<usize>().map_err(|e| e.to_string())?;
+                Ok(DirectiveOutput::with_bytes(vec![0xEA; count]))
+            } else {
+                Err("expected a byte count".into())
+            }
+        });
+
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            .FILL #3
+            END
+            JMP END
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xEA, 0xEA, 0xEA, 0x4C, 0x03, 0xC0], &segments[0].code[..]);
+    }
+
+    #[test]
+    fn can_define_symbols_from_custom_directives() {
+        let mut assembler = Assembler::new();
+        assembler.register_directive("here", |_, addr| {
+            Ok(DirectiveOutput {
+                bytes: Vec::new(),
+                symbols: vec![("HERE".into(), addr)],
+            })
+        });
+
+        let segments = assembler.assemble_string("
+            .ORG $C000
+            NOP
+            .HERE
+            JMP HERE
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xEA, 0x4C, 0x01, 0xC0], &segments[0].code[..]);
+    }
+
+    #[test]
+    fn errors_when_custom_directive_fails() {
+        let mut assembler = Assembler::new();
+        assembler.register_directive("FAIL", |_, _| Err("no thanks".into()));
+
+        let result = assembler.assemble_string(".FAIL", None);
+
+        assert_eq!("Directive .FAIL failed: no thanks",
+                   result.unwrap_err().message);
+    }
+
+    #[test]
+    fn can_list_cross_references() {
+        let mut assembler = Assembler::new();
+        assembler.assemble_string("
+            SCREEN = $0400
+            START   LDA #$00
+            LOOP    STA SCREEN
+                    BNE LOOP
+                    JMP START
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(Disassembler::clean_asm("
+            SYMBOL  DEFINED  REFERENCED
+            LOOP          4  5
+            SCREEN        2  4
+            START         3  6
+        "),
+                   Disassembler::clean_asm(assembler.cross_reference_listing()));
+    }
+
+    #[test]
+    fn can_assemble_three_digit_addresses() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            LDA $C00
+            LDA $044
+            STA $C00,X
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xAD, 0x00, 0x0C, 0xA5, 0x44, 0x9D, 0x00, 0x0C],
+                   &segments[0].code[..]);
+    }
+
+    #[test]
+    fn can_assemble_addresses_with_leading_zeros() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            LDA $04400
+            LDA $0044
+            LDA ($0044),Y
+            JMP ($00C00)
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(&[0xAD, 0x00, 0x44, 0xAD, 0x44, 0x00, 0xB1, 0x44, 0x6C, 0x00, 0x0C],
+                   &segments[0].code[..]);
+    }
+
+    #[test]
+    fn can_org_at_short_addresses() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $80
+            NOP
+            .ORG $C00
+            NOP
+        ",
+                             None)
+            .unwrap();
+
+        assert_eq!(0x0080, segments[0].address);
+        assert_eq!(0x0C00, segments[1].address);
+    }
+
+    #[test]
+    fn errors_on_indirect_indexed_pointer_outside_zero_page() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            LDA ($100),Y
+        ",
+                                               None);
+
+        assert_eq!("Address too large. Line 2", result.unwrap_err().message);
+    }
+
+    #[test]
+    fn errors_on_branches_that_leave_their_segment() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            .ORG $C000
+            LOOP NOP
+            .ORG $C002
+            BNE LOOP
+        ",
+                                               None);
+
+        assert_eq!("Branch at C002 to LOOP at C000 leaves its segment", result.unwrap_err().message);
+    }
+
+    #[test]
+    fn errors_on_branches_that_are_too_far() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            .ORG $C000
+            LOOP NOP
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            .BYTE #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00, #$00
+            BNE LOOP
+        ",
+                                               None);
+
+        assert_eq!("Branch too far: branch at C081 to LOOP at C000", result.unwrap_err().message);
+    }
+
+    #[test]
+    fn allows_code_to_end_at_the_top_of_memory() {
+        let mut assembler = Assembler::new();
+        let segments = assembler.assemble_string("
+            .ORG $FFFA
+            .BYTE #$00, #$C0, #$00, #$C0, #$00, #$C0
+        ",
+                                                 None)
+            .unwrap();
+
+        assert_eq!(6, segments[0].code.len());
+    }
+
+    #[test]
+    fn line_addresses_count_includes_as_their_include_line() {
+        let mut assembler = Assembler::new();
+        let include = assembler.assemble_string(".INCLUDE <std/memset.inc>", 0xC000).unwrap();
+        let include_size = include[0].code.len() as u16;
+
+        assembler.assemble_string("LDA #$01\n.INCLUDE <std/memset.inc>\n\nLDY #$02", 0xC000).unwrap();
+
+        assert_eq!(Some(0xC000), assembler.line_address(1));
+        assert_eq!(Some(0xC002), assembler.line_address(2));
+        assert_eq!(None, assembler.line_address(3));
+        assert_eq!(Some(0xC002 + include_size), assembler.line_address(4));
+    }
+
+    #[test]
+    fn line_addresses_follow_peephole_optimizations() {
+        let mut assembler = Assembler::new();
+        assembler.enable_peephole_optimizations();
+
+        assembler.assemble_string("CLC\nCLC\nLDA #$01", 0xC000).unwrap();
+
+        assert_eq!(Some(0xC000), assembler.line_address(1));
+        assert_eq!(None, assembler.line_address(2));
+        assert_eq!(Some(0xC001), assembler.line_address(3));
+    }
+
+    #[test]
+    fn errors_on_code_that_runs_past_the_top_of_memory() {
+        let mut assembler = Assembler::new();
+        let result = assembler.assemble_string("
+            .ORG $FFFE
+            JMP $C000
+        ",
+                                               None);
+
+        assert_eq!("Code at FFFE runs past FFFF", result.unwrap_err().message);
+
+        let result = assembler.assemble_string("
+            .ORG $FFFD
+            JMP END
+            END
+        ",
+                                               None);
+
+        assert_eq!("Label END is past the end of memory", result.unwrap_err().message);
+    }
+}
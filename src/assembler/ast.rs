@@ -0,0 +1,207 @@
+use ::opcodes::OpCode;
+use assembler::assembler::AssemblerError;
+use assembler::lexer::Lexer;
+use assembler::pseudo;
+use assembler::token::{LexerToken, Span, Spanned};
+
+/// One part of a line of source code, before any symbols are resolved or
+/// any code is generated. A line holds at most a label and an instruction,
+/// or one directive or assignment.
+///
+/// Statements, along with `LexerToken`, are the stable front end of the
+/// assembler: new variants are only added in minor releases, and existing
+/// ones aren't changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Statement {
+    /// A label naming the address of what follows, written `MAIN` or
+    /// `MAIN:`
+    Label(String),
+    /// An instruction or pseudo-instruction and the tokens of its operand,
+    /// such as `LDA ($10),Y`. The mnemonic is as written.
+    Instruction {
+        mnemonic: String,
+        operand: Vec<Spanned<LexerToken>>,
+    },
+    /// A directive and the tokens of its arguments, such as `.ORG $C000`.
+    /// The name is as written, without the period.
+    Directive {
+        name: String,
+        args: Vec<Spanned<LexerToken>>,
+    },
+    /// A variable, such as `PORT = $6000`
+    Assignment {
+        name: String,
+        value: Spanned<LexerToken>,
+    },
+}
+
+/// Returns the tokens of each line of 6502 assembly code, with where each
+/// token was. Blank lines and lines of only a comment have no tokens, and
+/// `.INCLUDE`d files are lexed in place of the directive.
+///
+/// # Example
+/// ```
+/// use rs6502::{tokenize, LexerToken, Span};
+///
+/// let lines = tokenize("  LDA $10").unwrap();
+///
+/// assert_eq!(LexerToken::Address("10".into()), lines[0][1].node);
+/// assert_eq!(Span { line: 1, column: 7, len: 3 }, lines[0][1].span);
+/// ```
+pub fn tokenize<S>(source: S) -> Result<Vec<Vec<Spanned<LexerToken>>>, AssemblerError>
+    where S: Into<String>
+{
+    Ok(Lexer::new().lex_string_spanned(source)?)
+}
+
+/// Returns the statements of 6502 assembly code, with where each one was.
+/// Only the shape of each line is checked, so a statement can still fail
+/// to assemble, such as an instruction with an addressing mode it doesn't
+/// have or a label that is never defined.
+///
+/// # Example
+/// ```
+/// use rs6502::{parse_statements, Statement};
+///
+/// let statements = parse_statements("
+///     PORT = $6000
+/// MAIN: LDA #$01
+///     .BYTE $02
+/// ").unwrap();
+///
+/// assert_eq!(Statement::Label("MAIN".into()), statements[1].node);
+/// match statements[2].node {
+///     Statement::Instruction { ref mnemonic, ref operand } => {
+///         assert_eq!("LDA", mnemonic);
+///         assert_eq!(1, operand.len());
+///     }
+///     _ => panic!("expected an instruction"),
+/// }
+/// assert_eq!(4, statements[3].span.line);
+/// ```
+pub fn parse_statements<S>(source: S) -> Result<Vec<Spanned<Statement>>, AssemblerError>
+    where S: Into<String>
+{
+    let mut statements = Vec::new();
+
+    for line in tokenize(source)? {
+        let mut rest = &line[..];
+
+        if let Some(first) = rest.first() {
+            if let LexerToken::Ident(ref name) = first.node {
+                if !is_instruction(name) {
+                    match rest.get(1).map(|token| &token.node) {
+                        Some(&LexerToken::Assignment) => {
+                            let value = match rest.get(2) {
+                                Some(value) => value,
+                                None => return Err(unexpected_end_of_line(first.span)),
+                            };
+                            if let Some(extra) = rest.get(3) {
+                                return Err(unexpected_token(extra.span));
+                            }
+                            statements.push(Spanned {
+                                node: Statement::Assignment {
+                                    name: name.clone(),
+                                    value: value.clone(),
+                                },
+                                span: first.span.to(value.span),
+                            });
+                            continue;
+                        }
+                        Some(&LexerToken::Colon) => {
+                            statements.push(Spanned {
+                                node: Statement::Label(name.clone()),
+                                span: first.span.to(rest[1].span),
+                            });
+                            rest = &rest[2..];
+                        }
+                        _ => {
+                            statements.push(Spanned {
+                                node: Statement::Label(name.clone()),
+                                span: first.span,
+                            });
+                            rest = &rest[1..];
+                        }
+                    }
+                }
+            }
+        }
+
+        let (first, last) = match (rest.first(), rest.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => continue,
+        };
+        let node = match (&first.node, rest.get(1).map(|token| &token.node)) {
+            (LexerToken::Ident(mnemonic), _) if is_instruction(mnemonic) => {
+                Statement::Instruction {
+                    mnemonic: mnemonic.clone(),
+                    operand: rest[1..].to_vec(),
+                }
+            }
+            (&LexerToken::Ident(_), _) => return Err(expected_instruction(first.span)),
+            (&LexerToken::Period, Some(LexerToken::Ident(name))) => {
+                Statement::Directive {
+                    name: name.clone(),
+                    args: rest[2..].to_vec(),
+                }
+            }
+            _ => return Err(unexpected_token(first.span)),
+        };
+        statements.push(Spanned {
+            node,
+            span: first.span.to(last.span),
+        });
+    }
+
+    Ok(statements)
+}
+
+/// Pseudo-instructions count as instructions whether or not the assembler
+/// has them enabled
+fn is_instruction(ident: &str) -> bool {
+    OpCode::from_mnemonic(ident).is_some() || pseudo::is_pseudo_op(ident)
+}
+
+fn unexpected_token(span: Span) -> AssemblerError {
+    AssemblerError::from(format!("Unexpected token. Line {} col {}", span.line, span.column))
+}
+
+fn unexpected_end_of_line(span: Span) -> AssemblerError {
+    AssemblerError::from(format!("Unexpected end of line. Line {}", span.line))
+}
+
+fn expected_instruction(span: Span) -> AssemblerError {
+    AssemblerError::from(format!("Expected instruction. Line {} col {}", span.line, span.column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_labels_from_instructions_and_keeps_operand_spans() {
+        let statements = parse_statements("LOOP DEX\n  BNE LOOP ; again").unwrap();
+
+        assert_eq!(vec![Statement::Label("LOOP".into()),
+                        Statement::Instruction {
+                            mnemonic: "DEX".into(),
+                            operand: Vec::new(),
+                        },
+                        Statement::Instruction {
+                            mnemonic: "BNE".into(),
+                            operand: vec![Spanned {
+                                              node: LexerToken::Ident("LOOP".into()),
+                                              span: Span { line: 2, column: 7, len: 4 },
+                                          }],
+                        }],
+                   statements.iter().map(|statement| statement.node.clone()).collect::<Vec<_>>());
+        assert_eq!(Span { line: 2, column: 3, len: 8 }, statements[2].span);
+    }
+
+    #[test]
+    fn rejects_a_label_followed_by_something_other_than_an_instruction() {
+        assert_eq!("Expected instruction. Line 2 col 6",
+                   parse_statements("\nMAIN LOOP").unwrap_err().message);
+        assert_eq!("Unexpected end of line. Line 1", parse_statements("PORT =").unwrap_err().message);
+    }
+}
@@ -0,0 +1,65 @@
+/// The checksums that can be patched into an image by the `.CHECKSUM`
+/// and `.CRC` directives
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChecksumAlgorithm {
+    /// An 8-bit sum of every byte, ignoring overflow
+    Sum8,
+    /// CRC-16/CCITT (polynomial $1021, initial value $FFFF), stored little-endian
+    Crc16,
+}
+
+impl ChecksumAlgorithm {
+    /// The number of bytes the checksum occupies in the image
+    pub fn size(&self) -> u16 {
+        match *self {
+            ChecksumAlgorithm::Sum8 => 1,
+            ChecksumAlgorithm::Crc16 => 2,
+        }
+    }
+
+    pub fn calculate(&self, bytes: &[u8]) -> Vec<u8> {
+        match *self {
+            ChecksumAlgorithm::Sum8 => vec![sum8(bytes)],
+            ChecksumAlgorithm::Crc16 => {
+                let crc = crc16(bytes);
+                vec![crc as u8, (crc >> 8) as u8]
+            }
+        }
+    }
+}
+
+fn sum8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for b in bytes {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sum_wraps_on_overflow() {
+        assert_eq!(vec![0x01], ChecksumAlgorithm::Sum8.calculate(&[0xFF, 0x01, 0x01]));
+    }
+
+    #[test]
+    fn crc_matches_ccitt_check_value() {
+        assert_eq!(vec![0xB1, 0x29], ChecksumAlgorithm::Crc16.calculate(b"123456789"));
+    }
+}
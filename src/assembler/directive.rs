@@ -0,0 +1,27 @@
+use assembler::token::LexerToken;
+
+/// The output of a user-registered directive handler. Any bytes are emitted
+/// at the current location and any symbols are added to the symbol table so
+/// that they can be referenced like labels.
+#[derive(Debug, Default, PartialEq)]
+pub struct DirectiveOutput {
+    pub bytes: Vec<u8>,
+    pub symbols: Vec<(String, u16)>,
+}
+
+impl DirectiveOutput {
+    pub fn new() -> DirectiveOutput {
+        Default::default()
+    }
+
+    pub fn with_bytes(bytes: Vec<u8>) -> DirectiveOutput {
+        DirectiveOutput {
+            bytes,
+            symbols: Vec::new(),
+        }
+    }
+}
+
+/// A handler for a custom directive. It receives the tokens that follow the
+/// directive name on its line, along with the current location counter.
+pub type DirectiveHandler = Box<dyn Fn(&[LexerToken], u16) -> Result<DirectiveOutput, String>>;
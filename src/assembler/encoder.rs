@@ -0,0 +1,425 @@
+use assembler::{AssemblerError, CodeSegment};
+use opcodes::{AddressingMode, OpCode};
+
+impl AssemblerError {
+    fn unbound_label(label: Label) -> AssemblerError {
+        AssemblerError::from(format!("Label {} was never bound to an address", label.0))
+    }
+
+    fn branch_out_of_range(address: u16, target: u16) -> AssemblerError {
+        AssemblerError::from(format!("Branch too far: ${:04X} to ${:04X}", address, target))
+    }
+}
+
+/// A position in code generated by an `Encoder`. A label can be used by
+/// branches and jumps before it is bound to an address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Label(usize);
+
+enum Fixup {
+    Relative(usize, Label),
+    Absolute(usize, Label),
+}
+
+macro_rules! implied_instructions {
+    ($($name:ident => $mnemonic:expr, $mode:ident;)*) => {
+        $(
+            pub fn $name(&mut self) -> &mut Encoder {
+                self.emit($mnemonic, AddressingMode::$mode, &[])
+            }
+        )*
+    }
+}
+
+macro_rules! byte_instructions {
+    ($($name:ident => $mnemonic:expr, $mode:ident;)*) => {
+        $(
+            pub fn $name(&mut self, value: u8) -> &mut Encoder {
+                self.emit($mnemonic, AddressingMode::$mode, &[value])
+            }
+        )*
+    }
+}
+
+macro_rules! word_instructions {
+    ($($name:ident => $mnemonic:expr, $mode:ident;)*) => {
+        $(
+            pub fn $name(&mut self, addr: u16) -> &mut Encoder {
+                self.emit($mnemonic, AddressingMode::$mode, &[addr as u8, (addr >> 8) as u8])
+            }
+        )*
+    }
+}
+
+macro_rules! branch_instructions {
+    ($($name:ident => $mnemonic:expr, $mode:ident;)*) => {
+        $(
+            pub fn $name(&mut self, label: Label) -> &mut Encoder {
+                self.emit($mnemonic, AddressingMode::$mode, &[0x00]);
+                let offset = self.code.len() - 1;
+                self.fixups.push(Fixup::Relative(offset, label));
+                self
+            }
+        )*
+    }
+}
+
+/// Generates machine code from Rust, as an alternative to assembling
+/// text. There is a method for each instruction and addressing mode,
+/// named after the mnemonic with a suffix for the mode (`_imm`, `_zp`,
+/// `_zpx`, `_zpy`, `_abs`, `_absx`, `_absy`, `_ind`, `_indx`, `_indy` and
+/// `_zpind`). Branches, `jmp` and `jsr` take a `Label`, which is fixed up
+/// once the code is finished. The 65C02 instructions are included.
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, Encoder};
+///
+/// let mut encoder = Encoder::new(0xC000);
+/// encoder.ldx_imm(0x05);
+/// let top = encoder.label();
+/// encoder.dex().bne(top).brk();
+///
+/// let segment = encoder.finish().unwrap();
+/// assert_eq!(vec![0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x00], segment.code);
+///
+/// let mut cpu = Cpu::new();
+/// cpu.load(&segment.code, segment.address).unwrap();
+/// cpu.reset();
+/// cpu.step_n(11).unwrap();
+/// assert_eq!(0x00, cpu.registers.X);
+/// ```
+pub struct Encoder {
+    origin: u16,
+    code: Vec<u8>,
+    labels: Vec<Option<u16>>,
+    fixups: Vec<Fixup>,
+}
+
+impl Encoder {
+    /// Creates an Encoder for code that will be loaded at `origin`
+    pub fn new(origin: u16) -> Encoder {
+        Encoder {
+            origin,
+            code: Vec::new(),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    /// The address the next instruction will be encoded at
+    pub fn address(&self) -> u16 {
+        self.origin.wrapping_add(self.code.len() as u16)
+    }
+
+    /// Creates a label that is not yet bound to an address, for use in
+    /// forward branches and jumps
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Binds a label to the address of the next instruction
+    ///
+    /// # Panics
+    /// Panics if the label is already bound
+    pub fn bind(&mut self, label: Label) -> &mut Encoder {
+        if self.labels[label.0].is_some() {
+            panic!("Label {} is already bound to an address", label.0);
+        }
+
+        self.labels[label.0] = Some(self.address());
+        self
+    }
+
+    /// Creates a label bound to the address of the next instruction
+    pub fn label(&mut self) -> Label {
+        let label = self.new_label();
+        self.bind(label);
+        label
+    }
+
+    /// Appends raw bytes, such as data, to the code
+    pub fn bytes(&mut self, bytes: &[u8]) -> &mut Encoder {
+        self.code.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn jmp(&mut self, label: Label) -> &mut Encoder {
+        self.emit("JMP", AddressingMode::Absolute, &[0x00, 0x00]);
+        let offset = self.code.len() - 2;
+        self.fixups.push(Fixup::Absolute(offset, label));
+        self
+    }
+
+    pub fn jsr(&mut self, label: Label) -> &mut Encoder {
+        self.emit("JSR", AddressingMode::Absolute, &[0x00, 0x00]);
+        let offset = self.code.len() - 2;
+        self.fixups.push(Fixup::Absolute(offset, label));
+        self
+    }
+
+    implied_instructions! {
+        asl => "ASL", Accumulator;
+        brk => "BRK", Implied;
+        clc => "CLC", Implied;
+        cld => "CLD", Implied;
+        cli => "CLI", Implied;
+        clv => "CLV", Implied;
+        dec => "DEC", Accumulator;
+        dex => "DEX", Implied;
+        dey => "DEY", Implied;
+        inc => "INC", Accumulator;
+        inx => "INX", Implied;
+        iny => "INY", Implied;
+        lsr => "LSR", Accumulator;
+        nop => "NOP", Implied;
+        pha => "PHA", Implied;
+        php => "PHP", Implied;
+        phx => "PHX", Implied;
+        phy => "PHY", Implied;
+        pla => "PLA", Implied;
+        plp => "PLP", Implied;
+        plx => "PLX", Implied;
+        ply => "PLY", Implied;
+        rol => "ROL", Accumulator;
+        ror => "ROR", Accumulator;
+        rti => "RTI", Implied;
+        rts => "RTS", Implied;
+        sec => "SEC", Implied;
+        sed => "SED", Implied;
+        sei => "SEI", Implied;
+        tax => "TAX", Implied;
+        tay => "TAY", Implied;
+        tsx => "TSX", Implied;
+        txa => "TXA", Implied;
+        txs => "TXS", Implied;
+        tya => "TYA", Implied;
+    }
+
+    byte_instructions! {
+        adc_imm => "ADC", Immediate;
+        adc_indx => "ADC", IndirectX;
+        adc_indy => "ADC", IndirectY;
+        adc_zp => "ADC", ZeroPage;
+        adc_zpind => "ADC", ZeroPageIndirect;
+        adc_zpx => "ADC", ZeroPageX;
+        and_imm => "AND", Immediate;
+        and_indx => "AND", IndirectX;
+        and_indy => "AND", IndirectY;
+        and_zp => "AND", ZeroPage;
+        and_zpind => "AND", ZeroPageIndirect;
+        and_zpx => "AND", ZeroPageX;
+        asl_zp => "ASL", ZeroPage;
+        asl_zpx => "ASL", ZeroPageX;
+        bit_imm => "BIT", Immediate;
+        bit_zp => "BIT", ZeroPage;
+        bit_zpx => "BIT", ZeroPageX;
+        cmp_imm => "CMP", Immediate;
+        cmp_indx => "CMP", IndirectX;
+        cmp_indy => "CMP", IndirectY;
+        cmp_zp => "CMP", ZeroPage;
+        cmp_zpind => "CMP", ZeroPageIndirect;
+        cmp_zpx => "CMP", ZeroPageX;
+        cpx_imm => "CPX", Immediate;
+        cpx_zp => "CPX", ZeroPage;
+        cpy_imm => "CPY", Immediate;
+        cpy_zp => "CPY", ZeroPage;
+        dec_zp => "DEC", ZeroPage;
+        dec_zpx => "DEC", ZeroPageX;
+        eor_imm => "EOR", Immediate;
+        eor_indx => "EOR", IndirectX;
+        eor_indy => "EOR", IndirectY;
+        eor_zp => "EOR", ZeroPage;
+        eor_zpind => "EOR", ZeroPageIndirect;
+        eor_zpx => "EOR", ZeroPageX;
+        inc_zp => "INC", ZeroPage;
+        inc_zpx => "INC", ZeroPageX;
+        lda_imm => "LDA", Immediate;
+        lda_indx => "LDA", IndirectX;
+        lda_indy => "LDA", IndirectY;
+        lda_zp => "LDA", ZeroPage;
+        lda_zpind => "LDA", ZeroPageIndirect;
+        lda_zpx => "LDA", ZeroPageX;
+        ldx_imm => "LDX", Immediate;
+        ldx_zp => "LDX", ZeroPage;
+        ldx_zpy => "LDX", ZeroPageY;
+        ldy_imm => "LDY", Immediate;
+        ldy_zp => "LDY", ZeroPage;
+        ldy_zpx => "LDY", ZeroPageX;
+        lsr_zp => "LSR", ZeroPage;
+        lsr_zpx => "LSR", ZeroPageX;
+        ora_imm => "ORA", Immediate;
+        ora_indx => "ORA", IndirectX;
+        ora_indy => "ORA", IndirectY;
+        ora_zp => "ORA", ZeroPage;
+        ora_zpind => "ORA", ZeroPageIndirect;
+        ora_zpx => "ORA", ZeroPageX;
+        rol_zp => "ROL", ZeroPage;
+        rol_zpx => "ROL", ZeroPageX;
+        ror_zp => "ROR", ZeroPage;
+        ror_zpx => "ROR", ZeroPageX;
+        sbc_imm => "SBC", Immediate;
+        sbc_indx => "SBC", IndirectX;
+        sbc_indy => "SBC", IndirectY;
+        sbc_zp => "SBC", ZeroPage;
+        sbc_zpind => "SBC", ZeroPageIndirect;
+        sbc_zpx => "SBC", ZeroPageX;
+        sta_indx => "STA", IndirectX;
+        sta_indy => "STA", IndirectY;
+        sta_zp => "STA", ZeroPage;
+        sta_zpind => "STA", ZeroPageIndirect;
+        sta_zpx => "STA", ZeroPageX;
+        stx_zp => "STX", ZeroPage;
+        stx_zpy => "STX", ZeroPageY;
+        sty_zp => "STY", ZeroPage;
+        sty_zpx => "STY", ZeroPageX;
+        stz_zp => "STZ", ZeroPage;
+        stz_zpx => "STZ", ZeroPageX;
+        trb_zp => "TRB", ZeroPage;
+        tsb_zp => "TSB", ZeroPage;
+    }
+
+    word_instructions! {
+        adc_abs => "ADC", Absolute;
+        adc_absx => "ADC", AbsoluteX;
+        adc_absy => "ADC", AbsoluteY;
+        and_abs => "AND", Absolute;
+        and_absx => "AND", AbsoluteX;
+        and_absy => "AND", AbsoluteY;
+        asl_abs => "ASL", Absolute;
+        asl_absx => "ASL", AbsoluteX;
+        bit_abs => "BIT", Absolute;
+        bit_absx => "BIT", AbsoluteX;
+        cmp_abs => "CMP", Absolute;
+        cmp_absx => "CMP", AbsoluteX;
+        cmp_absy => "CMP", AbsoluteY;
+        cpx_abs => "CPX", Absolute;
+        cpy_abs => "CPY", Absolute;
+        dec_abs => "DEC", Absolute;
+        dec_absx => "DEC", AbsoluteX;
+        eor_abs => "EOR", Absolute;
+        eor_absx => "EOR", AbsoluteX;
+        eor_absy => "EOR", AbsoluteY;
+        inc_abs => "INC", Absolute;
+        inc_absx => "INC", AbsoluteX;
+        jmp_abs => "JMP", Absolute;
+        jmp_ind => "JMP", Indirect;
+        jmp_indx => "JMP", AbsoluteIndexedIndirect;
+        jsr_abs => "JSR", Absolute;
+        lda_abs => "LDA", Absolute;
+        lda_absx => "LDA", AbsoluteX;
+        lda_absy => "LDA", AbsoluteY;
+        ldx_abs => "LDX", Absolute;
+        ldx_absy => "LDX", AbsoluteY;
+        ldy_abs => "LDY", Absolute;
+        ldy_absx => "LDY", AbsoluteX;
+        lsr_abs => "LSR", Absolute;
+        lsr_absx => "LSR", AbsoluteX;
+        ora_abs => "ORA", Absolute;
+        ora_absx => "ORA", AbsoluteX;
+        ora_absy => "ORA", AbsoluteY;
+        rol_abs => "ROL", Absolute;
+        rol_absx => "ROL", AbsoluteX;
+        ror_abs => "ROR", Absolute;
+        ror_absx => "ROR", AbsoluteX;
+        sbc_abs => "SBC", Absolute;
+        sbc_absx => "SBC", AbsoluteX;
+        sbc_absy => "SBC", AbsoluteY;
+        sta_abs => "STA", Absolute;
+        sta_absx => "STA", AbsoluteX;
+        sta_absy => "STA", AbsoluteY;
+        stx_abs => "STX", Absolute;
+        sty_abs => "STY", Absolute;
+        stz_abs => "STZ", Absolute;
+        stz_absx => "STZ", AbsoluteX;
+        trb_abs => "TRB", Absolute;
+        tsb_abs => "TSB", Absolute;
+    }
+
+    branch_instructions! {
+        bcc => "BCC", Relative;
+        bcs => "BCS", Relative;
+        beq => "BEQ", Relative;
+        bmi => "BMI", Relative;
+        bne => "BNE", Relative;
+        bpl => "BPL", Relative;
+        bra => "BRA", Relative;
+        bvc => "BVC", Relative;
+        bvs => "BVS", Relative;
+    }
+
+    /// Fixes up every branch and jump to a label and returns the code.
+    /// Fails if a label was never bound or a branch is out of range.
+    pub fn finish(mut self) -> Result<CodeSegment, AssemblerError> {
+        for fixup in &self.fixups {
+            match *fixup {
+                Fixup::Relative(offset, label) => {
+                    let target = self.labels[label.0].ok_or_else(|| AssemblerError::unbound_label(label))?;
+                    let next = self.origin.wrapping_add(offset as u16 + 1);
+                    let distance = target.wrapping_sub(next) as i16;
+                    if !(-128..=127).contains(&distance) {
+                        return Err(AssemblerError::branch_out_of_range(next.wrapping_sub(2), target));
+                    }
+                    self.code[offset] = distance as u8;
+                }
+                Fixup::Absolute(offset, label) => {
+                    let target = self.labels[label.0].ok_or_else(|| AssemblerError::unbound_label(label))?;
+                    self.code[offset] = target as u8;
+                    self.code[offset + 1] = (target >> 8) as u8;
+                }
+            }
+        }
+
+        Ok(CodeSegment {
+            address: self.origin,
+            code: self.code,
+        })
+    }
+
+    fn emit(&mut self, mnemonic: &str, mode: AddressingMode, operand: &[u8]) -> &mut Encoder {
+        let opcode = OpCode::from_mnemonic_and_addressing_mode(mnemonic, mode)
+            .expect("Encoder methods only use valid instructions");
+
+        self.code.push(opcode.code);
+        self.code.extend_from_slice(operand);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixes_up_forward_branches_and_jumps() {
+        let mut encoder = Encoder::new(0x2000);
+        let skip = encoder.new_label();
+        let subroutine = encoder.new_label();
+
+        encoder.lda_zp(0x10).beq(skip).jsr(subroutine);
+        encoder.bind(skip).rts();
+        encoder.bind(subroutine).sta_abs(0x4400).rts();
+
+        assert_eq!(vec![0xA5, 0x10, 0xF0, 0x03, 0x20, 0x08, 0x20, 0x60, 0x8D, 0x00, 0x44, 0x60],
+                   encoder.finish().unwrap().code);
+    }
+
+    #[test]
+    fn reports_unbound_labels() {
+        let mut encoder = Encoder::new(0x2000);
+        let nowhere = encoder.new_label();
+        encoder.jmp(nowhere);
+
+        assert_eq!("Label 0 was never bound to an address", encoder.finish().unwrap_err().message);
+    }
+
+    #[test]
+    fn reports_branches_that_are_too_far() {
+        let mut encoder = Encoder::new(0x2000);
+        let top = encoder.label();
+        encoder.bytes(&[0xEA; 200]).bne(top);
+
+        assert_eq!("Branch too far: $20C8 to $2000", encoder.finish().unwrap_err().message);
+    }
+}
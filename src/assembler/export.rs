@@ -0,0 +1,57 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Formats symbols as a Rust source file of `pub const` addresses, sorted
+/// by name, so host code can share addresses with the assembly
+pub fn format_rust_constants(symbols: &[(&str, u16)]) -> String {
+    let mut symbols = symbols.to_vec();
+    symbols.sort();
+
+    let mut result = String::from("// Generated from assembled 6502 source. Do not edit.\n\n");
+    for (symbol, addr) in symbols {
+        result.push_str(&format!("pub const {}: u16 = 0x{:04X};\n", rust_identifier(symbol), addr));
+    }
+
+    result
+}
+
+/// Prefixes code with the header of an Apple II binary (`B`) file: the load
+/// address followed by the length, both little-endian
+pub fn format_apple_binary(address: u16, code: &[u8]) -> Vec<u8> {
+    let mut result = vec![0x00; 4];
+    LittleEndian::write_u16(&mut result[0..2], address);
+    LittleEndian::write_u16(&mut result[2..4], code.len() as u16);
+    result.extend_from_slice(code);
+
+    result
+}
+
+/// Turns a symbol into an upper case Rust identifier
+fn rust_identifier(symbol: &str) -> String {
+    let identifier: String = symbol.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", identifier)
+    } else {
+        identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorts_symbols_and_makes_valid_identifiers() {
+        assert_eq!("// Generated from assembled 6502 source. Do not edit.\n\n\
+                    pub const _2ND: u16 = 0x0010;\n\
+                    pub const MAIN_LOOP: u16 = 0xC003;\n",
+                   format_rust_constants(&[("main.loop", 0xC003), ("2nd", 0x10)]));
+    }
+
+    #[test]
+    fn writes_the_address_and_length_before_the_code() {
+        assert_eq!(vec![0x00, 0x03, 0x02, 0x00, 0xEA, 0x60], format_apple_binary(0x0300, &[0xEA, 0x60]));
+    }
+}
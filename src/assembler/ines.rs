@@ -0,0 +1,110 @@
+/// How the NES cartridge wires up the nametables
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    /// The cartridge provides its own nametable RAM
+    FourScreen,
+}
+
+/// The cartridge configuration written to the header of an iNES (`.nes`)
+/// file. The default is an NROM cartridge with one 16kb bank of PRG ROM and
+/// no CHR ROM.
+///
+/// # Example
+/// ```
+/// use rs6502::{INesHeader, Mirroring};
+///
+/// let header = INesHeader {
+///     prg_banks: 2,
+///     chr_banks: 1,
+///     mirroring: Mirroring::Vertical,
+///     ..Default::default()
+/// };
+///
+/// assert_eq!([0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x01, 0x00, 0, 0, 0, 0, 0, 0, 0, 0], header.to_bytes());
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct INesHeader {
+    /// The number of 16kb banks of PRG ROM
+    pub prg_banks: u8,
+    /// The number of 8kb banks of CHR ROM. Zero means the cartridge has CHR
+    /// RAM instead.
+    pub chr_banks: u8,
+    pub mapper: u8,
+    pub mirroring: Mirroring,
+    /// Whether the cartridge has battery backed RAM at `$6000-$7FFF`
+    pub battery: bool,
+}
+
+pub const INES_HEADER_SIZE: usize = 16;
+pub const PRG_BANK_SIZE: usize = 0x4000;
+pub const CHR_BANK_SIZE: usize = 0x2000;
+
+impl Default for INesHeader {
+    fn default() -> INesHeader {
+        INesHeader {
+            prg_banks: 1,
+            chr_banks: 0,
+            mapper: 0,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+        }
+    }
+}
+
+impl INesHeader {
+    pub fn new() -> INesHeader {
+        Default::default()
+    }
+
+    /// The size of the PRG ROM in bytes
+    pub fn prg_size(&self) -> usize {
+        self.prg_banks as usize * PRG_BANK_SIZE
+    }
+
+    /// The size of the CHR ROM in bytes
+    pub fn chr_size(&self) -> usize {
+        self.chr_banks as usize * CHR_BANK_SIZE
+    }
+
+    pub fn to_bytes(&self) -> [u8; INES_HEADER_SIZE] {
+        let mut flags6 = (self.mapper & 0x0F) << 4;
+        match self.mirroring {
+            Mirroring::Horizontal => {}
+            Mirroring::Vertical => flags6 |= 0x01,
+            Mirroring::FourScreen => flags6 |= 0x08,
+        }
+        if self.battery {
+            flags6 |= 0x02;
+        }
+
+        let mut bytes = [0x00; INES_HEADER_SIZE];
+        bytes[0..4].copy_from_slice(b"NES\x1A");
+        bytes[4] = self.prg_banks;
+        bytes[5] = self.chr_banks;
+        bytes[6] = flags6;
+        bytes[7] = self.mapper & 0xF0;
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_mapper_across_the_flags() {
+        let header = INesHeader {
+            mapper: 0x42,
+            mirroring: Mirroring::FourScreen,
+            battery: true,
+            ..Default::default()
+        };
+
+        let bytes = header.to_bytes();
+        assert_eq!(0x2A, bytes[6]);
+        assert_eq!(0x40, bytes[7]);
+    }
+}
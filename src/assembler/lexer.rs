@@ -3,13 +3,12 @@
 // I have seen online and so is good enough.
 
 use std;
-use std::error::Error;
 use std::fs::File;
 use std::io::Read;
 use std::iter::Peekable;
+use std::path::{Path, PathBuf};
 use std::str;
-use assembler::token::{ImmediateBase, LexerToken};
-use ::opcodes::OpCode;
+use assembler::token::{ImmediateBase, LexerToken, Span, Spanned};
 
 #[derive(Debug, PartialEq)]
 pub struct LexerError {
@@ -17,18 +16,6 @@ pub struct LexerError {
 }
 
 impl LexerError {
-    fn unexpected_ident<A, B>(expected: A, found: B, line: u32, column: u32) -> LexerError
-        where A: std::fmt::Display,
-              B: std::fmt::Display
-    {
-        LexerError {
-            message: format!("Unexpected identifier. Found '{}', expected '{}'. Line {}, col {}",
-                             found,
-                             expected,
-                             line,
-                             column),
-        }
-    }
     fn out_of_bounds<A>(addr: A, line: u32, column: u32) -> LexerError
         where A: std::fmt::Display
     {
@@ -43,7 +30,7 @@ impl LexerError {
     }
 
     fn unexpected_eof() -> LexerError {
-        LexerError::from(format!("Unexpected end of file"))
+        LexerError::from("Unexpected end of file".to_string())
     }
 
     fn expected_memory_address(line: u32, column: u32) -> LexerError {
@@ -53,11 +40,37 @@ impl LexerError {
     fn unexpected_token(line: u32, column: u32) -> LexerError {
         LexerError::from(format!("Unexpected token. Line {} col {}", line, column))
     }
+
+    fn invalid_include(line: u32) -> LexerError {
+        LexerError::from(format!("Expected <std/file> or \"file\" after .INCLUDE. Line {}", line))
+    }
+
+    fn unknown_standard_include(name: &str, line: u32) -> LexerError {
+        LexerError::from(format!("Unknown standard include '<{}>'. Line {}", name, line))
+    }
+
+    fn includes_too_deep(line: u32) -> LexerError {
+        LexerError::from(format!("Includes nested more than {} deep. Line {}", MAX_INCLUDE_DEPTH, line))
+    }
+}
+
+/// The routines that ship with the crate, included with `.INCLUDE <std/NAME>`
+const STANDARD_INCLUDES: [(&str, &str); 4] = [("std/bcd.inc", include_str!("std/bcd.inc")),
+                                              ("std/math16.inc", include_str!("std/math16.inc")),
+                                              ("std/memcpy.inc", include_str!("std/memcpy.inc")),
+                                              ("std/memset.inc", include_str!("std/memset.inc"))];
+
+/// Guards against files that include themselves
+const MAX_INCLUDE_DEPTH: u32 = 16;
+
+enum Include<'a> {
+    Standard(&'a str),
+    File(&'a str),
 }
 
 impl From<std::io::Error> for LexerError {
     fn from(error: std::io::Error) -> LexerError {
-        LexerError { message: error.description().into() }
+        LexerError { message: error.to_string() }
     }
 }
 
@@ -67,7 +80,7 @@ impl From<String> for LexerError {
     }
 }
 
-impl<'a> From<&'a str> for LexerError {
+impl From<&str> for LexerError {
     fn from(error: &str) -> LexerError {
         LexerError { message: error.into() }
     }
@@ -78,11 +91,20 @@ impl<'a> From<&'a str> for LexerError {
 pub struct Lexer {
     line: u32,
     col: u32,
+    include_depth: u32,
+    include_dir: PathBuf,
+    source_lines: Vec<u32>,
 }
 
 impl Lexer {
     pub fn new() -> Lexer {
-        Lexer { line: 0, col: 0 }
+        Lexer {
+            line: 0,
+            col: 0,
+            include_depth: 0,
+            include_dir: PathBuf::new(),
+            source_lines: Vec::new(),
+        }
     }
 
     /// Returns a vector of Tokens given an input of
@@ -90,7 +112,15 @@ impl Lexer {
     pub fn lex_string<S>(&mut self, input: S) -> Result<Vec<Vec<LexerToken>>, LexerError>
         where S: Into<String>
     {
-        Ok(self.lex(input.into())?)
+        Ok(without_spans(self.lex(input.into())?))
+    }
+
+    /// Returns the tokens of each line of 6502 assembly code, with where
+    /// each token was
+    pub fn lex_string_spanned<S>(&mut self, input: S) -> Result<Vec<Vec<Spanned<LexerToken>>>, LexerError>
+        where S: Into<String>
+    {
+        self.lex(input.into())
     }
 
     /// Returns a vector of Tokens given a file
@@ -103,13 +133,59 @@ impl Lexer {
 
         file.read_to_string(&mut contents)?;
 
-        Ok(self.lex(contents)?)
+        // Files are included relative to the file including them
+        if let Some(dir) = path.as_ref().parent() {
+            self.include_dir = dir.to_path_buf();
+        }
+
+        Ok(without_spans(self.lex(contents)?))
+    }
+
+    /// Returns the line of the lexed source each line of tokens came from.
+    /// Lines of an `.INCLUDE`d file come from the line of the `.INCLUDE`.
+    pub fn source_lines(&self) -> &[u32] {
+        &self.source_lines
     }
 
-    fn advance<I>(&mut self, mut peeker: &mut Peekable<I>)
+    /// Lexes the source of an `.INCLUDE`d file in place of the directive
+    fn lex_include(&mut self, include: Include) -> Result<Vec<Vec<Spanned<LexerToken>>>, LexerError> {
+        if self.include_depth == MAX_INCLUDE_DEPTH {
+            return Err(LexerError::includes_too_deep(self.line));
+        }
+
+        let (source, include_dir) = match include {
+            Include::Standard(name) => {
+                let source = STANDARD_INCLUDES.iter()
+                    .find(|&&(include, _)| include.eq_ignore_ascii_case(name))
+                    .map(|&(_, source)| String::from(source))
+                    .ok_or_else(|| LexerError::unknown_standard_include(name, self.line))?;
+                (source, self.include_dir.clone())
+            }
+            Include::File(name) => {
+                let path = self.include_dir.join(name);
+                let mut source = String::new();
+                File::open(&path)?.read_to_string(&mut source)?;
+                (source, path.parent().map(Path::to_path_buf).unwrap_or_default())
+            }
+        };
+
+        let line = self.line;
+        let parent_dir = std::mem::replace(&mut self.include_dir, include_dir);
+        self.include_depth += 1;
+
+        let result = self.lex(source);
+
+        self.include_depth -= 1;
+        self.include_dir = parent_dir;
+        self.line = line;
+
+        result
+    }
+
+    fn advance<I>(&mut self, peeker: &mut Peekable<I>)
         where I: Iterator<Item = char>
     {
-        if let None = peeker.peek() {
+        if peeker.peek().is_none() {
             return;
         }
 
@@ -118,30 +194,44 @@ impl Lexer {
     }
 
     /// Performs the bulk of the lexing logic
-    fn lex(&mut self, source: String) -> Result<Vec<Vec<LexerToken>>, LexerError> {
+    fn lex(&mut self, source: String) -> Result<Vec<Vec<Spanned<LexerToken>>>, LexerError> {
 
         let mut result = Vec::new();
 
         for line in source.lines() {
+            // Every line lexed since the last one of this source, including
+            // those of an include, came from that line
+            if self.include_depth == 0 {
+                self.source_lines.resize(result.len(), self.line);
+            }
             self.line += 1;
             self.col = 0;
 
             // Skip blank lines
-            if line.trim().len() == 0 {
+            if line.trim().is_empty() {
                 result.push(Vec::new());
                 continue;
             }
 
+            if let Some(include) = parse_include(line) {
+                let include = include.ok_or_else(|| LexerError::invalid_include(self.line))?;
+                result.extend(self.lex_include(include)?);
+                continue;
+            }
+
             let mut tokens = Vec::new();
-            let mut iter = line.chars();
+            let mut spans = Vec::new();
+            let iter = line.chars();
             let mut peeker = iter.peekable();
 
             loop {
                 // Break out if we've reached the end of the line
-                if let None = peeker.peek() {
+                if peeker.peek().is_none() {
                     break;
                 }
 
+                let start = self.col;
+
                 // Consume any leading whitespace voids we're sitting in
                 if peeker.peek().unwrap().is_whitespace() {
                     self.consume_whitespace(&mut peeker);
@@ -181,9 +271,29 @@ impl Lexer {
                 } else {
                     return Err(LexerError::unexpected_token(self.line, self.col + 1));
                 }
+
+                if tokens.len() > spans.len() {
+                    spans.push(Span {
+                        line: self.line,
+                        column: start + 1,
+                        len: self.col - start,
+                    });
+                }
             }
 
-            result.push(tokens);
+            result.push(tokens.into_iter()
+                .zip(spans)
+                .map(|(token, span)| {
+                    Spanned {
+                        node: token,
+                        span,
+                    }
+                })
+                .collect());
+        }
+
+        if self.include_depth == 0 {
+            self.source_lines.resize(result.len(), self.line);
         }
 
         Ok(result)
@@ -191,21 +301,21 @@ impl Lexer {
 
     /// Consumes alphanumeric characters until it reachs something that terminates it
     fn consume_alphanumeric<I>(&mut self,
-                               mut peeker: &mut Peekable<I>)
+                               peeker: &mut Peekable<I>)
                                -> Result<LexerToken, LexerError>
         where I: Iterator<Item = char>
     {
         let mut tok = String::new();
 
         loop {
-            if let None = peeker.peek() {
+            if peeker.peek().is_none() {
                 break;
             }
             let c = *peeker.peek().unwrap();
 
             if c.is_alphanumeric() || c == '_' {
                 tok.push(c);
-                self.advance(&mut peeker);
+                self.advance(peeker);
             } else {
                 break;
             }
@@ -215,7 +325,7 @@ impl Lexer {
     }
 
     /// Decides the base of a number we are about to consume
-    fn consume_number<I>(&mut self, mut peeker: &mut Peekable<I>) -> Result<LexerToken, LexerError>
+    fn consume_number<I>(&mut self, peeker: &mut Peekable<I>) -> Result<LexerToken, LexerError>
         where I: Iterator<Item = char>
     {
         // Default to base16
@@ -224,12 +334,12 @@ impl Lexer {
         let c = *peeker.peek().unwrap();
         if c == '$' {
             // The number is base16
-            self.advance(&mut peeker);
-            self.consume_digits(&mut peeker, &base)
+            self.advance(peeker);
+            self.consume_digits(peeker, &base)
         } else if c == '#' {
             // The number is base 10
-            self.advance(&mut peeker);
-            if let None = peeker.peek() {
+            self.advance(peeker);
+            if peeker.peek().is_none() {
                 return Err(LexerError::unexpected_eof());
             }
 
@@ -237,10 +347,10 @@ impl Lexer {
             if *peeker.peek().unwrap() == '$' {
                 // Skip over the dollar sign and revert to base16
                 base = ImmediateBase::Base16;
-                self.advance(&mut peeker);
+                self.advance(peeker);
             }
 
-            self.consume_digits(&mut peeker, &base)
+            self.consume_digits(peeker, &base)
         } else {
             Err(LexerError::error_consuming_number(self.line, self.col))
         }
@@ -248,7 +358,7 @@ impl Lexer {
 
     /// Consumes number of a specified base until it can't anymore
     fn consume_digits<I>(&mut self,
-                         mut peeker: &mut Peekable<I>,
+                         peeker: &mut Peekable<I>,
                          base: &ImmediateBase)
                          -> Result<LexerToken, LexerError>
         where I: Iterator<Item = char>
@@ -261,30 +371,30 @@ impl Lexer {
             16
         };
         loop {
-            if let None = peeker.peek() {
+            if peeker.peek().is_none() {
                 break;
             }
             let c = *peeker.peek().unwrap();
             if c.is_digit(b) {
                 result.push(c);
-                self.advance(&mut peeker);
+                self.advance(peeker);
             } else {
                 break;
             }
         }
 
-        Ok(LexerToken::Immediate(result.to_uppercase(), base.clone()))
+        Ok(LexerToken::Immediate(result.to_uppercase(), *base))
     }
 
     /// Consumes a memory address
-    fn consume_address<I>(&mut self, mut peeker: &mut Peekable<I>) -> Result<LexerToken, LexerError>
+    fn consume_address<I>(&mut self, peeker: &mut Peekable<I>) -> Result<LexerToken, LexerError>
         where I: Iterator<Item = char>
     {
         // Grab the actual numbers
-        if let LexerToken::Immediate(val, _) = self.consume_number(&mut peeker)? {
+        if let LexerToken::Immediate(val, _) = self.consume_number(peeker)? {
             let val = val.to_uppercase();
-            // if the length is greater than 4.. its outside the memory bounds
-            if val.len() > 4 {
+            // if there are more than 4 significant digits.. its outside the memory bounds
+            if val.trim_start_matches('0').len() > 4 {
                 return Err(LexerError::out_of_bounds(&val, self.line, self.col - val.len() as u32));
             }
 
@@ -298,23 +408,54 @@ impl Lexer {
     /// Consumes whitespace characters until it encounters a
     /// non-whitespace character
     #[inline(always)]
-    fn consume_whitespace<I>(&mut self, mut peeker: &mut Peekable<I>)
+    fn consume_whitespace<I>(&mut self, peeker: &mut Peekable<I>)
         where I: Iterator<Item = char>
     {
         loop {
-            if let None = peeker.peek() {
+            if peeker.peek().is_none() {
                 break;
             } else {
                 if !peeker.peek().unwrap().is_whitespace() {
                     break;
                 } else {
-                    self.advance(&mut peeker);
+                    self.advance(peeker);
                 }
             }
         }
     }
 }
 
+fn without_spans(lines: Vec<Vec<Spanned<LexerToken>>>) -> Vec<Vec<LexerToken>> {
+    lines.into_iter().map(|line| line.into_iter().map(|token| token.node).collect()).collect()
+}
+
+/// Returns the target of an `.INCLUDE` directive, or `Some(None)` if the
+/// line is an `.INCLUDE` without a valid target
+fn parse_include(line: &str) -> Option<Option<Include<'_>>> {
+    let line = line.split(';').next().unwrap_or("").trim();
+    match line.get(..8) {
+        Some(directive) if directive.eq_ignore_ascii_case(".INCLUDE") => {}
+        _ => return None,
+    }
+
+    let rest = &line[8..];
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let target = rest.trim();
+    if target.len() < 2 {
+        return Some(None);
+    }
+
+    let name = &target[1..target.len() - 1];
+    Some(match (target.chars().next(), target.chars().last()) {
+        (Some('<'), Some('>')) => Some(Include::Standard(name)),
+        (Some('"'), Some('"')) => Some(Include::File(name)),
+        _ => None,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -464,4 +605,51 @@ mod tests {
                      LexerToken::Immediate("FF".into(), ImmediateBase::Base16)],
                    &tokens[2][..]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn can_lex_addresses_with_leading_zeros() {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.lex_string("
+            LDA $04400
+        ")
+            .unwrap();
+
+        assert_eq!(&[LexerToken::Ident("LDA".into()), LexerToken::Address("04400".into())],
+                   &tokens[1][..]);
+    }
+
+    #[test]
+    fn errors_on_addresses_larger_than_memory() {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.lex_string("
+            LDA $14400
+        ");
+
+        assert_eq!(Err(LexerError::out_of_bounds("14400", 2, 17)), tokens);
+    }
+
+    #[test]
+    fn includes_the_standard_library_in_place() {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.lex_string("
+            .include <STD/BCD.INC> ; conversions
+            NOP
+        ")
+            .unwrap();
+
+        let nop = tokens.iter().position(|line| line == &[LexerToken::Ident("NOP".into())]).unwrap();
+        assert!(nop > 2);
+        assert_eq!(Err(LexerError::unknown_standard_include("std/missing.inc", 1)),
+                   Lexer::new().lex_string(".INCLUDE <std/missing.inc>"));
+    }
+
+    #[test]
+    fn errors_on_includes_without_a_target() {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.lex_string("
+            .INCLUDE std/bcd.inc
+        ");
+
+        assert_eq!(Err(LexerError::invalid_include(2)), tokens);
+    }
+}
@@ -0,0 +1,84 @@
+use assembler::assembler::CodeSegment;
+
+/// Formats a linker-style map of assembled code: the address range and
+/// size of each segment, the gaps between them and the address of each
+/// symbol, all sorted by address. Empty segments are left out.
+pub fn format_map(segments: &[CodeSegment], symbols: &[(&str, u16)]) -> String {
+    let mut ranges: Vec<(usize, usize)> = segments.iter()
+        .filter(|segment| !segment.code.is_empty())
+        .map(|segment| (segment.address as usize, segment.address as usize + segment.code.len() - 1))
+        .collect();
+    ranges.sort();
+
+    let mut result = String::from("SEGMENTS\n");
+    for &(start, end) in &ranges {
+        result.push_str(&format_range(start, end));
+    }
+
+    result.push_str("\nGAPS\n");
+    for pair in ranges.windows(2) {
+        let (end, start) = (pair[0].1 + 1, pair[1].0);
+        if start > end {
+            result.push_str(&format_range(end, start - 1));
+        }
+    }
+
+    let mut symbols = symbols.to_vec();
+    symbols.sort_by_key(|&(symbol, addr)| (addr, symbol));
+
+    result.push_str("\nLABELS\n");
+    for (symbol, addr) in symbols {
+        result.push_str(&format!("${:04X}  {}\n", addr, symbol));
+    }
+
+    result
+}
+
+/// Formats an inclusive range of addresses and its size
+fn format_range(start: usize, end: usize) -> String {
+    let size = end - start + 1;
+    format!("${:04X}-${:04X}  {} {}\n",
+            start,
+            end,
+            size,
+            if size == 1 { "byte" } else { "bytes" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_segments_and_gaps_in_address_order() {
+        let segments = vec![CodeSegment {
+                                address: 0xD000,
+                                code: vec![0x00],
+                            },
+                            CodeSegment {
+                                address: 0x0300,
+                                code: Vec::new(),
+                            },
+                            CodeSegment {
+                                address: 0xC000,
+                                code: vec![0xEA; 4],
+                            },
+                            CodeSegment {
+                                address: 0xC004,
+                                code: vec![0x60],
+                            }];
+
+        assert_eq!("SEGMENTS\n\
+                    $C000-$C003  4 bytes\n\
+                    $C004-$C004  1 byte\n\
+                    $D000-$D000  1 byte\n\
+                    \n\
+                    GAPS\n\
+                    $C005-$CFFF  4091 bytes\n\
+                    \n\
+                    LABELS\n\
+                    $C000  B\n\
+                    $C000  C\n\
+                    $D000  A\n",
+                   format_map(&segments, &[("A", 0xD000), ("C", 0xC000), ("B", 0xC000)]));
+    }
+}
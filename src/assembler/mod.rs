@@ -1,9 +1,31 @@
 
+#[allow(clippy::module_inception)]
 mod assembler;
+mod ast;
+mod checksum;
+mod directive;
+mod encoder;
+mod export;
+mod ines;
 mod token;
 mod lexer;
+mod map;
+mod options;
 mod parser;
+mod peephole;
+mod pseudo;
+mod relocation;
+mod xref;
 
-pub use self::assembler::{Assembler, CodeSegment};
-pub use self::token::LexerToken;
-pub use self::lexer::Lexer;
\ No newline at end of file
+pub use self::assembler::{Assembler, AssemblerError, AssemblerWarning, CodeSegment};
+pub use self::ast::{parse_statements, tokenize, Statement};
+pub use self::checksum::ChecksumAlgorithm;
+pub use self::directive::DirectiveOutput;
+pub use self::encoder::{Encoder, Label};
+pub use self::ines::{INesHeader, Mirroring};
+pub use self::options::AssemblerOptions;
+pub use self::peephole::Optimization;
+pub use self::pseudo::Expansion;
+pub use self::relocation::{relocate, RelocatableCode};
+pub use self::token::{ImmediateBase, LexerToken, Span, Spanned};
+pub use self::xref::CrossReference;
@@ -0,0 +1,28 @@
+/// Settings that apply to everything an Assembler assembles
+///
+/// # Example
+/// ```
+/// use rs6502::{Assembler, AssemblerOptions};
+///
+/// let mut assembler = Assembler::with_options(AssemblerOptions { origin: 0xC000 });
+/// let segments = assembler.assemble_string("
+///     NOP
+///     .ORG $E000
+///     NOP
+/// ", None).unwrap();
+///
+/// assert_eq!(0xC000, segments[0].address);
+/// assert_eq!(0xE000, segments[1].address);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AssemblerOptions {
+    /// Where code before the first `.ORG` directive is placed, unless the
+    /// call to assemble gives an origin of its own. Defaults to `$0000`.
+    pub origin: u16,
+}
+
+impl AssemblerOptions {
+    pub fn new() -> AssemblerOptions {
+        Default::default()
+    }
+}
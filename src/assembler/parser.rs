@@ -1,11 +1,12 @@
 use std;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::Peekable;
 
-use byteorder::{ByteOrder, LittleEndian};
-
-use ::opcodes::{AddressingMode, OpCode};
+use ::opcodes::{AddressingMode, CpuVariant, OpCode};
+use assembler::checksum::ChecksumAlgorithm;
+use assembler::pseudo::{self, Expansion};
 use assembler::token::{ImmediateBase, LexerToken, ParserToken};
+use assembler::xref::{CrossReference, CrossReferenceTable};
 
 #[derive(Debug, PartialEq)]
 pub struct ParserError {
@@ -29,10 +30,6 @@ impl ParserError {
         ParserError::from(format!("Unexpected end of line. Line {}", line))
     }
 
-    fn expected_eol(line: u32) -> ParserError {
-        ParserError::from(format!("Expected end of line. Line {}", line))
-    }
-
     fn cannot_parse_address(line: u32) -> ParserError {
         ParserError::from(format!("Unable to parse address. Line {}", line))
     }
@@ -53,9 +50,20 @@ impl ParserError {
         ParserError::from(format!("Unable to parse immedate value. Line {}", line))
     }
 
+    fn invalid_address_range(line: u32) -> ParserError {
+        ParserError::from(format!("Invalid address range. Line {}", line))
+    }
+
     fn unknown_identifier(line: u32) -> ParserError {
         ParserError::from(format!("Unknown identifier. Line {}", line))
     }
+
+    fn not_available_on_variant(opcode: &OpCode, variant: CpuVariant, line: u32) -> ParserError {
+        ParserError::from(format!("Instruction '{}' is not available on the selected CPU ({}). Line {}",
+                                  opcode.mnemonic,
+                                  variant,
+                                  line))
+    }
 }
 
 impl From<String> for ParserError {
@@ -64,7 +72,7 @@ impl From<String> for ParserError {
     }
 }
 
-impl<'a> From<&'a str> for ParserError {
+impl From<&str> for ParserError {
     fn from(error: &str) -> ParserError {
         ParserError { message: error.into() }
     }
@@ -75,7 +83,15 @@ pub struct Variable(LexerToken);
 
 pub struct Parser {
     symbol_table: HashMap<String, Variable>,
+    zero_page_symbols: HashSet<String>,
+    in_zero_page_segment: bool,
+    custom_directives: Vec<String>,
+    cross_references: CrossReferenceTable,
+    pseudo_ops: bool,
+    expansions: Vec<Expansion>,
+    variant: Option<CpuVariant>,
     line: u32,
+    token_lines: Vec<u32>,
 }
 
 /// Parser processes a list of 6502 Assembly tokens
@@ -83,37 +99,85 @@ impl Parser {
     pub fn new() -> Parser {
         Parser {
             symbol_table: HashMap::new(),
+            zero_page_symbols: HashSet::new(),
+            in_zero_page_segment: false,
+            custom_directives: Vec::new(),
+            cross_references: CrossReferenceTable::new(),
+            pseudo_ops: false,
+            expansions: Vec::new(),
+            variant: None,
             line: 0,
+            token_lines: Vec::new(),
         }
     }
 
+    /// Creates a Parser that accepts the given directive names in addition
+    /// to the built in directives. The tokens following a custom directive
+    /// are passed through untouched for the Assembler to handle.
+    pub fn with_custom_directives(directives: Vec<String>) -> Parser {
+        Parser { custom_directives: directives, ..Parser::new() }
+    }
+
+    /// Returns where each symbol was defined and referenced in the
+    /// source code parsed so far
+    pub fn cross_references(&self) -> Vec<CrossReference> {
+        self.cross_references.to_vec()
+    }
+
+    /// Accepts the 16-bit pseudo-instructions `LDAX` and `STAX`, which are
+    /// expanded into pairs of real instructions
+    pub fn enable_pseudo_ops(&mut self) {
+        self.pseudo_ops = true;
+    }
+
+    /// Rejects instructions the given processor doesn't have, such as the
+    /// 65C02's `STZ` on an NMOS 6502. Every instruction is accepted until
+    /// a variant is set.
+    pub fn set_variant(&mut self, variant: CpuVariant) {
+        self.variant = Some(variant);
+    }
+
+    /// Returns every pseudo-instruction expanded in the source code parsed
+    /// so far
+    pub fn expansions(&self) -> Vec<Expansion> {
+        self.expansions.clone()
+    }
+
+    /// Returns the line of lexer tokens each token of the last `parse`
+    /// came from, counting from 1
+    pub fn token_lines(&self) -> &[u32] {
+        &self.token_lines
+    }
+
     pub fn parse(&mut self, tokens: Vec<Vec<LexerToken>>) -> Result<Vec<ParserToken>, ParserError> {
         let mut result = Vec::new();
 
         for line in &tokens {
-            let mut added_label = false;
+            // Every token added since the last line came from that line
+            self.token_lines.resize(result.len(), self.line);
             self.line += 1;
 
             let mut peeker = line.iter().peekable();
 
             // Skip blank lines
-            if let None = peeker.peek() {
+            if peeker.peek().is_none() {
                 continue;
             }
 
             let next = *peeker.peek().unwrap();
 
-            if let &LexerToken::Ident(ref ident) = next {
+            if let LexerToken::Ident(ident) = next {
                 // Check if this is an opcode
-                if Self::is_opcode(ident.clone()) {
+                if self.is_instruction(ident) {
                     // Yep its an opcode, lets figure out its addressing mode
-                    let mut opcode = self.consume_opcode(&mut peeker, ident.clone())?;
+                    let mut opcode = self.consume_instruction(&mut peeker, ident)?;
                     result.append(&mut opcode);
                 } else {
                     // Skip the ident and we'll check what is next
                     let original_ident = peeker.next().unwrap();
                     // if there is nothing else - lets mark this as a Label and move on
-                    if let None = peeker.peek() {
+                    if peeker.peek().is_none() {
+                        self.cross_references.define(ident.clone(), self.line);
                         result.push(ParserToken::Label(ident.clone()));
                         continue;
                     }
@@ -121,67 +185,102 @@ impl Parser {
                     // A colon after the ident also indicates a label
                     let next = *peeker.peek().unwrap();
                     if let &LexerToken::Colon = next {
+                        self.cross_references.define(ident.clone(), self.line);
                         result.push(ParserToken::Label(ident.clone()));
                         continue;
                     }
 
                     // Is the next one a label as well? Thats an error:
-                    if let &LexerToken::Ident(ref ident) = next {
+                    if let LexerToken::Ident(ident) = next {
                         // Lets add the original as a label
-                        if let &LexerToken::Ident(ref original_ident) = original_ident {
+                        if let LexerToken::Ident(original_ident) = original_ident {
+                            self.cross_references.define(original_ident.clone(), self.line);
                             result.push(ParserToken::Label(original_ident.clone()));
                         }
 
-                        if !Self::is_opcode(ident.clone()) {
+                        if !self.is_instruction(ident) {
                             return Err(ParserError::expected_instruction(self.line));
                         } else {
                             // Oh it is an opcode after the label - consume it
-                            let mut opcode = self.consume_opcode(&mut peeker, ident.clone())?;
+                            let mut opcode = self.consume_instruction(&mut peeker, ident)?;
                             result.append(&mut opcode);
                         }
                     } else if let &LexerToken::Assignment = next {
                         // Its a variable assignment - lets store the variable in the symbol table
                         peeker.next(); // Jump the assignment operator
-                        if let None = peeker.peek() {
+                        if peeker.peek().is_none() {
                             return Err(ParserError::unexpected_eol(self.line));
                         }
 
+                        self.cross_references.define(ident.clone(), self.line);
+
                         let next = *peeker.peek().unwrap();
-                        if let &LexerToken::Address(ref address) = next {
+                        if let LexerToken::Address(address) = next {
                             self.symbol_table
                                 .insert(ident.clone(),
                                         Variable(LexerToken::Address(address.clone())));
-                        } else if let &LexerToken::Ident(ref var_ident) = next {
+                        } else if let LexerToken::Ident(var_ident) = next {
                             // Its another variable
+                            self.cross_references.reference(var_ident.clone(), self.line);
                             self.symbol_table
                                 .insert(ident.clone(),
                                         Variable(LexerToken::Ident(var_ident.clone())));
                         }
+
+                        if self.in_zero_page_segment {
+                            self.zero_page_symbols.insert(ident.clone());
+                        }
+                        self.validate_zero_page_symbol(ident)?;
                     }
                 }
             } else if let &LexerToken::Period = next {
                 // Its a directive? Lets make sure:
                 peeker.next();
-                if let None = peeker.peek() {
+                if peeker.peek().is_none() {
                     return Err(ParserError::unexpected_eol(self.line));
                 }
 
                 let next = *peeker.peek().unwrap();
-                if let &LexerToken::Ident(ref directive) = next {
+                if let LexerToken::Ident(directive) = next {
                     // Lets check if its a valid directive:
                     let directive = directive.to_uppercase();
                     match &directive[..] {
                         "ORG" => {
+                            self.in_zero_page_segment = false;
                             result.push(self.consume_org_directive(&mut peeker)?);
                         }
+                        "ZP" => {
+                            self.consume_zero_page_directive(&mut peeker)?;
+                        }
+                        "ZEROPAGE" => {
+                            self.in_zero_page_segment = true;
+                        }
+                        "CODE" => {
+                            self.in_zero_page_segment = false;
+                        }
                         "BYTE" => {
                             result.push(self.consume_byte_directive(&mut peeker)?);
                         }
+                        "CHECKSUM" => {
+                            result.push(self.consume_checksum_directive(&mut peeker, ChecksumAlgorithm::Sum8)?);
+                        }
+                        "CRC" => {
+                            result.push(self.consume_checksum_directive(&mut peeker, ChecksumAlgorithm::Crc16)?);
+                        }
+                        "SIZE" => {
+                            result.push(self.consume_size_directive(&mut peeker)?);
+                        }
+                        _ if self.custom_directives.contains(&directive) => {
+                            peeker.next();
+                            let args = peeker.cloned().collect();
+                            result.push(ParserToken::CustomDirective(directive, args));
+                        }
                         _ => return Err(ParserError::unknown_identifier(self.line)),
                     }
                 }
             }
         }
+        self.token_lines.resize(result.len(), self.line);
 
         Ok(result)
     }
@@ -189,15 +288,111 @@ impl Parser {
     fn is_opcode<S>(mnemonic: S) -> bool
         where S: Into<String>
     {
-        if let Some(opcode) = OpCode::from_mnemonic(mnemonic) {
-            true
+        OpCode::from_mnemonic(mnemonic).is_some()
+    }
+
+    /// Returns true for opcodes, and for pseudo-instructions when they are
+    /// enabled
+    fn is_instruction(&self, ident: &str) -> bool {
+        Self::is_opcode(ident) || (self.pseudo_ops && pseudo::is_pseudo_op(ident))
+    }
+
+    fn consume_instruction<'a, I>(&mut self,
+                                  peeker: &mut Peekable<I>,
+                                  ident: &str)
+                                  -> Result<Vec<ParserToken>, ParserError>
+        where I: Iterator<Item = &'a LexerToken>
+    {
+        let tokens = if Self::is_opcode(ident) {
+            self.consume_opcode(peeker, ident.to_string())?
         } else {
-            false
+            self.consume_pseudo_op(peeker, ident)?
+        };
+
+        if let Some(variant) = self.variant {
+            for token in &tokens {
+                if let ParserToken::OpCode(ref opcode) = *token {
+                    if !opcode.is_available_on(variant) {
+                        return Err(ParserError::not_available_on_variant(opcode, variant, self.line));
+                    }
+                }
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Expands a pseudo-instruction into the pair of instructions it stands
+    /// for. `LDAX` loads the low byte of a 16-bit value into A and the high
+    /// byte into X, from an immediate value or from an address and the one
+    /// after it. `STAX` stores A and X at an address and the one after it.
+    fn consume_pseudo_op<'a, I>(&mut self,
+                                peeker: &mut Peekable<I>,
+                                ident: &str)
+                                -> Result<Vec<ParserToken>, ParserError>
+        where I: Iterator<Item = &'a LexerToken>
+    {
+        let mnemonic = ident.to_uppercase();
+
+        // Jump over the pseudo-instruction
+        peeker.next();
+        let operand = match peeker.next() {
+            Some(operand) => operand.clone(),
+            None => return Err(ParserError::unexpected_eol(self.line)),
+        };
+        if peeker.peek().is_some() {
+            return Err(ParserError::unexpected_token(self.line));
+        }
+
+        let source = format!("{} {}", mnemonic, pseudo::format_operand(&operand));
+        let operand = if let LexerToken::Ident(ref variable) = operand {
+            self.cross_references.reference(variable.clone(), self.line);
+            self.get_variable_value(variable.clone())?.0
+        } else {
+            operand
+        };
+
+        let (low, high) = match (&mnemonic[..], operand) {
+            ("LDAX", LexerToken::Immediate(ref value, base)) => {
+                let radix = if base == ImmediateBase::Base10 { 10 } else { 16 };
+                let value = u16::from_str_radix(value, radix)
+                    .map_err(|_| ParserError::cannot_parse_immediate(self.line))?;
+
+                (LexerToken::Immediate(format!("{:02X}", value & 0xFF), ImmediateBase::Base16),
+                 LexerToken::Immediate(format!("{:02X}", value >> 8), ImmediateBase::Base16))
+            }
+            (_, LexerToken::Address(ref address)) => {
+                let next = self.parse_address(address)?
+                    .checked_add(1)
+                    .ok_or_else(|| ParserError::address_out_of_bounds(self.line))?;
+
+                (LexerToken::Address(address.clone()),
+                 LexerToken::Address(format!("{:0width$X}", next, width = address.len())))
+            }
+            _ => return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line)),
+        };
+
+        let (first, second) = if mnemonic == "LDAX" { ("LDA", "LDX") } else { ("STA", "STX") };
+        let mut result = Vec::new();
+        let mut instructions = Vec::new();
+        for &(opcode, ref operand) in &[(first, low), (second, high)] {
+            instructions.push(format!("{} {}", opcode, pseudo::format_operand(operand)));
+
+            let line = [LexerToken::Ident(opcode.into()), operand.clone()];
+            result.append(&mut self.consume_opcode(&mut line.iter().peekable(), opcode)?);
         }
+
+        self.expansions.push(Expansion {
+            line: self.line,
+            source,
+            instructions,
+        });
+
+        Ok(result)
     }
 
     fn consume_opcode<'a, I, S>(&mut self,
-                                mut peeker: &mut Peekable<I>,
+                                peeker: &mut Peekable<I>,
                                 ident: S)
                                 -> Result<Vec<ParserToken>, ParserError>
         where I: Iterator<Item = &'a LexerToken>,
@@ -208,37 +403,41 @@ impl Parser {
 
         // If there is nothing else after this opcode.. lets check if there is
         // a matching opcode with an implied addressing mode
-        if let None = peeker.peek() {
+        if peeker.peek().is_none() {
             if let Some(opcode) =
                    OpCode::from_mnemonic_and_addressing_mode(ident.clone(), AddressingMode::Implied) {
-                return Ok(vec![ParserToken::OpCode(opcode)]);
+                Ok(vec![ParserToken::OpCode(opcode)])
             } else if let Some(opcode) =
                           OpCode::from_mnemonic_and_addressing_mode(ident.clone(),
                                                                     AddressingMode::Accumulator) {
-                return Ok(vec![ParserToken::OpCode(opcode)]);
+                Ok(vec![ParserToken::OpCode(opcode)])
             } else {
-                return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
+                Err(ParserError::invalid_opcode_addressing_mode_combination(self.line))
             }
         } else {
             // Check the next token, is it an address or identifier?
             let mut next = (*peeker.peek().unwrap()).clone();
+            let mut zero_page = false;
             next = if let LexerToken::Ident(ref label) = next {
                 // Lets see if its a variable?
+                self.cross_references.reference(label.clone(), self.line);
                 if let Ok(variable) = self.get_variable_value(label.clone()) {
+                    zero_page = self.is_zero_page_symbol(label);
                     variable.clone().0
+                } else if self.is_zero_page_symbol(label) {
+                    // Zero page symbols must be given a value before use
+                    return Err(ParserError::unknown_identifier(self.line));
                 } else {
-                    // takes care of this later
-                    let ident = ident.clone().into().to_uppercase();
-                    let addressing_mode = if ident == "JMP" || ident == "JSR" {
-                        AddressingMode::Absolute
-                    } else {
-                        AddressingMode::Relative
-                    };
-
-                    if let Some(opcode) =
-                           OpCode::from_mnemonic_and_addressing_mode(ident.clone(), addressing_mode) {
+                    // takes care of this later. A label is a branch target for
+                    // instructions with relative addressing, and an absolute
+                    // address for everything else
+                    let ident = ident.clone().into();
+                    let opcode = OpCode::from_mnemonic_and_addressing_mode(ident.clone(), AddressingMode::Relative)
+                        .or_else(|| OpCode::from_mnemonic_and_addressing_mode(ident, AddressingMode::Absolute));
+
+                    if let Some(opcode) = opcode {
                         return Ok(vec![ParserToken::OpCode(opcode),
-                                       ParserToken::LabelArg(label.clone())]);
+                                       ParserToken::LabelArg(self.resolve_label_name(label.clone()))]);
                     } else {
                         return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
                     }
@@ -248,19 +447,67 @@ impl Parser {
             };
             if let LexerToken::Address(ref address) = next {
                 // Its an address. What sort of address?
-                if address.len() <= 4 {
-                    // Its zero-page or absolute.. lets try and convert it to a raw byte
-                    let addressing_mode = if address.len() <= 2 {
-                        // Its a 1 byte address
-                        AddressingMode::ZeroPage
+                // Its zero-page or absolute.. lets try and convert it to a raw byte
+                let addressing_mode = if zero_page || self.is_zero_page_address(address)? {
+                    // Its a 1 byte address
+                    AddressingMode::ZeroPage
+                } else {
+                    AddressingMode::Absolute
+                };
+                let bytes = if zero_page {
+                    vec![self.parse_zero_page_pointer(address)?]
+                } else {
+                    self.parse_address_bytes(address)?
+                };
+                // consume the address and peek what is next:
+                peeker.next();
+                if peeker.peek().is_none() {
+                    // Nothing else.. find an opcode with this ident and addressing mode
+                    if let Some(opcode) =
+                           OpCode::from_mnemonic_and_addressing_mode(ident, addressing_mode) {
+                        // We found one..
+                        let mut final_vec = vec![ParserToken::OpCode(opcode)];
+                        // Push the address bytes into the result
+                        for b in bytes {
+                            final_vec.push(ParserToken::RawByte(b));
+                        }
+                        return Ok(final_vec);
                     } else {
-                        AddressingMode::Absolute
-                    };
-                    let bytes = self.parse_address_bytes(address)?;
-                    // consume the address and peek what is next:
+                        return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
+                    }
+                }
+
+                // There is something after this address - if its
+                // a comma, then we're peachy. If its something else.. Thats
+                // an error.
+                let next = *peeker.peek().unwrap();
+                if let &LexerToken::Comma = next {
+                    // Yes, its a comma. Consume it and check what is next
                     peeker.next();
-                    if let None = peeker.peek() {
-                        // Nothing else.. find an opcode with this ident and addressing mode
+                    // If theres nothing after the comma thats an error
+                    if peeker.peek().is_none() {
+                        return Err(ParserError::unexpected_eol(self.line));
+                    }
+
+                    let next = *peeker.peek().unwrap();
+                    if let LexerToken::Ident(register) = next {
+                        let register = register.to_uppercase();
+                        if register != "X" && register != "Y" {
+                            return Err(ParserError::unexpected_token(self.line));
+                        }
+                        let addressing_mode = if register == "X" {
+                            if addressing_mode == AddressingMode::ZeroPage {
+                                AddressingMode::ZeroPageX
+                            } else {
+                                AddressingMode::AbsoluteX
+                            }
+                        } else {
+                            if addressing_mode == AddressingMode::ZeroPage {
+                                AddressingMode::ZeroPageY
+                            } else {
+                                AddressingMode::AbsoluteY
+                            }
+                        };
                         if let Some(opcode) =
                                OpCode::from_mnemonic_and_addressing_mode(ident, addressing_mode) {
                             // We found one..
@@ -269,73 +516,22 @@ impl Parser {
                             for b in bytes {
                                 final_vec.push(ParserToken::RawByte(b));
                             }
-                            return Ok(final_vec);
-                        } else {
-                            return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
-                        }
-                    }
-
-                    // There is something after this address - if its
-                    // a comma, then we're peachy. If its something else.. Thats
-                    // an error.
-                    let next = *peeker.peek().unwrap();
-                    if let &LexerToken::Comma = next {
-                        // Yes, its a comma. Consume it and check what is next
-                        peeker.next();
-                        // If theres nothing after the comma thats an error
-                        if let None = peeker.peek() {
-                            return Err(ParserError::unexpected_eol(self.line));
-                        }
-
-                        let next = *peeker.peek().unwrap();
-                        if let &LexerToken::Ident(ref register) = next {
-                            let register = register.to_uppercase();
-                            if register != "X" && register != "Y" {
-                                return Err(ParserError::unexpected_token(self.line));
-                            }
-                            let addressing_mode = if register == "X" {
-                                if addressing_mode == AddressingMode::ZeroPage {
-                                    AddressingMode::ZeroPageX
-                                } else {
-                                    AddressingMode::AbsoluteX
-                                }
-                            } else {
-                                if addressing_mode == AddressingMode::ZeroPage {
-                                    AddressingMode::ZeroPageY
-                                } else {
-                                    AddressingMode::AbsoluteY
-                                }
-                            };
-                            if let Some(opcode) =
-                                   OpCode::from_mnemonic_and_addressing_mode(ident, addressing_mode) {
-                                // We found one..
-                                let mut final_vec = vec![ParserToken::OpCode(opcode)];
-                                // Push the address bytes into the result
-                                for b in bytes {
-                                    final_vec.push(ParserToken::RawByte(b));
-                                }
-                                return Ok(final_vec);
-                            } else {
-                                return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
-                            }
+                            Ok(final_vec)
                         } else {
-                            return Err(ParserError::unexpected_token(self.line));
+                            Err(ParserError::invalid_opcode_addressing_mode_combination(self.line))
                         }
                     } else {
-                        return Err(ParserError::unexpected_token(self.line));
+                        Err(ParserError::unexpected_token(self.line))
                     }
-                    let next = *peeker.peek().unwrap();
-
                 } else {
-                    return Err(ParserError::cannot_parse_address(self.line));
+                    Err(ParserError::unexpected_token(self.line))
                 }
             } else if let LexerToken::OpenParenthesis = next {
                 // We're moving into Indirect memory addressing
-                let addressing_mode = AddressingMode::Indirect;
                 peeker.next(); // skip the opening paren
 
                 // If we have nothing else, thats an error
-                if let None = peeker.peek() {
+                if peeker.peek().is_none() {
                     return Err(ParserError::unexpected_eol(self.line));
                 }
 
@@ -343,36 +539,35 @@ impl Parser {
                 let mut next = (*peeker.peek().unwrap()).clone();
                 next = if let LexerToken::Ident(ref label) = next {
                     // Lets see if its a variable?
+                    self.cross_references.reference(label.clone(), self.line);
                     if let Ok(variable) = self.get_variable_value(label.clone()) {
                         variable.clone().0
                     } else {
-                        next.clone()
+                        // Its a label - the indirect jump takes care of this later
+                        let label = self.resolve_label_name(label.clone());
+                        return self.consume_indirect_label(peeker, ident, label);
                     }
                 } else {
                     next.clone()
                 };
                 if let LexerToken::Address(ref address) = next {
-                    if address.len() > 4 {
-                        return Err(ParserError::address_out_of_bounds(self.line));
-                    }
-
                     let bytes = self.parse_address_bytes(address)?;
 
                     // The address is the right length - lets jump over that and peek next
                     peeker.next();
-                    if let None = peeker.peek() {
+                    if peeker.peek().is_none() {
                         return Err(ParserError::unexpected_eol(self.line));
                     }
                     let next = *peeker.peek().unwrap();
                     if let &LexerToken::Comma = next {
                         // If its a comma - lets target IndirectX
                         peeker.next(); // skip the comma
-                        if let None = peeker.peek() {
+                        if peeker.peek().is_none() {
                             return Err(ParserError::unexpected_eol(self.line));
                         }
 
                         let next = *peeker.peek().unwrap();
-                        if let &LexerToken::Ident(ref register) = next {
+                        if let LexerToken::Ident(register) = next {
                             let register = register.to_uppercase();
                             if register != "X" {
                                 return Err(ParserError::unexpected_token(self.line));
@@ -380,39 +575,62 @@ impl Parser {
 
                             peeker.next(); // Jump over the X
 
-                            if let None = peeker.peek() {
+                            if peeker.peek().is_none() {
                                 return Err(ParserError::unexpected_eol(self.line));
                             }
 
                             let next = *peeker.peek().unwrap();
                             if let &LexerToken::CloseParenthesis = next {
                                 peeker.next();
+                                // The 65C02's JMP ($0000,X) indexes a 16-bit pointer
+                                if let Some(opcode) =
+                                       OpCode::from_mnemonic_and_addressing_mode(ident.clone(),
+                                                                                 AddressingMode::AbsoluteIndexedIndirect) {
+                                    if self.is_zero_page_address(address)? {
+                                        return Err(ParserError::address_out_of_bounds(self.line));
+                                    }
+                                    let mut final_vec = vec![ParserToken::OpCode(opcode)];
+                                    for b in bytes {
+                                        final_vec.push(ParserToken::RawByte(b));
+                                    }
+                                    return Ok(final_vec);
+                                }
+
                                 // Lets make sure we can find an appropriate opcode
                                 if let Some(opcode) = OpCode::from_mnemonic_and_addressing_mode(ident, AddressingMode::IndirectX) {
                                     // We have everything we need now.. lets return an IndirectX opcode
                                     // accompanied by the address
-                                    return Ok(vec![ParserToken::OpCode(opcode), ParserToken::RawByte(bytes[0])]);
+                                    let pointer = self.parse_zero_page_pointer(address)?;
+                                    Ok(vec![ParserToken::OpCode(opcode), ParserToken::RawByte(pointer)])
                                 } else {
-                                    return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
+                                    Err(ParserError::invalid_opcode_addressing_mode_combination(self.line))
                                 }
                             } else {
-                                return Err(ParserError::unexpected_token(self.line));
+                                Err(ParserError::unexpected_token(self.line))
                             }
                         } else {
-                            return Err(ParserError::unexpected_token(self.line));
+                            Err(ParserError::unexpected_token(self.line))
                         }
                     } else if let &LexerToken::CloseParenthesis = next {
                         // We're headed for Indirect or IndirectY ..
-                        let addressing_mode = AddressingMode::IndirectY;
                         peeker.next(); // Skip the closing paren
 
-                        if let None = peeker.peek() {
+                        if peeker.peek().is_none() {
+                            // A zero page pointer is the 65C02's (zp) addressing
+                            if self.is_zero_page_address(address)? {
+                                if let Some(opcode) =
+                                       OpCode::from_mnemonic_and_addressing_mode(ident.clone(), AddressingMode::ZeroPageIndirect) {
+                                    let pointer = self.parse_zero_page_pointer(address)?;
+                                    return Ok(vec![ParserToken::OpCode(opcode), ParserToken::RawByte(pointer)]);
+                                }
+                            }
+
                             // If this is the end.. then lets check if this
                             // is the indirect jump: JMP ($0000)
                             if let Some(opcode) = OpCode::from_mnemonic_and_addressing_mode(ident, AddressingMode::Indirect) {
                                 // Yep, we've found the only Indirect opcode
                                 // Lets make sure the address is 16-bit
-                                if address.len() != 4 {
+                                if self.is_zero_page_address(address)? {
                                     return Err(ParserError::address_out_of_bounds(self.line));
                                 }
                                 let mut final_vec = vec![ParserToken::OpCode(opcode)];
@@ -430,39 +648,34 @@ impl Parser {
                         if let &LexerToken::Comma = next {
                             // Great, lets continue
                             peeker.next();  // Skip the comma
-                            if let None = peeker.peek() {
+                            if peeker.peek().is_none() {
                                 return Err(ParserError::unexpected_eol(self.line));
                             }
 
                             let next = *peeker.peek().unwrap();
-                            if let &LexerToken::Ident(ref register) = next {
+                            if let LexerToken::Ident(register) = next {
                                 let register = register.to_uppercase();
                                 // If its not IndirectY .. thats a problem
                                 if register != "Y" {
                                     return Err(ParserError::unexpected_token(self.line));
                                 }
                                 if let Some(opcode) = OpCode::from_mnemonic_and_addressing_mode(ident, AddressingMode::IndirectY) {
-                                    // Yep, we've found the only Indirect opcode
-                                    let mut final_vec = vec![ParserToken::OpCode(opcode)];
-                                    for b in bytes {
-                                        final_vec.push(ParserToken::RawByte(b));
-                                    }
-
-                                    return Ok(final_vec);
+                                    let pointer = self.parse_zero_page_pointer(address)?;
+                                    Ok(vec![ParserToken::OpCode(opcode), ParserToken::RawByte(pointer)])
                                 } else {
-                                    return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
+                                    Err(ParserError::invalid_opcode_addressing_mode_combination(self.line))
                                 }
                             } else {
-                                return Err(ParserError::unexpected_token(self.line));
+                                Err(ParserError::unexpected_token(self.line))
                             }
                         } else {
-                            return Err(ParserError::unexpected_token(self.line));
+                            Err(ParserError::unexpected_token(self.line))
                         }
                     } else {
-                        return Err(ParserError::unexpected_token(self.line));
+                        Err(ParserError::unexpected_token(self.line))
                     }
                 } else {
-                    return Err(ParserError::cannot_parse_address(self.line));
+                    Err(ParserError::cannot_parse_address(self.line))
                 }
             } else if let LexerToken::Immediate(ref immediate, base) = next {
                 peeker.next(); // Jump over the immediate
@@ -475,44 +688,146 @@ impl Parser {
                     if let Some(opcode) =
                            OpCode::from_mnemonic_and_addressing_mode(ident,
                                                                      AddressingMode::Immediate) {
-                        return Ok(vec![ParserToken::OpCode(opcode), ParserToken::RawByte(val)]);
+                        Ok(vec![ParserToken::OpCode(opcode), ParserToken::RawByte(val)])
                     } else {
-                        return Err(ParserError::invalid_opcode_addressing_mode_combination(self.line));
+                        Err(ParserError::invalid_opcode_addressing_mode_combination(self.line))
                     }
                 } else {
-                    return Err(ParserError::cannot_parse_immediate(self.line));
+                    Err(ParserError::cannot_parse_immediate(self.line))
                 }
             } else {
-                return Err(ParserError::expected_address(self.line));
+                Err(ParserError::expected_address(self.line))
             }
         }
-
-        unreachable!();
     }
 
     fn consume_org_directive<'a, I>(&mut self,
-                                    mut peeker: &mut Peekable<I>)
+                                    peeker: &mut Peekable<I>)
                                     -> Result<ParserToken, ParserError>
         where I: Iterator<Item = &'a LexerToken>
     {
         // Jump over the directive
         peeker.next();
-        if let None = peeker.peek() {
+        if peeker.peek().is_none() {
             return Err(ParserError::expected_address(self.line));
         }
 
         let next = peeker.next().unwrap();
 
-        if let &LexerToken::Address(ref address) = next {
-            let bytes = self.parse_address_bytes(address)?;
-            return Ok(ParserToken::OrgDirective(LittleEndian::read_u16(&bytes)));
+        if let LexerToken::Address(address) = next {
+            Ok(ParserToken::OrgDirective(self.parse_address(address)?))
+        } else {
+            Err(ParserError::expected_address(self.line))
+        }
+    }
+
+    fn consume_zero_page_directive<'a, I>(&mut self,
+                                          peeker: &mut Peekable<I>)
+                                          -> Result<(), ParserError>
+        where I: Iterator<Item = &'a LexerToken>
+    {
+        // Jump over the directive
+        peeker.next();
+        if peeker.peek().is_none() {
+            return Err(ParserError::unexpected_eol(self.line));
+        }
+
+        loop {
+            let next = peeker.next().unwrap();
+            if let LexerToken::Ident(symbol) = next {
+                self.zero_page_symbols.insert(symbol.clone());
+                self.validate_zero_page_symbol(symbol)?;
+            } else {
+                return Err(ParserError::unexpected_token(self.line));
+            }
+
+            // More than one symbol can be declared, separated by commas
+            if peeker.peek().is_none() {
+                return Ok(());
+            }
+            if let &LexerToken::Comma = peeker.next().unwrap() {
+                if peeker.peek().is_none() {
+                    return Err(ParserError::unexpected_eol(self.line));
+                }
+            } else {
+                return Err(ParserError::unexpected_token(self.line));
+            }
+        }
+    }
+
+    fn consume_checksum_directive<'a, I>(&mut self,
+                                         peeker: &mut Peekable<I>,
+                                         algorithm: ChecksumAlgorithm)
+                                         -> Result<ParserToken, ParserError>
+        where I: Iterator<Item = &'a LexerToken>
+    {
+        // Jump over the directive
+        peeker.next();
+
+        // Expecting a range of addresses: $C000, $FFFF
+        let start = self.consume_directive_address(peeker)?;
+        if let Some(&&LexerToken::Comma) = peeker.peek() {
+            peeker.next();
         } else {
+            return Err(ParserError::unexpected_token(self.line));
+        }
+        let end = self.consume_directive_address(peeker)?;
+
+        if start > end {
+            return Err(ParserError::invalid_address_range(self.line));
+        }
+
+        Ok(ParserToken::Checksum(algorithm, start, end))
+    }
+
+    fn consume_size_directive<'a, I>(&mut self,
+                                     peeker: &mut Peekable<I>)
+                                     -> Result<ParserToken, ParserError>
+        where I: Iterator<Item = &'a LexerToken>
+    {
+        // Jump over the directive
+        peeker.next();
+
+        let size = self.consume_directive_address(peeker)?;
+
+        // The fill byte is optional: .SIZE $4000, #$EA
+        if peeker.peek().is_none() {
+            return Ok(ParserToken::SegmentSize(size, 0xFF));
+        }
+        if let &LexerToken::Comma = peeker.next().unwrap() {
+            if let Some(&&LexerToken::Immediate(ref value, base)) = peeker.peek() {
+                return Ok(ParserToken::SegmentSize(size, self.unwrap_immediate(&value[..], base)));
+            }
+            return Err(ParserError::expected_immediate(self.line));
+        }
+
+        Err(ParserError::unexpected_token(self.line))
+    }
+
+    fn consume_directive_address<'a, I>(&mut self, peeker: &mut Peekable<I>) -> Result<u16, ParserError>
+        where I: Iterator<Item = &'a LexerToken>
+    {
+        if peeker.peek().is_none() {
             return Err(ParserError::expected_address(self.line));
         }
+
+        let next = peeker.next().unwrap();
+        if let LexerToken::Address(address) = next {
+            self.parse_address(address)
+        } else if let LexerToken::Ident(ident) = next {
+            self.cross_references.reference(ident.clone(), self.line);
+            if let Variable(LexerToken::Address(ref address)) = self.get_variable_value(ident.clone())? {
+                self.parse_address(address)
+            } else {
+                Err(ParserError::expected_address(self.line))
+            }
+        } else {
+            Err(ParserError::expected_address(self.line))
+        }
     }
 
     fn consume_byte_directive<'a, I>(&mut self,
-                                     mut peeker: &mut Peekable<I>)
+                                     peeker: &mut Peekable<I>)
                                      -> Result<ParserToken, ParserError>
         where I: Iterator<Item = &'a LexerToken>
     {
@@ -520,13 +835,14 @@ impl Parser {
 
         // Jump over the directive
         peeker.next();
-        if let None = peeker.peek() {
+        if peeker.peek().is_none() {
             return Err(ParserError::expected_immediate(self.line));
         }
 
         loop {
-            let mut next = peeker.next().unwrap();
-            if let &LexerToken::Ident(ref ident) = next {
+            let next = peeker.next().unwrap();
+            if let LexerToken::Ident(ident) = next {
+                self.cross_references.reference(ident.clone(), self.line);
                 let variable = self.get_variable_value(ident.clone())?;
                 if let LexerToken::Immediate(ref value, base) = variable.0 {
                     let immediate = self.unwrap_immediate(&value[..], base);
@@ -542,7 +858,7 @@ impl Parser {
             }
 
             // Check if the next thing is a comma. If it is, consume it and go again
-            if let None = peeker.peek() {
+            if peeker.peek().is_none() {
                 break;
             }
 
@@ -566,22 +882,128 @@ impl Parser {
         };
 
         let value = value.into();
-        let immediate = u8::from_str_radix(&value[..], base).unwrap();
+        
+
+        u8::from_str_radix(&value[..], base).unwrap()
+    }
+
+    fn parse_address(&self, address: &str) -> Result<u16, ParserError> {
+        u16::from_str_radix(address, 16).map_err(|_| ParserError::cannot_parse_address(self.line))
+    }
+
+    /// Addresses are zero page when their value fits in a single byte.
+    /// Writing an address with four or more digits (e.g. $0044) forces it
+    /// to be treated as a full 16-bit address.
+    fn is_zero_page_address(&self, address: &str) -> Result<bool, ParserError> {
+        Ok(self.parse_address(address)? <= 0xFF && address.len() < 4)
+    }
+
+    /// Indexed indirect addressing always reads its pointer from the zero
+    /// page, so the address must fit in a byte regardless of how its written
+    fn parse_zero_page_pointer(&self, address: &str) -> Result<u8, ParserError> {
+        let addr = self.parse_address(address)?;
+        if addr > 0xFF {
+            return Err(ParserError::address_out_of_bounds(self.line));
+        }
 
-        immediate
+        Ok(addr as u8)
     }
 
     fn parse_address_bytes(&self, address: &str) -> Result<Vec<u8>, ParserError> {
-        if let Ok(addr) = u16::from_str_radix(address, 16) {
-            if address.len() <= 2 {
-                return Ok(vec![addr as u8]);
-            } else {
-                let low_byte = addr as u8;
-                let high_byte = (addr >> 0x08) as u8;
-                return Ok(vec![low_byte, high_byte]);
+        let addr = self.parse_address(address)?;
+
+        if self.is_zero_page_address(address)? {
+            Ok(vec![addr as u8])
+        } else {
+            Ok(vec![addr as u8, (addr >> 0x08) as u8])
+        }
+    }
+
+    fn consume_indirect_label<'a, I, S>(&mut self,
+                                        peeker: &mut Peekable<I>,
+                                        ident: S,
+                                        label: String)
+                                        -> Result<Vec<ParserToken>, ParserError>
+        where I: Iterator<Item = &'a LexerToken>,
+              S: Into<String>
+    {
+        peeker.next(); // Jump over the label
+
+        if peeker.peek().is_none() {
+            return Err(ParserError::unexpected_eol(self.line));
+        }
+
+        // JMP (label,X) on the 65C02
+        let mut mode = AddressingMode::Indirect;
+        if let Some(&&LexerToken::Comma) = peeker.peek() {
+            peeker.next(); // Skip the comma
+            match peeker.next() {
+                Some(LexerToken::Ident(register)) if register.to_uppercase() == "X" => {}
+                Some(_) => return Err(ParserError::unexpected_token(self.line)),
+                None => return Err(ParserError::unexpected_eol(self.line)),
             }
+            mode = AddressingMode::AbsoluteIndexedIndirect;
+        }
+
+        if peeker.peek().is_none() {
+            return Err(ParserError::unexpected_eol(self.line));
+        }
+
+        let next = *peeker.peek().unwrap();
+        if let &LexerToken::CloseParenthesis = next {
+            peeker.next(); // Skip the closing paren
+        } else {
+            return Err(ParserError::unexpected_token(self.line));
+        }
+
+        // Only JMP ($0000) and JMP ($0000,X) can take a label, so nothing
+        // else may follow
+        if peeker.peek().is_some() {
+            return Err(ParserError::unexpected_token(self.line));
+        }
+
+        if let Some(opcode) = OpCode::from_mnemonic_and_addressing_mode(ident, mode) {
+            Ok(vec![ParserToken::OpCode(opcode), ParserToken::LabelArg(label)])
         } else {
-            Err(ParserError::cannot_parse_address(self.line))
+            Err(ParserError::invalid_opcode_addressing_mode_combination(self.line))
+        }
+    }
+
+    /// Returns true if the symbol, or any variable it is assigned from,
+    /// was declared as a zero page symbol
+    fn is_zero_page_symbol(&self, ident: &str) -> bool {
+        if self.zero_page_symbols.contains(ident) {
+            return true;
+        }
+
+        if let Some(&Variable(LexerToken::Ident(ref next))) = self.symbol_table.get(ident) {
+            self.is_zero_page_symbol(next)
+        } else {
+            false
+        }
+    }
+
+    /// Errors if a zero page symbol that already has a value resolves
+    /// to an address outside of the zero page
+    fn validate_zero_page_symbol(&self, ident: &str) -> Result<(), ParserError> {
+        if !self.is_zero_page_symbol(ident) {
+            return Ok(());
+        }
+
+        if let Ok(Variable(LexerToken::Address(ref address))) = self.get_variable_value(ident) {
+            self.parse_zero_page_pointer(address)?;
+        }
+
+        Ok(())
+    }
+
+    /// Follows a chain of variables (e.g. `VECTOR = TABLE`) through to the
+    /// name of the label it eventually refers to
+    fn resolve_label_name(&self, ident: String) -> String {
+        if let Some(&Variable(LexerToken::Ident(ref next))) = self.symbol_table.get(&ident) {
+            self.resolve_label_name(next.clone())
+        } else {
+            ident
         }
     }
 
@@ -590,16 +1012,15 @@ impl Parser {
     {
         let ident = ident.into();
 
-        if let Some(ref var) = self.symbol_table.get(&ident) {
-            let var = var.clone();
+        if let Some(var) = self.symbol_table.get(&ident) {
             if let LexerToken::Ident(ref ident) = var.0 {
                 // If this is _yet another_ variable .. recursively find its value:
-                return self.get_variable_value(ident.clone());
+                self.get_variable_value(ident.clone())
             } else {
-                return Ok(Variable(var.clone().0));
+                Ok(Variable(var.clone().0))
             }
         } else {
-            return Err(ParserError::unknown_identifier(self.line));
+            Err(ParserError::unknown_identifier(self.line))
         }
     }
 }
@@ -607,7 +1028,7 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ::assembler::token::{ImmediateBase, LexerToken, ParserToken};
+    use ::assembler::token::{LexerToken, ParserToken};
     use ::opcodes::{AddressingMode, OpCode};
 
     #[test]
@@ -783,6 +1204,71 @@ mod tests {
                    &result[..]);
     }
 
+    #[test]
+    fn can_parse_indirect_jump_through_label() {
+        let tokens = vec![vec![LexerToken::Ident("JMP".into()),
+                               LexerToken::OpenParenthesis,
+                               LexerToken::Ident("VECTOR".into()),
+                               LexerToken::CloseParenthesis]];
+
+        let mut parser = Parser::new();
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(&[
+                     ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("JMP", AddressingMode::Indirect).unwrap()),
+                     ParserToken::LabelArg("VECTOR".into())],
+                   &result[..]);
+    }
+
+    #[test]
+    fn errors_on_indexed_indirect_label() {
+        let tokens = vec![vec![LexerToken::Ident("LDA".into()),
+                               LexerToken::OpenParenthesis,
+                               LexerToken::Ident("VECTOR".into()),
+                               LexerToken::CloseParenthesis,
+                               LexerToken::Comma,
+                               LexerToken::Ident("Y".into())]];
+
+        let mut parser = Parser::new();
+        let result = parser.parse(tokens);
+
+        assert_eq!(Err(ParserError::unexpected_token(1)), result);
+    }
+
+    #[test]
+    fn uses_zero_page_addressing_for_zero_page_symbols() {
+        let tokens = vec![vec![LexerToken::Ident("POINTER".into()),
+                               LexerToken::Assignment,
+                               LexerToken::Address("0010".into())],
+                          vec![LexerToken::Period,
+                               LexerToken::Ident("ZP".into()),
+                               LexerToken::Ident("POINTER".into())],
+                          vec![LexerToken::Ident("LDA".into()),
+                               LexerToken::Ident("POINTER".into())]];
+
+        let mut parser = Parser::new();
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(&[
+                     ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("LDA", AddressingMode::ZeroPage).unwrap()),
+                     ParserToken::RawByte(0x10)],
+                   &result[..]);
+    }
+
+    #[test]
+    fn errors_on_zero_page_symbols_above_zero_page() {
+        let tokens = vec![vec![LexerToken::Period,
+                               LexerToken::Ident("ZEROPAGE".into())],
+                          vec![LexerToken::Ident("POINTER".into()),
+                               LexerToken::Assignment,
+                               LexerToken::Address("0100".into())]];
+
+        let mut parser = Parser::new();
+        let result = parser.parse(tokens);
+
+        assert_eq!(Err(ParserError::address_out_of_bounds(2)), result);
+    }
+
     #[test]
     fn errors_on_eight_bit_indirect_jump_instruction() {
         let tokens = vec![vec![LexerToken::Ident("JMP".into()),
@@ -796,6 +1282,24 @@ mod tests {
         assert_eq!(Err(ParserError::address_out_of_bounds(1)), result);
     }
 
+    #[test]
+    fn label_operands_use_the_addressing_modes_of_the_opcode() {
+        let tokens = vec![vec![LexerToken::Ident("BNE".into()), LexerToken::Ident("LOOP".into())],
+                          vec![LexerToken::Ident("JSR".into()), LexerToken::Ident("LOOP".into())],
+                          vec![LexerToken::Ident("LDA".into()), LexerToken::Ident("TABLE".into())]];
+
+        let mut parser = Parser::new();
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(&[ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("BNE", AddressingMode::Relative).unwrap()),
+                     ParserToken::LabelArg("LOOP".into()),
+                     ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("JSR", AddressingMode::Absolute).unwrap()),
+                     ParserToken::LabelArg("LOOP".into()),
+                     ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("LDA", AddressingMode::Absolute).unwrap()),
+                     ParserToken::LabelArg("TABLE".into())],
+                   &result[..]);
+    }
+
     #[test]
     fn can_parse_implied_stack_instructions() {
         let tokens = vec![vec![LexerToken::Ident("PHA".into())]];
@@ -834,4 +1338,81 @@ mod tests {
 
         assert_eq!(&[ParserToken::OrgDirective(0xC000)], &result[..]);
     }
+
+    #[test]
+    fn records_cross_references() {
+        let tokens = vec![vec![LexerToken::Ident("ADDR".into()),
+                               LexerToken::Assignment,
+                               LexerToken::Address("4400".into())],
+                          vec![LexerToken::Ident("MAIN".into())],
+                          vec![LexerToken::Ident("LDA".into()), LexerToken::Ident("ADDR".into())],
+                          vec![LexerToken::Ident("JMP".into()), LexerToken::Ident("MAIN".into())]];
+
+        let mut parser = Parser::new();
+        parser.parse(tokens).unwrap();
+
+        assert_eq!(vec![CrossReference {
+                            symbol: "ADDR".into(),
+                            definition: Some(1),
+                            references: vec![3],
+                        },
+                        CrossReference {
+                            symbol: "MAIN".into(),
+                            definition: Some(2),
+                            references: vec![4],
+                        }],
+                   parser.cross_references());
+    }
+
+    #[test]
+    fn expands_pseudo_ops_through_variables_and_after_labels() {
+        let tokens = vec![vec![LexerToken::Ident("PTR".into()),
+                               LexerToken::Assignment,
+                               LexerToken::Address("FF".into())],
+                          vec![LexerToken::Ident("MAIN".into()),
+                               LexerToken::Ident("STAX".into()),
+                               LexerToken::Ident("PTR".into())]];
+
+        let mut parser = Parser::new();
+        parser.enable_pseudo_ops();
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(vec![ParserToken::Label("MAIN".into()),
+                        ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("STA", AddressingMode::ZeroPage)
+                            .unwrap()),
+                        ParserToken::RawByte(0xFF),
+                        ParserToken::OpCode(OpCode::from_mnemonic_and_addressing_mode("STX", AddressingMode::Absolute)
+                            .unwrap()),
+                        ParserToken::RawByte(0x00),
+                        ParserToken::RawByte(0x01)],
+                   result);
+        assert_eq!(vec!["STA $FF".to_string(), "STX $100".to_string()],
+                   parser.expansions()[0].instructions);
+    }
+
+    #[test]
+    fn can_parse_custom_directives() {
+        let tokens = vec![vec![LexerToken::Period,
+                               LexerToken::Ident("HEADER".into()),
+                               LexerToken::Address("C000".into())]];
+
+        let mut parser = Parser::with_custom_directives(vec!["HEADER".into()]);
+        let result = parser.parse(tokens).unwrap();
+
+        assert_eq!(&[ParserToken::CustomDirective("HEADER".into(),
+                                                  vec![LexerToken::Address("C000".into())])],
+                   &result[..]);
+    }
+
+    #[test]
+    fn errors_on_unregistered_directives() {
+        let tokens = vec![vec![LexerToken::Period,
+                               LexerToken::Ident("HEADER".into()),
+                               LexerToken::Address("C000".into())]];
+
+        let mut parser = Parser::new();
+        let result = parser.parse(tokens);
+
+        assert_eq!(Err(ParserError::unknown_identifier(1)), result);
+    }
 }
\ No newline at end of file
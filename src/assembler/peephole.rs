@@ -0,0 +1,218 @@
+use ::opcodes::{AddressingMode, OpCode};
+use assembler::token::ParserToken;
+
+/// A change made to the code by the peephole optimizer
+#[derive(Debug, PartialEq)]
+pub struct Optimization {
+    pub message: String,
+}
+
+impl Optimization {
+    fn redundant_compare(addr: u16, opcode: &OpCode, load: &OpCode) -> Optimization {
+        Optimization {
+            message: format!("Removed {} #$00 at {:04X}, {} already set the flags",
+                             opcode.mnemonic,
+                             addr,
+                             load.mnemonic),
+        }
+    }
+
+    fn repeated_instruction(addr: u16, opcode: &OpCode) -> Optimization {
+        Optimization { message: format!("Removed repeated {} at {:04X}", opcode.mnemonic, addr) }
+    }
+
+    fn jump_to_next_instruction(addr: u16, label: &str) -> Optimization {
+        Optimization { message: format!("Removed JMP {} at {:04X} to the next instruction", label, addr) }
+    }
+}
+
+const FLAG_INSTRUCTIONS: [&str; 7] = ["CLC", "SEC", "CLD", "SED", "CLI", "SEI", "CLV"];
+
+/// Removes instructions that have no effect from the parsed tokens:
+///
+/// - A compare with zero straight after a load of the same register, as the
+///   load has already set the zero and negative flags. This assumes the
+///   carry flag set by the compare is not relied on.
+/// - A flag instruction that repeats the one before it
+/// - A `JMP` to the label that immediately follows it
+///
+/// Instructions that a label points at are never removed, nor are any
+/// compared with an instruction before a label. Each removal is returned
+/// alongside the optimized tokens and the source line of each token kept,
+/// with the address it had before optimizing.
+pub fn optimize(tokens: Vec<ParserToken>,
+                lines: &[u32],
+                origin: u16)
+                -> (Vec<ParserToken>, Vec<u32>, Vec<Optimization>) {
+    let mut result = Vec::new();
+    let mut result_lines = Vec::new();
+    let mut optimizations = Vec::new();
+    let mut previous: Option<Vec<ParserToken>> = None;
+    let mut addr = origin;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let opcode = match tokens[i] {
+            ParserToken::OpCode(opcode) => opcode,
+            ref token => {
+                match *token {
+                    ParserToken::OrgDirective(org_addr) => addr = org_addr,
+                    ParserToken::RawBytes(ref bytes) => addr = addr.wrapping_add(bytes.len() as u16),
+                    ParserToken::Checksum(algorithm, _, _) => addr = addr.wrapping_add(algorithm.size()),
+                    _ => {}
+                }
+
+                result.push(token.clone());
+                result_lines.push(lines[i]);
+                previous = None;
+                i += 1;
+                continue;
+            }
+        };
+
+        let length = match tokens.get(i + 1) {
+            Some(&ParserToken::LabelArg(_)) => 2,
+            _ => opcode.length as usize,
+        };
+        let instruction = tokens[i..i + length].to_vec();
+
+        let optimization = if let Some(label) = jump_to_following_label(&instruction, &tokens[i + length..]) {
+            Some(Optimization::jump_to_next_instruction(addr, label))
+        } else {
+            previous.as_ref().and_then(|previous| redundant(addr, previous, &instruction))
+        };
+
+        match optimization {
+            Some(optimization) => optimizations.push(optimization),
+            None => {
+                result.extend_from_slice(&instruction);
+                result_lines.extend_from_slice(&lines[i..i + length]);
+                previous = Some(instruction);
+            }
+        }
+
+        addr = addr.wrapping_add(opcode.length as u16);
+        i += length;
+    }
+
+    (result, result_lines, optimizations)
+}
+
+/// Returns the label a `JMP` goes to, if that label comes straight after it
+fn jump_to_following_label<'a>(instruction: &'a [ParserToken], following: &[ParserToken]) -> Option<&'a str> {
+    let target = match (&instruction[0], instruction.get(1)) {
+        (&ParserToken::OpCode(opcode), Some(ParserToken::LabelArg(label)))
+            if opcode.mnemonic == "JMP" && opcode.mode == AddressingMode::Absolute => label,
+        _ => return None,
+    };
+
+    for token in following {
+        match *token {
+            ParserToken::Label(ref label) if label == target => return Some(target),
+            ParserToken::Label(_) => {}
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+/// Checks whether an instruction has no effect given the one before it
+fn redundant(addr: u16, previous: &[ParserToken], instruction: &[ParserToken]) -> Option<Optimization> {
+    let (load, opcode) = match (&previous[0], &instruction[0]) {
+        (&ParserToken::OpCode(load), &ParserToken::OpCode(opcode)) => (load, opcode),
+        _ => return None,
+    };
+
+    let register = |mnemonic: &str| mnemonic.chars().last();
+
+    if opcode.mode == AddressingMode::Immediate && instruction[1] == ParserToken::RawByte(0x00) &&
+       ["CMP", "CPX", "CPY"].contains(&opcode.mnemonic) &&
+       ["LDA", "LDX", "LDY"].contains(&load.mnemonic) {
+        let compared = if opcode.mnemonic == "CMP" { Some('A') } else { register(opcode.mnemonic) };
+        if compared == register(load.mnemonic) {
+            return Some(Optimization::redundant_compare(addr, &opcode, &load));
+        }
+    }
+
+    if load == opcode && FLAG_INSTRUCTIONS.contains(&opcode.mnemonic) {
+        return Some(Optimization::repeated_instruction(addr, &opcode));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assembler::lexer::Lexer;
+    use assembler::parser::Parser;
+
+    fn optimize_source(code: &str) -> (Vec<ParserToken>, Vec<Optimization>) {
+        let mut lexer = Lexer::new();
+        let tokens = lexer.lex_string(code).unwrap();
+        let mut parser = Parser::new();
+        let tokens = parser.parse(tokens).unwrap();
+        let (tokens, _, optimizations) = optimize(tokens, parser.token_lines(), 0xC000);
+
+        (tokens, optimizations)
+    }
+
+    fn messages(optimizations: &[Optimization]) -> Vec<&str> {
+        optimizations.iter().map(|o| &o.message[..]).collect()
+    }
+
+    #[test]
+    fn removes_compares_with_zero_after_a_load() {
+        let (tokens, optimizations) = optimize_source("
+            LDA #$00
+            CMP #$00
+            LDX $10
+            CPX #$00
+            LDY $10
+            CPX #$00
+        ");
+
+        assert_eq!(vec!["Removed CMP #$00 at C002, LDA already set the flags",
+                        "Removed CPX #$00 at C006, LDX already set the flags"],
+                   messages(&optimizations));
+        assert_eq!(8, tokens.len());
+    }
+
+    #[test]
+    fn removes_repeated_flag_instructions() {
+        let (tokens, optimizations) = optimize_source("
+            CLC
+            CLC
+            SEC
+            CLC
+        ");
+
+        assert_eq!(vec!["Removed repeated CLC at C001"], messages(&optimizations));
+        assert_eq!(3, tokens.len());
+    }
+
+    #[test]
+    fn removes_jumps_to_the_next_instruction() {
+        let (_, optimizations) = optimize_source("
+                 JMP NEXT
+            NEXT NOP
+                 JMP NEXT
+                 NOP
+        ");
+
+        assert_eq!(vec!["Removed JMP NEXT at C000 to the next instruction"], messages(&optimizations));
+    }
+
+    #[test]
+    fn keeps_instructions_that_a_label_points_at() {
+        let (_, optimizations) = optimize_source("
+                 CLC
+            SKIP CLC
+                 LDA #$00
+            TEST CMP #$00
+        ");
+
+        assert_eq!(0, optimizations.len());
+    }
+}
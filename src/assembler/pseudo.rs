@@ -0,0 +1,71 @@
+use assembler::token::{ImmediateBase, LexerToken};
+
+/// The 16-bit pseudo-instructions the parser expands when they are enabled
+const PSEUDO_OPS: [&str; 2] = ["LDAX", "STAX"];
+
+/// A pseudo-instruction in the source and the instructions it was
+/// expanded into
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expansion {
+    pub line: u32,
+    pub source: String,
+    pub instructions: Vec<String>,
+}
+
+pub fn is_pseudo_op(mnemonic: &str) -> bool {
+    PSEUDO_OPS.contains(&&mnemonic.to_uppercase()[..])
+}
+
+/// Writes an operand the way it would appear in source code
+pub fn format_operand(token: &LexerToken) -> String {
+    match *token {
+        LexerToken::Immediate(ref value, ImmediateBase::Base16) => format!("#${}", value),
+        LexerToken::Immediate(ref value, ImmediateBase::Base10) => format!("#{}", value),
+        LexerToken::Address(ref address) => format!("${}", address),
+        LexerToken::Ident(ref ident) => ident.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Formats a list of expansions as a listing section, with each
+/// instruction of an expansion on its own line
+pub fn format_expansions(expansions: &[Expansion]) -> String {
+    let width = expansions.iter().map(|e| e.source.len()).max().unwrap_or(0).max(9);
+
+    let mut result = format!("{:>4}  {:<width$}  {}\n", "LINE", "PSEUDO-OP", "EXPANSION", width = width);
+
+    for expansion in expansions {
+        for (index, instruction) in expansion.instructions.iter().enumerate() {
+            if index == 0 {
+                result.push_str(&format!("{:>4}  {:<width$}  {}\n",
+                                         expansion.line,
+                                         expansion.source,
+                                         instruction,
+                                         width = width));
+            } else {
+                result.push_str(&format!("{:>4}  {:<width$}  {}\n", "", "", instruction, width = width));
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_each_instruction_of_an_expansion() {
+        let expansions = vec![Expansion {
+                                  line: 3,
+                                  source: "LDAX #$1234".into(),
+                                  instructions: vec!["LDA #$34".into(), "LDX #$12".into()],
+                              }];
+
+        assert_eq!(concat!("LINE  PSEUDO-OP    EXPANSION\n",
+                           "   3  LDAX #$1234  LDA #$34\n",
+                           "                   LDX #$12\n"),
+                   format_expansions(&expansions));
+    }
+}
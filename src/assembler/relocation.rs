@@ -0,0 +1,45 @@
+/// Position independent machine code, assembled as though it were loaded
+/// at address `$0000`. Each entry in the relocation table is the offset of
+/// a little-endian address in the code that must be adjusted by the
+/// address the code is eventually loaded at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelocatableCode {
+    pub code: Vec<u8>,
+    pub relocations: Vec<u16>,
+}
+
+impl RelocatableCode {
+    /// Returns a copy of the code adjusted to run from the given address
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Assembler;
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let relocatable = assembler.assemble_relocatable("
+    ///     MAIN NOP
+    ///          JMP MAIN
+    /// ").unwrap();
+    ///
+    /// assert_eq!(vec![0xEA, 0x4C, 0x00, 0xC0], relocatable.relocated_to(0xC000));
+    /// assert_eq!(vec![0xEA, 0x4C, 0x00, 0x20], relocatable.relocated_to(0x2000));
+    /// ```
+    pub fn relocated_to(&self, base: u16) -> Vec<u8> {
+        let mut code = self.code.clone();
+        relocate(&mut code, &self.relocations, base);
+        code
+    }
+}
+
+/// Adds `base` to each little-endian address in `code` listed in the
+/// relocation table. Relocating already relocated code moves it by `base`
+/// again, so always start from the code as it was assembled.
+pub fn relocate(code: &mut [u8], relocations: &[u16], base: u16) {
+    for &offset in relocations {
+        let offset = offset as usize;
+        let addr = (code[offset] as u16 | ((code[offset + 1] as u16) << 8)).wrapping_add(base);
+
+        code[offset] = (addr & 0xFF) as u8;
+        code[offset + 1] = (addr >> 8) as u8;
+    }
+}
@@ -1,4 +1,5 @@
-use ::opcodes::{AddressingMode, OpCode};
+use ::opcodes::OpCode;
+use assembler::checksum::ChecksumAlgorithm;
 
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
 pub enum ImmediateBase {
@@ -6,26 +7,73 @@ pub enum ImmediateBase {
     Base16,
 }
 
-#[derive(Clone, Debug, PartialEq )]
+/// A token of source code, as the lexer reads it. Along with `Statement`,
+/// lexer tokens are the stable front end of the assembler for tools such
+/// as linters and converters from other dialects: new variants are only
+/// added in minor releases, and existing ones aren't changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum LexerToken {
+    /// A mnemonic, label, variable or directive name, as written
     Ident(String),
+    /// `=`
     Assignment,
+    /// The hex digits of a `$` number, uppercased, such as `C000` for
+    /// `$C000`
     Address(String),
+    /// `(`
     OpenParenthesis,
+    /// `)`
     CloseParenthesis,
+    /// `,`
     Comma,
+    /// `.`, which starts a directive
     Period,
+    /// The digits of a `#` number and their base, such as `("10",
+    /// Base10)` for `#10` and `("FF", Base16)` for `#$FF`
     Immediate(String, ImmediateBase),
+    /// `:`
     Colon,
 }
 
+/// Where something is in the source code. Lines and columns count from 1,
+/// and columns count characters. Lines of an `.INCLUDE`d file are
+/// numbered within that file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    /// The number of characters covered
+    pub len: u32,
+}
+
+impl Span {
+    /// Returns a span from the start of this one to the end of another on
+    /// the same line
+    pub fn to(&self, end: Span) -> Span {
+        Span {
+            line: self.line,
+            column: self.column,
+            len: end.column + end.len - self.column,
+        }
+    }
+}
+
+/// Something from the source code, and where it was
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum ParserToken {
     Label(String),
     LabelArg(String),
     OpCode(OpCode),
-    Absolute(String),
     RawByte(u8),
     OrgDirective(u16),
     RawBytes(Vec<u8>),
+    CustomDirective(String, Vec<LexerToken>),
+    Checksum(ChecksumAlgorithm, u16, u16),
+    SegmentSize(u16, u8),
 }
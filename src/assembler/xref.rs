@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+/// The definition and usage of a single symbol within
+/// assembled source code
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CrossReference {
+    pub symbol: String,
+
+    /// The line the symbol was defined on, if it was defined
+    pub definition: Option<u32>,
+
+    /// Every line that references the symbol, in source order
+    pub references: Vec<u32>,
+}
+
+/// Collects symbol definitions and references as source
+/// code is parsed
+#[derive(Debug, Default)]
+pub struct CrossReferenceTable {
+    symbols: BTreeMap<String, CrossReference>,
+}
+
+impl CrossReferenceTable {
+    pub fn new() -> CrossReferenceTable {
+        Default::default()
+    }
+
+    pub fn define<S>(&mut self, symbol: S, line: u32)
+        where S: Into<String>
+    {
+        self.entry(symbol).definition = Some(line);
+    }
+
+    pub fn reference<S>(&mut self, symbol: S, line: u32)
+        where S: Into<String>
+    {
+        self.entry(symbol).references.push(line);
+    }
+
+    /// Returns every symbol seen, sorted by name
+    pub fn to_vec(&self) -> Vec<CrossReference> {
+        self.symbols.values().cloned().collect()
+    }
+
+    fn entry<S>(&mut self, symbol: S) -> &mut CrossReference
+        where S: Into<String>
+    {
+        let symbol = symbol.into();
+
+        self.symbols.entry(symbol.clone()).or_insert_with(|| {
+            CrossReference { symbol, ..Default::default() }
+        })
+    }
+}
+
+/// Formats a list of cross references as a classic assembler
+/// cross-reference section
+pub fn format_cross_references(references: &[CrossReference]) -> String {
+    let width = references.iter().map(|r| r.symbol.len()).max().unwrap_or(0).max(6);
+
+    let mut result = format!("{:<width$}  {:>7}  {}\n",
+                             "SYMBOL",
+                             "DEFINED",
+                             "REFERENCED",
+                             width = width);
+
+    for reference in references {
+        let definition = match reference.definition {
+            Some(line) => format!("{}", line),
+            None => "-".into(),
+        };
+        let lines = reference.references
+            .iter()
+            .map(|line| format!("{}", line))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        result.push_str(&format!("{:<width$}  {:>7}  {}\n",
+                                 reference.symbol,
+                                 definition,
+                                 lines,
+                                 width = width));
+    }
+
+    result
+}
@@ -0,0 +1,158 @@
+use assembler::CodeSegment;
+use opcodes::{AddressingMode, OpCode};
+
+const MEMORY_SIZE: usize = 0x10000;
+
+/// An inclusive range of loaded bytes that no path of execution
+/// from the entry points ever reaches
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UnreachableRegion {
+    pub start: u16,
+    pub end: u16,
+}
+
+/// Follows every path of execution from the given entry points through the
+/// loaded segments and returns the regions of bytes that are never reached.
+/// Both outcomes of a branch are followed, as is the return from a `JSR`.
+/// Paths end at `RTS`, `RTI`, `BRK`, indirect jumps, invalid opcodes and
+/// the edges of the loaded code.
+///
+/// # Example
+/// ```
+/// use rs6502::{find_unreachable_code, CodeSegment, UnreachableRegion};
+///
+/// // JMP $C004, a stray NOP, then RTS
+/// let segments = vec![CodeSegment { address: 0xC000, code: vec![0x4C, 0x04, 0xC0, 0xEA, 0x60] }];
+///
+/// assert_eq!(vec![UnreachableRegion { start: 0xC003, end: 0xC003 }],
+///            find_unreachable_code(&segments, &[0xC000]));
+/// ```
+pub fn find_unreachable_code(segments: &[CodeSegment], entry_points: &[u16]) -> Vec<UnreachableRegion> {
+    let mut memory: Vec<Option<u8>> = vec![None; MEMORY_SIZE];
+    for segment in segments {
+        for (offset, byte) in segment.code.iter().enumerate() {
+            memory[(segment.address as usize + offset) % MEMORY_SIZE] = Some(*byte);
+        }
+    }
+
+    let mut reached = vec![false; MEMORY_SIZE];
+    let mut visited = vec![false; MEMORY_SIZE];
+    let mut pending: Vec<u16> = entry_points.to_vec();
+
+    while let Some(addr) = pending.pop() {
+        if visited[addr as usize] {
+            continue;
+        }
+        visited[addr as usize] = true;
+
+        let opcode = match memory[addr as usize].and_then(OpCode::from_raw_byte) {
+            Some(opcode) => *opcode,
+            None => continue,
+        };
+
+        // Every byte of the instruction must be loaded for it to run
+        let operand: Vec<u8> = match (1..opcode.length as u16)
+            .map(|offset| memory[addr.wrapping_add(offset) as usize])
+            .collect() {
+            Some(operand) => operand,
+            None => continue,
+        };
+
+        for offset in 0..opcode.length as u16 {
+            reached[addr.wrapping_add(offset) as usize] = true;
+        }
+
+        let next = addr.wrapping_add(opcode.length as u16);
+        match (opcode.mnemonic, opcode.mode) {
+            ("RTS", _) | ("RTI", _) | ("BRK", _) | ("JMP", AddressingMode::Indirect) => {}
+            ("JMP", _) => pending.push(absolute_target(&operand)),
+            ("JSR", _) => {
+                pending.push(absolute_target(&operand));
+                pending.push(next);
+            }
+            (_, AddressingMode::Relative) => {
+                pending.push(next.wrapping_add(operand[0] as i8 as u16));
+                pending.push(next);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut start: Option<usize> = None;
+    for addr in 0..MEMORY_SIZE {
+        if memory[addr].is_some() && !reached[addr] {
+            if start.is_none() {
+                start = Some(addr);
+            }
+        } else if let Some(region_start) = start.take() {
+            result.push(UnreachableRegion {
+                start: region_start as u16,
+                end: (addr - 1) as u16,
+            });
+        }
+    }
+
+    if let Some(region_start) = start {
+        result.push(UnreachableRegion {
+            start: region_start as u16,
+            end: (MEMORY_SIZE - 1) as u16,
+        });
+    }
+
+    result
+}
+
+fn absolute_target(operand: &[u8]) -> u16 {
+    operand[0] as u16 | ((operand[1] as u16) << 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(address: u16, code: Vec<u8>) -> CodeSegment {
+        CodeSegment {
+            address,
+            code,
+        }
+    }
+
+    #[test]
+    fn reports_nothing_when_all_code_is_reachable() {
+        // LDX #$05, DEX, BNE -3, RTS
+        let segments = vec![segment(0xC000, vec![0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x60])];
+
+        assert_eq!(0, find_unreachable_code(&segments, &[0xC000]).len());
+    }
+
+    #[test]
+    fn follows_subroutine_calls_and_returns() {
+        // JSR $C005, BRK, NOP, NOP, RTS
+        let segments = vec![segment(0xC000, vec![0x20, 0x05, 0xC0, 0x00, 0xEA, 0x60, 0xEA])];
+
+        assert_eq!(vec![UnreachableRegion { start: 0xC004, end: 0xC004 },
+                        UnreachableRegion { start: 0xC006, end: 0xC006 }],
+                   find_unreachable_code(&segments, &[0xC000]));
+    }
+
+    #[test]
+    fn does_not_follow_indirect_jumps() {
+        // JMP ($C003), then a pointer and an RTS
+        let segments = vec![segment(0xC000, vec![0x6C, 0x03, 0xC0, 0x05, 0xC0, 0x60])];
+
+        assert_eq!(vec![UnreachableRegion { start: 0xC003, end: 0xC005 }],
+                   find_unreachable_code(&segments, &[0xC000]));
+    }
+
+    #[test]
+    fn considers_every_entry_point_and_segment() {
+        let segments = vec![segment(0xC000, vec![0x4C, 0x00, 0xD0, 0x60]),
+                            segment(0xD000, vec![0x60, 0xEA]),
+                            segment(0xE000, vec![0xEA, 0x60])];
+
+        assert_eq!(vec![UnreachableRegion { start: 0xC003, end: 0xC003 },
+                        UnreachableRegion { start: 0xD001, end: 0xD001 }],
+                   find_unreachable_code(&segments, &[0xC000, 0xE000]));
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt::Write;
+
+use ::opcodes::{AddressingMode, OpCode};
+use assembler::parse_statements;
+
+/// The directives DASM accepts without a leading period, which would
+/// otherwise be taken for labels
+const DASM_DIRECTIVES: [&str; 16] = ["ORG", "PROCESSOR", "SEG", "SEG.U", "DC", "DC.B", "DC.W", "BYTE", "WORD",
+                                     "DS", "DS.B", "INCLUDE", "MAC", "ENDM", "IF", "ENDIF"];
+
+/// An assembler whose source `convert` can translate
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Dialect {
+    /// DASM, with directives such as `ORG` and `DC.B`
+    Dasm,
+    /// ACME, with directives such as `*=` and `!byte`
+    Acme,
+    /// cc65's ca65, with directives such as `.segment` and `.res`
+    Ca65,
+}
+
+impl Dialect {
+    /// Returns the dialect named `dasm`, `acme` or `ca65`, in any case
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match &name.to_lowercase()[..] {
+            "dasm" => Some(Dialect::Dasm),
+            "acme" => Some(Dialect::Acme),
+            "ca65" => Some(Dialect::Ca65),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of a directive without its prefix, uppercased, or
+    /// None if the word isn't written as a directive in this dialect
+    fn directive(&self, word: &str) -> Option<String> {
+        let word = word.to_uppercase();
+        let name = match *self {
+            Dialect::Dasm if word.starts_with('.') => &word[1..],
+            Dialect::Dasm if DASM_DIRECTIVES.contains(&&word[..]) => &word[..],
+            Dialect::Acme if word.starts_with('!') => &word[1..],
+            Dialect::Ca65 if word.starts_with('.') => &word[1..],
+            _ => return None,
+        };
+
+        Some(name.to_string())
+    }
+}
+
+/// A line of source that `convert` couldn't translate. The line is kept in
+/// the converted source as a comment.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConversionIssue {
+    /// The line of the original source, counting from 1
+    pub line: u32,
+    pub message: String,
+}
+
+/// Source code translated by `convert`, and the lines it couldn't translate
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Conversion {
+    pub source: String,
+    pub issues: Vec<ConversionIssue>,
+}
+
+/// Translates the source of another assembler into source this crate's
+/// `Assembler` accepts. Labels, instructions, comments and the common
+/// directives for origins, data, segments, includes and variables are
+/// translated, and binary, decimal and character literals are written in
+/// hex. Directives that only matter to the other assembler, such as
+/// `processor 6502`, are kept as comments.
+///
+/// Expressions, local and anonymous labels, macros and conditional
+/// assembly aren't translated. Each line using one is kept as a comment and
+/// reported as a `ConversionIssue`, so the rest of the file can still be
+/// converted and the issues fixed by hand.
+///
+/// # Example
+/// ```
+/// use rs6502::{convert, Dialect};
+///
+/// let conversion = convert("
+///         *= $c000
+/// start   lda #%00001111
+///         sta 16
+///         !byte 1, 2
+///         jmp start+1
+/// ", Dialect::Acme);
+///
+/// assert_eq!("
+///     .ORG $C000
+/// start
+///     LDA #$0F
+///     STA $10
+///     .BYTE #$01, #$02
+/// ; jmp start+1
+/// ", conversion.source);
+/// assert_eq!(6, conversion.issues[0].line);
+/// ```
+pub fn convert(source: &str, dialect: Dialect) -> Conversion {
+    let mut conversion = Conversion::default();
+
+    for (index, line) in source.lines().enumerate() {
+        let (code, comment) = split_comment(line);
+        let lines = convert_line(code, dialect).and_then(|lines| {
+            for converted in &lines {
+                parse_statements(&converted[..]).map_err(|error| error.message)?;
+            }
+            Ok(lines)
+        });
+
+        match lines {
+            Ok(mut lines) => {
+                match (lines.pop(), comment) {
+                    (Some(last), Some(comment)) => lines.push(format!("{} {}", last, comment)),
+                    (Some(last), None) => lines.push(last),
+                    (None, Some(comment)) => lines.push(comment.to_string()),
+                    (None, None) => lines.push(String::new()),
+                }
+                for line in lines {
+                    writeln!(conversion.source, "{}", line).unwrap();
+                }
+            }
+            Err(message) => {
+                writeln!(conversion.source, "; {}", line.trim()).unwrap();
+                conversion.issues.push(ConversionIssue {
+                    line: index as u32 + 1,
+                    message,
+                });
+            }
+        }
+    }
+
+    conversion
+}
+
+/// Splits a line into its code and its comment, which starts at the first
+/// `;` outside of quotes
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, ';') => return (&line[..index], Some(&line[index..])),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            _ => {}
+        }
+    }
+
+    (line, None)
+}
+
+/// Returns the first word of some code and the rest of it
+fn split_word(code: &str) -> (&str, &str) {
+    let code = code.trim();
+    match code.find(char::is_whitespace) {
+        Some(index) => (&code[..index], code[index..].trim_start()),
+        None => (code, ""),
+    }
+}
+
+fn convert_line(code: &str, dialect: Dialect) -> Result<Vec<String>, String> {
+    let trimmed = code.trim();
+    if trimmed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // `*= $C000` and `* = $C000` set the origin in DASM and ACME
+    if trimmed.starts_with('*') && trimmed[1..].trim_start().starts_with('=') {
+        let value = trimmed[1..].trim_start()[1..].trim();
+        return Ok(vec![format!("    .ORG {}", convert_operand(value)?)]);
+    }
+
+    let (first, rest) = split_word(trimmed);
+
+    // `NAME = value`, `NAME EQU value` and the like
+    if let Some(index) = trimmed.find('=') {
+        let name = trimmed[..index].trim().trim_end_matches(':');
+        if is_identifier(name) {
+            return assignment(name, &trimmed[index + 1..]);
+        }
+    }
+    let (second, value) = split_word(rest);
+    if ["EQU", ".EQU", "SET", ".SET"].contains(&&second.to_uppercase()[..]) {
+        return assignment(first.trim_end_matches(':'), value);
+    }
+
+    let mut lines = Vec::new();
+    // DASM takes anything in the first column for a label
+    let in_first_column = !code.starts_with(char::is_whitespace);
+    let (op, args) = if first.ends_with(':') ||
+                        (in_first_column && !is_mnemonic(first) &&
+                         (dialect == Dialect::Dasm || dialect.directive(first).is_none())) {
+        lines.push(label(first.trim_end_matches(':'))?);
+        split_word(rest)
+    } else {
+        (first, rest)
+    };
+
+    if op.is_empty() {
+        return Ok(lines);
+    }
+
+    if is_mnemonic(op) {
+        let accumulator = OpCode::from_mnemonic_and_addressing_mode(op, AddressingMode::Accumulator).is_some();
+        if args.is_empty() || (accumulator && args.eq_ignore_ascii_case("A")) {
+            lines.push(format!("    {}", op.to_uppercase()));
+        } else {
+            lines.push(format!("    {} {}", op.to_uppercase(), convert_operand(args)?));
+        }
+        return Ok(lines);
+    }
+
+    let directive = dialect.directive(op).ok_or_else(|| format!("Unknown instruction or directive '{}'", op))?;
+    match &directive[..] {
+        "ORG" => lines.push(format!("    .ORG {}", convert_operand(args)?)),
+        "BYTE" | "BYT" | "BY" | "DB" | "DC" | "DC.B" | "8" | "08" | "TEXT" | "TX" | "ASCII" => {
+            lines.push(bytes(&data(args, 1)?));
+        }
+        "ASCIIZ" => {
+            let mut values = data(args, 1)?;
+            values.push(0);
+            lines.push(bytes(&values));
+        }
+        "WORD" | "WO" | "DW" | "DC.W" | "16" | "ADDR" => lines.push(bytes(&data(args, 2)?)),
+        "DS" | "DS.B" | "RES" | "FILL" | "FI" => {
+            let values = data(args, 1)?;
+            let (count, fill) = match values.len() {
+                1 => (values[0], 0),
+                2 => (values[0], values[1]),
+                _ => return Err("Expected a count and an optional fill byte".to_string()),
+            };
+            if count > 0 {
+                lines.push(bytes(&vec![fill; count as usize]));
+            }
+        }
+        "ZEROPAGE" | "ZP" => lines.push(String::from("    .ZEROPAGE")),
+        "CODE" => lines.push(String::from("    .CODE")),
+        "SEGMENT" => {
+            match &args.trim_matches('"').to_uppercase()[..] {
+                "ZEROPAGE" | "ZP" => lines.push(String::from("    .ZEROPAGE")),
+                "CODE" => lines.push(String::from("    .CODE")),
+                segment => return Err(format!("Unsupported segment '{}'", segment)),
+            }
+        }
+        "INCLUDE" | "SOURCE" | "SRC" => {
+            if !(args.starts_with('"') && args.ends_with('"') && args.len() > 1) {
+                return Err("Expected a quoted file name".to_string());
+            }
+            lines.push(format!("    .INCLUDE {}", args));
+        }
+        "PROC" => lines.push(label(args)?),
+        "ENDPROC" | "PROCESSOR" | "CPU" | "SETCPU" | "P02" | "TO" | "SEG" | "EXPORT" | "EXPORTZP" | "IMPORT" |
+        "IMPORTZP" | "END" => lines.push(format!("; {}", trimmed)),
+        _ => return Err(format!("Unsupported directive '{}'", op)),
+    }
+
+    Ok(lines)
+}
+
+fn is_mnemonic(word: &str) -> bool {
+    OpCode::from_mnemonic(word).is_some()
+}
+
+fn is_identifier(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_alphabetic() || c == '_') &&
+    word.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn label(name: &str) -> Result<String, String> {
+    if is_identifier(name) {
+        Ok(name.to_string())
+    } else {
+        Err(format!("Unsupported label '{}': local and anonymous labels aren't supported", name))
+    }
+}
+
+fn assignment(name: &str, value: &str) -> Result<Vec<String>, String> {
+    let value = value.trim();
+    if !is_identifier(value) && number(value).is_none() {
+        return Err(format!("Unsupported value '{}': expressions aren't supported", value));
+    }
+
+    Ok(vec![format!("{} = {}", label(name)?, convert_operand(value)?)])
+}
+
+/// Writes an operand with every number in hex, other than decimal
+/// immediates, which the assembler accepts as they are
+fn convert_operand(operand: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars = operand.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        match c {
+            ' ' | '\t' => {}
+            '#' | '(' | ')' | ',' => result.push(c),
+            '$' | '%' | '\'' | '"' | '0'..='9' => {
+                let mut end = operand.len();
+                while let Some(&(index, c)) = chars.peek() {
+                    if c == ',' || c == ')' || c.is_whitespace() {
+                        end = index;
+                        break;
+                    }
+                    chars.next();
+                }
+                let literal = &operand[start..end];
+                let value = number(literal).ok_or_else(|| format!("Unsupported number '{}'", literal))?;
+                if result.ends_with('#') && literal.starts_with(|c: char| c.is_ascii_digit()) {
+                    result.push_str(literal);
+                } else {
+                    result.push_str(&hex(value));
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = operand.len();
+                while let Some(&(index, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        end = index;
+                        break;
+                    }
+                    chars.next();
+                }
+                // Index registers are written in upper case
+                let ident = &operand[start..end];
+                if result.ends_with(',') && (ident == "x" || ident == "y") {
+                    result.push_str(&ident.to_uppercase());
+                } else {
+                    result.push_str(ident);
+                }
+            }
+            '.' | '@' => return Err("Local labels aren't supported".to_string()),
+            _ => return Err(format!("Unsupported '{}': expressions aren't supported", c)),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns the bytes of a list of numbers and strings, with each number
+/// taking `width` bytes, little-endian
+fn data(args: &str, width: u32) -> Result<Vec<u8>, String> {
+    let mut values = Vec::new();
+
+    for arg in split_args(args) {
+        if arg.len() > 1 && (arg.starts_with('"') && arg.ends_with('"') && width == 1) {
+            values.extend(arg[1..arg.len() - 1].bytes());
+            continue;
+        }
+
+        let value = match number(arg) {
+            Some(value) if value < 1 << (8 * width) => value,
+            Some(_) => return Err(format!("Value '{}' doesn't fit in {} byte(s)", arg, width)),
+            None => return Err(format!("Unsupported data '{}': only numbers and strings are supported", arg)),
+        };
+        values.push(value as u8);
+        if width == 2 {
+            values.push((value >> 8) as u8);
+        }
+    }
+
+    Ok(values)
+}
+
+/// Splits arguments at the commas outside of quotes
+fn split_args(args: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+
+    for (index, c) in args.char_indices() {
+        match (quote, c) {
+            (None, ',') => {
+                result.push(args[start..index].trim());
+                start = index + 1;
+            }
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if open == c => quote = None,
+            _ => {}
+        }
+    }
+    if !args[start..].trim().is_empty() {
+        result.push(args[start..].trim());
+    }
+
+    result
+}
+
+/// Parses a `$` hex, `%` binary, decimal or single character literal
+fn number(literal: &str) -> Option<u32> {
+    let quoted = literal.len() == 3 &&
+                 ((literal.starts_with('\'') && literal.ends_with('\'')) ||
+                  (literal.starts_with('"') && literal.ends_with('"')));
+    let value = if quoted {
+        Ok(literal.as_bytes()[1] as u32)
+    } else if let Some(digits) = literal.strip_prefix('$') {
+        u32::from_str_radix(digits, 16)
+    } else if let Some(digits) = literal.strip_prefix('%') {
+        u32::from_str_radix(digits, 2)
+    } else {
+        literal.parse()
+    };
+
+    value.ok().filter(|&value| value <= 0xFFFF)
+}
+
+/// Writes a value the way the assembler reads it, with 2 digits when it
+/// fits in the zero page
+fn hex(value: u32) -> String {
+    if value <= 0xFF {
+        format!("${:02X}", value)
+    } else {
+        format!("${:04X}", value)
+    }
+}
+
+fn bytes(values: &[u8]) -> String {
+    let values: Vec<String> = values.iter().map(|value| format!("#${:02X}", value)).collect();
+    format!("    .BYTE {}", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_dasm_data_and_variables() {
+        let conversion = convert("        processor 6502
+SCREEN  equ $0400
+        org $0801
+msg     dc.b \"HI\", 0 ; greeting
+        dc.w $1234
+        ds 2, $ea
+.loop   dex",
+                                 Dialect::Dasm);
+
+        assert_eq!("; processor 6502
+SCREEN = $0400
+    .ORG $0801
+msg
+    .BYTE #$48, #$49, #$00 ; greeting
+    .BYTE #$34, #$12
+    .BYTE #$EA, #$EA
+; .loop   dex
+",
+                   conversion.source);
+        assert_eq!(vec![ConversionIssue {
+                            line: 7,
+                            message: String::from("Unsupported label '.loop': local and anonymous labels aren't \
+                                                   supported"),
+                        }],
+                   conversion.issues);
+    }
+
+    #[test]
+    fn converts_ca65_segments_and_procedures() {
+        let conversion = convert(".segment \"ZEROPAGE\"
+ptr = $10
+.code
+.proc main
+    asl a
+    lda (ptr),y
+    .asciiz \"A\"
+.endproc
+    .macro push",
+                                 Dialect::Ca65);
+
+        assert_eq!("    .ZEROPAGE
+ptr = $10
+    .CODE
+main
+    ASL
+    LDA (ptr),Y
+    .BYTE #$41, #$00
+; .endproc
+; .macro push
+",
+                   conversion.source);
+        assert_eq!("Unsupported directive '.macro'", conversion.issues[0].message);
+    }
+}
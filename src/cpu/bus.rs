@@ -0,0 +1,112 @@
+use cpu::memory_map::{MemoryRegion, RegionKind};
+use cpu::protection::MemoryAccess;
+
+/// The address space the Cpu reads and writes through. `MemoryBus` is the
+/// default: 64kb of RAM with devices mapped over it. Implement this to
+/// emulate a machine with its own memory map, such as ROM, mirrored RAM or
+/// hardware registers.
+///
+/// Reads take `&self`, so a bus whose registers change when they are read
+/// should keep them in a `Cell` or `RefCell`.
+///
+/// # Example
+/// ```
+/// use std::cell::Cell;
+/// use rs6502::{Bus, Cpu};
+///
+/// // 2kb of RAM mirrored four times, a counter at $4000 and ROM above
+/// struct Console {
+///     ram: [u8; 0x800],
+///     counter: Cell<u8>,
+///     rom: Vec<u8>,
+/// }
+///
+/// impl Bus for Console {
+///     fn read_byte(&self, addr: u16) -> u8 {
+///         match addr {
+///             0x0000..=0x1FFF => self.ram[addr as usize % 0x800],
+///             0x4000 => {
+///                 self.counter.set(self.counter.get() + 1);
+///                 self.counter.get()
+///             }
+///             0x8000..=0xFFFF => self.rom[addr as usize - 0x8000],
+///             _ => 0,
+///         }
+///     }
+///
+///     fn write_byte(&mut self, addr: u16, byte: u8) {
+///         if addr < 0x2000 {
+///             self.ram[addr as usize % 0x800] = byte;
+///         }
+///     }
+/// }
+///
+/// let mut rom = vec![0; 0x8000];
+/// // LDA $4000, STA $0800, LDA $4000
+/// rom[..8].copy_from_slice(&[0xAD, 0x00, 0x40, 0x8D, 0x00, 0x08, 0xAD, 0x00]);
+/// rom[8] = 0x40;
+/// // Reset vector at $FFFC points at $8000
+/// rom[0x7FFC] = 0x00;
+/// rom[0x7FFD] = 0x80;
+///
+/// let mut cpu = Cpu::with_bus(Console { ram: [0; 0x800], counter: Cell::new(0), rom: rom });
+/// cpu.reset();
+/// cpu.step_n(3).unwrap();
+///
+/// assert_eq!(0x02, cpu.registers.A);
+/// assert_eq!(0x01, cpu.memory.read_byte(0x0000));
+/// ```
+pub trait Bus {
+    fn read_byte(&self, addr: u16) -> u8;
+
+    fn write_byte(&mut self, addr: u16, byte: u8);
+
+    /// Writes a byte as a loader would, past any write protection the bus
+    /// has, such as to put code into ROM. By default this is `write_byte`.
+    fn poke(&mut self, addr: u16, byte: u8) {
+        self.write_byte(addr, byte);
+    }
+
+    /// Reads a byte without any side effects, for tools such as the rewind
+    /// history that mustn't change what the program sees. Returns None
+    /// where that isn't possible, such as for a register that changes when
+    /// it is read. By default this is `read_byte`, so a bus with such
+    /// registers should return None for them.
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(self.read_byte(addr))
+    }
+
+    /// Reads a little-endian word, wrapping around to 0x0000 at the top
+    /// of memory
+    fn read_u16(&self, addr: u16) -> u16 {
+        let low = self.read_byte(addr) as u16;
+        let high = self.read_byte(addr.wrapping_add(1)) as u16;
+
+        low | (high << 8)
+    }
+
+    /// Writes a little-endian word, low byte first, wrapping around to
+    /// 0x0000 at the top of memory like `read_u16`
+    fn write_u16(&mut self, addr: u16, value: u16) {
+        self.write_byte(addr, value as u8);
+        self.write_byte(addr.wrapping_add(1), (value >> 8) as u8);
+    }
+
+    /// Called by `Machine::run_frame` at the end of every frame
+    fn end_frame(&mut self) {}
+
+    /// The extra cycles the Cpu waits for an access to finish, such as for
+    /// slow ROM or I/O. They are added to the cycles of the instruction or
+    /// interrupt making the access. By default every access is as fast as
+    /// RAM.
+    fn wait_states(&self, _addr: u16, _access: MemoryAccess) -> u8 {
+        0
+    }
+
+    /// Describes what is mapped where, for `Cpu::memory_map`. Where regions
+    /// overlap the first one listed takes precedence. By default the whole
+    /// address space is reported as an undescribed custom bus.
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        vec![MemoryRegion::new(0x0000..=0xFFFF, RegionKind::Bus)]
+    }
+}
@@ -0,0 +1,79 @@
+use cpu::stack_guard::{StackFrame, StackFrameKind};
+
+/// A shadow of the subroutine calls and interrupts on the stack, kept
+/// alongside the real one for a debugger's backtrace. It also catches an
+/// `RTS` that doesn't return from a `JSR`, which usually means code pushed
+/// or pulled one byte too many.
+///
+/// # Example
+/// ```
+/// use rs6502::Cpu;
+///
+/// let mut cpu = Cpu::new();
+/// // JSR $C004, BRK, PHA, RTS
+/// cpu.load(&[0x20, 0x04, 0xC0, 0x00, 0x48, 0x60], None).unwrap();
+/// cpu.reset();
+/// cpu.enable_call_stack();
+///
+/// cpu.step().unwrap();
+/// let frame = cpu.call_stack().unwrap().frames()[0];
+/// assert_eq!((0xC000, 0xC004, 0xC003), (frame.from, frame.to, frame.return_address()));
+///
+/// // The RTS returns to the byte the PHA pushed
+/// cpu.step_n(2).unwrap();
+/// assert_eq!(&[0xC005], cpu.call_stack().unwrap().unbalanced_returns());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CallStack {
+    frames: Vec<StackFrame>,
+    unbalanced_returns: Vec<u16>,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        Default::default()
+    }
+
+    /// Returns the calls and interrupts that haven't returned, outermost
+    /// first
+    pub fn frames(&self) -> &[StackFrame] {
+        &self.frames
+    }
+
+    /// Returns the address of every `RTS` that didn't return from the
+    /// innermost `JSR`, in the order they ran
+    pub fn unbalanced_returns(&self) -> &[u16] {
+        &self.unbalanced_returns
+    }
+
+    pub fn push(&mut self, frame: StackFrame) {
+        self.frames.push(frame);
+    }
+
+    /// Drops the frames returned from by an instruction that left the
+    /// stack pointer at `stack_pointer`
+    pub fn unwind(&mut self, stack_pointer: u8) {
+        while self.frames.last().is_some_and(|frame| frame.stack_pointer <= stack_pointer) {
+            self.frames.pop();
+        }
+    }
+
+    /// Records an `RTS` at `pc` that left the stack pointer at
+    /// `stack_pointer`. A balanced `RTS` pulls the stack pointer back to
+    /// where it was before the innermost `JSR`.
+    pub fn returned(&mut self, pc: u16, stack_pointer: u8) {
+        let balanced = self.frames
+            .last()
+            .is_some_and(|frame| frame.kind == StackFrameKind::Call && frame.stack_pointer == stack_pointer);
+        if !balanced {
+            self.unbalanced_returns.push(pc);
+        }
+
+        self.unwind(stack_pointer);
+    }
+
+    /// Forgets the frames, as a reset does, keeping the unbalanced returns
+    pub fn reset(&mut self) {
+        self.frames.clear();
+    }
+}
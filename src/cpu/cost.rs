@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use ::opcodes::{AddressingMode, OpCode};
+
+/// Weights for what each instruction costs, such as the energy it uses.
+/// Instructions without a weight of their own cost the number of cycles
+/// they took, so the default model counts cycles.
+///
+/// A weight for a mnemonic and addressing mode takes precedence over a
+/// weight for the mnemonic alone.
+#[derive(Clone, Debug, Default)]
+pub struct CostModel {
+    mnemonics: HashMap<String, f64>,
+    modes: HashMap<String, HashMap<AddressingMode, f64>>,
+}
+
+impl CostModel {
+    pub fn new() -> CostModel {
+        Default::default()
+    }
+
+    /// Sets the cost of a mnemonic in every addressing mode
+    pub fn set_mnemonic_weight(&mut self, mnemonic: &str, weight: f64) {
+        self.mnemonics.insert(mnemonic.to_uppercase(), weight);
+    }
+
+    /// Sets the cost of a mnemonic in a single addressing mode
+    pub fn set_weight(&mut self, mnemonic: &str, mode: AddressingMode, weight: f64) {
+        self.modes.entry(mnemonic.to_uppercase()).or_default().insert(mode, weight);
+    }
+
+    /// Returns what an instruction that took `cycles` cycles costs
+    pub fn cost(&self, opcode: &OpCode, cycles: u8) -> f64 {
+        self.modes
+            .get(opcode.mnemonic)
+            .and_then(|modes| modes.get(&opcode.mode))
+            .or_else(|| self.mnemonics.get(opcode.mnemonic))
+            .cloned()
+            .unwrap_or(cycles as f64)
+    }
+}
+
+/// The costs accumulated while a cost model is attached to the Cpu, in
+/// total and by the address of each instruction
+#[derive(Clone, Debug, Default)]
+pub struct Costs {
+    total: f64,
+    addresses: HashMap<u16, f64>,
+}
+
+impl Costs {
+    pub fn new() -> Costs {
+        Default::default()
+    }
+
+    /// Records the cost of the instruction at an address
+    pub fn record(&mut self, addr: u16, cost: f64) {
+        self.total += cost;
+        *self.addresses.entry(addr).or_insert(0.0) += cost;
+    }
+
+    /// Returns the cost of everything executed
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    /// Returns the cost of the instruction at an address, over every time
+    /// it was executed
+    pub fn at(&self, addr: u16) -> f64 {
+        self.addresses.get(&addr).cloned().unwrap_or(0.0)
+    }
+
+    /// Returns the cost of every address that was executed, in address
+    /// order
+    pub fn by_address(&self) -> Vec<(u16, f64)> {
+        let mut result: Vec<(u16, f64)> = self.addresses.iter().map(|(&addr, &cost)| (addr, cost)).collect();
+        result.sort_by_key(|&(addr, _)| addr);
+
+        result
+    }
+
+    /// Clears all recorded costs, such as at the start of a new run
+    pub fn clear(&mut self) {
+        self.total = 0.0;
+        self.addresses.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_the_most_specific_weight() {
+        let lda_immediate = OpCode::from_raw_byte(0xA9).unwrap();
+        let lda_zero_page = OpCode::from_raw_byte(0xA5).unwrap();
+        let inx = OpCode::from_raw_byte(0xE8).unwrap();
+
+        let mut model = CostModel::new();
+        model.set_mnemonic_weight("lda", 1.5);
+        model.set_weight("LDA", AddressingMode::Immediate, 0.5);
+
+        assert_eq!(0.5, model.cost(lda_immediate, 2));
+        assert_eq!(1.5, model.cost(lda_zero_page, 3));
+        assert_eq!(2.0, model.cost(inx, 2));
+    }
+}
@@ -0,0 +1,149 @@
+use std::cell::Cell;
+use std::ops::RangeInclusive;
+
+const EXECUTED: u8 = 0x01;
+const READ: u8 = 0x02;
+const WRITTEN: u8 = 0x04;
+
+/// Which addresses the Cpu executed, read and wrote, such as for a report
+/// of how much of a test ROM its tests reach, or to tell code from data
+/// when disassembling. The opcode and operand bytes of an instruction
+/// count as executed rather than read.
+///
+/// # Example
+/// ```
+/// use rs6502::Cpu;
+///
+/// let mut cpu = Cpu::new();
+/// // LDA $C006, STA $10, BRK, then a byte of data
+/// cpu.load(&[0xAD, 0x06, 0xC0, 0x85, 0x10, 0x00, 0x42], None).unwrap();
+/// cpu.reset();
+/// cpu.enable_coverage();
+///
+/// cpu.step_n(2).unwrap();
+///
+/// let coverage = cpu.coverage().unwrap();
+/// assert_eq!(vec![0xC000..=0xC004], coverage.executed_ranges());
+/// assert!(coverage.read(0xC006) && !coverage.executed(0xC006));
+/// assert!(coverage.written(0x10));
+/// ```
+#[derive(Clone)]
+pub struct Coverage {
+    flags: Vec<Cell<u8>>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage { flags: vec![Cell::new(0); 0x10000] }
+    }
+
+    /// Records the bytes of an instruction that was executed
+    pub fn record_executed(&self, addr: u16, length: u8) {
+        for offset in 0..length as u16 {
+            self.mark(addr.wrapping_add(offset), EXECUTED);
+        }
+    }
+
+    pub fn record_read(&self, addr: u16) {
+        self.mark(addr, READ);
+    }
+
+    pub fn record_written(&self, addr: u16) {
+        self.mark(addr, WRITTEN);
+    }
+
+    /// Returns true if the address was part of an executed instruction
+    pub fn executed(&self, addr: u16) -> bool {
+        self.has(addr, EXECUTED)
+    }
+
+    /// Returns true if the address was read as data
+    pub fn read(&self, addr: u16) -> bool {
+        self.has(addr, READ)
+    }
+
+    pub fn written(&self, addr: u16) -> bool {
+        self.has(addr, WRITTEN)
+    }
+
+    /// Returns the number of addresses that were part of an executed
+    /// instruction
+    pub fn executed_count(&self) -> usize {
+        self.flags.iter().filter(|flags| flags.get() & EXECUTED != 0).count()
+    }
+
+    /// Returns the runs of addresses that were part of executed
+    /// instructions, in address order
+    pub fn executed_ranges(&self) -> Vec<RangeInclusive<u16>> {
+        self.ranges(|flags| flags & EXECUTED != 0)
+    }
+
+    /// Returns the runs of addresses that were read or written but never
+    /// executed, in address order
+    pub fn data_ranges(&self) -> Vec<RangeInclusive<u16>> {
+        self.ranges(|flags| flags & EXECUTED == 0 && flags & (READ | WRITTEN) != 0)
+    }
+
+    /// Forgets everything recorded, such as at the start of a new run
+    pub fn clear(&self) {
+        for flags in &self.flags {
+            flags.set(0);
+        }
+    }
+
+    fn mark(&self, addr: u16, flag: u8) {
+        let flags = &self.flags[addr as usize];
+        flags.set(flags.get() | flag);
+    }
+
+    fn has(&self, addr: u16, flag: u8) -> bool {
+        self.flags[addr as usize].get() & flag != 0
+    }
+
+    fn ranges<F>(&self, matches: F) -> Vec<RangeInclusive<u16>>
+        where F: Fn(u8) -> bool
+    {
+        let mut ranges = Vec::new();
+        let mut start = None;
+
+        for (addr, flags) in self.flags.iter().enumerate() {
+            match (start, matches(flags.get())) {
+                (None, true) => start = Some(addr as u16),
+                (Some(first), false) => {
+                    ranges.push(first..=(addr - 1) as u16);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(first) = start {
+            ranges.push(first..=0xFFFF);
+        }
+
+        ranges
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Coverage {
+        Coverage::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_runs_of_code_and_data_up_to_the_top_of_memory() {
+        let coverage = Coverage::new();
+        coverage.record_executed(0xFFFE, 3);
+        coverage.record_read(0x0200);
+        coverage.record_read(0x0001);
+        coverage.record_written(0x0201);
+
+        assert_eq!(vec![0x0000..=0x0000, 0xFFFE..=0xFFFF], coverage.executed_ranges());
+        assert_eq!(vec![0x0001..=0x0001, 0x0200..=0x0201], coverage.data_ranges());
+        assert_eq!(3, coverage.executed_count());
+    }
+}
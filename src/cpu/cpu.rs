@@ -1,50 +1,372 @@
-use byteorder::{LittleEndian, ByteOrder};
+use std;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
 
-use ::opcodes::{AddressingMode, OpCode};
+use ::assembler::Assembler;
+use ::opcodes::{AddressingMode, CpuVariant, OpCode};
 
+use cpu::cost::{CostModel, Costs};
+use cpu::coverage::Coverage;
 use cpu::cpu_error::CpuError;
+use cpu::cycles::{BusCycle, Cycle, CycleHook};
+use cpu::dispatch::OpcodeTraits;
+use cpu::execute::ExecuteError;
+use cpu::event_queue::{EventQueue, ExternalEvent};
+use cpu::events::{CpuEvent, CpuEventListener, InterruptKind};
+use cpu::exit::{ExitCodeSource, ExitCondition, StopReason};
+use cpu::bus::Bus;
+use cpu::call_stack::CallStack;
 use cpu::flags::StatusFlags;
+use cpu::hexdump;
+use cpu::history::{Change, History};
+use cpu::host_call::HostCall;
+use cpu::interrupt_statistics::InterruptStatistics;
+use cpu::limits::RunLimits;
 use cpu::memory_bus::MemoryBus;
+use cpu::memory_map::MemoryMap;
+use cpu::profiler::Profiler;
+use cpu::protection::{MemoryAccess, Protection, ProtectionMap};
 use cpu::registers::Registers;
-use cpu::stack::Stack;
+use cpu::self_modifying_code::SelfModifyingCodePolicy;
+use cpu::snapshot::CpuSnapshot;
+use cpu::stack;
+use cpu::stack_guard::{StackFrame, StackFrameKind, StackGuard, StackViolation};
+use cpu::statistics::InstructionStatistics;
+use cpu::step_hook::{DecodedInstruction, StepHook};
+use cpu::symbols::Symbols;
+use cpu::trace::{TraceFilter, TraceLine, TraceSink};
+use cpu::steps::{IterSteps, Steps};
+use cpu::unknown_opcode::{self, UnknownOpcodePolicy};
+use cpu::verify::{self, VerifyError};
 
 const DEFAULT_CODE_SEGMENT_START_ADDRESS: u16 = 0xC000;  // Default to a 16KB ROM, leaving 48KB of main memory
 
-const STACK_START: usize = 0x100;
-const STACK_END: usize = 0x1FF;
 
-const RESET_VECTOR: usize = 0xFFFC;
-const NMI_VECTOR: usize = 0xFFFA;
-const IRQ_VECTOR: usize = 0xFFFE;
+const RESET_VECTOR: u16 = 0xFFFC;
+const NMI_VECTOR: u16 = 0xFFFA;
+const IRQ_VECTOR: u16 = 0xFFFE;
 
-#[derive(Debug)]
+// How long `Cpu::execute` lets a program run before giving up on it
+const EXECUTE_INSTRUCTION_LIMIT: u64 = 1_000_000;
+
+// How long `Cpu::step_until` runs when no run limits have been set
+const STEP_UNTIL_INSTRUCTION_LIMIT: u64 = 1_000_000;
+
+const BRK_OPCODE: u8 = 0x00;
+const JSR_OPCODE: u8 = 0x20;
+const RTI_OPCODE: u8 = 0x40;
+const RTS_OPCODE: u8 = 0x60;
+
+/// The number of cycles the 6502 reset sequence takes
+const RESET_CYCLES: u8 = 7;
+
+/// The number of cycles an IRQ or NMI takes to reach its handler
+const INTERRUPT_CYCLES: u8 = 7;
+
+/// The number of cycles a host call takes unless set otherwise, the same
+/// as a `NOP #imm`
+const HOST_CALL_CYCLES: u8 = 2;
+
+/// The mnemonic host calls are reported under in statistics and traces
+const HOST_CALL_MNEMONIC: &str = "HCL";
+
+/// The operand of a decoded instruction
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Operand {
+    /// An immediate value, or the offset of a branch
     Immediate(u8),
+    /// The effective address the instruction accesses
     Memory(u16),
+    /// No operand, or the accumulator
     Implied,
 }
 
-/// A representation of a 6502 microprocessor
-pub struct Cpu {
-    pub memory: MemoryBus,
+/// A representation of a 6502 microprocessor, reading and writing memory
+/// through a bus that is a `MemoryBus` unless another is given
+pub struct Cpu<B = MemoryBus> {
+    pub memory: B,
     pub registers: Registers,
     pub flags: StatusFlags,
-    pub stack: Stack,
+    statistics: Option<InstructionStatistics>,
+    interrupt_statistics: Option<InterruptStatistics>,
+    cost_model: Option<(CostModel, Costs)>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    stack_guard: Option<(StackGuard, Vec<StackFrame>)>,
+    call_stack: Option<CallStack>,
+    history: Option<(History, Vec<(u16, u8)>)>,
+    idle_detection: bool,
+    idle: bool,
+    irq_line: bool,
+    nmi_line: bool,
+    nmi_pending: bool,
+    event_queue: EventQueue,
+    variant: CpuVariant,
+    illegal_opcodes: bool,
+    unofficial_nops: bool,
+    #[cfg(feature = "reference-dispatch")]
+    mnemonic_dispatch: bool,
+    unknown_opcode_policy: UnknownOpcodePolicy<B>,
+    halted: Option<StopReason>,
+    breakpoints: BTreeSet<u16>,
+    resume_from: Option<u16>,
+    host_call_opcode: Option<u8>,
+    host_call_cycles: u8,
+    host_calls: HashMap<u8, HostCall<B>>,
+    protection: ProtectionMap,
+    protection_fault: Cell<Option<(u16, MemoryAccess)>>,
+    code_regions: Vec<RangeInclusive<u16>>,
+    self_modifying_code_policy: SelfModifyingCodePolicy,
+    code_write: Option<u16>,
+    instruction_pc: u16,
+    listeners: Vec<CpuEventListener>,
+    pending_events: RefCell<Vec<CpuEvent>>,
+    cycle_hooks: Vec<CycleHook>,
+    pre_step_hook: Option<StepHook<B>>,
+    trace_sinks: Vec<(TraceFilter, TraceSink)>,
+    symbols: Option<Rc<Symbols>>,
+    post_step_hook: Option<StepHook<B>>,
+    pending_accesses: RefCell<Vec<BusCycle>>,
+    exit_condition: Option<ExitCondition>,
+    exit_code_source: ExitCodeSource,
+    limits: Option<RunLimits>,
+    branch_cycles: u8,
+    wait_states: Cell<u8>,
+    cycles: u64,
+    dispatch: Box<DispatchTable<B>>,
 }
 
+/// Executes an instruction, given its opcode and operand
+type Handler<B> = fn(&mut Cpu<B>, &OpCode, &Operand);
+
+/// The opcode, handler and traits for every byte, indexed by the byte
+type DispatchTable<B> = [Option<(&'static OpCode, Handler<B>, OpcodeTraits)>; 256];
+
 pub type CpuLoadResult = Result<(), CpuError>;
 pub type CpuStepResult = Result<u8, CpuError>;
 pub type CpuMultiStepResult = Result<u64, CpuError>;
 
+impl Default for Cpu {
+    fn default() -> Cpu {
+        Cpu::new()
+    }
+}
+
 impl Cpu {
     /// Returns a default instance of a Cpu
     pub fn new() -> Cpu {
-        Cpu {
-            memory: MemoryBus::new(),
+        Cpu::with_bus(MemoryBus::new())
+    }
+
+    /// Returns a Cpu over an existing 64kb memory image, for embedders
+    /// that have already composed the whole address space. Nothing needs
+    /// to be loaded: set the PC, or call `reset` to start at the reset
+    /// vector in the image, and step.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut image = [0x00; 0x10000];
+    /// // LDA #$42, STA $10 at $0400
+    /// image[0x0400..0x0404].copy_from_slice(&[0xA9, 0x42, 0x85, 0x10]);
+    ///
+    /// let mut cpu = Cpu::with_memory(&image);
+    /// cpu.registers.PC = 0x0400;
+    /// cpu.step_n(2).unwrap();
+    ///
+    /// assert_eq!(0x42, cpu.memory.read_byte(0x10));
+    /// ```
+    pub fn with_memory(image: &[u8; 1024 * 64]) -> Cpu {
+        Cpu::with_bus(MemoryBus::from_image(image))
+    }
+
+    /// Captures the registers, flags, RAM, interrupt lines and cycle count,
+    /// to be put back later with `restore`
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // INX, STX $10, JMP $C000
+    /// cpu.load(&[0xE8, 0x86, 0x10, 0x4C, 0x00, 0xC0], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// let snapshot = cpu.snapshot();
+    /// cpu.step_n(30).unwrap();
+    /// cpu.restore(&snapshot).unwrap();
+    ///
+    /// assert_eq!(0xC000, cpu.registers.PC);
+    /// assert_eq!(0x00, cpu.registers.X);
+    /// assert_eq!(0x00, cpu.memory.read_byte(0x10));
+    /// assert_eq!(0, cpu.cycles());
+    /// ```
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            registers: self.registers,
+            flags: self.flags,
+            memory: self.memory.to_image().to_vec(),
+            irq_line: self.irq_line,
+            nmi_line: self.nmi_line,
+            nmi_pending: self.nmi_pending,
+            idle: self.idle,
+            halted: self.halted,
+            cycles: self.cycles,
+        }
+    }
+
+    /// Puts the Cpu back into the state of a snapshot. Returns an error,
+    /// leaving the Cpu alone, if the snapshot doesn't hold 64kb of RAM.
+    pub fn restore(&mut self, snapshot: &CpuSnapshot) -> Result<(), CpuError> {
+        if snapshot.memory.len() != self.memory.len() {
+            return Err(CpuError::invalid_snapshot(snapshot.memory.len()));
+        }
+
+        self.memory.copy_from_slice(&snapshot.memory);
+        self.registers = snapshot.registers;
+        self.flags = snapshot.flags;
+        self.irq_line = snapshot.irq_line;
+        self.nmi_line = snapshot.nmi_line;
+        self.nmi_pending = snapshot.nmi_pending;
+        self.idle = snapshot.idle;
+        self.halted = snapshot.halted;
+        self.resume_from = None;
+        self.cycles = snapshot.cycles;
+
+        Ok(())
+    }
+
+    /// Returns a Cpu that emulates the CMOS 65C02
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new_65c02();
+    /// // LDX #$42, PHX, PLY
+    /// cpu.load(&[0xA2, 0x42, 0xDA, 0x7A], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_n(3).unwrap();
+    ///
+    /// assert_eq!(0x42, cpu.registers.Y);
+    /// ```
+    pub fn new_65c02() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.set_variant(CpuVariant::Cmos65C02);
+        cpu
+    }
+
+    /// Assembles a program, loads it into a new Cpu at `origin` (`$C000` by
+    /// default) and runs it until it reaches a `BRK`, halts or goes idle.
+    /// Programs that run for more than a million instructions are stopped
+    /// with a limit error.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let cpu = Cpu::execute("
+    ///         LDX #$00
+    ///     LOOP
+    ///         INX
+    ///         CPX #$10
+    ///         BNE LOOP
+    /// ", None).unwrap();
+    ///
+    /// assert_eq!(0x10, cpu.registers.X);
+    /// ```
+    pub fn execute<O>(asm: &str, origin: O) -> Result<Cpu, ExecuteError>
+        where O: Into<Option<u16>>
+    {
+        let origin = origin.into().unwrap_or(DEFAULT_CODE_SEGMENT_START_ADDRESS);
+        let segments = Assembler::new().assemble_string(asm, origin)?;
+
+        let mut cpu = Cpu::new();
+        for segment in &segments {
+            cpu.load(&segment.code, segment.address)?;
+        }
+        cpu.reset();
+
+        let guard = RunLimits::new().max_instructions(EXECUTE_INSTRUCTION_LIMIT).start();
+        let mut instructions = 0;
+        while !cpu.idle && cpu.halted.is_none() && cpu.memory.read_byte(cpu.registers.PC) != BRK_OPCODE {
+            if let Some(limit) = guard.reached(instructions, 0) {
+                return Err(CpuError::limit_reached(cpu.registers.PC, limit).into());
+            }
+            cpu.step()?;
+            instructions += 1;
+        }
+
+        Ok(cpu)
+    }
+}
+
+impl<B> Cpu<B>
+    where B: Bus
+{
+    /// Returns a Cpu that reads and writes memory through a custom bus
+    /// rather than a `MemoryBus`. The bus type is part of the Cpu type, so
+    /// accessing memory costs no more than it does with a `MemoryBus`.
+    pub fn with_bus(bus: B) -> Cpu<B> {
+        let mut cpu = Cpu {
+            memory: bus,
+
             registers: Registers::new(),
             flags: Default::default(),
-            stack: Stack::new(),
-        }
+            statistics: None,
+            interrupt_statistics: None,
+            cost_model: None,
+            profiler: None,
+            coverage: None,
+            stack_guard: None,
+            call_stack: None,
+            history: None,
+            idle_detection: false,
+            idle: false,
+            irq_line: false,
+            nmi_line: false,
+            nmi_pending: false,
+            event_queue: EventQueue::new(),
+            variant: CpuVariant::Nmos6502,
+            illegal_opcodes: false,
+            unofficial_nops: false,
+            #[cfg(feature = "reference-dispatch")]
+            mnemonic_dispatch: false,
+            unknown_opcode_policy: UnknownOpcodePolicy::Error,
+            halted: None,
+            breakpoints: BTreeSet::new(),
+            resume_from: None,
+            host_call_opcode: None,
+            host_call_cycles: HOST_CALL_CYCLES,
+            host_calls: HashMap::new(),
+            protection: ProtectionMap::new(),
+            protection_fault: Cell::new(None),
+            code_regions: Vec::new(),
+            self_modifying_code_policy: SelfModifyingCodePolicy::Ignore,
+            code_write: None,
+            instruction_pc: 0,
+            listeners: Vec::new(),
+            pending_events: RefCell::new(Vec::new()),
+            cycle_hooks: Vec::new(),
+            pre_step_hook: None,
+            trace_sinks: Vec::new(),
+            symbols: None,
+            post_step_hook: None,
+            pending_accesses: RefCell::new(Vec::new()),
+            exit_condition: None,
+            exit_code_source: ExitCodeSource::Accumulator,
+            limits: None,
+            branch_cycles: 0,
+            wait_states: Cell::new(0),
+            cycles: 0,
+            dispatch: Box::new([None; 256]),
+        };
+        cpu.build_dispatch_table();
+        cpu
     }
 
     /// Loads code into the Cpu main memory at an optional offset. If no
@@ -53,10 +375,8 @@ impl Cpu {
     pub fn load<T>(&mut self, code: &[u8], addr: T) -> CpuLoadResult
         where T: Into<Option<u16>>
     {
-        let addr = addr.into();
-        let addr: u16 = if addr.is_some() {
-            let addr = addr.unwrap();
-            if addr as u32 + code.len() as u32 - 1 > u16::max_value() as u32 {
+        let addr: u16 = if let Some(addr) = addr.into() {
+            if addr as u32 + code.len() as u32 - 1 > u16::MAX as u32 {
                 return Err(CpuError::code_segment_out_of_range(addr));
             } else if addr == 0 {
                 DEFAULT_CODE_SEGMENT_START_ADDRESS
@@ -67,8 +387,9 @@ impl Cpu {
             DEFAULT_CODE_SEGMENT_START_ADDRESS
         };
 
-        for x in 0..code.len() {
-            self.memory.write_byte(addr + x as u16, code[x]);
+        // Loading puts code into ROM, so it writes past any protection
+        for (x, byte) in code.iter().enumerate() {
+            self.memory.poke(addr + x as u16, *byte);
         }
 
         // Set the Program Counter to point at the
@@ -80,145 +401,1975 @@ impl Cpu {
 
     /// Sets the start vector in memory if its currently zero.
     fn set_start_vector(&mut self, addr: u16) {
-        let current = LittleEndian::read_u16(&self.memory[RESET_VECTOR..]);
+        let current = self.memory.read_u16(RESET_VECTOR);
         if current == 0 {
-            LittleEndian::write_u16(&mut self.memory[RESET_VECTOR..], addr);
+            self.memory.poke(RESET_VECTOR, addr as u8);
+            self.memory.poke(RESET_VECTOR + 1, (addr >> 8) as u8);
         }
     }
 
-    /// Runs N instructions of code through the Cpu
+    /// Runs N instructions of code through the Cpu. Stops early if the
+    /// Cpu becomes idle or halts, and returns a limit error if one of the
+    /// run limits is reached first.
     pub fn step_n(&mut self, n: u32) -> CpuMultiStepResult {
+        let guard = self.limits.map(|limits| limits.start());
         let mut v = 0;
-        for _ in 0..n {
+        for i in 0..n as u64 {
+            if self.idle || self.halted.is_some() {
+                break;
+            }
+            if let Some(limit) = guard.as_ref().and_then(|guard| guard.reached(i, v)) {
+                return Err(CpuError::limit_reached(self.registers.PC, limit));
+            }
             v += self.step()? as u64;
         }
 
         Ok(v)
     }
 
+    /// Limits how long each call to `step_n`, `run`, `run_until_brk`,
+    /// `run_for_cycles` or `step_until` can go on for
+    pub fn set_limits(&mut self, limits: RunLimits) {
+        self.limits = Some(limits);
+    }
+
+    pub fn clear_limits(&mut self) {
+        self.limits = None;
+    }
+
+    pub fn limits(&self) -> Option<RunLimits> {
+        self.limits
+    }
+
+    /// Returns an iterator that executes one instruction each time it is
+    /// advanced, yielding information about the instruction executed
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.load(&[0xA9, 0x01, 0xE8, 0x00], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// let count = cpu.steps().take_while(|s| !s.is_brk()).count();
+    ///
+    /// assert_eq!(2, count);
+    /// ```
+    pub fn steps(&mut self) -> Steps<'_, B> {
+        Steps::new(self)
+    }
+
+    /// Returns an iterator like `steps` that yields the result of each
+    /// instruction, ending after the first error, so a run can be
+    /// collected with any error that stopped it
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, CpuError, StepInfo};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDA #$01, INX, then an unknown opcode
+    /// cpu.load(&[0xA9, 0x01, 0xE8, 0xC3], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// let steps: Result<Vec<StepInfo>, CpuError> = cpu.iter_steps().take(1000).collect();
+    ///
+    /// assert_eq!(CpuError::unknown_opcode(0xC003, 0xC3), steps.unwrap_err());
+    /// assert_eq!(0xC003, cpu.registers.PC);
+    /// ```
+    pub fn iter_steps(&mut self) -> IterSteps<'_, B> {
+        IterSteps::new(self)
+    }
+
     pub fn reset(&mut self) {
         self.registers = Default::default();
         self.flags = Default::default();
-        self.registers.PC = LittleEndian::read_u16(&self.memory[RESET_VECTOR..]);
+        self.registers.PC = self.memory.read_u16(RESET_VECTOR);
+        self.idle = false;
+        self.halted = None;
+        self.resume_from = None;
+        self.nmi_pending = false;
+        if let Some((_, ref mut frames)) = self.stack_guard {
+            frames.clear();
+        }
+        if let Some(ref mut call_stack) = self.call_stack {
+            call_stack.reset();
+        }
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.reset();
+        }
     }
 
-    /// Runs a single instruction of code through the Cpu
+    /// Performs the reset sequence of a real 6502, so a ROM image can be
+    /// booted the way hardware would. Unlike `reset`, the registers are
+    /// left alone: the interrupt flag is set, the stack pointer drops by
+    /// 3 as though the return address and status were pushed (without
+    /// writing them) and execution starts at the reset vector at `$FFFC`.
+    /// Returns the 7 cycles the sequence takes.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.memory.write_bytes(0xFFFC, &[0x00, 0xE0]);
+    ///
+    /// assert_eq!(7, cpu.power_on_reset());
+    /// assert_eq!(0xE000, cpu.registers.PC);
+    /// assert_eq!(0xFC, cpu.registers.S);
+    /// assert!(cpu.flags.interrupt_disabled);
+    /// ```
+    pub fn power_on_reset(&mut self) -> u8 {
+        self.flags.interrupt_disabled = true;
+        self.registers.S = self.registers.S.wrapping_sub(0x03);
+        self.registers.PC = self.memory.read_u16(RESET_VECTOR);
+        self.idle = false;
+        self.halted = None;
+        self.resume_from = None;
+        self.nmi_pending = false;
+        if let Some((_, ref mut frames)) = self.stack_guard {
+            frames.clear();
+        }
+        if let Some(ref mut call_stack) = self.call_stack {
+            call_stack.reset();
+        }
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.reset();
+        }
+
+        RESET_CYCLES
+    }
+
+    /// Sets what the Cpu does when it reaches a byte that is not a known
+    /// opcode. By default `step` returns an error.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, UnknownOpcodePolicy};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.set_unknown_opcode_policy(UnknownOpcodePolicy::Nop);
+    /// // An undocumented 3 byte NOP, then INX
+    /// cpu.load(&[0x1C, 0x00, 0x44, 0xE8], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_n(2).unwrap();
+    ///
+    /// assert_eq!(0x01, cpu.registers.X);
+    /// ```
+    pub fn set_unknown_opcode_policy(&mut self, policy: UnknownOpcodePolicy<B>) {
+        self.unknown_opcode_policy = policy;
+    }
+
+    /// Designates an opcode byte as the host call instruction. It takes a
+    /// one byte operand selecting which registered host call to run, and
+    /// takes precedence over any instruction that normally uses the byte.
+    /// A host call is an instruction like any other to hooks, statistics,
+    /// traces, coverage and history, where it appears as `HCL`, and takes
+    /// 2 cycles unless `set_host_call_cycles` says otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.enable_host_calls(0x02);
+    /// cpu.register_host_call(0x01, |cpu| cpu.registers.A = 0x42);
+    /// // Host call $01, then INX
+    /// cpu.load(&[0x02, 0x01, 0xE8], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_n(2).unwrap();
+    ///
+    /// assert_eq!(0x42, cpu.registers.A);
+    /// assert_eq!(0x01, cpu.registers.X);
+    /// ```
+    pub fn enable_host_calls(&mut self, opcode: u8) {
+        self.host_call_opcode = Some(opcode);
+    }
+
+    pub fn disable_host_calls(&mut self) {
+        self.host_call_opcode = None;
+    }
+
+    /// Sets how many cycles a host call takes, so that a call standing in
+    /// for a ROM routine can cost what the routine would have
+    pub fn set_host_call_cycles(&mut self, cycles: u8) {
+        self.host_call_cycles = cycles;
+    }
+
+    pub fn host_call_cycles(&self) -> u8 {
+        self.host_call_cycles
+    }
+
+    /// Registers a host function that runs when the host call opcode is
+    /// executed with the given id as its operand
+    pub fn register_host_call<F>(&mut self, id: u8, handler: F)
+        where F: FnMut(&mut Cpu<B>) + 'static
+    {
+        self.host_calls.insert(id, Box::new(handler));
+    }
+
+    /// Protects a region of memory from the instructions the Cpu runs.
+    /// An access that isn't allowed makes `step` return a memory protection
+    /// error, and a blocked write leaves memory unchanged. Stack operations
+    /// and interrupts are not checked. This is checked before the ranges
+    /// `MemoryBus::mark_rom` drops writes to, so a write blocked here never
+    /// reaches the bus.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, CpuErrorKind, MemoryAccess, Protection};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.protect(0xC000..=0xFFFF, Protection::ReadOnly);
+    /// // STA $C000
+    /// cpu.load(&[0x8D, 0x00, 0xC0], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// let error = cpu.step().unwrap_err();
+    ///
+    /// assert_eq!(0xC000, error.addr());
+    /// assert_eq!(&CpuErrorKind::MemoryProtection { pc: 0xC000, access: MemoryAccess::Write },
+    ///            error.kind());
+    /// assert_eq!(0x8D, cpu.memory.read_byte(0xC000));
+    /// ```
+    pub fn protect(&mut self, range: RangeInclusive<u16>, protection: Protection) {
+        self.protection.protect(range, protection);
+    }
+
+    /// Removes all memory protection
+    pub fn clear_protection(&mut self) {
+        self.protection.clear();
+    }
+
+    /// Reports what is mapped where in the address space, along with the
+    /// memory protection and code regions set on the Cpu
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, Protection, RandomDevice};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.memory.map_device(0xD000..=0xD000, RandomDevice::new(1));
+    /// cpu.protect(0xE000..=0xFFFF, Protection::ReadOnly);
+    ///
+    /// assert_eq!("$D000-$D000  Device: Random number generator\n\
+    ///             $0000-$FFFF  RAM\n\
+    ///             $E000-$FFFF  Protected: read only\n",
+    ///            cpu.memory_map().to_string());
+    /// ```
+    pub fn memory_map(&self) -> MemoryMap {
+        MemoryMap {
+            regions: self.memory.memory_map(),
+            protection: self.protection.regions().to_vec(),
+            code: self.code_regions.clone(),
+        }
+    }
+
+    /// Marks a region of memory as code for self-modifying code detection
+    pub fn mark_code(&mut self, range: RangeInclusive<u16>) {
+        self.code_regions.push(range);
+    }
+
+    /// Sets what the Cpu does when an instruction writes into memory marked
+    /// as code. By default nothing happens.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, CpuError, SelfModifyingCodePolicy};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // STA $C001
+    /// cpu.load(&[0x8D, 0x01, 0xC0], None).unwrap();
+    /// cpu.reset();
+    /// cpu.mark_code(0xC000..=0xC002);
+    /// cpu.set_self_modifying_code_policy(SelfModifyingCodePolicy::Error);
+    ///
+    /// assert_eq!(Err(CpuError::self_modifying_code(0xC000, 0xC001)), cpu.step());
+    /// ```
+    pub fn set_self_modifying_code_policy(&mut self, policy: SelfModifyingCodePolicy) {
+        self.self_modifying_code_policy = policy;
+    }
+
+    /// Registers a listener that is told about every event the Cpu raises
+    ///
+    /// # Example
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rs6502::{Cpu, CpuEvent};
+    ///
+    /// let writes = Rc::new(RefCell::new(Vec::new()));
+    /// let listener_writes = writes.clone();
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.add_event_listener(move |event| {
+    ///     if let CpuEvent::MemoryWrite { addr, value } = *event {
+    ///         listener_writes.borrow_mut().push((addr, value));
+    ///     }
+    /// });
+    /// // LDA #$42, STA $0200
+    /// cpu.load(&[0xA9, 0x42, 0x8D, 0x00, 0x02], None).unwrap();
+    /// cpu.reset();
+    /// cpu.step_n(2).unwrap();
+    ///
+    /// assert_eq!(vec![(0x0200, 0x42)], *writes.borrow());
+    /// ```
+    pub fn add_event_listener<F>(&mut self, listener: F)
+        where F: FnMut(&CpuEvent) + 'static
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub fn clear_event_listeners(&mut self) {
+        self.listeners.clear();
+    }
+
+    /// Registers a hook that is called once for every clock cycle, in
+    /// order, with the bus access made on that cycle. Instructions still
+    /// execute as a whole, so the hooks for an instruction's cycles run
+    /// once it has finished, followed by its events. This is slower than
+    /// stepping without hooks, but lets devices that depend on when a read
+    /// or write happens within an instruction, such as raster effects or
+    /// bit-banged serial ports, be modelled on top of the Cpu.
+    ///
+    /// # Example
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rs6502::{Cpu, MemoryAccess};
+    ///
+    /// let writes = Rc::new(RefCell::new(Vec::new()));
+    /// let hook_writes = writes.clone();
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.add_cycle_hook(move |cycle| {
+    ///     if let Some(bus) = cycle.bus {
+    ///         if bus.access == MemoryAccess::Write {
+    ///             hook_writes.borrow_mut().push((cycle.index, bus.addr));
+    ///         }
+    ///     }
+    /// });
+    /// // STA $0200
+    /// cpu.load(&[0x8D, 0x00, 0x02], None).unwrap();
+    /// cpu.reset();
+    /// cpu.step().unwrap();
+    ///
+    /// // The store happens on the last of the instruction's 4 cycles
+    /// assert_eq!(vec![(3, 0x0200)], *writes.borrow());
+    /// ```
+    pub fn add_cycle_hook<F>(&mut self, hook: F)
+        where F: FnMut(&Cycle) + 'static
+    {
+        self.cycle_hooks.push(Box::new(hook));
+    }
+
+    pub fn clear_cycle_hooks(&mut self) {
+        self.cycle_hooks.clear();
+    }
+
+    /// Sets a hook that runs before every instruction, once its operand
+    /// has been decoded. The hook is given the Cpu, so it can read the
+    /// registers or change memory before the instruction sees it.
+    ///
+    /// # Example
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rs6502::{Cpu, Operand};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDA $10, LDX $10
+    /// cpu.load(&[0xA5, 0x10, 0xA6, 0x10], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// // Keep $10 topped up, whatever the program does
+    /// cpu.set_pre_step_hook(|cpu, _| cpu.memory.write_byte(0x10, 0x63));
+    ///
+    /// let trace = Rc::new(RefCell::new(Vec::new()));
+    /// let log = trace.clone();
+    /// cpu.set_post_step_hook(move |cpu, instruction| {
+    ///     log.borrow_mut().push((instruction.opcode.mnemonic, instruction.operand, cpu.registers.A));
+    /// });
+    ///
+    /// cpu.step_n(2).unwrap();
+    ///
+    /// assert_eq!(vec![("LDA", Operand::Memory(0x10), 0x63), ("LDX", Operand::Memory(0x10), 0x63)],
+    ///            *trace.borrow());
+    /// ```
+    pub fn set_pre_step_hook<F>(&mut self, hook: F)
+        where F: FnMut(&mut Cpu<B>, &DecodedInstruction) + 'static
+    {
+        self.pre_step_hook = Some(Box::new(hook));
+    }
+
+    /// Sets a hook that runs after every instruction that executes
+    /// successfully, with the registers as the instruction left them
+    pub fn set_post_step_hook<F>(&mut self, hook: F)
+        where F: FnMut(&mut Cpu<B>, &DecodedInstruction) + 'static
+    {
+        self.post_step_hook = Some(Box::new(hook));
+    }
+
+    /// Removes both the pre and post step hooks
+    pub fn clear_step_hooks(&mut self) {
+        self.pre_step_hook = None;
+        self.post_step_hook = None;
+    }
+
+    /// Sends a trace line for every instruction the filter lets through to
+    /// the sink. Each sink has its own filter, so a long run can log only
+    /// what is of interest.
+    ///
+    /// # Example
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rs6502::{Cpu, InstructionClass, TraceFilter};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDX #$02, STX $10, DEX, BNE -5
+    /// cpu.load(&[0xA2, 0x02, 0x86, 0x10, 0xCA, 0xD0, 0xFB], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// let log = Rc::new(RefCell::new(Vec::new()));
+    /// let lines = log.clone();
+    /// cpu.add_trace_sink(TraceFilter::new().only(InstructionClass::Stores),
+    ///                    move |line| lines.borrow_mut().push(line.to_string()));
+    ///
+    /// cpu.step_n(7).unwrap();
+    ///
+    /// assert_eq!(vec!["C002  86 10     STX $10         A:00 X:02 Y:00 P:24 SP:FF",
+    ///                 "C002  86 10     STX $10         A:00 X:01 Y:00 P:24 SP:FF"],
+    ///            *log.borrow());
+    /// ```
+    pub fn add_trace_sink<F>(&mut self, filter: TraceFilter, sink: F)
+        where F: FnMut(&TraceLine) + 'static
+    {
+        self.trace_sinks.push((filter, Box::new(sink)));
+    }
+
+    pub fn clear_trace_sinks(&mut self) {
+        self.trace_sinks.clear();
+    }
+
+    /// Names addresses in trace lines and halt reports, such as with the
+    /// labels of the assembled program
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Assembler, Cpu};
+    ///
+    /// let mut assembler = Assembler::new();
+    /// let segments = assembler.assemble_string("
+    ///     MAIN_LOOP INX
+    ///               INX
+    ///               INX
+    ///               JMP MAIN_LOOP
+    /// ", 0xC000).unwrap();
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.load(&segments[0].code, None).unwrap();
+    /// cpu.reset();
+    /// cpu.set_symbols(assembler.symbols());
+    /// cpu.add_breakpoint(0xC002);
+    ///
+    /// cpu.step_n(3).unwrap();
+    ///
+    /// assert_eq!(Some("Breakpoint at MAIN_LOOP+2 ($C002)".into()), cpu.halt_report());
+    /// ```
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = Some(Rc::new(symbols));
+    }
+
+    pub fn clear_symbols(&mut self) {
+        self.symbols = None;
+    }
+
+    pub fn symbols(&self) -> Option<&Symbols> {
+        self.symbols.as_deref()
+    }
+
+    /// Returns the number of cycles `step` has run since the Cpu was
+    /// created. Resets don't clear it.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the registers and flags on one line, for printing from a
+    /// debugger or a failing test
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.load(&[0xA9, 0x80], None).unwrap();
+    /// cpu.reset();
+    /// cpu.step().unwrap();
+    ///
+    /// assert_eq!("A:80 X:00 Y:00 P:Nv-bdIzc SP:FF PC:C002", cpu.state_line());
+    /// ```
+    pub fn state_line(&self) -> String {
+        format!("A:{:02X} X:{:02X} Y:{:02X} P:{} SP:{:02X} PC:{:04X}",
+                self.registers.A,
+                self.registers.X,
+                self.registers.Y,
+                self.flags,
+                self.registers.S,
+                self.registers.PC)
+    }
+
+    /// Formats the memory in the range as a hex dump, sixteen bytes to a
+    /// line alongside their ASCII, for printing when a test fails
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.memory.write_bytes(0x0200, b"6502");
+    ///
+    /// assert_eq!("0200  36 35 30 32 00                                    |6502.|\n",
+    ///            cpu.hexdump(0x0200..=0x0204));
+    /// ```
+    pub fn hexdump(&self, range: RangeInclusive<u16>) -> String {
+        let start = *range.start();
+        let bytes: Vec<u8> = range.map(|addr| self.memory.read_byte(addr)).collect();
+
+        hexdump::format(start, &bytes)
+    }
+
+    /// Returns true if the Cpu has stopped executing instructions
+    /// until it is reset, or resumed from a breakpoint
+    pub fn is_halted(&self) -> bool {
+        self.halted.is_some()
+    }
+
+    /// Returns why the Cpu halted, or None if it is still running
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, StopReason};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.enable_illegal_opcodes();
+    /// // INX, KIL
+    /// cpu.load(&[0xE8, 0x02], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_n(10).unwrap();
+    ///
+    /// assert_eq!(Some(StopReason::Jammed { pc: 0xC001, opcode: 0x02 }), cpu.halted());
+    /// ```
+    pub fn halted(&self) -> Option<StopReason> {
+        self.halted
+    }
+
+    /// Describes why the Cpu halted, naming the address it stopped at
+    /// when the Cpu has symbols, or None if it is still running
+    pub fn halt_report(&self) -> Option<String> {
+        self.halted.map(|reason| reason.describe(self.symbols()))
+    }
+
+    /// Stops the Cpu when the Program Counter reaches an address, before
+    /// the instruction there is executed. The Cpu halts with
+    /// `StopReason::Breakpoint` until `resume` is called.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, StopReason};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // INX, INX, INX
+    /// cpu.load(&[0xE8, 0xE8, 0xE8], None).unwrap();
+    /// cpu.reset();
+    /// cpu.add_breakpoint(0xC001);
+    ///
+    /// cpu.step_n(3).unwrap();
+    /// assert_eq!(Some(StopReason::Breakpoint(0xC001)), cpu.halted());
+    /// assert_eq!(1, cpu.registers.X);
+    ///
+    /// cpu.resume();
+    /// cpu.step_n(2).unwrap();
+    /// assert_eq!(3, cpu.registers.X);
+    /// ```
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Returns the breakpoints, in address order
+    pub fn breakpoints(&self) -> Vec<u16> {
+        self.breakpoints.iter().cloned().collect()
+    }
+
+    /// Continues after stopping at a breakpoint. The instruction at the
+    /// breakpoint is executed by the next step rather than stopping again.
+    /// Does nothing if the Cpu didn't stop at a breakpoint.
+    pub fn resume(&mut self) {
+        if let Some(StopReason::Breakpoint(addr)) = self.halted {
+            self.halted = None;
+            self.resume_from = Some(addr);
+        }
+    }
+
+    /// Sets how a guest program signals that it has finished. By default
+    /// programs never exit.
+    pub fn set_exit_condition(&mut self, condition: ExitCondition) {
+        self.exit_condition = Some(condition);
+    }
+
+    pub fn clear_exit_condition(&mut self) {
+        self.exit_condition = None;
+    }
+
+    /// Sets where the exit code is read from when a program exits through
+    /// a trap address or a `BRK` marker. Defaults to the accumulator.
+    pub fn set_exit_code_source(&mut self, source: ExitCodeSource) {
+        self.exit_code_source = source;
+    }
+
+    /// Returns the exit code of the program, or None if it hasn't exited
+    /// since the last reset
+    pub fn exit_code(&self) -> Option<u8> {
+        match self.halted {
+            Some(StopReason::Exited(code)) => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Runs instructions until the program exits, returning its exit code.
+    /// Returns None if the Cpu becomes idle or halts without the program
+    /// exiting, or a limit error if one of the run limits is reached first.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, ExitCondition};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.set_exit_condition(ExitCondition::PortWrite(0xFFF0));
+    /// // LDX #$05, DEX, BNE -3, STX $FFF0
+    /// cpu.load(&[0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x8E, 0xF0, 0xFF], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// assert_eq!(Some(0x00), cpu.run().unwrap());
+    /// ```
+    pub fn run(&mut self) -> Result<Option<u8>, CpuError> {
+        self.run_until(|_, _| false)?;
+
+        Ok(self.exit_code())
+    }
+
+    /// Runs instructions until the Cpu reaches a `BRK`, halts or goes idle,
+    /// and says which. Unlike `run`, the `BRK` is not executed, which suits
+    /// programs that end with one. Returns an error if an instruction
+    /// fails or one of the run limits is reached first.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, StopReason};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDX #$05, DEX, BNE -3, BRK
+    /// cpu.load(&[0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0x00], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// assert_eq!(StopReason::Brk(0xC005), cpu.run_until_brk().unwrap());
+    /// assert_eq!(0x00, cpu.registers.X);
+    /// ```
+    pub fn run_until_brk(&mut self) -> Result<StopReason, CpuError> {
+        let guard = self.limits.map(|limits| limits.start());
+        let mut instructions = 0;
+        let mut cycles = 0;
+        loop {
+            let pc = self.registers.PC;
+            if let Some(reason) = self.halted {
+                return Ok(reason);
+            }
+            if self.idle {
+                return Ok(StopReason::Idle(pc));
+            }
+            if self.memory.read_byte(pc) == BRK_OPCODE {
+                return Ok(StopReason::Brk(pc));
+            }
+
+            if let Some(limit) = guard.as_ref().and_then(|guard| guard.reached(instructions, cycles)) {
+                return Err(CpuError::limit_reached(pc, limit));
+            }
+
+            cycles += self.step()? as u64;
+            instructions += 1;
+        }
+    }
+
+    /// Runs instructions until at least `n` cycles have passed, returning
+    /// how many cycles the last instruction ran past `n`, so a frame based
+    /// emulator can take them off the next frame. Stops early, with no
+    /// overshoot, if the Cpu becomes idle or halts, and returns a limit
+    /// error if one of the run limits is reached first.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // NOP, JMP $C000 - 5 cycles per loop
+    /// cpu.load(&[0xEA, 0x4C, 0x00, 0xC0], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// // NOP, JMP, NOP takes 7 cycles, one more than asked for
+    /// let overshoot = cpu.run_for_cycles(6).unwrap();
+    /// assert_eq!(1, overshoot);
+    ///
+    /// // JMP, NOP fits the rest of the next 6 exactly
+    /// assert_eq!(0, cpu.run_for_cycles(6 - overshoot).unwrap());
+    /// ```
+    pub fn run_for_cycles(&mut self, n: u64) -> CpuMultiStepResult {
+        let guard = self.limits.map(|limits| limits.start());
+        let mut instructions = 0;
+        let mut cycles = 0;
+        while cycles < n && !self.idle && self.halted.is_none() {
+            if let Some(limit) = guard.as_ref().and_then(|guard| guard.reached(instructions, cycles)) {
+                return Err(CpuError::limit_reached(self.registers.PC, limit));
+            }
+
+            cycles += self.step()? as u64;
+            instructions += 1;
+        }
+
+        Ok(cycles.saturating_sub(n))
+    }
+
+    /// Checks an assertion about the registers or memory, for scripted
+    /// debugging sessions. The left hand side is a register (`A`, `X`,
+    /// `Y`, `SP` or `PC`), a byte `[addr]`, a word `word[addr]` or the
+    /// bytes `[start..end]`, excluding `end`. It is compared with `==` or
+    /// `!=` against a list of values, where `value*count` repeats a value.
+    /// Numbers are written as they are in the assembler.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.load(&[0xA9, 0x01], None).unwrap();
+    ///
+    /// assert!(cpu.verify("word[$FFFC] == $C000").is_ok());
+    /// assert!(cpu.verify("[$0200..$0210] == 00*16").is_ok());
+    /// assert!(cpu.verify("[$C000..$C002] == $A9, 1").is_ok());
+    /// assert!(cpu.verify("A == 1").is_err());
+    /// ```
+    pub fn verify(&self, assertion: &str) -> Result<(), VerifyError> {
+        verify::verify(self, assertion)
+    }
+
+    /// Reads a little endian word from a pair of zero page locations, as a
+    /// zero page pointer is read by `($nn),Y`. A pointer at `$FF` takes its
+    /// high byte from `$00`.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.set_zp_word(0x02, 0x1234);
+    ///
+    /// assert_eq!(0x34, cpu.memory.read_byte(0x02));
+    /// assert_eq!(0x1234, cpu.zp_word(0x02));
+    /// ```
+    pub fn zp_word(&self, addr: u8) -> u16 {
+        let low = self.memory.read_byte(addr as u16) as u16;
+        let high = self.memory.read_byte(addr.wrapping_add(1) as u16) as u16;
+
+        low | (high << 8)
+    }
+
+    /// Writes a little endian word to a pair of zero page locations,
+    /// wrapping within the zero page like `zp_word`
+    pub fn set_zp_word(&mut self, addr: u8, value: u16) {
+        self.memory.write_byte(addr as u16, value as u8);
+        self.memory.write_byte(addr.wrapping_add(1) as u16, (value >> 8) as u8);
+    }
+
+    /// Runs a single instruction, except that a JSR runs until the
+    /// subroutine it calls returns to the instruction after it
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // JSR $C004, BRK, INX, INX, RTS
+    /// cpu.load(&[0x20, 0x04, 0xC0, 0x00, 0xE8, 0xE8, 0x60], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_over().unwrap();
+    ///
+    /// assert_eq!(0x02, cpu.registers.X);
+    /// assert_eq!(0xC003, cpu.registers.PC);
+    /// ```
+    pub fn step_over(&mut self) -> CpuMultiStepResult {
+        let pc = self.registers.PC;
+        if self.memory.read_byte(pc) != JSR_OPCODE {
+            return Ok(self.step()? as u64);
+        }
+
+        let stack_pointer = self.registers.S;
+        let return_addr = pc.wrapping_add(3);
+        self.run_until(|cpu, _| cpu.registers.PC == return_addr && cpu.registers.S >= stack_pointer)
+    }
+
+    /// Runs until the current subroutine or interrupt handler returns.
+    /// Returns from subroutines it calls along the way don't count.
+    pub fn step_out(&mut self) -> CpuMultiStepResult {
+        let stack_pointer = self.registers.S;
+        self.run_until(|cpu, byte| {
+            (byte == RTS_OPCODE || byte == RTI_OPCODE) && cpu.registers.S > stack_pointer
+        })
+    }
+
+    /// Runs to the cursor: until the Program Counter reaches `addr`, as if
+    /// it were a breakpoint that is removed again once the run stops. The
+    /// Cpu isn't left halted at the cursor, so it carries on from there
+    /// with the next step. A run started at the cursor goes on until it
+    /// comes back round.
+    ///
+    /// The breakpoints, exit conditions and run limits still apply, and
+    /// the reason the run stopped is returned: `StopReason::Breakpoint`
+    /// with the address of the cursor or of another breakpoint, or the
+    /// reason the Cpu halted or went idle. A halted Cpu doesn't run.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, StopReason};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDX #$03, DEX, BNE -3, INY, INY
+    /// cpu.load(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0xC8, 0xC8], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// assert_eq!(StopReason::Breakpoint(0xC005), cpu.run_to(0xC005).unwrap());
+    /// assert_eq!(0x00, cpu.registers.X);
+    /// assert!(!cpu.is_halted() && cpu.breakpoints().is_empty());
+    /// ```
+    pub fn run_to(&mut self, addr: u16) -> Result<StopReason, CpuError> {
+        if let Some(reason) = self.halted {
+            return Ok(reason);
+        }
+
+        let temporary = self.breakpoints.insert(addr);
+        if temporary && self.registers.PC == addr {
+            self.resume_from = Some(addr);
+        }
+
+        let result = self.run_until(|_, _| false);
+        if temporary {
+            self.breakpoints.remove(&addr);
+            if self.halted == Some(StopReason::Breakpoint(addr)) {
+                self.halted = None;
+                return Ok(StopReason::Breakpoint(addr));
+            }
+        }
+        result?;
+
+        Ok(self.halted.unwrap_or(StopReason::Idle(self.registers.PC)))
+    }
+
+    /// Steps until the predicate returns true, returning the cycles taken.
+    /// The predicate is checked before each step, so nothing runs if it is
+    /// already true. Stops early if the Cpu becomes idle or halts.
+    ///
+    /// The run limits set with `set_limits` apply, and without them a run
+    /// that goes past a million instructions returns a limit error.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDX #$00, INX, CPX #$10, BNE -5, STX $0200
+    /// cpu.load(&[0xA2, 0x00, 0xE8, 0xE0, 0x10, 0xD0, 0xFB, 0x8E, 0x00, 0x02], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_until(|cpu| cpu.registers.PC == 0xC007).unwrap();
+    ///
+    /// assert_eq!(0x10, cpu.registers.X);
+    /// ```
+    pub fn step_until<F>(&mut self, mut predicate: F) -> CpuMultiStepResult
+        where F: FnMut(&Cpu<B>) -> bool
+    {
+        let limits = self.limits.unwrap_or_else(|| RunLimits::new().max_instructions(STEP_UNTIL_INSTRUCTION_LIMIT));
+        let guard = limits.start();
+        let mut instructions = 0;
+        let mut cycles = 0;
+        while !predicate(self) && !self.idle && self.halted.is_none() {
+            if let Some(limit) = guard.reached(instructions, cycles) {
+                return Err(CpuError::limit_reached(self.registers.PC, limit));
+            }
+
+            cycles += self.step()? as u64;
+            instructions += 1;
+        }
+
+        Ok(cycles)
+    }
+
+    /// Runs instructions until `done` returns true after one of them, or the
+    /// Cpu becomes idle or halts. `done` is given the opcode byte just run.
+    fn run_until<F>(&mut self, mut done: F) -> CpuMultiStepResult
+        where F: FnMut(&Cpu<B>, u8) -> bool
+    {
+        let guard = self.limits.map(|limits| limits.start());
+        let mut instructions = 0;
+        let mut cycles = 0;
+        while !self.idle && self.halted.is_none() {
+            if let Some(limit) = guard.as_ref().and_then(|guard| guard.reached(instructions, cycles)) {
+                return Err(CpuError::limit_reached(self.registers.PC, limit));
+            }
+
+            let byte = self.memory.read_byte(self.registers.PC);
+            cycles += self.step()? as u64;
+            instructions += 1;
+
+            if done(self, byte) {
+                break;
+            }
+        }
+
+        Ok(cycles)
+    }
+
+    /// Halts the Cpu if the instruction at `pc` follows a trap address or
+    /// `BRK` marker exit condition
+    fn check_exit(&mut self, pc: u16, byte: u8) -> bool {
+        let exited = match self.exit_condition {
+            Some(ExitCondition::Trap(addr)) => pc == addr,
+            Some(ExitCondition::Brk(marker)) => {
+                byte == 0x00 && self.memory.read_byte(pc.wrapping_add(1)) == marker
+            }
+            _ => false,
+        };
+
+        if exited {
+            let code = match self.exit_code_source {
+                ExitCodeSource::Accumulator => self.registers.A,
+                ExitCodeSource::Memory(addr) => self.memory.read_byte(addr),
+            };
+            self.exit(code);
+        }
+
+        exited
+    }
+
+    fn exit(&mut self, code: u8) {
+        self.halted = Some(StopReason::Exited(code));
+    }
+
+    /// Detects jumps and branches to themselves, which spin forever
+    /// until an interrupt arrives. When one is executed the Cpu becomes
+    /// idle: it stops executing instructions until the next interrupt
+    /// or reset, rather than spinning through the loop.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.enable_idle_detection();
+    /// // INX, then JMP $C001
+    /// cpu.load(&[0xE8, 0x4C, 0x01, 0xC0], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_n(1000).unwrap();
+    ///
+    /// assert!(cpu.is_idle());
+    /// assert_eq!(0xC001, cpu.registers.PC);
+    /// ```
+    pub fn enable_idle_detection(&mut self) {
+        self.idle_detection = true;
+    }
+
+    pub fn disable_idle_detection(&mut self) {
+        self.idle_detection = false;
+        self.idle = false;
+    }
+
+    /// Returns true if the Cpu is waiting for an interrupt
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Sets the processor the Cpu emulates. Defaults to the NMOS 6502.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, CpuVariant};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.set_variant(CpuVariant::Ricoh2A03);
+    /// // SED, LDA #$09, CLC, ADC #$01
+    /// cpu.load(&[0xF8, 0xA9, 0x09, 0x18, 0x69, 0x01], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step_n(4).unwrap();
+    ///
+    /// assert_eq!(0x0A, cpu.registers.A);
+    /// ```
+    pub fn set_variant(&mut self, variant: CpuVariant) {
+        self.variant = variant;
+        self.build_dispatch_table();
+    }
+
+    pub fn variant(&self) -> CpuVariant {
+        self.variant
+    }
+
+    /// Executes the stable undocumented opcodes of the NMOS 6502, such as
+    /// `LAX`, `DCP` and the multi-byte `NOP`s, instead of treating them as
+    /// unknown opcodes.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.enable_illegal_opcodes();
+    /// // LAX $10
+    /// cpu.memory.write_byte(0x10, 0x42);
+    /// cpu.load(&[0xA7, 0x10], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// cpu.step().unwrap();
+    ///
+    /// assert_eq!(0x42, cpu.registers.A);
+    /// assert_eq!(0x42, cpu.registers.X);
+    /// ```
+    pub fn enable_illegal_opcodes(&mut self) {
+        self.illegal_opcodes = true;
+        self.build_dispatch_table();
+    }
+
+    /// Treats the undocumented opcodes as unknown opcodes again
+    pub fn disable_illegal_opcodes(&mut self) {
+        self.illegal_opcodes = false;
+        self.build_dispatch_table();
+    }
+
+    /// Executes the undocumented `NOP`s of the NMOS 6502, such as `$04`
+    /// (`NOP zp`) or `$1C` (`NOP abs,X`), even while the other illegal
+    /// opcodes are treated as unknown. They take the length and cycles of
+    /// the addressing mode they decode, and reads by them are real reads,
+    /// which is how programs use them as timing filler or to skip a byte.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.enable_unofficial_nops();
+    /// // NOP $10, NOP $4400,X, NOP
+    /// cpu.load(&[0x04, 0x10, 0x1C, 0x00, 0x44, 0x1A], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// assert_eq!(Ok(3), cpu.step());
+    /// assert_eq!(Ok(4), cpu.step());
+    /// assert_eq!(Ok(2), cpu.step());
+    /// assert_eq!(0xC006, cpu.registers.PC);
+    /// ```
+    pub fn enable_unofficial_nops(&mut self) {
+        self.unofficial_nops = true;
+        self.build_dispatch_table();
+    }
+
+    pub fn disable_unofficial_nops(&mut self) {
+        self.unofficial_nops = false;
+        self.build_dispatch_table();
+    }
+
+    /// Decodes each instruction by searching the opcode lists and picks its
+    /// handler by mnemonic, instead of using the dispatch table. It runs
+    /// the same instructions, only slower, and is there for the dispatch
+    /// benchmark to compare against.
+    #[cfg(feature = "reference-dispatch")]
+    pub fn enable_mnemonic_dispatch(&mut self) {
+        self.mnemonic_dispatch = true;
+    }
+
+    #[cfg(feature = "reference-dispatch")]
+    pub fn disable_mnemonic_dispatch(&mut self) {
+        self.mnemonic_dispatch = false;
+    }
+
+    /// Looks up the opcode the Cpu executes for a byte on its variant.
+    /// Undocumented opcodes are only found when illegal opcodes are
+    /// enabled, apart from the undocumented `NOP`s when unofficial `NOP`s
+    /// are.
+    pub fn decode(&self, byte: u8) -> Option<&'static OpCode> {
+        self.dispatch[byte as usize].map(|(opcode, _, _)| opcode)
+    }
+
+    /// Starts counting every instruction executed by the Cpu, broken down
+    /// by mnemonic and addressing mode. Any previous counts are discarded.
+    pub fn enable_statistics(&mut self) {
+        self.statistics = Some(InstructionStatistics::new());
+    }
+
+    /// Stops counting executed instructions
+    pub fn disable_statistics(&mut self) {
+        self.statistics = None;
+    }
+
+    /// Returns the instruction counts recorded since statistics were
+    /// enabled, or None if they are not enabled
+    pub fn statistics(&self) -> Option<&InstructionStatistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Starts measuring, for each kind of interrupt, how often it is taken,
+    /// the worst latency from it being signalled to its handler starting,
+    /// and the time spent in its handler up to the `RTI`. Any previous
+    /// measurements are discarded.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, InterruptKind};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // NOP, with an NMI handler of INX, RTI
+    /// cpu.load(&[0xEA], None).unwrap();
+    /// cpu.memory.write_bytes(0xD000, &[0xE8, 0x40]);
+    /// cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+    /// cpu.reset();
+    /// cpu.enable_interrupt_statistics();
+    ///
+    /// cpu.nmi();
+    /// cpu.step_n(3).unwrap();
+    ///
+    /// let nmi = cpu.interrupt_statistics().unwrap().source(InterruptKind::Nmi);
+    /// assert_eq!(1, nmi.count);
+    /// assert_eq!(7, nmi.max_latency);
+    /// // INX and RTI
+    /// assert_eq!(8, nmi.handler_cycles);
+    /// ```
+    pub fn enable_interrupt_statistics(&mut self) {
+        self.interrupt_statistics = Some(InterruptStatistics::new());
+    }
+
+    pub fn disable_interrupt_statistics(&mut self) {
+        self.interrupt_statistics = None;
+    }
+
+    /// Returns the interrupt statistics measured since they were enabled,
+    /// or None if they are not enabled
+    pub fn interrupt_statistics(&self) -> Option<&InterruptStatistics> {
+        self.interrupt_statistics.as_ref()
+    }
+
+    /// Attaches a cost model, which prices every instruction executed from
+    /// now on. Any previously accumulated costs are discarded.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{CostModel, Cpu};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDA #$01, STA $10, LDA #$02
+    /// cpu.load(&[0xA9, 0x01, 0x85, 0x10, 0xA9, 0x02], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// // Stores cost double, everything else costs its cycles
+    /// let mut model = CostModel::new();
+    /// model.set_mnemonic_weight("STA", 6.0);
+    /// cpu.set_cost_model(model);
+    ///
+    /// cpu.step_n(3).unwrap();
+    ///
+    /// let costs = cpu.costs().unwrap();
+    /// assert_eq!(10.0, costs.total());
+    /// assert_eq!(6.0, costs.at(0xC002));
+    /// ```
+    pub fn set_cost_model(&mut self, model: CostModel) {
+        self.cost_model = Some((model, Costs::new()));
+    }
+
+    /// Detaches the cost model and discards its costs
+    pub fn clear_cost_model(&mut self) {
+        self.cost_model = None;
+    }
+
+    /// Returns the costs accumulated since the cost model was attached or
+    /// the costs were last cleared, or None if there is no cost model
+    pub fn costs(&self) -> Option<&Costs> {
+        self.cost_model.as_ref().map(|(_, costs)| costs)
+    }
+
+    /// Clears the accumulated costs, keeping the cost model, so that each
+    /// run can be priced separately
+    pub fn clear_costs(&mut self) {
+        if let Some((_, ref mut costs)) = self.cost_model {
+            costs.clear();
+        }
+    }
+
+    /// Starts recording which addresses are executed, read and written.
+    /// Anything previously recorded is discarded. See `Coverage`.
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
+    }
+
+    /// Returns what was recorded since coverage was enabled, or None if it
+    /// isn't enabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Attaches a profiler, which counts the executions and cycles of every
+    /// instruction from now on. See `Profiler`.
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Detaches the profiler and discards what it measured
+    pub fn clear_profiler(&mut self) {
+        self.profiler = None;
+    }
+
+    /// Returns the profiler, or None if there isn't one
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Clears what the profiler has measured, keeping the profiler, so
+    /// that each run can be profiled separately
+    pub fn clear_profile(&mut self) {
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.clear();
+        }
+    }
+
+    /// Fails a step with `CpuErrorKind::StackViolation` when the stack
+    /// breaks the guard, with a backtrace of the `JSR`s and interrupts that
+    /// hadn't returned at the time. See `StackGuard`.
+    pub fn set_stack_guard(&mut self, guard: StackGuard) {
+        self.stack_guard = Some((guard, Vec::new()));
+    }
+
+    pub fn clear_stack_guard(&mut self) {
+        self.stack_guard = None;
+    }
+
+    /// Starts keeping a shadow call stack of the `JSR`s and interrupts
+    /// that haven't returned. See `CallStack`.
+    pub fn enable_call_stack(&mut self) {
+        self.call_stack = Some(CallStack::new());
+    }
+
+    pub fn disable_call_stack(&mut self) {
+        self.call_stack = None;
+    }
+
+    /// Returns the call stack, or None if it isn't enabled
+    pub fn call_stack(&self) -> Option<&CallStack> {
+        self.call_stack.as_ref()
+    }
+
+    /// Runs a single instruction of code through the Cpu. Nothing is
+    /// executed while the Cpu is idle or halted. If an NMI is pending, or
+    /// the IRQ line is asserted and interrupts are enabled, the step enters
+    /// the interrupt handler instead, taking 7 cycles. NMIs are taken
+    /// first.
     pub fn step(&mut self) -> CpuStepResult {
-        let byte = self.memory.read_byte(self.registers.PC);
-
-        if let Some(opcode) = OpCode::from_raw_byte(byte) {
-            let operand = self.get_operand_from_opcode(&opcode);
-
-            self.registers.PC += opcode.length as u16;
-
-            match opcode.mnemonic {
-                "ADC" => self.adc(&operand),
-                "AND" => self.and(&operand),
-                "ASL" => self.asl(&operand),
-                "BCC" => self.bcc(&operand),
-                "BCS" => self.bcs(&operand),
-                "BEQ" => self.beq(&operand),
-                "BIT" => self.bit(&operand),
-                "BMI" => self.bmi(&operand),
-                "BNE" => self.bne(&operand),
-                "BPL" => self.bpl(&operand),
-                "BRK" => self.brk(),
-                "BVC" => self.bvc(&operand),
-                "BVS" => self.bvs(&operand),
-                "CLC" => self.set_carry_flag(false),
-                "CLD" => self.set_decimal_flag(false),
-                "CLI" => self.set_interrupt_flag(false),
-                "CLV" => self.set_overflow_flag(false),
-                "CMP" => {
-                    let a = self.registers.A;
-                    self.compare(&operand, a)
+        self.deliver_events();
+
+        let change = self.history.as_ref().map(|_| self.begin_change());
+        let result = self.step_once();
+        if let Some((ref mut history, ref mut writes)) = self.history {
+            // Only steps that ran an instruction or took an interrupt are
+            // kept, so that `step_back(n)` undoes n of them
+            match (change, &result) {
+                (Some(mut change), &Ok(cycles)) if cycles > 0 => {
+                    change.writes.append(writes);
+                    history.push(change);
+                }
+                _ => writes.clear(),
+            }
+        }
+
+        let cycles = result?;
+        self.cycles += cycles as u64;
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.advance(cycles as u64);
+        }
+
+        Ok(cycles)
+    }
+
+    /// Keeps up to `depth` steps of history, so that `step_back` can undo
+    /// them. Each step records the registers and flags before it and the
+    /// old value of every byte the Cpu writes, so the cost is bounded by
+    /// the writes an instruction makes rather than the size of memory.
+    /// Writes made to the bus by anything other than the Cpu, such as a
+    /// host call or a device, aren't recorded. Neither are writes to an
+    /// address the bus can't `peek` without side effects, such as a mapped
+    /// device, so stepping back leaves devices as they are.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDA #$01, STA $10, INC $10
+    /// cpu.load(&[0xA9, 0x01, 0x85, 0x10, 0xE6, 0x10], None).unwrap();
+    /// cpu.reset();
+    /// cpu.enable_history(100);
+    ///
+    /// cpu.step_n(3).unwrap();
+    /// assert_eq!(0x02, cpu.memory.read_byte(0x10));
+    ///
+    /// assert_eq!(2, cpu.step_back(2));
+    /// assert_eq!(0x00, cpu.memory.read_byte(0x10));
+    /// assert_eq!(0xC002, cpu.registers.PC);
+    /// ```
+    pub fn enable_history(&mut self, depth: usize) {
+        self.history = Some((History::new(depth), Vec::new()));
+    }
+
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    /// Returns the number of steps `step_back` can undo
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |(history, _)| history.len())
+    }
+
+    /// Undoes up to `steps` of the most recent steps, putting back the
+    /// registers, flags and memory the Cpu had before them. Steps that ran
+    /// nothing, because the Cpu was idle or halted, and steps that failed
+    /// aren't in the history. Returns the number of steps undone, which is
+    /// fewer than asked for when the history runs out. See
+    /// `enable_history`.
+    pub fn step_back(&mut self, steps: usize) -> usize {
+        let mut undone = 0;
+        while undone < steps {
+            let change = match self.history {
+                Some((ref mut history, _)) => history.pop(),
+                None => None,
+            };
+            let change = match change {
+                Some(change) => change,
+                None => break,
+            };
+
+            for &(addr, value) in change.writes.iter().rev() {
+                self.memory.poke(addr, value);
+            }
+            self.registers = change.registers;
+            self.flags = change.flags;
+            self.cycles = change.cycles;
+            self.idle = change.idle;
+            self.nmi_pending = change.nmi_pending;
+            self.halted = change.halted;
+            self.resume_from = change.resume_from;
+            undone += 1;
+        }
+
+        undone
+    }
+
+    /// Records the state of the Cpu before a step. The step can push up to
+    /// 3 bytes without going through `write_byte`, so the bytes it could
+    /// push are recorded up front.
+    fn begin_change(&self) -> Change {
+        let writes = (0..3)
+            .filter_map(|offset| {
+                let addr = stack::addr(self.registers.S.wrapping_sub(offset));
+                self.memory.peek(addr).map(|value| (addr, value))
+            })
+            .collect();
+
+        Change {
+            registers: self.registers,
+            flags: self.flags,
+            cycles: self.cycles,
+            idle: self.idle,
+            nmi_pending: self.nmi_pending,
+            halted: self.halted,
+            resume_from: self.resume_from,
+            writes,
+        }
+    }
+
+    fn step_once(&mut self) -> CpuStepResult {
+        if self.halted.is_some() {
+            return Ok(0);
+        }
+
+        let stack_pointer = self.registers.S;
+        self.wait_states.set(0);
+
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            let pc = self.registers.PC;
+            self.interrupt(InterruptKind::Nmi, NMI_VECTOR);
+            self.check_stack_guard(pc, stack_pointer, true)?;
+            return Ok(INTERRUPT_CYCLES.saturating_add(self.wait_states.get()));
+        }
+
+        if self.irq_line && !self.flags.interrupt_disabled {
+            let pc = self.registers.PC;
+            self.interrupt(InterruptKind::Irq, IRQ_VECTOR);
+            self.check_stack_guard(pc, stack_pointer, true)?;
+            return Ok(INTERRUPT_CYCLES.saturating_add(self.wait_states.get()));
+        }
+
+        if self.idle {
+            return Ok(0);
+        }
+
+        let pc = self.registers.PC;
+        if self.resume_from.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.halted = Some(StopReason::Breakpoint(pc));
+            return Ok(0);
+        }
+
+        let byte = self.memory.read_byte(pc);
+        self.wait(pc, MemoryAccess::Execute);
+        self.protection_fault.set(None);
+        self.code_write = None;
+        self.instruction_pc = pc;
+        self.pending_events.borrow_mut().clear();
+        self.pending_accesses.borrow_mut().clear();
+
+        if self.check_exit(pc, byte) {
+            return Ok(0);
+        }
+
+        if !self.protection.allows(pc, MemoryAccess::Execute) {
+            return Err(CpuError::memory_protection(pc, pc, MemoryAccess::Execute));
+        }
+
+        if Some(byte) == self.host_call_opcode {
+            // Host calls are only known once they are registered, so they
+            // are built here rather than taken from the dispatch table
+            let id = self.memory.read_byte(pc.wrapping_add(1));
+            if !self.host_calls.contains_key(&id) {
+                return Err(CpuError::unknown_host_call(pc, id));
+            }
+
+            let opcode = OpCode {
+                code: byte,
+                mnemonic: HOST_CALL_MNEMONIC,
+                length: 2,
+                time: self.host_call_cycles,
+                mode: AddressingMode::Immediate,
+            };
+            return self.execute_opcode(pc, byte, stack_pointer, &opcode, Self::host_call, OpcodeTraits::default());
+        }
+
+        #[cfg(feature = "reference-dispatch")]
+        {
+            if self.mnemonic_dispatch {
+                return self.dispatch_by_mnemonic(pc, byte, stack_pointer);
+            }
+        }
+
+        if let Some((opcode, handler, traits)) = self.dispatch[byte as usize] {
+            self.execute_opcode(pc, byte, stack_pointer, opcode, handler, traits)
+        } else {
+            self.unknown_opcode(byte)
+        }
+    }
+
+    /// Runs an instruction the way `step` did before the dispatch table:
+    /// searching for its opcode and comparing mnemonics on every step
+    #[cfg(feature = "reference-dispatch")]
+    fn dispatch_by_mnemonic(&mut self, pc: u16, byte: u8, stack_pointer: u8) -> CpuStepResult {
+        let opcode = OpCode::search(byte, self.variant).filter(|opcode| self.decodes(opcode));
+        if let Some(opcode) = opcode {
+            let traits = OpcodeTraits::new(opcode, self.variant);
+            self.execute_opcode(pc, byte, stack_pointer, opcode, Self::handler(opcode.mnemonic), traits)
+        } else {
+            self.unknown_opcode(byte)
+        }
+    }
+
+    /// Runs an instruction fetched from `pc`, along with everything that
+    /// watches instructions run: hooks, statistics, traces, coverage and
+    /// the stack guard
+    fn execute_opcode(&mut self,
+                      pc: u16,
+                      byte: u8,
+                      stack_pointer: u8,
+                      opcode: &OpCode,
+                      handler: Handler<B>,
+                      traits: OpcodeTraits)
+                      -> CpuStepResult {
+        let operand = self.get_operand_from_opcode(opcode);
+        let operand_reads = self.pending_accesses.borrow().len();
+        let decoded = DecodedInstruction {
+            pc,
+            opcode: *opcode,
+            operand,
+        };
+
+        // The hooks need the Cpu, so take them out while they run
+        if let Some(mut hook) = self.pre_step_hook.take() {
+            hook(self, &decoded);
+            self.pre_step_hook.get_or_insert(hook);
+        }
+
+        let trace = if self.trace_sinks.is_empty() {
+            None
+        } else {
+            Some(TraceLine {
+                pc,
+                opcode: *opcode,
+                operand: [self.memory.read_byte(pc.wrapping_add(1)), self.memory.read_byte(pc.wrapping_add(2))],
+                a: self.registers.A,
+                x: self.registers.X,
+                y: self.registers.Y,
+                s: self.registers.S,
+                p: self.flags.to_u8(),
+                branch_taken: false,
+                symbols: self.symbols.clone(),
+            })
+        };
+
+        if traits.indexed_dummy_read {
+            self.indexed_dummy_read();
+        }
+        let page_crossing_cycles = if traits.page_crossing_penalty {
+            self.page_crossing_cycles(opcode)
+        } else {
+            0
+        };
+        self.branch_cycles = 0;
+
+        if let Some(ref mut statistics) = self.statistics {
+            statistics.record(opcode);
+        }
+
+        self.registers.PC = self.registers.PC.wrapping_add(opcode.length as u16);
+
+        handler(self, opcode, &operand);
+
+        if traits.call {
+            let frame = StackFrame {
+                kind: StackFrameKind::Call,
+                from: pc,
+                to: self.registers.PC,
+                stack_pointer,
+            };
+            self.push_frame(frame);
+        }
+
+        if !traits.own_stack_accounting {
+            self.wait_for_stack(stack_pointer);
+        }
+
+        let cycles = opcode.time + page_crossing_cycles + self.branch_cycles;
+        let cycles = cycles.saturating_add(self.wait_states.get());
+
+        if let Some((ref model, ref mut costs)) = self.cost_model {
+            costs.record(pc, model.cost(opcode, cycles));
+        }
+
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.record(pc, opcode, cycles);
+        }
+
+        if let Some(ref coverage) = self.coverage {
+            coverage.record_executed(pc, opcode.length);
+        }
+
+        if traits.interrupt_return {
+            if let Some(ref mut statistics) = self.interrupt_statistics {
+                statistics.returned(cycles as u64);
+            }
+        }
+
+        // A jump or branch to itself will never leave without an interrupt
+        if self.idle_detection && traits.jump && self.registers.PC == pc {
+            self.idle = true;
+        }
+
+        if let Some((addr, access)) = self.protection_fault.take() {
+            return Err(CpuError::memory_protection(pc, addr, access));
+        }
+
+        if let Some(addr) = self.code_write.take() {
+            return Err(CpuError::self_modifying_code(pc, addr));
+        }
+
+        // Interrupts report their own stack pushes, and TXS moves the
+        // stack pointer without touching the stack
+        if !traits.own_stack_accounting {
+            self.record_stack_events(stack_pointer);
+        }
+
+        if !self.cycle_hooks.is_empty() {
+            let fetch = BusCycle {
+                access: MemoryAccess::Execute,
+                addr: pc,
+                value: byte,
+            };
+            self.run_cycle_hooks(pc, cycles, Some(fetch), operand_reads);
+        }
+
+        if !self.listeners.is_empty() {
+            self.record_event(CpuEvent::InstructionRetired {
+                pc,
+                opcode: *opcode,
+                cycles,
+            });
+            self.dispatch_events();
+        }
+
+        if let Some(mut trace) = trace {
+            trace.branch_taken = self.branch_cycles > 0;
+            for &mut (ref filter, ref mut sink) in &mut self.trace_sinks {
+                if filter.matches(&trace) {
+                    sink(&trace);
+                }
+            }
+        }
+
+        if let Some(mut hook) = self.post_step_hook.take() {
+            hook(self, &decoded);
+            self.post_step_hook.get_or_insert(hook);
+        }
+
+        if let Some(ref mut call_stack) = self.call_stack {
+            if traits.subroutine_return {
+                call_stack.returned(pc, self.registers.S);
+            } else {
+                call_stack.unwind(self.registers.S);
+            }
+        }
+
+        // TXS moves the stack pointer without pushing or pulling
+        self.check_stack_guard(pc, stack_pointer, !traits.sets_stack_pointer)?;
+
+        Ok(cycles)
+    }
+
+    /// Runs the host call selected by the operand of the host call opcode
+    fn host_call(&mut self, _: &OpCode, operand: &Operand) {
+        let id = self.unwrap_immediate(operand);
+
+        // The handler needs the Cpu, so take it out while it runs
+        if let Some(mut handler) = self.host_calls.remove(&id) {
+            handler(self);
+            self.host_calls.entry(id).or_insert(handler);
+        }
+    }
+
+    /// Handles a byte that isn't a known opcode according to the
+    /// unknown opcode policy
+    fn unknown_opcode(&mut self, byte: u8) -> CpuStepResult {
+        match self.unknown_opcode_policy {
+            UnknownOpcodePolicy::Error => Err(CpuError::unknown_opcode(self.registers.PC, byte)),
+            UnknownOpcodePolicy::Nop => {
+                self.registers.PC = self.registers.PC.wrapping_add(unknown_opcode::unknown_opcode_length(byte) as u16);
+                Ok(2)
+            }
+            UnknownOpcodePolicy::Halt => {
+                self.halted = Some(StopReason::UnknownOpcode {
+                    pc: self.registers.PC,
+                    opcode: byte,
+                });
+                Ok(0)
+            }
+            UnknownOpcodePolicy::Callback(_) => {
+                // The handler needs the Cpu, so take it out while it runs
+                let policy = std::mem::replace(&mut self.unknown_opcode_policy,
+                                               UnknownOpcodePolicy::Error);
+                if let UnknownOpcodePolicy::Callback(mut handler) = policy {
+                    let result = handler(self, byte);
+                    self.unknown_opcode_policy = UnknownOpcodePolicy::Callback(handler);
+                    result
+                } else {
+                    unreachable!()
                 }
-                "CPX" => {
-                    let x = self.registers.X;
-                    self.compare(&operand, x)
+            }
+        }
+    }
+
+    /// Decodes every byte for the current variant and illegal opcode
+    /// settings, so that executing an instruction is a lookup into the
+    /// dispatch table rather than a search of the opcode tables
+    fn build_dispatch_table(&mut self) {
+        for byte in 0..=0xFF {
+            let opcode = OpCode::decode(byte, self.variant).filter(|opcode| self.decodes(opcode));
+            self.dispatch[byte as usize] =
+                opcode.map(|opcode| (opcode, Self::handler(opcode.mnemonic), OpcodeTraits::new(opcode, self.variant)));
+        }
+    }
+
+    /// Whether the illegal opcode and unofficial `NOP` settings let an
+    /// opcode run
+    fn decodes(&self, opcode: &OpCode) -> bool {
+        // The 65C02 has no undocumented opcodes: every byte it doesn't
+        // define is a NOP
+        self.variant == CpuVariant::Cmos65C02 || self.illegal_opcodes || !opcode.is_undocumented() ||
+        (self.unofficial_nops && opcode.mnemonic == "NOP")
+    }
+
+    /// Returns the function that executes an instruction
+    fn handler(mnemonic: &str) -> Handler<B> {
+        match mnemonic {
+            "ADC" => |cpu, _, operand| cpu.adc(operand),
+            "AND" => |cpu, _, operand| cpu.and(operand),
+            "ASL" => |cpu, _, operand| cpu.asl(operand),
+            "BCC" => |cpu, _, operand| cpu.bcc(operand),
+            "BCS" => |cpu, _, operand| cpu.bcs(operand),
+            "BEQ" => |cpu, _, operand| cpu.beq(operand),
+            "BIT" => |cpu, _, operand| cpu.bit(operand),
+            "BMI" => |cpu, _, operand| cpu.bmi(operand),
+            "BNE" => |cpu, _, operand| cpu.bne(operand),
+            "BPL" => |cpu, _, operand| cpu.bpl(operand),
+            "BRA" => |cpu, _, operand| cpu.bra(operand),
+            "BRK" => |cpu, _, _| cpu.brk(),
+            "BVC" => |cpu, _, operand| cpu.bvc(operand),
+            "BVS" => |cpu, _, operand| cpu.bvs(operand),
+            "CLC" => |cpu, _, _| cpu.set_carry_flag(false),
+            "CLD" => |cpu, _, _| cpu.set_decimal_flag(false),
+            "CLI" => |cpu, _, _| cpu.set_interrupt_flag(false),
+            "CLV" => |cpu, _, _| cpu.set_overflow_flag(false),
+            "CMP" => {
+                |cpu, _, operand| {
+                    let a = cpu.registers.A;
+                    cpu.compare(operand, a)
                 }
-                "CPY" => {
-                    let y = self.registers.Y;
-                    self.compare(&operand, y)
+            }
+            "CPX" => {
+                |cpu, _, operand| {
+                    let x = cpu.registers.X;
+                    cpu.compare(operand, x)
                 }
-                "DEC" => self.dec(&operand),
-                "DEX" => self.dex(),
-                "DEY" => self.dey(),
-                "EOR" => self.eor(&operand),
-                "INC" => self.inc(&operand),
-                "INX" => self.inx(),
-                "INY" => self.iny(),
-                "JMP" => self.jmp(&operand),
-                "JSR" => self.jsr(&operand),
-                "LDA" => self.lda(&operand),
-                "LDX" => self.ldx(&operand),
-                "LDY" => self.ldy(&operand),
-                "LSR" => self.lsr(&operand),
-                "NOP" => self.nop(),
-                "ORA" => self.ora(&operand),
-                "PHA" => self.pha(),
-                "PHP" => self.php(),
-                "PLA" => self.pla(),
-                "PLP" => self.plp(),
-                "ROL" => self.rol(&operand),
-                "ROR" => self.ror(&operand),
-                "RTI" => self.rti(),
-                "RTS" => self.rts(),
-                "SBC" => self.sbc(&operand),
-                "SEC" => self.set_carry_flag(true),
-                "SED" => self.set_decimal_flag(true),
-                "SEI" => self.set_interrupt_flag(true),
-                "STA" => self.sta(&operand),
-                "STX" => self.stx(&operand),
-                "STY" => self.sty(&operand),
-                "TAX" => self.tax(),
-                "TAY" => self.tay(),
-                "TSX" => self.tsx(),
-                "TXA" => self.txa(),
-                "TXS" => self.txs(),
-                "TYA" => self.tya(),
-                _ => return Err(CpuError::unknown_opcode(self.registers.PC, opcode.code)),
-            }
-
-            Ok(opcode.time)
-        } else {
-            Err(CpuError::unknown_opcode(self.registers.PC, byte))
+            }
+            "CPY" => {
+                |cpu, _, operand| {
+                    let y = cpu.registers.Y;
+                    cpu.compare(operand, y)
+                }
+            }
+            "DEC" => |cpu, _, operand| cpu.dec(operand),
+            "DEX" => |cpu, _, _| cpu.dex(),
+            "DEY" => |cpu, _, _| cpu.dey(),
+            "EOR" => |cpu, _, operand| cpu.eor(operand),
+            "INC" => |cpu, _, operand| cpu.inc(operand),
+            "INX" => |cpu, _, _| cpu.inx(),
+            "INY" => |cpu, _, _| cpu.iny(),
+            "JMP" => |cpu, _, operand| cpu.jmp(operand),
+            "JSR" => |cpu, _, operand| cpu.jsr(operand),
+            "LDA" => |cpu, _, operand| cpu.lda(operand),
+            "LDX" => |cpu, _, operand| cpu.ldx(operand),
+            "LDY" => |cpu, _, operand| cpu.ldy(operand),
+            "LSR" => |cpu, _, operand| cpu.lsr(operand),
+            "NOP" => |cpu, _, _| cpu.nop(),
+            "ORA" => |cpu, _, operand| cpu.ora(operand),
+            "PHA" => |cpu, _, _| cpu.pha(),
+            "PHP" => |cpu, _, _| cpu.php(),
+            "PHX" => |cpu, _, _| cpu.phx(),
+            "PHY" => |cpu, _, _| cpu.phy(),
+            "PLA" => |cpu, _, _| cpu.pla(),
+            "PLP" => |cpu, _, _| cpu.plp(),
+            "PLX" => |cpu, _, _| cpu.plx(),
+            "PLY" => |cpu, _, _| cpu.ply(),
+            "ROL" => |cpu, _, operand| cpu.rol(operand),
+            "ROR" => |cpu, _, operand| cpu.ror(operand),
+            "RTI" => |cpu, _, _| cpu.rti(),
+            "RTS" => |cpu, _, _| cpu.rts(),
+            "SBC" => |cpu, _, operand| cpu.sbc(operand),
+            "SEC" => |cpu, _, _| cpu.set_carry_flag(true),
+            "SED" => |cpu, _, _| cpu.set_decimal_flag(true),
+            "SEI" => |cpu, _, _| cpu.set_interrupt_flag(true),
+            "STA" => |cpu, _, operand| cpu.sta(operand),
+            "STX" => |cpu, _, operand| cpu.stx(operand),
+            "STY" => |cpu, _, operand| cpu.sty(operand),
+            "STZ" => |cpu, _, operand| cpu.stz(operand),
+            "TAX" => |cpu, _, _| cpu.tax(),
+            "TAY" => |cpu, _, _| cpu.tay(),
+            "TRB" => |cpu, _, operand| cpu.trb(operand),
+            "TSB" => |cpu, _, operand| cpu.tsb(operand),
+            "TSX" => |cpu, _, _| cpu.tsx(),
+            "TXA" => |cpu, _, _| cpu.txa(),
+            "TXS" => |cpu, _, _| cpu.txs(),
+            "TYA" => |cpu, _, _| cpu.tya(),
+            // Undocumented opcodes
+            "ALR" => {
+                |cpu, _, operand| {
+                    cpu.and(operand);
+                    cpu.lsr(&Operand::Implied)
+                }
+            }
+            "ANC" => {
+                |cpu, _, operand| {
+                    cpu.and(operand);
+                    cpu.flags.carry = cpu.flags.sign
+                }
+            }
+            "ARR" => |cpu, _, operand| cpu.arr(operand),
+            "AXS" => |cpu, _, operand| cpu.axs(operand),
+            "DCP" => {
+                |cpu, _, operand| {
+                    cpu.dec(operand);
+                    let a = cpu.registers.A;
+                    cpu.compare(operand, a)
+                }
+            }
+            "ISC" => {
+                |cpu, _, operand| {
+                    cpu.inc(operand);
+                    cpu.sbc(operand)
+                }
+            }
+            "KIL" => {
+                |cpu, opcode, _| {
+                    // The processor locks up with the opcode on the bus
+                    let pc = cpu.instruction_pc;
+                    cpu.registers.PC = pc;
+                    cpu.halted = Some(StopReason::Jammed {
+                        pc,
+                        opcode: opcode.code,
+                    })
+                }
+            }
+            "LAX" => {
+                |cpu, _, operand| {
+                    cpu.lda(operand);
+                    cpu.tax()
+                }
+            }
+            "RLA" => {
+                |cpu, _, operand| {
+                    cpu.rol(operand);
+                    cpu.and(operand)
+                }
+            }
+            "RRA" => {
+                |cpu, _, operand| {
+                    cpu.ror(operand);
+                    cpu.adc(operand)
+                }
+            }
+            "SAX" => |cpu, _, operand| cpu.sax(operand),
+            "SLO" => {
+                |cpu, _, operand| {
+                    cpu.asl(operand);
+                    cpu.ora(operand)
+                }
+            }
+            "SRE" => {
+                |cpu, _, operand| {
+                    cpu.lsr(operand);
+                    cpu.eor(operand)
+                }
+            }
+            _ => unreachable!("No handler for {}", mnemonic),
         }
     }
 
     fn get_operand_from_opcode(&self, opcode: &OpCode) -> Operand {
         use ::opcodes::AddressingMode::*;
 
-        let operand_start = self.registers.PC + 1;
+        let operand_start = self.registers.PC.wrapping_add(1);
 
         match opcode.mode {
             Unknown => unreachable!(),
             Implied => Operand::Implied,
-            Immediate => Operand::Immediate(self.read_byte(operand_start)),
-            Relative => Operand::Immediate(self.read_byte(operand_start)),
+            Immediate => Operand::Immediate(self.fetch_byte(operand_start)),
+            Relative => Operand::Immediate(self.fetch_byte(operand_start)),
             Accumulator => Operand::Implied,
-            ZeroPage => Operand::Memory((self.read_byte(operand_start) as u16) & 0xFF),
+            ZeroPage => Operand::Memory((self.fetch_byte(operand_start) as u16) & 0xFF),
             ZeroPageX => {
-                Operand::Memory((self.registers.X as u16 + self.read_byte(operand_start) as u16) &
+                Operand::Memory((self.registers.X as u16 + self.fetch_byte(operand_start) as u16) &
                                 0xFF)
             }
             ZeroPageY => {
-                Operand::Memory((self.registers.Y as u16 + self.read_byte(operand_start) as u16) &
+                Operand::Memory((self.registers.Y as u16 + self.fetch_byte(operand_start) as u16) &
                                 0xFF)
             }
-            Absolute => Operand::Memory(self.read_u16(operand_start)),
-            AbsoluteX => Operand::Memory(self.registers.X as u16 + self.read_u16(operand_start)),
-            AbsoluteY => Operand::Memory(self.registers.Y as u16 + self.read_u16(operand_start)),
-            Indirect => Operand::Memory(self.read_u16(self.read_u16(operand_start))),
+            Absolute => Operand::Memory(self.fetch_u16(operand_start)),
+            AbsoluteX => Operand::Memory(self.fetch_u16(operand_start).wrapping_add(self.registers.X as u16)),
+            AbsoluteY => Operand::Memory(self.fetch_u16(operand_start).wrapping_add(self.registers.Y as u16)),
+            Indirect => Operand::Memory(self.read_indirect_target(self.fetch_u16(operand_start))),
             IndirectX => {
-                Operand::Memory(self.read_u16((self.registers.X as u16 +
-                                               self.read_byte(self.registers.PC + 1) as u16) &
-                                              0xFF))
+                let pointer = self.fetch_byte(operand_start).wrapping_add(self.registers.X);
+                Operand::Memory(self.read_zero_page_u16(pointer))
             }
             IndirectY => {
-                Operand::Memory(self.registers.Y as u16 +
-                                self.read_u16(self.read_byte(self.registers.PC + 1) as u16))
+                let pointer = self.fetch_byte(operand_start);
+                Operand::Memory(self.read_zero_page_u16(pointer).wrapping_add(self.registers.Y as u16))
+            }
+            ZeroPageIndirect => Operand::Memory(self.read_zero_page_u16(self.fetch_byte(operand_start))),
+            AbsoluteIndexedIndirect => {
+                let pointer = self.fetch_u16(operand_start).wrapping_add(self.registers.X as u16);
+                Operand::Memory(self.read_u16(pointer))
+            }
+        }
+    }
+
+    /// Reads the target of `JMP (pointer)`. The NMOS 6502 doesn't carry
+    /// into the high byte of the pointer, so `JMP ($10FF)` reads its high
+    /// byte from `$1000` rather than `$1100`. The 65C02 fixed this.
+    fn read_indirect_target(&self, pointer: u16) -> u16 {
+        if self.variant == CpuVariant::Cmos65C02 {
+            return self.read_u16(pointer);
+        }
+
+        let low = self.read_byte(pointer) as u16;
+        let high = self.read_byte((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF)) as u16;
+
+        low | (high << 8)
+    }
+
+    /// Read-modify-write instructions using absolute,X addressing on the
+    /// NMOS 6502 read from the address before the carry into the high byte
+    /// is added, whether or not the index crosses a page.
+    fn indexed_dummy_read(&self) {
+        let base = self.memory.read_u16(self.registers.PC.wrapping_add(1));
+        self.dummy_read((base & 0xFF00) | (base.wrapping_add(self.registers.X as u16) & 0x00FF));
+    }
+
+    /// Indexed reads take an extra cycle when adding the index to the base
+    /// address crosses into the next page. Writes and read-modify-write
+    /// instructions always take the extra cycle, so it is part of their
+    /// base timing.
+    fn page_crossing_cycles(&self, opcode: &OpCode) -> u8 {
+        let operand_start = self.registers.PC.wrapping_add(1);
+        let (base, index) = match opcode.mode {
+            AddressingMode::AbsoluteX => (self.memory.read_u16(operand_start), self.registers.X),
+            AddressingMode::AbsoluteY => (self.memory.read_u16(operand_start), self.registers.Y),
+            AddressingMode::IndirectY => {
+                let pointer = self.memory.read_byte(operand_start);
+                let low = self.memory.read_byte(pointer as u16) as u16;
+                let high = self.memory.read_byte(pointer.wrapping_add(1) as u16) as u16;
+                (low | (high << 8), self.registers.Y)
             }
+            _ => return 0,
+        };
+
+        if base & 0xFF00 != base.wrapping_add(index as u16) & 0xFF00 {
+            1
+        } else {
+            0
         }
     }
 
@@ -238,45 +2389,405 @@ impl Cpu {
         }
     }
 
-    /// Execute the Non-Maskable Interrupt handler. This ignores the interrupt
-    /// flag and forces execution to the NMI
+    /// Signals a Non-Maskable Interrupt. NMIs are edge-triggered, so the
+    /// signal is latched and the next `step` enters the handler at the NMI
+    /// vector (`$FFFA`) regardless of the interrupt flag, taking 7 cycles.
+    /// An NMI signalled during an instruction, such as from a host call, is
+    /// taken once the instruction has finished. `RTI` returns to the
+    /// interrupted code with its status restored.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // SEC, then an NMI handler of CLC, RTI
+    /// cpu.load(&[0x38], None).unwrap();
+    /// cpu.memory.write_bytes(0xD000, &[0x18, 0x40]);
+    /// cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+    /// cpu.reset();
+    ///
+    /// cpu.step().unwrap();
+    /// cpu.nmi();
+    /// assert!(cpu.nmi_pending());
+    ///
+    /// assert_eq!(Ok(7), cpu.step());
+    /// assert_eq!(0xD000, cpu.registers.PC);
+    ///
+    /// cpu.step_n(2).unwrap();
+    /// assert_eq!(0xC001, cpu.registers.PC);
+    /// assert!(cpu.flags.carry);
+    /// ```
     pub fn nmi(&mut self) {
-        // Always handle an NMI
-        let handler_addr = LittleEndian::read_u16(&self.memory[NMI_VECTOR..]);
+        self.nmi_pending = true;
+        self.idle = false;
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.signalled(InterruptKind::Nmi);
+        }
+    }
 
-        // ..unless its not set to something other than zero:
-        if handler_addr == 0 {
-            return;
+    /// Holds the NMI line low. Only the change from released to asserted
+    /// signals an NMI, so a line that stays asserted is not taken again
+    /// until it has been released.
+    pub fn assert_nmi(&mut self) {
+        if !self.nmi_line {
+            self.nmi();
         }
-        let mem = &mut self.memory[STACK_START..STACK_END + 0x01];
+        self.nmi_line = true;
+    }
+
+    pub fn release_nmi(&mut self) {
+        self.nmi_line = false;
+    }
+
+    /// Returns true if an NMI has been signalled but not yet taken
+    pub fn nmi_pending(&self) -> bool {
+        self.nmi_pending
+    }
+
+    /// Holds the IRQ line low, as a device does until its interrupt is
+    /// acknowledged. The line is checked before each instruction, and while
+    /// it is asserted and interrupts are enabled the Cpu enters the handler
+    /// at the IRQ vector (`$FFFE`). As the line is level-triggered, the
+    /// handler is entered again after `RTI` unless the line was released.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Cpu;
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // SEI, CLI, then an IRQ handler of RTI
+    /// cpu.load(&[0x78, 0x58], None).unwrap();
+    /// cpu.memory.write_bytes(0xD000, &[0x40]);
+    /// cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+    /// cpu.reset();
+    ///
+    /// cpu.step().unwrap();
+    /// cpu.assert_irq();
+    /// cpu.step().unwrap();
+    /// assert_eq!(0xC002, cpu.registers.PC);
+    ///
+    /// assert_eq!(Ok(7), cpu.step());
+    /// assert_eq!(0xD000, cpu.registers.PC);
+    ///
+    /// cpu.release_irq();
+    /// cpu.step().unwrap();
+    /// assert_eq!(0xC002, cpu.registers.PC);
+    /// ```
+    pub fn assert_irq(&mut self) {
+        self.irq_line = true;
+        self.idle = false;
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.signalled(InterruptKind::Irq);
+        }
+    }
 
-        self.stack.push_u16(mem, self.registers.PC);
-        self.stack.push(mem, self.flags.to_u8());
-        self.flags.interrupt_disabled = true;
-        self.registers.PC = handler_addr;
+    pub fn release_irq(&mut self) {
+        self.irq_line = false;
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.irq_released();
+        }
+    }
+
+    /// Returns true while the IRQ line is asserted
+    pub fn irq_asserted(&self) -> bool {
+        self.irq_line
     }
 
-    /// Execute the Interrupt ReQuest handler if we currently are accepting
-    /// maskable interrupts. Ignore it otherwise.
+    /// Execute the Interrupt ReQuest handler at the IRQ vector (`$FFFE`) if
+    /// we currently are accepting maskable interrupts. Ignore it otherwise.
+    /// This takes the interrupt immediately, like a pulse on the IRQ line;
+    /// devices that hold the line should use `assert_irq` instead.
     pub fn irq(&mut self) {
         // If interrupts are disabled, don't worry about this
         if self.flags.interrupt_disabled {
             return;
         }
 
-        let handler_addr = LittleEndian::read_u16(&self.memory[IRQ_VECTOR..]);
+        self.wait_states.set(0);
+        self.interrupt(InterruptKind::Irq, IRQ_VECTOR);
+        // Unlike an interrupt taken by `step`, nothing else counts the
+        // cycles of this one
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.advance(INTERRUPT_CYCLES.saturating_add(self.wait_states.get()) as u64);
+        }
+    }
 
-        // ..unless its not set to something other than zero:
-        if handler_addr == 0 {
-            return;
+    /// Schedules an event for the given cycle of `cycles`. It is delivered
+    /// by the first `step` to start on or after that cycle, before the
+    /// Cpu checks for interrupts, so an IRQ asserted at cycle N is taken
+    /// by the step that starts at N. A Cpu that is idle has nothing to do
+    /// until the next event arrives, so its cycle count jumps ahead to it.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, ExternalEvent};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // CLI, then NOPs, with an IRQ handler of RTI
+    /// cpu.load(&[0x58, 0xEA, 0xEA, 0xEA], None).unwrap();
+    /// cpu.memory.write_bytes(0xD000, &[0x40]);
+    /// cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+    /// cpu.reset();
+    ///
+    /// cpu.schedule(2, ExternalEvent::Write { addr: 0x0200, value: 0xC1 });
+    /// cpu.schedule(4, ExternalEvent::AssertIrq);
+    ///
+    /// // CLI and a NOP take cycles 0-3, so the IRQ waits for the step at 4
+    /// cpu.step_n(2).unwrap();
+    /// assert_eq!(0xC1, cpu.memory.read_byte(0x0200));
+    /// assert!(!cpu.irq_asserted());
+    ///
+    /// assert_eq!(Ok(7), cpu.step());
+    /// assert_eq!(0xD000, cpu.registers.PC);
+    /// ```
+    pub fn schedule(&mut self, cycle: u64, event: ExternalEvent) {
+        self.event_queue.push(cycle, event);
+    }
+
+    /// Returns the events scheduled but not yet delivered
+    pub fn event_queue(&self) -> &EventQueue {
+        &self.event_queue
+    }
+
+    /// Replaces the scheduled events, such as with a recording of the input
+    /// to a run being replayed
+    pub fn set_event_queue(&mut self, queue: EventQueue) {
+        self.event_queue = queue;
+    }
+
+    /// Delivers the scheduled events that are due by the current cycle
+    fn deliver_events(&mut self) {
+        if self.idle && self.halted.is_none() {
+            if let Some(cycle) = self.event_queue.next_cycle() {
+                self.cycles = self.cycles.max(cycle);
+            }
         }
 
-        let mem = &mut self.memory[STACK_START..STACK_END + 0x01];
+        while let Some((_, event)) = self.event_queue.pop_due(self.cycles) {
+            match event {
+                ExternalEvent::AssertIrq => self.assert_irq(),
+                ExternalEvent::ReleaseIrq => self.release_irq(),
+                ExternalEvent::Nmi => self.nmi(),
+                ExternalEvent::AssertNmi => self.assert_nmi(),
+                ExternalEvent::ReleaseNmi => self.release_nmi(),
+                ExternalEvent::Write { addr, value } => self.memory.write_byte(addr, value),
+            }
+        }
+    }
 
-        self.stack.push_u16(mem, self.registers.PC);
-        self.stack.push(mem, self.flags.to_u8());
+    /// Enters an interrupt handler the way every interrupt does: the return
+    /// address is pushed, high byte first, followed by the status with bit
+    /// 5 set and the B flag set only for `BRK`. Further IRQs are then
+    /// masked and execution continues at the address in the vector. The
+    /// 65C02 also clears the decimal flag.
+    fn interrupt(&mut self, kind: InterruptKind, vector: u16) {
+        let return_addr = self.registers.PC;
+        if kind != InterruptKind::Brk {
+            self.pending_accesses.borrow_mut().clear();
+        }
+
+        let handler_addr = self.memory.read_u16(vector);
+        let stack_pointer = self.registers.S;
+        self.push_frame(StackFrame {
+            kind: StackFrameKind::Interrupt(kind),
+            from: return_addr,
+            to: handler_addr,
+            stack_pointer,
+        });
+        let status = self.flags.to_pushed_byte(kind == InterruptKind::Brk);
+
+        stack::push_u16(&mut self.memory, &mut self.registers.S, self.registers.PC);
+        stack::push(&mut self.memory, &mut self.registers.S, status);
         self.flags.interrupt_disabled = true;
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.flags.decimal = false;
+        }
+
+        self.wait_for_stack(stack_pointer);
+        self.wait(vector, MemoryAccess::Read);
+        self.wait(vector.wrapping_add(1), MemoryAccess::Read);
+        let cycles = INTERRUPT_CYCLES.saturating_add(self.wait_states.get());
+        if let Some(ref mut statistics) = self.interrupt_statistics {
+            statistics.entered(kind, cycles as u64);
+        }
+
+        self.record_stack_events(stack_pointer);
+        if let Some(ref coverage) = self.coverage {
+            coverage.record_read(vector);
+            coverage.record_read(vector.wrapping_add(1));
+        }
+        self.record_access(MemoryAccess::Read, vector, handler_addr as u8);
+        self.record_access(MemoryAccess::Read, vector.wrapping_add(1), (handler_addr >> 8) as u8);
+        self.interrupt_taken(kind, handler_addr);
+
+        // BRK runs the hooks with the rest of its instruction
+        if kind != InterruptKind::Brk && !self.cycle_hooks.is_empty() {
+            self.run_cycle_hooks(return_addr, cycles, None, 0);
+        }
+    }
+
+    /// Jumps to an interrupt handler once the return address and status
+    /// have been pushed
+    fn interrupt_taken(&mut self, kind: InterruptKind, handler_addr: u16) {
+        if !self.listeners.is_empty() {
+            self.record_event(CpuEvent::InterruptTaken {
+                kind,
+                return_addr: self.registers.PC,
+                handler: handler_addr,
+            });
+            self.dispatch_events();
+        }
+
         self.registers.PC = handler_addr;
+        self.idle = false;
+    }
+
+    /// Queues an event for the listeners, if there are any
+    fn record_event(&self, event: CpuEvent) {
+        if !self.listeners.is_empty() {
+            self.pending_events.borrow_mut().push(event);
+        }
+    }
+
+    /// Queues a bus access for the cycle hooks, if there are any
+    fn record_access(&self, access: MemoryAccess, addr: u16, value: u8) {
+        if !self.cycle_hooks.is_empty() {
+            self.pending_accesses.borrow_mut().push(BusCycle {
+                access,
+                addr,
+                value,
+            });
+        }
+    }
+
+    /// Calls the cycle hooks for each of the cycles an instruction or
+    /// interrupt took. The opcode fetch and the first `leading` queued
+    /// accesses, which are the operand bytes, take the first cycles and
+    /// the rest of the queued accesses take the last.
+    fn run_cycle_hooks(&mut self, pc: u16, cycles: u8, fetch: Option<BusCycle>, leading: usize) {
+        let mut accesses = std::mem::take(&mut *self.pending_accesses.borrow_mut());
+        let trailing = accesses.split_off(leading.min(accesses.len()));
+
+        let mut bus: Vec<Option<BusCycle>> = fetch.into_iter().chain(accesses).map(Some).collect();
+        let length = (cycles as usize).max(bus.len() + trailing.len());
+        bus.resize(length - trailing.len(), None);
+        bus.extend(trailing.into_iter().map(Some));
+
+        for (index, bus) in bus.into_iter().enumerate() {
+            let cycle = Cycle {
+                pc,
+                index: index as u8,
+                bus,
+            };
+            for hook in &mut self.cycle_hooks {
+                hook(&cycle);
+            }
+        }
+    }
+
+    /// Adds the wait states of an access to those of the instruction or
+    /// interrupt making it
+    fn wait(&self, addr: u16, access: MemoryAccess) {
+        let cycles = self.memory.wait_states(addr, access);
+        self.wait_states.set(self.wait_states.get().saturating_add(cycles));
+    }
+
+    /// Adds the wait states of the pushes or pulls for each byte the stack
+    /// pointer has moved over since it was at `stack_pointer`
+    fn wait_for_stack(&self, stack_pointer: u8) {
+        let moved = self.registers.S.wrapping_sub(stack_pointer) as i8;
+        for offset in 0..moved.unsigned_abs() {
+            if moved < 0 {
+                self.wait(stack::addr(stack_pointer.wrapping_sub(offset)), MemoryAccess::Write);
+            } else {
+                self.wait(stack::addr(stack_pointer.wrapping_add(offset + 1)), MemoryAccess::Read);
+            }
+        }
+    }
+
+    /// Queues a push or pop event for each byte the stack pointer has
+    /// moved over since it was at `stack_pointer`
+    fn record_stack_events(&self, stack_pointer: u8) {
+        if self.listeners.is_empty() && self.cycle_hooks.is_empty() && self.coverage.is_none() {
+            return;
+        }
+
+        // An instruction moves the stack pointer by a few bytes at most,
+        // so the direction it moved in survives wrapping around the page
+        let moved = self.registers.S.wrapping_sub(stack_pointer) as i8;
+        if moved < 0 {
+            for offset in 0..moved.unsigned_abs() {
+                let addr = stack::addr(stack_pointer.wrapping_sub(offset));
+                let value = self.memory.read_byte(addr);
+                self.record_event(CpuEvent::StackPush {
+                    addr,
+                    value,
+                });
+                if let Some(ref coverage) = self.coverage {
+                    coverage.record_written(addr);
+                }
+                self.record_access(MemoryAccess::Write, addr, value);
+            }
+        } else {
+            for offset in 1..moved as u8 + 1 {
+                let addr = stack::addr(stack_pointer.wrapping_add(offset));
+                let value = self.memory.read_byte(addr);
+                self.record_event(CpuEvent::StackPop {
+                    addr,
+                    value,
+                });
+                if let Some(ref coverage) = self.coverage {
+                    coverage.record_read(addr);
+                }
+                self.record_access(MemoryAccess::Read, addr, value);
+            }
+        }
+    }
+
+    /// Records a call or interrupt with the stack guard and the call
+    /// stack, whichever are enabled, for the return to unwind later
+    fn push_frame(&mut self, frame: StackFrame) {
+        if let Some((_, ref mut frames)) = self.stack_guard {
+            frames.push(frame);
+        }
+        if let Some(ref mut call_stack) = self.call_stack {
+            call_stack.push(frame);
+        }
+    }
+
+    /// Checks how an instruction or interrupt moved the stack pointer
+    /// against the stack guard, then drops the frames it returned from
+    fn check_stack_guard(&mut self, pc: u16, stack_pointer: u8, pushed_or_pulled: bool) -> Result<(), CpuError> {
+        let current = self.registers.S;
+        if let Some((ref guard, ref mut frames)) = self.stack_guard {
+            if let Some(kind) = guard.check(stack_pointer, current).filter(|_| pushed_or_pulled) {
+                let violation = StackViolation {
+                    kind,
+                    stack_pointer: current,
+                    backtrace: frames.clone(),
+                };
+                return Err(CpuError::stack_violation(pc, violation));
+            }
+
+            // A return pulls the stack pointer back up to where it was
+            // before the call, as does a TXS that unwinds the stack
+            while frames.last().is_some_and(|frame| frame.stack_pointer <= current) {
+                frames.pop();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delivers queued events to every listener
+    fn dispatch_events(&mut self) {
+        let events = std::mem::take(&mut *self.pending_events.borrow_mut());
+        for event in &events {
+            for listener in &mut self.listeners {
+                listener(event);
+            }
+        }
     }
 
     // ## OpCode handlers ##
@@ -293,14 +2804,16 @@ impl Cpu {
 
         let carry = if self.flags.carry { 1 } else { 0 };
 
-        let value = self.unwrap_immediate(&operand) as u16;
-        let value_signs = self.registers.A & 0x80 == 0x80 && value & 0x80 == 0x80;
+        let value = self.unwrap_immediate(operand) as u16;
 
         // Do normal binary arithmetic first
-        let mut result = self.registers.A as u16 + value as u16 + carry as u16;
+        let mut result = self.registers.A as u16 + value + carry;
+
+        // Overflow when both inputs have the same sign and the binary result doesn't
+        self.flags.overflow = (self.registers.A as u16 ^ result) & (value ^ result) & 0x80 == 0x80;
 
         // Handle packed binary coded decimal
-        if self.flags.decimal {
+        if self.flags.decimal && self.variant.has_decimal_mode() {
             if (self.registers.A as u16 & 0x0F) + (value & 0x0F) + carry > 0x09 {
                 result += 0x06;
             }
@@ -311,32 +2824,39 @@ impl Cpu {
         }
 
         self.flags.carry = (result & 0x100) == 0x100;
-        self.flags.zero = result as u8 & 0xFF == 0x00;
+        self.flags.zero = (result as u8) == 0x00;
         self.flags.sign = result & 0x80 == 0x80;
 
-        if self.flags.sign != value_signs {
-            self.flags.overflow = true;
-        }
-
-        self.registers.A = result as u8 & 0xFF;
+        self.registers.A = result as u8;
     }
 
     fn and(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
         let result = self.registers.A & value;
 
         self.registers.A = result;
 
-        self.flags.zero = result as u8 & 0xFF == 0;
+        self.flags.zero = result == 0;
         self.flags.sign = result & 0x80 == 0x80;
     }
 
+    /// AND followed by ROR A, except that the carry comes from bit 6 of
+    /// the result and overflow from bit 6 XOR bit 5
+    fn arr(&mut self, operand: &Operand) {
+        self.and(operand);
+        self.ror(&Operand::Implied);
+
+        let result = self.registers.A;
+        self.flags.carry = result & 0x40 == 0x40;
+        self.flags.overflow = (result >> 6 ^ result >> 5) & 0x01 == 0x01;
+    }
+
     fn asl(&mut self, operand: &Operand) {
         let mut value = if let &Operand::Implied = operand {
             // Implied ASL uses the A register
             self.registers.A
         } else {
-            self.unwrap_immediate(&operand)
+            self.unwrap_immediate(operand)
         };
 
         // Test the seventh bit - if its set, shift it
@@ -344,22 +2864,36 @@ impl Cpu {
         self.flags.carry = (value & 0x80) == 0x80;
 
         // Shift the value left
-        value = value << 0x01;
+        let old = value;
+        value <<= 0x01;
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value as u8 & 0xFF == 0;
+        self.flags.zero = value == 0;
 
         if let &Operand::Implied = operand {
             self.registers.A = value;
         } else {
-            let addr = self.unwrap_address(&operand);
-            self.write_byte(addr, value);
+            let addr = self.unwrap_address(operand);
+            self.write_modified(addr, old, value);
         }
     }
 
+    /// Subtracts the operand from A AND X without borrow, storing the
+    /// result in X and setting the flags like CMP
+    fn axs(&mut self, operand: &Operand) {
+        let value = self.unwrap_immediate(operand);
+        let masked = self.registers.A & self.registers.X;
+        let result = masked.wrapping_sub(value);
+
+        self.flags.carry = masked >= value;
+        self.flags.sign = result & 0x80 == 0x80;
+        self.flags.zero = result == 0x00;
+        self.registers.X = result;
+    }
+
     fn bcc(&mut self, operand: &Operand) {
         // Branch if the carry flag is not set
         if !self.flags.carry {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
@@ -367,7 +2901,7 @@ impl Cpu {
     fn bcs(&mut self, operand: &Operand) {
         // Branch if the carry flag is set
         if self.flags.carry {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
@@ -375,17 +2909,21 @@ impl Cpu {
     fn beq(&mut self, operand: &Operand) {
         // Branch if the zero flag is set
         if self.flags.zero {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
 
     fn bit(&mut self, operand: &Operand) {
         let a = self.registers.A;
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
         let result = value & a;
 
         self.flags.zero = result == 0x00;
+        // The 65C02's BIT #imm only sets the zero flag
+        if let &Operand::Immediate(_) = operand {
+            return;
+        }
         self.flags.overflow = value & 0x40 == 0x40; // "The V flag and the N flag receive copies of the sixth and seventh bits of the tested number"
         self.flags.sign = value & 0x80 == 0x80;
     }
@@ -393,7 +2931,7 @@ impl Cpu {
     fn bmi(&mut self, operand: &Operand) {
         // Branch if the sign flag is set
         if self.flags.sign {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
@@ -401,7 +2939,7 @@ impl Cpu {
     fn bne(&mut self, operand: &Operand) {
         // Branch if the zero flag is not set
         if !self.flags.zero {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
@@ -409,20 +2947,30 @@ impl Cpu {
     fn bpl(&mut self, operand: &Operand) {
         // Branch if the sign flag is not set
         if !self.flags.sign {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
 
+    fn bra(&mut self, operand: &Operand) {
+        // Branch always
+        let offset = self.unwrap_immediate(operand);
+        self.relative_jump(offset);
+    }
+
     fn brk(&mut self) {
-        // Just call the IRQ handler - they're the same thing
-        self.irq();
+        // BRK is a software interrupt through the IRQ vector that can't be
+        // masked. The byte after it is padding that the return address
+        // skips, and the pushed status byte has the B flag set so the
+        // handler can tell it apart from an IRQ.
+        self.registers.PC = self.registers.PC.wrapping_add(1);
+        self.interrupt(InterruptKind::Brk, IRQ_VECTOR);
     }
 
     fn bvc(&mut self, operand: &Operand) {
         // Branch if the overflow flag is not set
         if !self.flags.overflow {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
@@ -430,7 +2978,7 @@ impl Cpu {
     fn bvs(&mut self, operand: &Operand) {
         // Branch if the overflow flag is set
         if self.flags.overflow {
-            let offset = self.unwrap_immediate(&operand);
+            let offset = self.unwrap_immediate(operand);
             self.relative_jump(offset);
         }
     }
@@ -452,7 +3000,7 @@ impl Cpu {
     }
 
     fn compare(&mut self, operand: &Operand, byte: u8) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
         let result: i16 = byte as i16 - value as i16;
 
         self.flags.carry = (result as u16) < 0x100;
@@ -461,100 +3009,116 @@ impl Cpu {
     }
 
     fn dec(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
-        let addr = self.unwrap_address(&operand);
+        // DEC A on the 65C02
+        if let &Operand::Implied = operand {
+            self.registers.A = self.registers.A.wrapping_sub(1);
+            self.flags.sign = self.registers.A & 0x80 == 0x80;
+            self.flags.zero = self.registers.A == 0x00;
+            return;
+        }
+
+        let value = self.unwrap_immediate(operand);
+        let addr = self.unwrap_address(operand);
         let result = value.wrapping_sub(1);
 
-        self.write_byte(addr, result);
+        self.write_modified(addr, value, result);
 
         self.flags.sign = result & 0x80 == 0x80;
-        self.flags.zero = result & 0xFF == 0x00;
+        self.flags.zero = result == 0x00;
     }
 
     fn dex(&mut self) {
         self.registers.X = self.registers.X.wrapping_sub(1);
 
         self.flags.sign = self.registers.X & 0x80 == 0x80;
-        self.flags.zero = self.registers.X & 0xFF == 0x00;
+        self.flags.zero = self.registers.X == 0x00;
     }
 
     fn dey(&mut self) {
         self.registers.Y = self.registers.Y.wrapping_sub(1);
 
         self.flags.sign = self.registers.Y & 0x80 == 0x80;
-        self.flags.zero = self.registers.Y & 0xFF == 0x00;
+        self.flags.zero = self.registers.Y == 0x00;
     }
 
     fn eor(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
         let result = self.registers.A ^ value;
 
         self.registers.A = result;
 
         self.flags.sign = result & 0x80 == 0x80;
-        self.flags.zero = result & 0xFF == 0x00;
+        self.flags.zero = result == 0x00;
     }
 
     fn inc(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
-        let addr = self.unwrap_address(&operand);
+        // INC A on the 65C02
+        if let &Operand::Implied = operand {
+            self.registers.A = self.registers.A.wrapping_add(1);
+            self.flags.sign = self.registers.A & 0x80 == 0x80;
+            self.flags.zero = self.registers.A == 0x00;
+            return;
+        }
+
+        let value = self.unwrap_immediate(operand);
+        let addr = self.unwrap_address(operand);
         let result = value.wrapping_add(1);
 
-        self.write_byte(addr, result);
+        self.write_modified(addr, value, result);
 
         self.flags.sign = result & 0x80 == 0x80;
-        self.flags.zero = result & 0xFF == 0x00;
+        self.flags.zero = result == 0x00;
     }
 
     fn inx(&mut self) {
         self.registers.X = self.registers.X.wrapping_add(1);
 
         self.flags.sign = self.registers.X & 0x80 == 0x80;
-        self.flags.zero = self.registers.X & 0xFF == 0x00;
+        self.flags.zero = self.registers.X == 0x00;
     }
 
     fn iny(&mut self) {
         self.registers.Y = self.registers.Y.wrapping_add(1);
 
         self.flags.sign = self.registers.Y & 0x80 == 0x80;
-        self.flags.zero = self.registers.Y & 0xFF == 0x00;
+        self.flags.zero = self.registers.Y == 0x00;
     }
 
     fn jmp(&mut self, operand: &Operand) {
-        let value = self.unwrap_address(&operand);
+        let value = self.unwrap_address(operand);
         self.registers.PC = value;
     }
 
     fn jsr(&mut self, operand: &Operand) {
-        let addr = self.unwrap_address(&operand);
-        let mut mem = &mut self.memory[STACK_START..STACK_END + 0x01];
-
-        self.stack.push_u16(mem, self.registers.PC);
+        // The 6502 pushes the address of the last byte of the JSR,
+        // which RTS adds one to when it returns
+        let addr = self.unwrap_address(operand);
+        stack::push_u16(&mut self.memory, &mut self.registers.S, self.registers.PC.wrapping_sub(1));
         self.registers.PC = addr;
     }
 
     fn lda(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
 
         self.registers.A = value;
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
     }
 
     fn ldx(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
 
         self.registers.X = value;
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
     }
 
     fn ldy(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
 
         self.registers.Y = value;
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
     }
 
     fn lsr(&mut self, operand: &Operand) {
@@ -562,21 +3126,22 @@ impl Cpu {
         let value = if let &Operand::Implied = operand {
             self.registers.A
         } else {
-            self.unwrap_immediate(&operand)
+            self.unwrap_immediate(operand)
         };
 
         self.flags.carry = value & 0x01 == 0x01;
 
+        let old = value;
         let value = value >> 0x01;
 
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
 
         if let &Operand::Implied = operand {
             self.registers.A = value;
         } else {
-            let addr = self.unwrap_address(&operand);
-            self.memory.write_byte(addr, value);
+            let addr = self.unwrap_address(operand);
+            self.write_modified(addr, old, value);
         }
     }
 
@@ -585,58 +3150,76 @@ impl Cpu {
     }
 
     fn ora(&mut self, operand: &Operand) {
-        let value = self.unwrap_immediate(&operand);
+        let value = self.unwrap_immediate(operand);
         let result = self.registers.A | value;
 
         self.flags.sign = result & 0x80 == 0x80;
-        self.flags.zero = result & 0xFF == 0x00;
+        self.flags.zero = result == 0x00;
 
         self.registers.A = result;
     }
 
     fn pha(&mut self) {
-        let mut mem = &mut self.memory[STACK_START..STACK_END + 0x01];
-
-        self.stack.push(mem, self.registers.A).unwrap();
+        stack::push(&mut self.memory, &mut self.registers.S, self.registers.A);
     }
 
     fn php(&mut self) {
-        let mut mem = &mut self.memory[STACK_START..STACK_END + 0x01];
+        stack::push(&mut self.memory, &mut self.registers.S, self.flags.to_pushed_byte(true));
+    }
 
-        self.stack.push(mem, self.flags.to_u8()).unwrap();
+    fn phx(&mut self) {
+        stack::push(&mut self.memory, &mut self.registers.S, self.registers.X);
     }
 
-    fn pla(&mut self) {
-        let mem = &mut self.memory[STACK_START..STACK_END + 0x01];
+    fn phy(&mut self) {
+        stack::push(&mut self.memory, &mut self.registers.S, self.registers.Y);
+    }
 
-        let value = self.stack.pop(mem).unwrap();
+    fn pla(&mut self) {
+        let value = stack::pop(&self.memory, &mut self.registers.S);
 
         self.registers.A = value;
+        self.flags.sign = value & 0x80 == 0x80;
+        self.flags.zero = value == 0x00;
     }
 
     fn plp(&mut self) {
-        let mem = &mut self.memory[STACK_START..STACK_END + 0x01];
+        let value = stack::pop(&self.memory, &mut self.registers.S);
+
+        self.flags = StatusFlags::from_pulled_byte(value);
+    }
+
+    fn plx(&mut self) {
+        let value = stack::pop(&self.memory, &mut self.registers.S);
+
+        self.registers.X = value;
+        self.flags.sign = value & 0x80 == 0x80;
+        self.flags.zero = value == 0x00;
+    }
 
-        let value = self.stack.pop(mem).unwrap();
+    fn ply(&mut self) {
+        let value = stack::pop(&self.memory, &mut self.registers.S);
 
-        self.flags = value.into();
+        self.registers.Y = value;
+        self.flags.sign = value & 0x80 == 0x80;
+        self.flags.zero = value == 0x00;
     }
 
     fn rts(&mut self) {
-        let mut mem = &mut self.memory[STACK_START..STACK_END + 0x01];
-        let addr = self.stack.pop_u16(mem).unwrap();
+        let addr = stack::pop_u16(&self.memory, &mut self.registers.S);
 
-        self.registers.PC = addr;
+        self.registers.PC = addr.wrapping_add(1);
     }
 
     fn rol(&mut self, operand: &Operand) {
         let value = if let &Operand::Implied = operand {
             self.registers.A
         } else {
-            self.unwrap_immediate(&operand)
+            self.unwrap_immediate(operand)
         };
 
         let carry = value & 0x80 == 0x80;
+        let old = value;
 
         let value = if self.flags.carry {
             (value << 0x01) | 0x01
@@ -646,23 +3229,24 @@ impl Cpu {
 
         self.flags.carry = carry;
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
 
         if let &Operand::Implied = operand {
             self.registers.A = value;
         } else {
-            let addr = self.unwrap_address(&operand);
-            self.memory.write_byte(addr, value);
+            let addr = self.unwrap_address(operand);
+            self.write_modified(addr, old, value);
         }
     }
     fn ror(&mut self, operand: &Operand) {
         let value = if let &Operand::Implied = operand {
             self.registers.A
         } else {
-            self.unwrap_immediate(&operand)
+            self.unwrap_immediate(operand)
         };
 
         let carry = value & 0x01 == 0x01;   // Carry flag is the low bit in a ROR
+        let old = value;
 
         let value = if self.flags.carry {
             (value >> 0x01) | 0x80
@@ -672,44 +3256,40 @@ impl Cpu {
 
         self.flags.carry = carry;
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
 
         if let &Operand::Implied = operand {
             self.registers.A = value;
         } else {
-            let addr = self.unwrap_address(&operand);
-            self.memory.write_byte(addr, value);
+            let addr = self.unwrap_address(operand);
+            self.write_modified(addr, old, value);
         }
     }
 
     fn rti(&mut self) {
-        let mem = &mut self.memory[STACK_START..STACK_END + 0x01];
+        let value = stack::pop(&self.memory, &mut self.registers.S);
+        let pc = stack::pop_u16(&self.memory, &mut self.registers.S);
 
-        let value = self.stack.pop(mem).expect("ERR: Returning from an interrupt with an empty stack. Did you forget to set the interrupt handler address?");
-        let pc = self.stack.pop_u16(mem).expect("ERR: Returning from an interrupt with an empty stack. Did you forget to set the interrupt handler address?");
-
-        self.flags = value.into();
+        self.flags = StatusFlags::from_pulled_byte(value);
         self.registers.PC = pc;
     }
 
     fn sbc(&mut self, operand: &Operand) {
         let carry = if self.flags.carry { 0 } else { 1 };
 
-        let value = self.unwrap_immediate(&operand) as i16;
-        let value_signs = self.registers.A & 0x80 == 0x80 && value & 0x80 == 0x80;
+        let value = self.unwrap_immediate(operand) as i16;
 
         // Do normal binary arithmetic first
-        let mut result = self.registers.A as i16 - value as i16 - carry as i16;
+        let mut result = self.registers.A as i16 - value - carry as i16;
 
-        self.flags.zero = result as u8 & 0xFF == 0x00;
+        self.flags.zero = (result as u8) == 0x00;
         self.flags.sign = result & 0x80 == 0x80;
+        // Overflow when the inputs have different signs and the result has the sign of the value
+        let a = self.registers.A as i16;
+        self.flags.overflow = (a ^ result) & (a ^ value) & 0x80 == 0x80;
 
-        if self.flags.sign != value_signs {
-            self.flags.overflow = true;
-        }
-
-        if self.flags.decimal {
-            if (((self.registers.A as i16) & 0x0F) - carry as i16) < ((value as i16) & 0x0F) {
+        if self.flags.decimal && self.variant.has_decimal_mode() {
+            if (((self.registers.A as i16) & 0x0F) - carry as i16) < (value & 0x0F) {
                 result -= 0x06;
             }
             if (result as u16) > 0x99 {
@@ -721,92 +3301,249 @@ impl Cpu {
         self.registers.A = result as u8;
     }
 
+    fn sax(&mut self, operand: &Operand) {
+        let addr = self.unwrap_address(operand);
+        let value = self.registers.A & self.registers.X;
+
+        self.write_byte(addr, value);
+    }
+
     fn sta(&mut self, operand: &Operand) {
-        let addr = self.unwrap_address(&operand);
+        let addr = self.unwrap_address(operand);
         let value = self.registers.A;
 
         self.write_byte(addr, value);
     }
 
     fn stx(&mut self, operand: &Operand) {
-        let addr = self.unwrap_address(&operand);
+        let addr = self.unwrap_address(operand);
         let value = self.registers.X;
 
         self.write_byte(addr, value);
     }
 
     fn sty(&mut self, operand: &Operand) {
-        let addr = self.unwrap_address(&operand);
+        let addr = self.unwrap_address(operand);
         let value = self.registers.Y;
 
         self.write_byte(addr, value);
     }
 
+    fn stz(&mut self, operand: &Operand) {
+        let addr = self.unwrap_address(operand);
+
+        self.write_byte(addr, 0x00);
+    }
+
     fn tax(&mut self) {
         self.registers.X = self.registers.A;
 
         self.flags.sign = self.registers.A & 0x80 == 0x80;
-        self.flags.zero = self.registers.A & 0xFF == 0x00;
+        self.flags.zero = self.registers.A == 0x00;
     }
 
     fn tay(&mut self) {
         self.registers.Y = self.registers.A;
 
         self.flags.sign = self.registers.A & 0x80 == 0x80;
-        self.flags.zero = self.registers.A & 0xFF == 0x00;
+        self.flags.zero = self.registers.A == 0x00;
+    }
+
+    /// Clears the bits of A in memory. The zero flag is set from A AND
+    /// memory, like BIT.
+    fn trb(&mut self, operand: &Operand) {
+        let value = self.unwrap_immediate(operand);
+        let addr = self.unwrap_address(operand);
+
+        self.flags.zero = value & self.registers.A == 0x00;
+        let result = value & !self.registers.A;
+        self.write_modified(addr, value, result);
+    }
+
+    /// Sets the bits of A in memory. The zero flag is set from A AND
+    /// memory, like BIT.
+    fn tsb(&mut self, operand: &Operand) {
+        let value = self.unwrap_immediate(operand);
+        let addr = self.unwrap_address(operand);
+
+        self.flags.zero = value & self.registers.A == 0x00;
+        let result = value | self.registers.A;
+        self.write_modified(addr, value, result);
     }
 
     fn tsx(&mut self) {
-        let value = self.stack.pointer as u8;
+        let value = self.registers.S;
         self.registers.X = value;
 
         self.flags.sign = value & 0x80 == 0x80;
-        self.flags.zero = value & 0xFF == 0x00;
+        self.flags.zero = value == 0x00;
     }
 
     fn txa(&mut self) {
         self.registers.A = self.registers.X;
 
         self.flags.sign = self.registers.X & 0x80 == 0x80;
-        self.flags.zero = self.registers.X & 0xFF == 0x00;
+        self.flags.zero = self.registers.X == 0x00;
     }
 
     fn txs(&mut self) {
-        self.stack.pointer = self.registers.X as usize;
+        self.registers.S = self.registers.X;
     }
 
     fn tya(&mut self) {
         self.registers.A = self.registers.Y;
 
         self.flags.sign = self.registers.Y & 0x80 == 0x80;
-        self.flags.zero = self.registers.Y & 0xFF == 0x00;
+        self.flags.zero = self.registers.Y == 0x00;
     }
 
     fn relative_jump(&mut self, offset: u8) {
+        let from = self.registers.PC;
+
         // If the sign bit is there, negate the PC by the difference
         // between 256 and the offset
         if offset & 0x80 == 0x80 {
-            self.registers.PC -= 0x100 - offset as u16;
+            self.registers.PC = self.registers.PC.wrapping_sub(0x100 - offset as u16);
         } else {
-            self.registers.PC += offset as u16;
+            self.registers.PC = self.registers.PC.wrapping_add(offset as u16);
         }
+
+        // A taken branch costs a cycle, and another if it lands in a
+        // different page
+        self.branch_cycles = if from & 0xFF00 == self.registers.PC & 0xFF00 { 1 } else { 2 };
     }
 
     /// Convenience wrapper for accessing a byte
     /// in memory
     fn read_byte(&self, addr: u16) -> u8 {
-        self.memory.read_byte(addr)
+        if let Some(ref coverage) = self.coverage {
+            coverage.record_read(addr);
+        }
+        self.fetch_byte(addr)
+    }
+
+    /// Reads a byte of the instruction being executed, which coverage
+    /// counts as executed rather than read
+    fn fetch_byte(&self, addr: u16) -> u8 {
+        self.check_protection(addr, MemoryAccess::Read);
+        let value = self.memory.read_byte(addr);
+        self.wait(addr, MemoryAccess::Read);
+        self.record_event(CpuEvent::MemoryRead {
+            addr,
+            value,
+        });
+        self.record_access(MemoryAccess::Read, addr, value);
+
+        value
     }
 
     /// Convenience wrapper for writing a byte
     /// to memory
     fn write_byte(&mut self, addr: u16, byte: u8) {
-        self.memory.write_byte(addr, byte);
+        if self.check_protection(addr, MemoryAccess::Write) {
+            self.check_code_write(addr);
+            if let Some((_, ref mut writes)) = self.history {
+                if let Some(value) = self.memory.peek(addr) {
+                    writes.push((addr, value));
+                }
+            }
+            self.memory.write_byte(addr, byte);
+            self.wait(addr, MemoryAccess::Write);
+            if let Some(ref coverage) = self.coverage {
+                coverage.record_written(addr);
+            }
+            if self.exit_condition == Some(ExitCondition::PortWrite(addr)) {
+                self.exit(byte);
+            }
+            self.record_event(CpuEvent::MemoryWrite {
+                addr,
+                value: byte,
+            });
+            self.record_access(MemoryAccess::Write, addr, byte);
+        }
     }
 
-    /// Convenience wrapper for accessing a word
-    /// in memory
+    /// Writes the result of a read-modify-write instruction. The NMOS 6502
+    /// writes the unmodified value back while it works out the result, so
+    /// a register with write side effects sees both writes. The 65C02
+    /// reads the address again instead.
+    fn write_modified(&mut self, addr: u16, old: u8, new: u8) {
+        if self.variant == CpuVariant::Cmos65C02 {
+            self.dummy_read(addr);
+        } else {
+            self.write_byte(addr, old);
+        }
+        self.write_byte(addr, new);
+    }
+
+    /// A read the processor makes on the way to the real one, whose value
+    /// is thrown away. It reaches the bus, listeners and cycle hooks but
+    /// isn't checked against memory protection.
+    fn dummy_read(&self, addr: u16) {
+        let value = self.memory.read_byte(addr);
+        self.wait(addr, MemoryAccess::Read);
+        self.record_event(CpuEvent::MemoryRead {
+            addr,
+            value,
+        });
+        self.record_access(MemoryAccess::Read, addr, value);
+    }
+
+    /// Applies the self-modifying code policy if the address is in a
+    /// region marked as code
+    fn check_code_write(&mut self, addr: u16) {
+        if !self.code_regions.iter().any(|range| range.contains(&addr)) {
+            return;
+        }
+
+        let pc = self.instruction_pc;
+        match self.self_modifying_code_policy {
+            SelfModifyingCodePolicy::Ignore => {}
+            SelfModifyingCodePolicy::Error => {
+                if self.code_write.is_none() {
+                    self.code_write = Some(addr);
+                }
+            }
+            SelfModifyingCodePolicy::Callback(ref mut handler) => handler(pc, addr),
+        }
+    }
+
+    /// Reads a word through the Cpu's own checks, wrapping around to
+    /// 0x0000 at the top of memory like `Bus::read_u16`
     fn read_u16(&self, addr: u16) -> u16 {
-        self.memory.read_u16(addr)
+        let low = self.read_byte(addr) as u16;
+        let high = self.read_byte(addr.wrapping_add(1)) as u16;
+
+        low | (high << 8)
+    }
+
+    fn fetch_u16(&self, addr: u16) -> u16 {
+        let low = self.fetch_byte(addr) as u16;
+        let high = self.fetch_byte(addr.wrapping_add(1)) as u16;
+
+        low | (high << 8)
+    }
+
+    /// Reads a pointer from the zero page. Like the 6502, a pointer at
+    /// `$FF` takes its high byte from `$00` rather than `$0100`.
+    fn read_zero_page_u16(&self, pointer: u8) -> u16 {
+        let low = self.read_byte(pointer as u16) as u16;
+        let high = self.read_byte(pointer.wrapping_add(1) as u16) as u16;
+
+        low | (high << 8)
+    }
+
+    /// Returns false, remembering the first fault for the current
+    /// instruction, if the access isn't allowed
+    fn check_protection(&self, addr: u16, access: MemoryAccess) -> bool {
+        if self.protection.allows(addr, access) {
+            return true;
+        }
+
+        if self.protection_fault.get().is_none() {
+            self.protection_fault.set(Some((addr, access)));
+        }
+
+        false
     }
 }
@@ -1,7 +1,23 @@
+use std::fmt;
+
+use cpu::limits::Limit;
+use cpu::protection::MemoryAccess;
+use cpu::stack_guard::{StackViolation, StackViolationKind};
+
 #[derive(Debug, PartialEq)]
 pub enum CpuErrorKind {
     SegFault,
     InvalidOpCode,
+    /// The instruction at `pc` accessed protected memory
+    MemoryProtection { pc: u16, access: MemoryAccess },
+    /// The instruction at `pc` wrote into memory marked as code
+    SelfModifyingCode { pc: u16 },
+    /// A run was stopped by one of its `RunLimits`
+    LimitReached(Limit),
+    /// The stack broke the Cpu's `StackGuard`
+    StackViolation(StackViolation),
+    /// A snapshot couldn't be restored
+    InvalidSnapshot,
 }
 
 #[derive(Debug, PartialEq)]
@@ -12,10 +28,19 @@ pub struct CpuError {
 }
 
 impl CpuError {
+    /// The address the error occurred at
+    pub fn addr(&self) -> u16 {
+        self.addr
+    }
+
+    pub fn kind(&self) -> &CpuErrorKind {
+        &self.kind
+    }
+
     pub fn code_segment_out_of_range(addr: u16) -> CpuError {
         CpuError {
-            message: format!("CODE segment out of bounds"),
-            addr: addr,
+            message: "CODE segment out of bounds".to_string(),
+            addr,
             kind: CpuErrorKind::SegFault,
         }
     }
@@ -23,8 +48,74 @@ impl CpuError {
     pub fn unknown_opcode(addr: u16, opcode: u8) -> CpuError {
         CpuError {
             message: format!("Unknown opcode {:02X} at {:04X}", opcode, addr),
-            addr: addr,
+            addr,
+            kind: CpuErrorKind::InvalidOpCode,
+        }
+    }
+
+    pub fn memory_protection(pc: u16, addr: u16, access: MemoryAccess) -> CpuError {
+        CpuError {
+            message: format!("{:?} access to protected memory at {:04X} by instruction at {:04X}",
+                             access,
+                             addr,
+                             pc),
+            addr,
+            kind: CpuErrorKind::MemoryProtection {
+                pc,
+                access,
+            },
+        }
+    }
+
+    pub fn self_modifying_code(pc: u16, addr: u16) -> CpuError {
+        CpuError {
+            message: format!("Instruction at {:04X} wrote to code at {:04X}", pc, addr),
+            addr,
+            kind: CpuErrorKind::SelfModifyingCode { pc },
+        }
+    }
+
+    pub fn limit_reached(addr: u16, limit: Limit) -> CpuError {
+        CpuError {
+            message: format!("{:?} limit reached at {:04X}", limit, addr),
+            addr,
+            kind: CpuErrorKind::LimitReached(limit),
+        }
+    }
+
+    pub fn stack_violation(pc: u16, violation: StackViolation) -> CpuError {
+        let problem = match violation.kind {
+            StackViolationKind::DepthExceeded(depth) => format!("Stack depth reached {} bytes", depth),
+            StackViolationKind::Overflow => "Stack overflowed".to_string(),
+            StackViolationKind::Underflow => "Stack underflowed".to_string(),
+        };
+
+        CpuError {
+            message: format!("{} at {:04X}, {} calls deep", problem, pc, violation.backtrace.len()),
+            addr: pc,
+            kind: CpuErrorKind::StackViolation(violation),
+        }
+    }
+
+    pub fn invalid_snapshot(memory_size: usize) -> CpuError {
+        CpuError {
+            message: format!("A snapshot needs 65536 bytes of memory, not {}", memory_size),
+            addr: 0,
+            kind: CpuErrorKind::InvalidSnapshot,
+        }
+    }
+
+    pub fn unknown_host_call(addr: u16, id: u8) -> CpuError {
+        CpuError {
+            message: format!("No host call registered for {:02X} at {:04X}", id, addr),
+            addr,
             kind: CpuErrorKind::InvalidOpCode,
         }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
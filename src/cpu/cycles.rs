@@ -0,0 +1,30 @@
+use cpu::protection::MemoryAccess;
+
+/// A byte that moved over the bus during a cycle. The opcode fetch is an
+/// `Execute` access.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BusCycle {
+    pub access: MemoryAccess,
+    pub addr: u16,
+    pub value: u8,
+}
+
+/// One clock cycle of an instruction or interrupt, as passed to the hooks
+/// registered with `Cpu::add_cycle_hook`.
+///
+/// The opcode fetch is always on the first cycle, followed by the operand
+/// bytes. As on the 6502, the remaining reads and writes, such as the
+/// store of `STA` or the pushes of `JSR`, happen on the last cycles of the
+/// instruction. Cycles the Cpu spends on internal work have no bus access.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cycle {
+    /// The address of the instruction, or the return address of an
+    /// interrupt
+    pub pc: u16,
+    /// Which cycle of the instruction this is, starting at 0
+    pub index: u8,
+    pub bus: Option<BusCycle>,
+}
+
+/// A hook registered with `Cpu::add_cycle_hook`
+pub type CycleHook = Box<dyn FnMut(&Cycle)>;
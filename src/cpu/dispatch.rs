@@ -0,0 +1,73 @@
+use ::opcodes::{AddressingMode, CpuVariant, OpCode};
+
+/// What `step` needs to know about an opcode beyond its handler. It is
+/// worked out once, when the dispatch table is built, so that stepping
+/// never compares mnemonics.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OpcodeTraits {
+    /// An indexed read that takes an extra cycle when the index carries
+    /// into the next page
+    pub page_crossing_penalty: bool,
+    /// A read-modify-write `abs,X` instruction, which on an NMOS 6502
+    /// reads from the address before the carry is fixed up
+    pub indexed_dummy_read: bool,
+    /// `JSR`, which pushes a call frame
+    pub call: bool,
+    /// `RTS`, which returns from a call frame
+    pub subroutine_return: bool,
+    /// `RTI`, which ends an interrupt handler
+    pub interrupt_return: bool,
+    /// `JMP` or a branch, which idles the Cpu when it targets itself
+    pub jump: bool,
+    /// `BRK` and `TXS`, whose stack accesses aren't counted after the
+    /// instruction: `BRK` reports its own and `TXS` makes none
+    pub own_stack_accounting: bool,
+    /// `TXS`, which moves the stack pointer without pushing or pulling
+    pub sets_stack_pointer: bool,
+}
+
+impl OpcodeTraits {
+    pub fn new(opcode: &OpCode, variant: CpuVariant) -> OpcodeTraits {
+        let mnemonic = opcode.mnemonic;
+
+        OpcodeTraits {
+            page_crossing_penalty: match mnemonic {
+                "ADC" | "AND" | "BIT" | "CMP" | "EOR" | "LAX" | "LDA" | "LDX" | "LDY" | "NOP" | "ORA" | "SBC" => {
+                    opcode.mode == AddressingMode::AbsoluteX || opcode.mode == AddressingMode::AbsoluteY ||
+                    opcode.mode == AddressingMode::IndirectY
+                }
+                _ => false,
+            },
+            indexed_dummy_read: match mnemonic {
+                "ASL" | "DCP" | "DEC" | "INC" | "ISC" | "LSR" | "RLA" | "ROL" | "ROR" | "RRA" | "SLO" | "SRE" => {
+                    opcode.mode == AddressingMode::AbsoluteX && variant != CpuVariant::Cmos65C02
+                }
+                _ => false,
+            },
+            call: mnemonic == "JSR",
+            subroutine_return: mnemonic == "RTS",
+            interrupt_return: mnemonic == "RTI",
+            jump: mnemonic == "JMP" || opcode.mode == AddressingMode::Relative,
+            own_stack_accounting: mnemonic == "BRK" || mnemonic == "TXS",
+            sets_stack_pointer: mnemonic == "TXS",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_nmos_read_modify_write_instructions_make_a_dummy_read() {
+        let inc = OpCode::decode(0xFE, CpuVariant::Nmos6502).unwrap();
+        let lda = OpCode::decode(0xBD, CpuVariant::Nmos6502).unwrap();
+
+        assert!(OpcodeTraits::new(inc, CpuVariant::Nmos6502).indexed_dummy_read);
+        assert!(OpcodeTraits::new(inc, CpuVariant::Ricoh2A03).indexed_dummy_read);
+        assert!(!OpcodeTraits::new(inc, CpuVariant::Cmos65C02).indexed_dummy_read);
+        assert!(!OpcodeTraits::new(lda, CpuVariant::Nmos6502).indexed_dummy_read);
+        assert!(OpcodeTraits::new(lda, CpuVariant::Nmos6502).page_crossing_penalty);
+        assert!(!OpcodeTraits::new(inc, CpuVariant::Nmos6502).page_crossing_penalty);
+    }
+}
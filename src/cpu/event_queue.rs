@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Something the outside world does to the Cpu, such as a device raising
+/// an interrupt or a key press landing in a keyboard register
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExternalEvent {
+    /// Holds the IRQ line low, as `Cpu::assert_irq` does
+    AssertIrq,
+    ReleaseIrq,
+    /// Signals an NMI, as `Cpu::nmi` does
+    Nmi,
+    /// Holds the NMI line low, as `Cpu::assert_nmi` does
+    AssertNmi,
+    ReleaseNmi,
+    /// Writes a byte to the bus, as a device latching input would
+    Write { addr: u16, value: u8 },
+}
+
+/// Events scheduled for the cycles they happen at, so that the host's
+/// timing doesn't depend on how it steps the Cpu. The same queue always
+/// produces the same run, which makes it a record of the input to replay.
+/// Events for the same cycle are delivered in the order they were pushed.
+///
+/// # Example
+/// ```
+/// use rs6502::{EventQueue, ExternalEvent};
+///
+/// let mut queue = EventQueue::new();
+/// queue.push(100, ExternalEvent::AssertIrq);
+/// queue.push(20, ExternalEvent::Write { addr: 0xC000, value: 0xC1 });
+///
+/// assert_eq!(Some(20), queue.next_cycle());
+/// assert_eq!(None, queue.pop_due(19));
+/// assert_eq!(Some((20, ExternalEvent::Write { addr: 0xC000, value: 0xC1 })), queue.pop_due(50));
+/// assert_eq!(None, queue.pop_due(50));
+/// assert_eq!(1, queue.len());
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EventQueue {
+    events: VecDeque<(u64, ExternalEvent)>,
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        Default::default()
+    }
+
+    /// Schedules an event for the given cycle of `Cpu::cycles`
+    pub fn push(&mut self, cycle: u64, event: ExternalEvent) {
+        let index = self.events.partition_point(|&(scheduled, _)| scheduled <= cycle);
+        self.events.insert(index, (cycle, event));
+    }
+
+    /// Removes the earliest event if it is due by `cycle`, returning it
+    /// with the cycle it was scheduled for
+    pub fn pop_due(&mut self, cycle: u64) -> Option<(u64, ExternalEvent)> {
+        if self.next_cycle()? <= cycle {
+            self.events.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the cycle the earliest event is scheduled for
+    pub fn next_cycle(&self) -> Option<u64> {
+        self.events.front().map(|&(cycle, _)| cycle)
+    }
+
+    /// Returns the events still to be delivered, earliest first
+    pub fn events(&self) -> impl Iterator<Item = &(u64, ExternalEvent)> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_events_for_the_same_cycle_in_order() {
+        let mut queue = EventQueue::new();
+        queue.push(10, ExternalEvent::AssertIrq);
+        queue.push(5, ExternalEvent::Nmi);
+        queue.push(10, ExternalEvent::ReleaseIrq);
+
+        let events: Vec<_> = queue.events().cloned().collect();
+        assert_eq!(vec![(5, ExternalEvent::Nmi), (10, ExternalEvent::AssertIrq), (10, ExternalEvent::ReleaseIrq)],
+                   events);
+    }
+}
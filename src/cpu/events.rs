@@ -0,0 +1,32 @@
+use ::opcodes::OpCode;
+
+/// The kinds of interrupt the Cpu can take
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum InterruptKind {
+    Irq,
+    Nmi,
+    Brk,
+}
+
+/// Something observable the Cpu did. Events for an instruction are
+/// delivered in the order they happened, once the instruction has finished.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CpuEvent {
+    /// An instruction finished executing
+    InstructionRetired { pc: u16, opcode: OpCode, cycles: u8 },
+    /// An instruction read a byte of memory, including its operand bytes
+    MemoryRead { addr: u16, value: u8 },
+    /// An instruction wrote a byte of memory
+    MemoryWrite { addr: u16, value: u8 },
+    /// The Cpu jumped to an interrupt handler, pushing `return_addr`
+    InterruptTaken {
+        kind: InterruptKind,
+        return_addr: u16,
+        handler: u16,
+    },
+    StackPush { addr: u16, value: u8 },
+    StackPop { addr: u16, value: u8 },
+}
+
+/// A listener registered with `Cpu::add_event_listener`
+pub type CpuEventListener = Box<dyn FnMut(&CpuEvent)>;
@@ -0,0 +1,21 @@
+use assembler::AssemblerError;
+use cpu::cpu_error::CpuError;
+
+/// Why `Cpu::execute` could not run a program to completion
+#[derive(Debug)]
+pub enum ExecuteError {
+    Assembler(AssemblerError),
+    Cpu(CpuError),
+}
+
+impl From<AssemblerError> for ExecuteError {
+    fn from(error: AssemblerError) -> ExecuteError {
+        ExecuteError::Assembler(error)
+    }
+}
+
+impl From<CpuError> for ExecuteError {
+    fn from(error: CpuError) -> ExecuteError {
+        ExecuteError::Cpu(error)
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use cpu::symbols::Symbols;
+
+/// A convention a guest program uses to tell the host it has finished.
+/// When it is followed the Cpu halts and records an exit code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExitCondition {
+    /// The Program Counter reaches the address. The instruction there is
+    /// not executed.
+    Trap(u16),
+    /// A `BRK` whose signature byte is the given marker. The `BRK` is not
+    /// executed.
+    Brk(u8),
+    /// An instruction writes to the address. The value written is the
+    /// exit code.
+    PortWrite(u16),
+}
+
+/// Where the exit code is read from when a program exits through a trap
+/// address or a `BRK` marker
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Default)]
+pub enum ExitCodeSource {
+    #[default]
+    Accumulator,
+    Memory(u16),
+}
+
+
+/// Why the Cpu halted, or why `Cpu::run_until_brk` stopped. A halted Cpu
+/// executes nothing until it is reset, or resumed from a breakpoint. `Brk`
+/// and `Idle` don't halt the Cpu.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StopReason {
+    /// The program exited with the given exit code
+    Exited(u8),
+    /// An unknown opcode was reached under `UnknownOpcodePolicy::Halt`
+    UnknownOpcode { pc: u16, opcode: u8 },
+    /// A `KIL` opcode locked up the processor, as it does on an NMOS 6502
+    Jammed { pc: u16, opcode: u8 },
+    /// The Program Counter reached a breakpoint. The instruction there has
+    /// not been executed.
+    Breakpoint(u16),
+    /// `run_until_brk` reached a `BRK` at the address. It has not been
+    /// executed.
+    Brk(u16),
+    /// `run_until_brk` found the Cpu idle at the address, waiting for an
+    /// interrupt
+    Idle(u16),
+}
+
+impl StopReason {
+    /// Describes the reason, with the address it happened at named from
+    /// the symbols, such as `Breakpoint at MAIN_LOOP+3 ($C013)`
+    pub fn describe(&self, symbols: Option<&Symbols>) -> String {
+        let at = |addr: u16| match symbols.and_then(|symbols| symbols.locate(addr)) {
+            Some(location) => format!("{} (${:04X})", location, addr),
+            None => format!("${:04X}", addr),
+        };
+
+        match *self {
+            StopReason::Exited(code) => format!("Exited with code {}", code),
+            StopReason::UnknownOpcode { pc, opcode } => format!("Unknown opcode ${:02X} at {}", opcode, at(pc)),
+            StopReason::Jammed { pc, opcode } => format!("Jammed by opcode ${:02X} at {}", opcode, at(pc)),
+            StopReason::Breakpoint(addr) => format!("Breakpoint at {}", at(addr)),
+            StopReason::Brk(addr) => format!("BRK at {}", at(addr)),
+            StopReason::Idle(addr) => format!("Idle at {}", at(addr)),
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.describe(None))
+    }
+}
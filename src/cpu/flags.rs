@@ -1,4 +1,10 @@
+use std::fmt;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StatusFlags {
     pub carry: bool,
     pub zero: bool,
@@ -11,16 +17,38 @@ pub struct StatusFlags {
 }
 
 impl StatusFlags {
+    /// Returns the status register as a byte. Bit 5 is not wired to
+    /// anything on a real 6502 and always reads back as 1.
     pub fn to_u8(&self) -> u8 {
         let carry = if self.carry { 0x01 } else { 0 };
         let zero = if self.zero { 0x02 } else { 0 };
         let interrupt_disabled = if self.interrupt_disabled { 0x04 } else { 0 };
         let decimal = if self.decimal { 0x08 } else { 0 };
         let breakpoint = if self.breakpoint { 0x10 } else { 0 };
+        let unused = 0x20;
         let overflow = if self.overflow { 0x40 } else { 0 };
         let sign = if self.sign { 0x80 } else { 0 };
 
-        carry | zero | interrupt_disabled | decimal | breakpoint | overflow | sign
+        carry | zero | interrupt_disabled | decimal | breakpoint | unused | overflow | sign
+    }
+
+    /// Returns the byte that is pushed to the stack for the status register.
+    ///
+    /// The B flag doesn't physically exist in the status register; it only
+    /// appears in the pushed copy. It is set when the push comes from software
+    /// (PHP and BRK) and clear when it comes from a hardware interrupt (IRQ and
+    /// NMI). Bit 5 is always set.
+    pub fn to_pushed_byte(&self, is_brk: bool) -> u8 {
+        let byte = self.to_u8() & !0x10;
+
+        if is_brk { byte | 0x10 } else { byte }
+    }
+
+    /// Creates the status register from a byte pulled off the stack by PLP or
+    /// RTI. Bits 4 and 5 of the pulled byte are ignored, since neither of them
+    /// exist in the real register.
+    pub fn from_pulled_byte(byte: u8) -> StatusFlags {
+        StatusFlags { breakpoint: false, ..byte.into() }
     }
 }
 
@@ -32,13 +60,40 @@ impl From<u8> for StatusFlags {
             interrupt_disabled: byte & 0x04 == 0x04,
             decimal: byte & 0x08 == 0x08,
             breakpoint: byte & 0x10 == 0x10,
-            unused: false,
+            unused: true,
             overflow: byte & 0x40 == 0x40,
             sign: byte & 0x80 == 0x80,
         }
     }
 }
 
+/// Formats the flags as `NV-BDIZC`, in upper case when set and lower case
+/// when clear, such as `nv-bdIzc` after a reset. Bit 5 is always `-`.
+impl fmt::Display for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [(self.sign, 'n'),
+                     (self.overflow, 'v'),
+                     (false, '-'),
+                     (self.breakpoint, 'b'),
+                     (self.decimal, 'd'),
+                     (self.interrupt_disabled, 'i'),
+                     (self.zero, 'z'),
+                     (self.carry, 'c')];
+
+        for &(set, flag) in &flags {
+            write!(f, "{}", if set { flag.to_ascii_uppercase() } else { flag })?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StatusFlags({})", self)
+    }
+}
+
 impl Default for StatusFlags {
     fn default() -> StatusFlags {
         StatusFlags {
@@ -47,7 +102,7 @@ impl Default for StatusFlags {
             interrupt_disabled: true,
             decimal: false,
             breakpoint: false,
-            unused: false,
+            unused: true,
             overflow: false,
             sign: false,
         }
@@ -59,19 +114,17 @@ mod tests {
     use super::*;
 
     #[test]
-    fn default_is_four() {
+    fn default_is_thirty_six() {
         let f = StatusFlags::default();
 
-        assert_eq!(0x04, f.to_u8());
+        assert_eq!(0x24, f.to_u8());
     }
 
     #[test]
     fn can_convert_to_u8() {
-        let mut f = StatusFlags::default();
+        let f = StatusFlags { carry: true, ..Default::default() };
 
-        f.carry = true;
-
-        assert_eq!(0x05, f.to_u8());
+        assert_eq!(0x25, f.to_u8());
     }
 
     #[test]
@@ -88,14 +141,53 @@ mod tests {
         let byte = f.to_u8();
         let result: StatusFlags = byte.into();
 
-        assert_eq!(true, result.carry);
-        assert_eq!(true, result.decimal);
-        assert_eq!(true, result.sign);
-        assert_eq!(true, result.overflow);
+        assert!(result.carry);
+        assert!(result.decimal);
+        assert!(result.sign);
+        assert!(result.overflow);
+
+        assert!(!result.interrupt_disabled);
+        assert!(!result.zero);
+        assert!(!result.breakpoint);
+        assert!(result.unused);
+    }
+
+    #[test]
+    fn unused_bit_always_reads_back_as_one() {
+        let f: StatusFlags = 0x00.into();
+
+        assert_eq!(0x20, f.to_u8());
+    }
+
+    #[test]
+    fn pushed_byte_sets_b_flag_for_brk() {
+        let f = StatusFlags::default();
+
+        assert_eq!(0x34, f.to_pushed_byte(true));
+    }
+
+    #[test]
+    fn pushed_byte_clears_b_flag_for_interrupts() {
+        let f = StatusFlags { breakpoint: true, ..Default::default() };
 
-        assert_eq!(false, result.interrupt_disabled);
-        assert_eq!(false, result.zero);
-        assert_eq!(false, result.breakpoint);
-        assert_eq!(false, result.unused);
+        assert_eq!(0x24, f.to_pushed_byte(false));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn formats_set_flags_in_upper_case() {
+        let f = StatusFlags { carry: true, sign: true, ..Default::default() };
+
+        assert_eq!("Nv-bdIzC", f.to_string());
+        assert_eq!("StatusFlags(Nv-bdIzC)", format!("{:?}", f));
+    }
+
+    #[test]
+    fn pulled_byte_ignores_b_and_unused_bits() {
+        let f = StatusFlags::from_pulled_byte(0x11);
+
+        assert!(f.carry);
+        assert!(!f.breakpoint);
+        assert!(f.unused);
+        assert_eq!(0x21, f.to_u8());
+    }
+}
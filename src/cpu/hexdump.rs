@@ -0,0 +1,45 @@
+const BYTES_PER_LINE: usize = 16;
+
+/// Formats bytes read from `start` onwards as a classic hex dump: the
+/// address, sixteen bytes in two groups of eight, then the bytes as ASCII
+/// between bars, with anything unprintable shown as `.`
+pub fn format(start: u16, bytes: &[u8]) -> String {
+    let mut result = String::new();
+
+    for (line, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        let addr = start.wrapping_add((line * BYTES_PER_LINE) as u16);
+        result.push_str(&format!("{:04X} ", addr));
+
+        for i in 0..BYTES_PER_LINE {
+            if i % 8 == 0 {
+                result.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => result.push_str(&format!("{:02X} ", byte)),
+                None => result.push_str("   "),
+            }
+        }
+
+        let ascii: String = chunk.iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        result.push_str(&format!(" |{}|\n", ascii));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_the_last_line_to_line_up_the_ascii() {
+        let mut bytes = b"Hello, World!\x00\x01\xFF".to_vec();
+        bytes.extend_from_slice(&[0x41, 0x42]);
+
+        assert_eq!("FFF0  48 65 6C 6C 6F 2C 20 57  6F 72 6C 64 21 00 01 FF  |Hello, World!...|\n\
+                    0000  41 42                                             |AB|\n",
+                   format(0xFFF0, &bytes));
+    }
+}
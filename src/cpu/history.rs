@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+use cpu::exit::StopReason;
+use cpu::flags::StatusFlags;
+use cpu::registers::Registers;
+
+/// The state of the Cpu before a step, and the memory the step wrote, so
+/// that the step can be undone
+pub struct Change {
+    pub registers: Registers,
+    pub flags: StatusFlags,
+    pub cycles: u64,
+    pub idle: bool,
+    pub nmi_pending: bool,
+    pub halted: Option<StopReason>,
+    pub resume_from: Option<u16>,
+    /// The addresses written, each with the value it held before, oldest
+    /// first
+    pub writes: Vec<(u16, u8)>,
+}
+
+/// The changes made by the most recent steps, up to a fixed depth. The
+/// oldest change is dropped to make room for a new one.
+pub struct History {
+    depth: usize,
+    changes: VecDeque<Change>,
+}
+
+impl History {
+    pub fn new(depth: usize) -> History {
+        History {
+            depth,
+            changes: VecDeque::with_capacity(depth),
+        }
+    }
+
+    pub fn push(&mut self, change: Change) {
+        if self.depth == 0 {
+            return;
+        }
+        if self.changes.len() == self.depth {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    /// Removes and returns the most recent change
+    pub fn pop(&mut self) -> Option<Change> {
+        self.changes.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(pc: u16) -> Change {
+        Change {
+            registers: Registers { PC: pc, ..Registers::new() },
+            flags: StatusFlags::default(),
+            cycles: 0,
+            idle: false,
+            nmi_pending: false,
+            halted: None,
+            resume_from: None,
+            writes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn drops_the_oldest_change_when_full() {
+        let mut history = History::new(2);
+
+        history.push(change(0xC000));
+        history.push(change(0xC001));
+        history.push(change(0xC002));
+
+        assert_eq!(2, history.len());
+        assert_eq!(Some(0xC002), history.pop().map(|change| change.registers.PC));
+        assert_eq!(Some(0xC001), history.pop().map(|change| change.registers.PC));
+        assert!(history.pop().is_none());
+    }
+}
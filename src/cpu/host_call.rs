@@ -0,0 +1,6 @@
+use cpu::cpu::Cpu;
+use cpu::memory_bus::MemoryBus;
+
+/// A host function that 6502 code can call through the host call opcode.
+/// It runs with the Program Counter already moved past the instruction.
+pub type HostCall<B = MemoryBus> = Box<dyn FnMut(&mut Cpu<B>)>;
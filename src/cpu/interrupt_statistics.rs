@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use cpu::events::InterruptKind;
+
+/// How often one kind of interrupt was taken, and how long it kept the
+/// Cpu waiting and busy, in cycles
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InterruptSourceStatistics {
+    /// The number of times the handler was entered
+    pub count: u64,
+    /// The longest time from the interrupt being signalled to the first
+    /// instruction of its handler, including the 7 cycles it takes to enter
+    /// the handler. A `BRK` always takes 7.
+    pub max_latency: u64,
+    /// The time from entering the handler to the end of its `RTI`, over
+    /// every time it was taken. Interrupts taken inside the handler count
+    /// towards both.
+    pub handler_cycles: u64,
+}
+
+/// Statistics for each kind of interrupt, for checking that real-time code
+/// such as a music player keeps up
+#[derive(Debug, Default)]
+pub struct InterruptStatistics {
+    sources: HashMap<InterruptKind, InterruptSourceStatistics>,
+    cycles: u64,
+    irq_signalled: Option<u64>,
+    nmi_signalled: Option<u64>,
+    // The handlers that are running, innermost last, and when each was entered
+    handlers: Vec<(InterruptKind, u64)>,
+}
+
+impl InterruptStatistics {
+    pub fn new() -> InterruptStatistics {
+        Default::default()
+    }
+
+    /// Returns the statistics of one kind of interrupt
+    pub fn source(&self, kind: InterruptKind) -> InterruptSourceStatistics {
+        self.sources.get(&kind).cloned().unwrap_or_default()
+    }
+
+    /// Returns the number of cycles run since the statistics were enabled
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Moves the clock on by the cycles of an instruction or interrupt
+    pub fn advance(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    /// Records that an IRQ or NMI was signalled. An interrupt that is
+    /// already waiting to be taken keeps the time it was first signalled.
+    pub fn signalled(&mut self, kind: InterruptKind) {
+        let signalled = match kind {
+            InterruptKind::Irq => &mut self.irq_signalled,
+            InterruptKind::Nmi => &mut self.nmi_signalled,
+            InterruptKind::Brk => return,
+        };
+        signalled.get_or_insert(self.cycles);
+    }
+
+    /// Forgets an IRQ that was released before it was taken
+    pub fn irq_released(&mut self) {
+        self.irq_signalled = None;
+    }
+
+    /// Records that the Cpu started entering a handler, which it finishes
+    /// doing `entry_cycles` later
+    pub fn entered(&mut self, kind: InterruptKind, entry_cycles: u64) {
+        let entered_at = self.cycles + entry_cycles;
+        let signalled = match kind {
+            InterruptKind::Irq => self.irq_signalled.take(),
+            InterruptKind::Nmi => self.nmi_signalled.take(),
+            InterruptKind::Brk => None,
+        };
+
+        let source = self.sources.entry(kind).or_default();
+        source.count += 1;
+        source.max_latency = source.max_latency.max(entered_at - signalled.unwrap_or(self.cycles));
+        self.handlers.push((kind, entered_at));
+    }
+
+    /// Records that the innermost handler returned with an `RTI` that takes
+    /// `rti_cycles`
+    pub fn returned(&mut self, rti_cycles: u64) {
+        if let Some((kind, entered_at)) = self.handlers.pop() {
+            let returned_at = self.cycles + rti_cycles;
+            self.sources.entry(kind).or_default().handler_cycles += returned_at.saturating_sub(entered_at);
+        }
+    }
+
+    /// Forgets the handlers that are running and the interrupts waiting to
+    /// be taken, as a reset does, keeping the statistics
+    pub fn reset(&mut self) {
+        self.irq_signalled = None;
+        self.nmi_signalled = None;
+        self.handlers.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_latency_and_time_in_nested_handlers() {
+        let mut statistics = InterruptStatistics::new();
+
+        statistics.signalled(InterruptKind::Irq);
+        statistics.advance(4);
+        statistics.entered(InterruptKind::Irq, 7);
+        statistics.advance(7);
+
+        statistics.signalled(InterruptKind::Nmi);
+        statistics.entered(InterruptKind::Nmi, 7);
+        statistics.advance(17);
+        statistics.returned(6);
+        statistics.advance(6);
+
+        statistics.advance(10);
+        statistics.returned(6);
+        statistics.advance(6);
+
+        assert_eq!(InterruptSourceStatistics {
+                       count: 1,
+                       max_latency: 11,
+                       handler_cycles: 39,
+                   },
+                   statistics.source(InterruptKind::Irq));
+        assert_eq!(InterruptSourceStatistics {
+                       count: 1,
+                       max_latency: 7,
+                       handler_cycles: 16,
+                   },
+                   statistics.source(InterruptKind::Nmi));
+        assert_eq!(InterruptSourceStatistics::default(), statistics.source(InterruptKind::Brk));
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant};
+
+// Reading the clock is slow compared to executing an instruction
+const INSTRUCTIONS_PER_CLOCK_CHECK: u64 = 256;
+
+/// Which of the `RunLimits` stopped the Cpu
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Limit {
+    Instructions,
+    Cycles,
+    Time,
+}
+
+/// Guards against runaway programs. Each call to `step_n`, `run`,
+/// `run_until_brk`, `run_for_cycles` or `step_until` is checked against
+/// the limits separately.
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, CpuErrorKind, Limit, RunLimits};
+///
+/// let mut cpu = Cpu::new();
+/// cpu.set_limits(RunLimits::new().max_instructions(1000));
+/// // JMP $C000
+/// cpu.load(&[0x4C, 0x00, 0xC0], None).unwrap();
+/// cpu.reset();
+///
+/// let error = cpu.run().unwrap_err();
+///
+/// assert_eq!(&CpuErrorKind::LimitReached(Limit::Instructions), error.kind());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RunLimits {
+    max_instructions: Option<u64>,
+    max_cycles: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+impl RunLimits {
+    pub fn new() -> RunLimits {
+        RunLimits::default()
+    }
+
+    pub fn max_instructions(mut self, instructions: u64) -> RunLimits {
+        self.max_instructions = Some(instructions);
+        self
+    }
+
+    /// The instruction that reaches the limit is finished, so a run can
+    /// go over it by a few cycles
+    pub fn max_cycles(mut self, cycles: u64) -> RunLimits {
+        self.max_cycles = Some(cycles);
+        self
+    }
+
+    /// A wall-clock deadline, measured from the start of the run
+    pub fn timeout(mut self, timeout: Duration) -> RunLimits {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn start(&self) -> LimitGuard {
+        LimitGuard {
+            limits: *self,
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+}
+
+/// Checks the progress of a single run against its limits
+pub struct LimitGuard {
+    limits: RunLimits,
+    deadline: Option<Instant>,
+}
+
+impl LimitGuard {
+    /// Returns the limit that has been reached before the next instruction
+    /// runs, if any
+    pub fn reached(&self, instructions: u64, cycles: u64) -> Option<Limit> {
+        if self.limits.max_instructions.is_some_and(|max| instructions >= max) {
+            return Some(Limit::Instructions);
+        }
+
+        if self.limits.max_cycles.is_some_and(|max| cycles >= max) {
+            return Some(Limit::Cycles);
+        }
+
+        if let Some(deadline) = self.deadline {
+            if instructions.is_multiple_of(INSTRUCTIONS_PER_CLOCK_CHECK) && Instant::now() >= deadline {
+                return Some(Limit::Time);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_first_limit_reached() {
+        let guard = RunLimits::new().max_instructions(10).max_cycles(20).start();
+
+        assert_eq!(None, guard.reached(9, 19));
+        assert_eq!(Some(Limit::Cycles), guard.reached(9, 20));
+        assert_eq!(Some(Limit::Instructions), guard.reached(10, 0));
+    }
+
+    #[test]
+    fn expired_deadline_is_reached() {
+        let guard = RunLimits::new().timeout(Duration::from_secs(0)).start();
+
+        assert_eq!(Some(Limit::Time), guard.reached(0, 0));
+    }
+}
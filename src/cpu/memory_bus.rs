@@ -1,30 +1,419 @@
-use byteorder::{ByteOrder, LittleEndian};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::{Deref, DerefMut, RangeInclusive};
+use std::path::Path;
 
-use std::ops::{Deref, DerefMut};
+use cpu::bus::Bus;
+use cpu::memory_map::{MemoryRegion, RegionKind};
+use cpu::protection::{MemoryAccess, Protection, ProtectionMap, RomWritePolicy};
+use devices::Device;
+
+/// How many dropped writes `RomWritePolicy::Record` keeps, so that a
+/// program stuck writing to ROM doesn't use up memory
+const MAX_RECORDED_ROM_WRITES: usize = 256;
+
+struct MappedDevice {
+    range: RangeInclusive<u16>,
+    // Reading a device register can change its state
+    device: RefCell<Box<dyn Device>>,
+}
 
 /// Default, 64kb memory bus
 pub struct MemoryBus {
     ram: [u8; 1024 * 64],
+    devices: Vec<MappedDevice>,
+    wait_states: Vec<(RangeInclusive<u16>, u8)>,
+    read_only: ProtectionMap,
+    rom_write_policy: RomWritePolicy,
+    rom_writes: Vec<(u16, u8)>,
+    rom_write_count: u64,
+    bus: Option<Box<dyn Bus>>,
+}
+
+impl Default for MemoryBus {
+    fn default() -> MemoryBus {
+        MemoryBus::new()
+    }
 }
 
 impl MemoryBus {
     pub fn new() -> MemoryBus {
-        MemoryBus { ram: [0; 1024 * 64] }
+        MemoryBus {
+            ram: [0; 1024 * 64],
+            devices: Vec::new(),
+            wait_states: Vec::new(),
+            read_only: ProtectionMap::new(),
+            rom_write_policy: RomWritePolicy::default(),
+            rom_writes: Vec::new(),
+            rom_write_count: 0,
+            bus: None,
+        }
+    }
+
+    /// A memory bus whose RAM starts out as a copy of a full 64kb memory
+    /// image
+    pub fn from_image(image: &[u8; 1024 * 64]) -> MemoryBus {
+        MemoryBus { ram: *image, ..MemoryBus::new() }
+    }
+
+    /// Returns a copy of the 64kb of RAM. Mapped devices and custom buses
+    /// are not part of the image.
+    pub fn to_image(&self) -> [u8; 1024 * 64] {
+        self.ram
+    }
+
+    /// Writes the RAM to a file as a raw 64kb image, such as to capture the
+    /// memory of a machine part way through a run
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::MemoryBus;
+    ///
+    /// let path = std::env::temp_dir().join("rs6502-save-image-example.bin");
+    ///
+    /// let mut memory = MemoryBus::new();
+    /// memory.write_bytes(0x0200, &[0xDE, 0xAD]);
+    /// memory.save_image(&path).unwrap();
+    ///
+    /// let mut restored = MemoryBus::new();
+    /// restored.load_image(&path).unwrap();
+    ///
+    /// assert_eq!(0xAD, restored.read_byte(0x0201));
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_image<P>(&self, path: P) -> io::Result<()>
+        where P: AsRef<Path>
+    {
+        File::create(path)?.write_all(&self.ram)
+    }
+
+    /// Replaces the RAM with a raw 64kb image read from a file. A file of
+    /// any other size is an `InvalidData` error and leaves the RAM as it
+    /// was.
+    pub fn load_image<P>(&mut self, path: P) -> io::Result<()>
+        where P: AsRef<Path>
+    {
+        let mut image = Vec::new();
+        File::open(path)?.read_to_end(&mut image)?;
+        if image.len() != self.ram.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("a memory image is 65536 bytes, not {}", image.len())));
+        }
+
+        self.ram.copy_from_slice(&image);
+        Ok(())
+    }
+
+    /// A memory bus that sends reads and writes to a custom bus instead of
+    /// RAM. Devices can still be mapped over it. Indexing the memory bus
+    /// directly only ever accesses its own RAM.
+    pub fn with_bus<B>(bus: B) -> MemoryBus
+        where B: Bus + 'static
+    {
+        MemoryBus { bus: Some(Box::new(bus)), ..MemoryBus::new() }
+    }
+
+    /// Maps a device over a range of addresses. Reads and writes through
+    /// `read_byte` and `write_byte` in that range go to the device instead
+    /// of RAM. Later mappings take precedence where ranges overlap.
+    pub fn map_device<D>(&mut self, range: RangeInclusive<u16>, device: D)
+        where D: Device + 'static
+    {
+        self.devices.insert(0,
+                            MappedDevice {
+                                range,
+                                device: RefCell::new(Box::new(device)),
+                            });
+    }
+
+    /// Makes every access to a range of addresses take extra cycles, such
+    /// as for ROM slower than RAM. Mapped devices report their own wait
+    /// states instead. Later ranges take precedence where ranges overlap.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, MemoryBus};
+    ///
+    /// let mut memory = MemoryBus::new();
+    /// memory.set_wait_states(0xC000..=0xFFFF, 1);
+    ///
+    /// let mut cpu = Cpu::with_bus(memory);
+    /// // LDA #$01 fetches 2 bytes from the slow ROM
+    /// cpu.load(&[0xA9, 0x01], None).unwrap();
+    /// cpu.reset();
+    ///
+    /// assert_eq!(4, cpu.step().unwrap());
+    /// ```
+    pub fn set_wait_states(&mut self, range: RangeInclusive<u16>, cycles: u8) {
+        self.wait_states.insert(0, (range, cycles));
+    }
+
+    /// Returns the extra cycles an access to an address takes, from the
+    /// device mapped there, the ranges given to `set_wait_states` or the
+    /// custom bus, in that order
+    pub fn wait_states(&self, addr: u16, access: MemoryAccess) -> u8 {
+        if let Some(mapped) = self.device_at(addr) {
+            return mapped.device.borrow().wait_states(addr - mapped.range.start(), access);
+        }
+
+        if let Some(&(_, cycles)) = self.wait_states.iter().find(|&(range, _)| range.contains(&addr)) {
+            return cycles;
+        }
+
+        match self.bus {
+            Some(ref bus) => bus.wait_states(addr, access),
+            None => 0,
+        }
+    }
+
+    /// Marks a range of addresses as ROM. Writes through `write_byte` to
+    /// the range are dropped, whatever is mapped there, while `poke` and
+    /// `Cpu::load` still write to it. What else happens to a dropped write
+    /// is up to the `RomWritePolicy`.
+    ///
+    /// A write that should stop the Cpu with an error is better caught by
+    /// `Cpu::protect`. Where both cover an address, the Cpu checks its own
+    /// protection first: a write it blocks fails the step and never reaches
+    /// the bus, so it isn't counted as a ROM write.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, RomWritePolicy};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // LDA #$FF, STA $C000
+    /// cpu.load(&[0xA9, 0xFF, 0x8D, 0x00, 0xC0], None).unwrap();
+    /// cpu.reset();
+    /// cpu.memory.mark_rom(0xC000..=0xFFFF);
+    /// cpu.memory.set_rom_write_policy(RomWritePolicy::Record);
+    ///
+    /// cpu.step_n(2).unwrap();
+    ///
+    /// assert_eq!(0xA9, cpu.memory.read_byte(0xC000));
+    /// assert_eq!(&[(0xC000, 0xFF)], cpu.memory.rom_writes());
+    /// ```
+    pub fn mark_rom(&mut self, range: RangeInclusive<u16>) {
+        self.read_only.protect(range, Protection::ReadOnly);
+    }
+
+    /// Makes every range marked as ROM writable again
+    pub fn clear_rom(&mut self) {
+        self.read_only.clear();
+    }
+
+    pub fn set_rom_write_policy(&mut self, policy: RomWritePolicy) {
+        self.rom_write_policy = policy;
+    }
+
+    /// Returns the address and value of the writes dropped by a ROM range
+    /// while the policy was `RomWritePolicy::Record`, in the order they
+    /// were made. Only the first 256 are kept; `rom_write_count` says
+    /// how many there were in all.
+    pub fn rom_writes(&self) -> &[(u16, u8)] {
+        &self.rom_writes
+    }
+
+    /// Returns how many writes ROM ranges have dropped, whatever the policy
+    pub fn rom_write_count(&self) -> u64 {
+        self.rom_write_count
+    }
+
+    pub fn clear_rom_writes(&mut self) {
+        self.rom_writes.clear();
+        self.rom_write_count = 0;
+    }
+
+    /// Tells every mapped device that a frame has ended
+    pub fn end_frame(&mut self) {
+        for mapped in &self.devices {
+            mapped.device.borrow_mut().end_frame();
+        }
+    }
+
+    /// Lists the ranges marked as ROM and the mapped devices in order of
+    /// precedence, followed by RAM or the regions of the custom bus
+    pub fn memory_map(&self) -> Vec<MemoryRegion> {
+        let mut regions: Vec<MemoryRegion> = self.read_only
+            .regions()
+            .iter()
+            .map(|(range, _)| MemoryRegion::new(range.clone(), RegionKind::Rom))
+            .collect();
+        regions.extend(self.devices.iter().map(|mapped| {
+            let name = mapped.device.borrow().name().to_string();
+            MemoryRegion::new(mapped.range.clone(), RegionKind::Device(name))
+        }));
+
+        match self.bus {
+            Some(ref bus) => regions.extend(bus.memory_map()),
+            None => regions.push(MemoryRegion::new(0x0000..=0xFFFF, RegionKind::Ram)),
+        }
+
+        regions
+    }
+
+    fn device_at(&self, addr: u16) -> Option<&MappedDevice> {
+        self.devices.iter().find(|mapped| mapped.range.contains(&addr))
     }
 
     pub fn write_byte(&mut self, addr: u16, byte: u8) {
+        if !self.read_only.allows(addr, MemoryAccess::Write) {
+            self.rom_write_count += 1;
+            if self.rom_write_policy == RomWritePolicy::Record && self.rom_writes.len() < MAX_RECORDED_ROM_WRITES {
+                self.rom_writes.push((addr, byte));
+            }
+            return;
+        }
+
+        self.poke(addr, byte);
+    }
+
+    /// Writes a byte whether or not its address is marked as ROM, as a loader
+    /// putting code into ROM would
+    pub fn poke(&mut self, addr: u16, byte: u8) {
+        if let Some(mapped) = self.devices.iter().find(|mapped| mapped.range.contains(&addr)) {
+            let mut device = mapped.device.borrow_mut();
+            device.write(addr - mapped.range.start(), byte);
+            device.transfer(&mut self.ram);
+            return;
+        }
+
+        if let Some(ref mut bus) = self.bus {
+            return bus.write_byte(addr, byte);
+        }
+
         let addr = addr as usize;
         self.ram[addr] = byte;
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
+        if let Some(mapped) = self.device_at(addr) {
+            return mapped.device.borrow_mut().read(addr - mapped.range.start());
+        }
+
+        if let Some(ref bus) = self.bus {
+            return bus.read_byte(addr);
+        }
+
         let addr = addr as usize;
         self.ram[addr]
     }
 
+    /// Reads a byte of RAM, or of the custom bus, without side effects.
+    /// Returns None for an address a device is mapped over, since reading
+    /// a device can change its state.
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        if self.device_at(addr).is_some() {
+            return None;
+        }
+
+        match self.bus {
+            Some(ref bus) => bus.peek(addr),
+            None => Some(self.ram[addr as usize]),
+        }
+    }
+
+    /// Reads a little-endian word. A word at `$FFFF` takes its high byte
+    /// from `$0000`.
     pub fn read_u16(&self, addr: u16) -> u16 {
-        let addr = addr as usize;
-        LittleEndian::read_u16(&self.ram[addr..])
+        let low = self.read_byte(addr) as u16;
+        let high = self.read_byte(addr.wrapping_add(1)) as u16;
+
+        low | (high << 8)
+    }
+
+    /// Writes a little-endian word, low byte first. A word at `$FFFF`
+    /// puts its high byte at `$0000`.
+    pub fn write_u16(&mut self, addr: u16, value: u16) {
+        self.write_byte(addr, value as u8);
+        self.write_byte(addr.wrapping_add(1), (value >> 8) as u8);
+    }
+
+    /// Writes a block of bytes into memory starting at addr, wrapping
+    /// around to 0x0000 if the block runs past the top of memory
+    pub fn write_bytes(&mut self, addr: u16, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.write_byte(addr.wrapping_add(i as u16), *byte);
+        }
+    }
+
+    /// Writes bytes with `poke`, wrapping at the top of memory like
+    /// `write_bytes`
+    pub fn poke_bytes(&mut self, addr: u16, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.poke(addr.wrapping_add(i as u16), *byte);
+        }
+    }
+
+    /// Fills every address in the range with a single byte
+    pub fn fill(&mut self, range: RangeInclusive<u16>, byte: u8) {
+        for addr in range {
+            self.write_byte(addr, byte);
+        }
+    }
+
+    /// Copies the bytes in the range to dest. The source and destination
+    /// blocks may overlap.
+    pub fn move_block(&mut self, range: RangeInclusive<u16>, dest: u16) {
+        let block = self.dump(range);
+        self.write_bytes(dest, &block);
+    }
+
+    /// Reads every byte in the range, such as to compare a region with
+    /// what a test expects
+    pub fn dump(&self, range: RangeInclusive<u16>) -> Vec<u8> {
+        range.map(|addr| self.read_byte(addr)).collect()
+    }
+
+    /// Compares the bytes in the range with the block of the same length
+    /// starting at other, returning the addresses (within the range) whose
+    /// bytes differ
+    pub fn compare_block(&self, range: RangeInclusive<u16>, other: u16) -> Vec<u16> {
+        let start = *range.start();
+
+        range.filter(|&addr| {
+                let other_addr = other.wrapping_add(addr.wrapping_sub(start));
+                self.read_byte(addr) != self.read_byte(other_addr)
+            })
+            .collect()
+    }
+}
+
+impl Bus for MemoryBus {
+    fn read_byte(&self, addr: u16) -> u8 {
+        MemoryBus::read_byte(self, addr)
+    }
+
+    fn write_byte(&mut self, addr: u16, byte: u8) {
+        MemoryBus::write_byte(self, addr, byte)
+    }
+
+    fn poke(&mut self, addr: u16, byte: u8) {
+        MemoryBus::poke(self, addr, byte)
+    }
+
+    fn peek(&self, addr: u16) -> Option<u8> {
+        MemoryBus::peek(self, addr)
+    }
+
+    fn read_u16(&self, addr: u16) -> u16 {
+        MemoryBus::read_u16(self, addr)
+    }
+
+    fn write_u16(&mut self, addr: u16, value: u16) {
+        MemoryBus::write_u16(self, addr, value)
+    }
+
+    fn end_frame(&mut self) {
+        MemoryBus::end_frame(self)
+    }
+
+    fn wait_states(&self, addr: u16, access: MemoryAccess) -> u8 {
+        MemoryBus::wait_states(self, addr, access)
+    }
+
+    fn memory_map(&self) -> Vec<MemoryRegion> {
+        MemoryBus::memory_map(self)
     }
 }
 
@@ -41,4 +430,228 @@ impl DerefMut for MemoryBus {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.ram
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use devices::Device;
+
+    struct Latch(u8);
+
+    impl Device for Latch {
+        fn read(&mut self, offset: u16) -> u8 {
+            self.0 + offset as u8
+        }
+
+        fn write(&mut self, _offset: u16, value: u8) {
+            self.0 = value;
+        }
+    }
+
+    #[test]
+    fn routes_mapped_addresses_to_devices() {
+        let mut memory = MemoryBus::new();
+        memory.map_device(0xD000..=0xD00F, Latch(0x10));
+
+        memory.write_byte(0xD004, 0x40);
+
+        assert_eq!(0x42, memory.read_byte(0xD002));
+        assert_eq!(0x00, memory[0xD004]);
+        assert_eq!(0x00, memory.read_byte(0xD010));
+    }
+
+    #[test]
+    fn peeks_ram_but_not_devices() {
+        let mut memory = MemoryBus::new();
+        memory.map_device(0xD000..=0xD00F, Latch(0x10));
+        memory.write_byte(0xC000, 0x42);
+
+        assert_eq!(Some(0x42), memory.peek(0xC000));
+        assert_eq!(None, memory.peek(0xD002));
+        assert_eq!(Some(0x00), memory.peek(0xD010));
+    }
+
+    struct MirroredBus([u8; 0x800]);
+
+    impl Bus for MirroredBus {
+        fn read_byte(&self, addr: u16) -> u8 {
+            self.0[addr as usize % 0x800]
+        }
+
+        fn write_byte(&mut self, addr: u16, byte: u8) {
+            self.0[addr as usize % 0x800] = byte;
+        }
+
+        fn memory_map(&self) -> Vec<MemoryRegion> {
+            vec![MemoryRegion::new(0x0000..=0x07FF, RegionKind::Ram),
+                 MemoryRegion::new(0x0800..=0xFFFF, RegionKind::Mirror(0x0000))]
+        }
+    }
+
+    #[test]
+    fn lists_devices_before_the_regions_they_cover() {
+        let mut memory = MemoryBus::with_bus(MirroredBus([0; 0x800]));
+        memory.map_device(0xD000..=0xD00F, Latch(0x10));
+
+        assert_eq!(vec![MemoryRegion::new(0xD000..=0xD00F, RegionKind::Device(String::from("Device"))),
+                        MemoryRegion::new(0x0000..=0x07FF, RegionKind::Ram),
+                        MemoryRegion::new(0x0800..=0xFFFF, RegionKind::Mirror(0x0000))],
+                   memory.memory_map());
+    }
+
+    struct SlowLatch;
+
+    impl Device for SlowLatch {
+        fn read(&mut self, _offset: u16) -> u8 {
+            0
+        }
+
+        fn write(&mut self, _offset: u16, _value: u8) {}
+
+        fn wait_states(&self, offset: u16, access: MemoryAccess) -> u8 {
+            if access == MemoryAccess::Write { 2 } else { offset as u8 }
+        }
+    }
+
+    #[test]
+    fn devices_report_wait_states_before_slow_ranges() {
+        let mut memory = MemoryBus::new();
+        memory.set_wait_states(0xC000..=0xFFFF, 1);
+        memory.set_wait_states(0xD000..=0xDFFF, 3);
+        memory.map_device(0xD000..=0xD00F, SlowLatch);
+
+        assert_eq!(4, memory.wait_states(0xD004, MemoryAccess::Read));
+        assert_eq!(2, memory.wait_states(0xD004, MemoryAccess::Write));
+        assert_eq!(3, memory.wait_states(0xD010, MemoryAccess::Read));
+        assert_eq!(1, memory.wait_states(0xC000, MemoryAccess::Execute));
+        assert_eq!(0, memory.wait_states(0x0200, MemoryAccess::Read));
+    }
+
+    #[test]
+    fn load_image_rejects_files_of_the_wrong_size() {
+        let path = ::std::env::temp_dir().join("rs6502-short-memory-image.bin");
+        File::create(&path).unwrap().write_all(&[0xEA; 16]).unwrap();
+
+        let mut memory = MemoryBus::from_image(&[0x42; 1024 * 64]);
+        let error = memory.load_image(&path).unwrap_err();
+        ::std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!(0x42, memory[0x1234]);
+    }
+
+    #[test]
+    fn can_write_bytes() {
+        let mut memory = MemoryBus::new();
+
+        memory.write_bytes(0xC000, &[0xA9, 0x01, 0x60]);
+
+        assert_eq!(&[0xA9, 0x01, 0x60], &memory[0xC000..0xC003]);
+    }
+
+    #[test]
+    fn write_bytes_wraps_at_top_of_memory() {
+        let mut memory = MemoryBus::new();
+
+        memory.write_bytes(0xFFFF, &[0x01, 0x02]);
+
+        assert_eq!(0x01, memory[0xFFFF]);
+        assert_eq!(0x02, memory[0x0000]);
+    }
+
+    #[test]
+    fn words_wrap_at_top_of_memory() {
+        let mut memory = MemoryBus::new();
+
+        memory.write_u16(0xFFFF, 0x1234);
+
+        assert_eq!(0x34, memory[0xFFFF]);
+        assert_eq!(0x12, memory[0x0000]);
+        assert_eq!(0x1234, memory.read_u16(0xFFFF));
+    }
+
+    #[test]
+    fn can_fill_range() {
+        let mut memory = MemoryBus::new();
+
+        memory.fill(0x0200..=0x020F, 0xEA);
+
+        assert_eq!(&[0xEA; 16], &memory[0x0200..0x0210]);
+        assert_eq!(0x00, memory[0x0210]);
+    }
+
+    #[test]
+    fn can_move_overlapping_block() {
+        let mut memory = MemoryBus::new();
+        memory.write_bytes(0x0200, &[1, 2, 3, 4]);
+
+        memory.move_block(0x0200..=0x0203, 0x0202);
+
+        assert_eq!(&[1, 2, 1, 2, 3, 4], &memory[0x0200..0x0206]);
+    }
+
+    #[test]
+    fn can_compare_blocks() {
+        let mut memory = MemoryBus::new();
+        memory.write_bytes(0x0200, &[1, 2, 3, 4]);
+        memory.write_bytes(0x0300, &[1, 9, 3, 8]);
+
+        assert_eq!(vec![0x0201, 0x0203],
+                   memory.compare_block(0x0200..=0x0203, 0x0300));
+    }
+
+    #[test]
+    fn rom_ranges_drop_writes_and_are_mapped_as_rom() {
+        let mut memory = MemoryBus::new();
+        memory.write_byte(0xF000, 0x42);
+        memory.mark_rom(0xF000..=0xFFFF);
+
+        memory.write_byte(0xF000, 0x00);
+        memory.write_byte(0xEFFF, 0x01);
+
+        assert_eq!(0x42, memory.read_byte(0xF000));
+        assert_eq!(0x01, memory.read_byte(0xEFFF));
+        assert!(memory.rom_writes().is_empty());
+        assert_eq!(1, memory.rom_write_count());
+        assert_eq!(vec![MemoryRegion::new(0xF000..=0xFFFF, RegionKind::Rom),
+                        MemoryRegion::new(0x0000..=0xFFFF, RegionKind::Ram)],
+                   memory.memory_map());
+    }
+
+    #[test]
+    fn poke_writes_past_rom() {
+        let mut memory = MemoryBus::new();
+        memory.mark_rom(0xF000..=0xFFFF);
+
+        memory.poke_bytes(0xFFFC, &[0x00, 0xF0]);
+
+        assert_eq!(0xF000, memory.read_u16(0xFFFC));
+        assert_eq!(0, memory.rom_write_count());
+    }
+
+    #[test]
+    fn recorded_rom_writes_are_capped_but_all_counted() {
+        let mut memory = MemoryBus::new();
+        memory.mark_rom(0xF000..=0xFFFF);
+        memory.set_rom_write_policy(RomWritePolicy::Record);
+
+        for _ in 0..1000 {
+            memory.write_byte(0xF000, 0x01);
+        }
+
+        assert_eq!(MAX_RECORDED_ROM_WRITES, memory.rom_writes().len());
+        assert_eq!(1000, memory.rom_write_count());
+
+        memory.clear_rom_writes();
+        assert_eq!(0, memory.rom_write_count());
+    }
+
+    #[test]
+    fn dumps_the_bytes_in_a_range() {
+        let mut memory = MemoryBus::new();
+        memory.write_bytes(0x0200, &[1, 2, 3]);
+
+        assert_eq!(vec![1, 2, 3, 0], memory.dump(0x0200..=0x0203));
+    }
+}
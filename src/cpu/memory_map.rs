@@ -0,0 +1,101 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use cpu::protection::Protection;
+
+/// What a region of the address space is backed by
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegionKind {
+    Ram,
+    Rom,
+    /// A device, by name
+    Device(String),
+    /// Another view of the memory starting at the given address
+    Mirror(u16),
+    /// Memory behind a custom bus that doesn't describe its layout
+    Bus,
+}
+
+/// A range of addresses and what is mapped there
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemoryRegion {
+    pub range: RangeInclusive<u16>,
+    pub kind: RegionKind,
+}
+
+impl MemoryRegion {
+    pub fn new(range: RangeInclusive<u16>, kind: RegionKind) -> MemoryRegion {
+        MemoryRegion {
+            range,
+            kind,
+        }
+    }
+}
+
+/// A report of how the address space is configured. Regions are listed in
+/// order of precedence, so where two overlap the first one wins. The
+/// memory protection and code regions set on the Cpu are listed
+/// separately, as they apply on top of whatever is mapped.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MemoryMap {
+    pub regions: Vec<MemoryRegion>,
+    pub protection: Vec<(RangeInclusive<u16>, Protection)>,
+    pub code: Vec<RangeInclusive<u16>>,
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for region in &self.regions {
+            write!(f, "{}  ", format_range(&region.range))?;
+            match region.kind {
+                RegionKind::Ram => writeln!(f, "RAM")?,
+                RegionKind::Rom => writeln!(f, "ROM")?,
+                RegionKind::Device(ref name) => writeln!(f, "Device: {}", name)?,
+                RegionKind::Mirror(addr) => writeln!(f, "Mirror of ${:04X}", addr)?,
+                RegionKind::Bus => writeln!(f, "Custom bus")?,
+            }
+        }
+
+        for &(ref range, protection) in &self.protection {
+            let description = match protection {
+                Protection::ReadOnly => "read only",
+                Protection::WriteOnly => "write only",
+                Protection::NoExecute => "no execute",
+            };
+            writeln!(f, "{}  Protected: {}", format_range(range), description)?;
+        }
+
+        for range in &self.code {
+            writeln!(f, "{}  Code", format_range(range))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn format_range(range: &RangeInclusive<u16>) -> String {
+    format!("${:04X}-${:04X}", range.start(), range.end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prints_one_line_per_region() {
+        let map = MemoryMap {
+            regions: vec![MemoryRegion::new(0x0800..=0x1FFF, RegionKind::Mirror(0x0000)),
+                          MemoryRegion::new(0x0000..=0x07FF, RegionKind::Ram),
+                          MemoryRegion::new(0x8000..=0xFFFF, RegionKind::Rom)],
+            protection: vec![(0xC000..=0xFFFF, Protection::ReadOnly)],
+            code: vec![0xC000..=0xC0FF],
+        };
+
+        assert_eq!("$0800-$1FFF  Mirror of $0000\n\
+                    $0000-$07FF  RAM\n\
+                    $8000-$FFFF  ROM\n\
+                    $C000-$FFFF  Protected: read only\n\
+                    $C000-$C0FF  Code\n",
+                   map.to_string());
+    }
+}
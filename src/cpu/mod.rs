@@ -1,13 +1,67 @@
 
+mod bus;
+mod call_stack;
+#[allow(clippy::module_inception)]
 mod cpu;
+mod cost;
+mod coverage;
 mod cpu_error;
+mod cycles;
+mod dispatch;
+mod event_queue;
+mod events;
+mod execute;
+mod exit;
 mod flags;
+mod hexdump;
+mod history;
+mod host_call;
+mod interrupt_statistics;
+mod limits;
 mod memory_bus;
+mod memory_map;
+mod profiler;
+mod protection;
 mod registers;
+mod self_modifying_code;
+mod snapshot;
 mod stack;
+mod stack_guard;
+mod statistics;
+mod step_hook;
+mod steps;
+mod symbols;
+mod trace;
+mod unknown_opcode;
+mod verify;
 
-pub use self::cpu::{Cpu, CpuStepResult};
-pub use self::cpu_error::CpuError;
+pub use self::bus::Bus;
+pub use self::call_stack::CallStack;
+pub use self::cpu::{Cpu, CpuStepResult, Operand};
+pub use self::cost::{CostModel, Costs};
+pub use self::coverage::Coverage;
+pub use self::cpu_error::{CpuError, CpuErrorKind};
+pub use self::cycles::{BusCycle, Cycle, CycleHook};
+pub use self::event_queue::{EventQueue, ExternalEvent};
+pub use self::events::{CpuEvent, CpuEventListener, InterruptKind};
+pub use self::execute::ExecuteError;
+pub use self::exit::{ExitCodeSource, ExitCondition, StopReason};
 pub use self::flags::StatusFlags;
+pub use self::host_call::HostCall;
+pub use self::interrupt_statistics::{InterruptSourceStatistics, InterruptStatistics};
+pub use self::limits::{Limit, LimitGuard, RunLimits};
 pub use self::memory_bus::MemoryBus;
-pub use self::registers::Registers;
\ No newline at end of file
+pub use self::memory_map::{MemoryMap, MemoryRegion, RegionKind};
+pub use self::profiler::{AddressProfile, OpcodeProfile, ProfileReport, Profiler};
+pub use self::protection::{MemoryAccess, Protection, RomWritePolicy};
+pub use self::registers::Registers;
+pub use self::self_modifying_code::{SelfModifyingCodeHandler, SelfModifyingCodePolicy};
+pub use self::snapshot::CpuSnapshot;
+pub use self::stack_guard::{StackFrame, StackFrameKind, StackGuard, StackViolation, StackViolationKind};
+pub use self::statistics::{InstructionCount, InstructionStatistics};
+pub use self::step_hook::{DecodedInstruction, StepHook};
+pub use self::steps::{IterSteps, StepInfo, Steps};
+pub use self::symbols::Symbols;
+pub use self::trace::{InstructionClass, TraceFilter, TraceLine, TraceSink};
+pub use self::unknown_opcode::{UnknownOpcodeHandler, UnknownOpcodePolicy};
+pub use self::verify::{parse_number, VerifyError};
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use ::opcodes::OpCode;
+
+/// How many times one opcode was executed, and the cycles it took over
+/// all of them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OpcodeProfile {
+    pub opcode: OpCode,
+    pub count: u64,
+    pub cycles: u64,
+}
+
+/// How many times the instruction at one address was executed, and the
+/// cycles it took over all of them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AddressProfile {
+    pub addr: u16,
+    pub count: u64,
+    pub cycles: u64,
+}
+
+/// What a `Profiler` measured, hottest first
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProfileReport {
+    /// Every opcode executed, by the cycles it took
+    pub opcodes: Vec<OpcodeProfile>,
+    /// Every address executed, by the cycles it took. Empty unless the
+    /// profiler was made with `Profiler::by_address`.
+    pub addresses: Vec<AddressProfile>,
+    /// The cycles taken by every instruction profiled
+    pub cycles: u64,
+}
+
+/// Counts the executions and cycles of each opcode, and optionally of each
+/// address, to find the hot loops in code being optimized
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, Profiler};
+///
+/// let mut cpu = Cpu::new();
+/// // LDX #$03, DEX, BNE -3
+/// cpu.load(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD], None).unwrap();
+/// cpu.reset();
+/// cpu.set_profiler(Profiler::by_address());
+///
+/// cpu.step_n(7).unwrap();
+///
+/// let report = cpu.profiler().unwrap().report();
+/// assert_eq!("BNE", report.opcodes[0].opcode.mnemonic);
+/// assert_eq!(8, report.opcodes[0].cycles);
+/// assert_eq!(0xC003, report.addresses[0].addr);
+/// assert_eq!(16, report.cycles);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    opcodes: HashMap<u8, OpcodeProfile>,
+    addresses: Option<HashMap<u16, AddressProfile>>,
+}
+
+impl Profiler {
+    /// A profiler that counts by opcode
+    pub fn new() -> Profiler {
+        Default::default()
+    }
+
+    /// A profiler that counts by opcode and by address
+    pub fn by_address() -> Profiler {
+        Profiler {
+            opcodes: HashMap::new(),
+            addresses: Some(HashMap::new()),
+        }
+    }
+
+    /// Records one execution of the opcode at an address
+    pub fn record(&mut self, addr: u16, opcode: &OpCode, cycles: u8) {
+        let profile = self.opcodes.entry(opcode.code).or_insert(OpcodeProfile {
+            opcode: *opcode,
+            count: 0,
+            cycles: 0,
+        });
+        profile.count += 1;
+        profile.cycles += cycles as u64;
+
+        if let Some(ref mut addresses) = self.addresses {
+            let profile = addresses.entry(addr).or_insert(AddressProfile {
+                addr,
+                count: 0,
+                cycles: 0,
+            });
+            profile.count += 1;
+            profile.cycles += cycles as u64;
+        }
+    }
+
+    /// Returns what was measured, with the opcodes and addresses that took
+    /// the most cycles first
+    pub fn report(&self) -> ProfileReport {
+        let mut opcodes: Vec<OpcodeProfile> = self.opcodes.values().cloned().collect();
+        opcodes.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.opcode.code.cmp(&b.opcode.code)));
+
+        let mut addresses: Vec<AddressProfile> = self.addresses
+            .as_ref()
+            .map_or_else(Vec::new, |addresses| addresses.values().cloned().collect());
+        addresses.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.addr.cmp(&b.addr)));
+
+        ProfileReport {
+            cycles: opcodes.iter().map(|profile| profile.cycles).sum(),
+            opcodes,
+            addresses,
+        }
+    }
+
+    /// Clears everything measured, such as at the start of a new run
+    pub fn clear(&mut self) {
+        self.opcodes.clear();
+        if let Some(ref mut addresses) = self.addresses {
+            addresses.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_most_expensive_first() {
+        let lda = OpCode::from_raw_byte(0xA9).unwrap();
+        let sta = OpCode::from_raw_byte(0x8D).unwrap();
+
+        let mut profiler = Profiler::new();
+        profiler.record(0xC000, lda, 2);
+        profiler.record(0xC002, lda, 2);
+        profiler.record(0xC004, sta, 4);
+        profiler.record(0xC004, sta, 4);
+
+        let report = profiler.report();
+
+        assert_eq!(vec![(0x8D, 2, 8), (0xA9, 2, 4)],
+                   report.opcodes.iter().map(|p| (p.opcode.code, p.count, p.cycles)).collect::<Vec<_>>());
+        assert!(report.addresses.is_empty());
+        assert_eq!(12, report.cycles);
+    }
+}
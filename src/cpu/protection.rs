@@ -0,0 +1,100 @@
+use std::ops::RangeInclusive;
+
+/// The ways the Cpu accesses memory
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MemoryAccess {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Restricts how the Cpu may access a region of memory
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Protection {
+    /// Can be read and executed but not written
+    ReadOnly,
+    /// Can be written but not read or executed
+    WriteOnly,
+    /// Can be read and written but not executed
+    NoExecute,
+}
+
+/// Determines what a `MemoryBus` does with a write to a range marked with
+/// `mark_rom`. The write is always dropped, as it is by real ROM.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Default)]
+pub enum RomWritePolicy {
+    /// Drop the write without a trace, as the hardware does
+    #[default]
+    Ignore,
+    /// Drop the write and record it for `MemoryBus::rom_writes`
+    Record,
+}
+
+
+impl Protection {
+    fn allows(&self, access: MemoryAccess) -> bool {
+        match *self {
+            Protection::ReadOnly => access != MemoryAccess::Write,
+            Protection::WriteOnly => access == MemoryAccess::Write,
+            Protection::NoExecute => access != MemoryAccess::Execute,
+        }
+    }
+}
+
+/// The protected regions of memory. Where regions overlap, an access
+/// must be allowed by every one of them.
+pub struct ProtectionMap {
+    regions: Vec<(RangeInclusive<u16>, Protection)>,
+}
+
+impl ProtectionMap {
+    pub fn new() -> ProtectionMap {
+        ProtectionMap { regions: Vec::new() }
+    }
+
+    pub fn protect(&mut self, range: RangeInclusive<u16>, protection: Protection) {
+        self.regions.push((range, protection));
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    pub fn regions(&self) -> &[(RangeInclusive<u16>, Protection)] {
+        &self.regions
+    }
+
+    pub fn allows(&self, addr: u16, access: MemoryAccess) -> bool {
+        self.regions
+            .iter()
+            .all(|&(ref range, protection)| !range.contains(&addr) || protection.allows(access))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unprotected_memory_allows_everything() {
+        let map = ProtectionMap::new();
+
+        assert!(map.allows(0x1234, MemoryAccess::Read));
+        assert!(map.allows(0x1234, MemoryAccess::Write));
+        assert!(map.allows(0x1234, MemoryAccess::Execute));
+    }
+
+    #[test]
+    fn overlapping_regions_must_all_allow_access() {
+        let mut map = ProtectionMap::new();
+        map.protect(0xC000..=0xFFFF, Protection::ReadOnly);
+        map.protect(0xF000..=0xFFFF, Protection::NoExecute);
+
+        assert!(map.allows(0xC000, MemoryAccess::Execute));
+        assert!(!map.allows(0xC000, MemoryAccess::Write));
+        assert!(!map.allows(0xF000, MemoryAccess::Execute));
+        assert!(map.allows(0xF000, MemoryAccess::Read));
+        assert!(map.allows(0xBFFF, MemoryAccess::Write));
+    }
+}
@@ -1,9 +1,17 @@
+use std::fmt;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[allow(non_snake_case)]
 pub struct Registers {
     pub A: u8,
     pub X: u8,
     pub Y: u8,
+    /// The stack pointer, an offset into page `$01`
+    pub S: u8,
     pub PC: u16,
 }
 
@@ -19,7 +27,47 @@ impl Default for Registers {
             A: 0,
             X: 0,
             Y: 0,
+            S: 0xFF,
             PC: 0,
         }
     }
 }
+
+/// Formats the registers on one line in hex, as `A:00 X:00 Y:00 SP:FF PC:C000`
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} PC:{:04X}",
+               self.A,
+               self.X,
+               self.Y,
+               self.S,
+               self.PC)
+    }
+}
+
+/// Formats the registers in hex, which is how they're read everywhere else
+impl fmt::Debug for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Registers")
+            .field("A", &format_args!("${:02X}", self.A))
+            .field("X", &format_args!("${:02X}", self.X))
+            .field("Y", &format_args!("${:02X}", self.Y))
+            .field("S", &format_args!("${:02X}", self.S))
+            .field("PC", &format_args!("${:04X}", self.PC))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_registers_in_hex() {
+        let registers = Registers { A: 0x0A, PC: 0xC000, ..Default::default() };
+
+        assert_eq!("A:0A X:00 Y:00 SP:FF PC:C000", registers.to_string());
+        assert_eq!("Registers { A: $0A, X: $00, Y: $00, S: $FF, PC: $C000 }", format!("{:?}", registers));
+    }
+}
@@ -0,0 +1,17 @@
+/// A handler told about every write into memory marked as code. It
+/// receives the address of the writing instruction and the address written.
+pub type SelfModifyingCodeHandler = Box<dyn FnMut(u16, u16)>;
+
+/// Determines what the Cpu does when an instruction writes into memory
+/// marked as code. The write is always carried out.
+#[derive(Default)]
+pub enum SelfModifyingCodePolicy {
+    /// Carry on as normal
+    #[default]
+    Ignore,
+    /// Return `CpuError::self_modifying_code` from `step`
+    Error,
+    /// Report the write to a user supplied handler and carry on
+    Callback(SelfModifyingCodeHandler),
+}
+
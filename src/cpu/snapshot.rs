@@ -0,0 +1,28 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use cpu::exit::StopReason;
+use cpu::flags::StatusFlags;
+use cpu::registers::Registers;
+
+/// The state of a Cpu and its RAM at one moment, taken by `Cpu::snapshot`
+/// and put back by `Cpu::restore`, such as for an emulator's save states.
+///
+/// With the `serde` feature enabled, snapshots can be serialized in any
+/// format serde supports. Settings such as the variant, breakpoints and
+/// hooks are not part of a snapshot.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuSnapshot {
+    pub registers: Registers,
+    pub flags: StatusFlags,
+    /// The 64kb of RAM
+    pub memory: Vec<u8>,
+    pub irq_line: bool,
+    pub nmi_line: bool,
+    pub nmi_pending: bool,
+    pub idle: bool,
+    pub halted: Option<StopReason>,
+    /// The number of cycles the Cpu had run
+    pub cycles: u64,
+}
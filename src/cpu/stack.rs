@@ -1,117 +1,88 @@
-use byteorder::{ByteOrder, LittleEndian};
+use cpu::bus::Bus;
 
-#[derive(Debug, PartialEq)]
-pub struct StackError {
-    message: String,
-}
-
-impl StackError {
-    pub fn overflow() -> StackError {
-        StackError { message: "Stack overflow occurred".into() }
-    }
+const STACK_PAGE: u16 = 0x100;
 
-    pub fn underflow() -> StackError {
-        StackError { message: "Stack underflow; unable to pop from empty stack".into() }
-    }
+/// The address in the stack page that a stack pointer points at
+pub fn addr(pointer: u8) -> u16 {
+    STACK_PAGE + pointer as u16
 }
 
-pub type StackPushResult = Result<(), StackError>;
-pub type StackPopResult<T> = Result<T, StackError>;
-
-pub struct Stack {
-    pub pointer: usize,
+/// Writes a byte at the stack pointer and moves it down. Like the 6502,
+/// the pointer wraps around within page `$01`.
+pub fn push<B>(bus: &mut B, pointer: &mut u8, val: u8)
+    where B: Bus + ?Sized
+{
+    bus.write_byte(addr(*pointer), val);
+    *pointer = pointer.wrapping_sub(0x01);
 }
 
-impl Stack {
-    pub fn new() -> Stack {
-        Stack { pointer: 0xFF }
-    }
-
-    pub fn push(&mut self, stack_area: &mut [u8], val: u8) -> StackPushResult {
-        if self.pointer > 0x00 {
-            stack_area[self.pointer] = val;
-            self.pointer -= 0x01;
-
-            Ok(())
-        } else {
-            Err(StackError::overflow())
-        }
-    }
-
-    pub fn push_u16(&mut self, stack_area: &mut [u8], val: u16) -> StackPushResult {
-        if self.pointer >= 0x01 {
-            LittleEndian::write_u16(&mut stack_area[self.pointer - 0x01..], val);
-            self.pointer -= 0x02;
-
-            Ok(())
-        } else {
-            Err(StackError::overflow())
-        }
-    }
-
-    pub fn pop(&mut self, stack_area: &[u8]) -> StackPopResult<u8> {
-        if self.pointer == 0xFF {
-            Err(StackError::underflow())
-        } else {
-            self.pointer += 0x01;
-            let val = stack_area[self.pointer];
+/// Pushes the high byte of a value followed by its low byte
+pub fn push_u16<B>(bus: &mut B, pointer: &mut u8, val: u16)
+    where B: Bus + ?Sized
+{
+    push(bus, pointer, (val >> 8) as u8);
+    push(bus, pointer, (val & 0xFF) as u8);
+}
 
-            Ok(val)
-        }
-    }
+/// Moves the stack pointer up and reads the byte it points at
+pub fn pop<B>(bus: &B, pointer: &mut u8) -> u8
+    where B: Bus + ?Sized
+{
+    *pointer = pointer.wrapping_add(0x01);
+    bus.read_byte(addr(*pointer))
+}
 
-    pub fn pop_u16(&mut self, stack_area: &mut [u8]) -> StackPopResult<u16> {
-        if self.pointer <= 0xFE {
-            self.pointer += 0x01;
-            let result = LittleEndian::read_u16(&stack_area[self.pointer..]);
-            self.pointer += 0x01;
+/// Pops the low byte of a value followed by its high byte
+pub fn pop_u16<B>(bus: &B, pointer: &mut u8) -> u16
+    where B: Bus + ?Sized
+{
+    let low = pop(bus, pointer) as u16;
+    let high = pop(bus, pointer) as u16;
 
-            Ok(result)
-        } else {
-            Err(StackError::underflow())
-        }
-    }
+    (high << 8) | low
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cpu::memory_bus::MemoryBus;
 
     #[test]
     fn can_push() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0xFF;
 
-        stack.push(&mut stack_area, 55);
+        push(&mut memory, &mut pointer, 55);
 
-        assert_eq!(55, stack_area[0xFF]);
+        assert_eq!(55, memory[0x1FF]);
+        assert_eq!(0xFE, pointer);
     }
 
     #[test]
     fn can_push_then_pop() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0xFF;
 
-        stack.push(&mut stack_area, 55);
-        let val = stack.pop(&mut stack_area).unwrap();
+        push(&mut memory, &mut pointer, 55);
+        let val = pop(&memory, &mut pointer);
 
         assert_eq!(55, val);
     }
 
     #[test]
     fn can_push_then_pop_multiple() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0xFF;
 
-        stack.push(&mut stack_area, 5);
-        stack.push(&mut stack_area, 10);
-        stack.push(&mut stack_area, 15);
-        stack.push(&mut stack_area, 20);
+        push(&mut memory, &mut pointer, 5);
+        push(&mut memory, &mut pointer, 10);
+        push(&mut memory, &mut pointer, 15);
+        push(&mut memory, &mut pointer, 20);
 
-        let twenty = stack.pop(&mut stack_area).unwrap();
-        let fifteen = stack.pop(&mut stack_area).unwrap();
-        let ten = stack.pop(&mut stack_area).unwrap();
-        let five = stack.pop(&mut stack_area).unwrap();
+        let twenty = pop(&memory, &mut pointer);
+        let fifteen = pop(&memory, &mut pointer);
+        let ten = pop(&memory, &mut pointer);
+        let five = pop(&memory, &mut pointer);
 
         assert_eq!(20, twenty);
         assert_eq!(15, fifteen);
@@ -120,48 +91,61 @@ mod tests {
     }
 
     #[test]
-    fn can_not_pop_empty_stack() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
+    fn pop_wraps_to_the_bottom_of_the_stack_page() {
+        let mut memory = MemoryBus::new();
+        memory[0x100] = 0x42;
+        let mut pointer = 0xFF;
 
-        let result = stack.pop(&mut stack_area);
+        let result = pop(&memory, &mut pointer);
 
-        assert_eq!(Err(StackError::underflow()), result);
+        assert_eq!(0x42, result);
+        assert_eq!(0x00, pointer);
     }
 
     #[test]
-    fn can_not_push_to_full_stack() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
-
-        for _ in 0..0xFF {
-            stack.push(&mut stack_area, 5);
-        }
+    fn push_wraps_to_the_top_of_the_stack_page() {
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0x00;
 
-        let result = stack.push(&mut stack_area, 5);
+        push(&mut memory, &mut pointer, 5);
+        push(&mut memory, &mut pointer, 6);
 
-        assert_eq!(Err(StackError::overflow()), result);
+        assert_eq!(5, memory[0x100]);
+        assert_eq!(6, memory[0x1FF]);
+        assert_eq!(0xFE, pointer);
     }
 
     #[test]
     fn can_push_u16() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0xFF;
 
-        stack.push_u16(&mut stack_area, 0x4400);
+        push_u16(&mut memory, &mut pointer, 0x4400);
 
-        assert_eq!(0x44, stack_area[0xFF]);
-        assert_eq!(0x00, stack_area[0xFE]);
+        assert_eq!(0x44, memory[0x1FF]);
+        assert_eq!(0x00, memory[0x1FE]);
     }
 
     #[test]
     fn can_push_then_pop_u16() {
-        let mut stack_area = [0u8; 0x100];
-        let mut stack = Stack::new();
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0xFF;
 
-        stack.push_u16(&mut stack_area, 0x4400);
-        let result = stack.pop_u16(&mut stack_area).unwrap();
+        push_u16(&mut memory, &mut pointer, 0x4400);
+        let result = pop_u16(&memory, &mut pointer);
 
         assert_eq!(0x4400, result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn u16_values_wrap_across_the_stack_page() {
+        let mut memory = MemoryBus::new();
+        let mut pointer = 0x00;
+
+        push_u16(&mut memory, &mut pointer, 0x1234);
+
+        assert_eq!(0x12, memory[0x100]);
+        assert_eq!(0x34, memory[0x1FF]);
+        assert_eq!(0x1234, pop_u16(&memory, &mut pointer));
+    }
+}
@@ -0,0 +1,138 @@
+use cpu::events::InterruptKind;
+
+/// What put a frame on the stack
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StackFrameKind {
+    /// A `JSR`
+    Call,
+    Interrupt(InterruptKind),
+}
+
+/// A subroutine call or interrupt that hasn't returned yet
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct StackFrame {
+    pub kind: StackFrameKind,
+    /// The address of the `JSR`, or the return address of the interrupt
+    pub from: u16,
+    /// The subroutine or interrupt handler that was entered
+    pub to: u16,
+    /// The stack pointer before the return address was pushed
+    pub stack_pointer: u8,
+}
+
+impl StackFrame {
+    /// Returns the address execution continues at when the frame returns
+    pub fn return_address(&self) -> u16 {
+        match self.kind {
+            StackFrameKind::Call => self.from.wrapping_add(3),
+            StackFrameKind::Interrupt(_) => self.from,
+        }
+    }
+}
+
+/// Why a `StackGuard` stopped the Cpu
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StackViolationKind {
+    /// The stack grew deeper than the guard's maximum, to this many bytes
+    DepthExceeded(u8),
+    /// A push wrapped the stack pointer from `$00` around to `$FF`
+    Overflow,
+    /// A pull wrapped the stack pointer from `$FF` around to `$00`
+    Underflow,
+}
+
+/// What a `StackGuard` caught, and the calls and interrupts that led to it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StackViolation {
+    pub kind: StackViolationKind,
+    /// The stack pointer after the instruction that broke the guard
+    pub stack_pointer: u8,
+    /// The calls and interrupts that hadn't returned, outermost first
+    pub backtrace: Vec<StackFrame>,
+}
+
+/// Catches runaway recursion before it corrupts the stack. A guarded Cpu
+/// fails with `CpuErrorKind::StackViolation` when the stack pointer wraps
+/// around the stack page, or when the stack grows past the maximum depth.
+///
+/// Depth is measured in bytes below `$01FF`, so a `JSR` from an empty
+/// stack takes it to a depth of 2.
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, CpuErrorKind, StackGuard, StackViolationKind};
+///
+/// let mut cpu = Cpu::new();
+/// cpu.set_stack_guard(StackGuard::new().max_depth(0x20));
+/// // JSR $C000, forever
+/// cpu.load(&[0x20, 0x00, 0xC0], None).unwrap();
+/// cpu.reset();
+///
+/// let error = cpu.step_n(100).unwrap_err();
+///
+/// match *error.kind() {
+///     CpuErrorKind::StackViolation(ref violation) => {
+///         assert_eq!(StackViolationKind::DepthExceeded(0x22), violation.kind);
+///         assert_eq!(17, violation.backtrace.len());
+///     }
+///     _ => panic!("expected a stack violation"),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StackGuard {
+    max_depth: Option<u8>,
+}
+
+impl StackGuard {
+    /// A guard that only catches the stack pointer wrapping
+    pub fn new() -> StackGuard {
+        StackGuard::default()
+    }
+
+    /// Also fails when the stack grows deeper than this many bytes
+    pub fn max_depth(mut self, bytes: u8) -> StackGuard {
+        self.max_depth = Some(bytes);
+        self
+    }
+
+    /// Returns what an instruction that moved the stack pointer from
+    /// `before` to `after` violated, if anything
+    pub fn check(&self, before: u8, after: u8) -> Option<StackViolationKind> {
+        // An instruction moves the stack pointer by a few bytes at most,
+        // so the direction it moved in survives wrapping around the page
+        let moved = after.wrapping_sub(before) as i8;
+        if moved < 0 && after > before {
+            return Some(StackViolationKind::Overflow);
+        }
+        if moved > 0 && after < before {
+            return Some(StackViolationKind::Underflow);
+        }
+
+        match self.max_depth {
+            Some(max) if depth(after) > max && depth(before) <= max => {
+                Some(StackViolationKind::DepthExceeded(depth(after)))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn depth(stack_pointer: u8) -> u8 {
+    0xFF - stack_pointer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catches_wrapping_and_crossing_the_maximum_depth() {
+        let guard = StackGuard::new().max_depth(0x10);
+
+        assert_eq!(Some(StackViolationKind::Overflow), guard.check(0x01, 0xFE));
+        assert_eq!(Some(StackViolationKind::Underflow), guard.check(0xFE, 0x01));
+        assert_eq!(Some(StackViolationKind::DepthExceeded(0x11)), guard.check(0xF0, 0xEE));
+        assert_eq!(None, guard.check(0xEE, 0xEC));
+        assert_eq!(None, StackGuard::new().check(0x20, 0x10));
+    }
+}
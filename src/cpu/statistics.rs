@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use ::opcodes::{AddressingMode, OpCode};
+
+/// The number of times a single mnemonic/addressing mode
+/// combination was executed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InstructionCount {
+    pub mnemonic: &'static str,
+    pub mode: AddressingMode,
+    pub count: u64,
+}
+
+/// Counts of every instruction the Cpu has executed, broken
+/// down by mnemonic and addressing mode
+#[derive(Debug, Default)]
+pub struct InstructionStatistics {
+    counts: HashMap<(&'static str, AddressingMode), u64>,
+}
+
+impl InstructionStatistics {
+    pub fn new() -> InstructionStatistics {
+        Default::default()
+    }
+
+    /// Records a single execution of an opcode
+    pub fn record(&mut self, opcode: &OpCode) {
+        *self.counts.entry((opcode.mnemonic, opcode.mode)).or_insert(0) += 1;
+    }
+
+    /// Returns how many times a mnemonic was executed with
+    /// a specific addressing mode
+    pub fn count(&self, mnemonic: &str, mode: AddressingMode) -> u64 {
+        let mnemonic = mnemonic.to_uppercase();
+
+        self.counts
+            .iter()
+            .filter(|&(&(m, a), _)| m == mnemonic && a == mode)
+            .map(|(_, count)| *count)
+            .sum()
+    }
+
+    /// Returns how many times a mnemonic was executed, across
+    /// all of its addressing modes
+    pub fn count_mnemonic(&self, mnemonic: &str) -> u64 {
+        let mnemonic = mnemonic.to_uppercase();
+
+        self.counts
+            .iter()
+            .filter(|&(&(m, _), _)| m == mnemonic)
+            .map(|(_, count)| *count)
+            .sum()
+    }
+
+    /// Returns the total number of instructions executed
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Returns a report of every instruction executed, with the
+    /// most frequently executed instructions first
+    pub fn report(&self) -> Vec<InstructionCount> {
+        let mut report: Vec<InstructionCount> = self.counts
+            .iter()
+            .map(|(&(mnemonic, mode), &count)| {
+                InstructionCount {
+                    mnemonic,
+                    mode,
+                    count,
+                }
+            })
+            .collect();
+
+        report.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.mnemonic.cmp(b.mnemonic))
+                .then(a.mode.cmp(&b.mode))
+        });
+
+        report
+    }
+
+    /// Clears all recorded counts
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+}
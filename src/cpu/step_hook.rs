@@ -0,0 +1,18 @@
+use ::opcodes::OpCode;
+
+use cpu::cpu::{Cpu, Operand};
+use cpu::memory_bus::MemoryBus;
+
+/// An instruction as decoded by the Cpu, as passed to the hooks set with
+/// `Cpu::set_pre_step_hook` and `Cpu::set_post_step_hook`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodedInstruction {
+    /// The address of the instruction
+    pub pc: u16,
+    pub opcode: OpCode,
+    pub operand: Operand,
+}
+
+/// A hook that runs before or after every instruction. It is given the
+/// Cpu, so it can inspect the registers or change memory.
+pub type StepHook<B = MemoryBus> = Box<dyn FnMut(&mut Cpu<B>, &DecodedInstruction)>;
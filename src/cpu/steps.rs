@@ -0,0 +1,128 @@
+use ::opcodes::{AddressingMode, OpCode};
+
+use cpu::bus::Bus;
+use cpu::cpu::Cpu;
+use cpu::cpu_error::CpuError;
+use cpu::memory_bus::MemoryBus;
+use cpu::unknown_opcode;
+
+/// Information about a single instruction executed by the Cpu
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepInfo {
+    /// The address the instruction was executed from
+    pub pc: u16,
+    pub opcode: OpCode,
+    /// The number of cycles the instruction took
+    pub cycles: u8,
+}
+
+impl StepInfo {
+    pub fn is_brk(&self) -> bool {
+        self.opcode.mnemonic == "BRK"
+    }
+}
+
+/// An iterator that executes one instruction per call to `next`. Iteration
+/// ends when the Cpu becomes idle or at the first error, which is available
+/// through `error` afterwards.
+pub struct Steps<'a, B: 'a = MemoryBus> {
+    cpu: &'a mut Cpu<B>,
+    error: Option<CpuError>,
+}
+
+impl<'a, B> Steps<'a, B>
+    where B: Bus
+{
+    pub fn new(cpu: &'a mut Cpu<B>) -> Steps<'a, B> {
+        Steps {
+            cpu,
+            error: None,
+        }
+    }
+
+    /// Returns the error that stopped iteration, if any
+    pub fn error(&self) -> Option<&CpuError> {
+        self.error.as_ref()
+    }
+}
+
+impl<'a, B> Iterator for Steps<'a, B>
+    where B: Bus
+{
+    type Item = StepInfo;
+
+    fn next(&mut self) -> Option<StepInfo> {
+        if self.error.is_some() || self.cpu.is_idle() || self.cpu.is_halted() {
+            return None;
+        }
+
+        match step(self.cpu) {
+            Ok(step) => Some(step),
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+/// An iterator that executes one instruction per call to `next`, yielding
+/// the result of each. Iteration ends when the Cpu becomes idle or halts,
+/// or after the first error is yielded, so the results can be collected
+/// into a `Result`.
+pub struct IterSteps<'a, B: 'a = MemoryBus> {
+    cpu: &'a mut Cpu<B>,
+    failed: bool,
+}
+
+impl<'a, B> IterSteps<'a, B>
+    where B: Bus
+{
+    pub fn new(cpu: &'a mut Cpu<B>) -> IterSteps<'a, B> {
+        IterSteps {
+            cpu,
+            failed: false,
+        }
+    }
+}
+
+impl<'a, B> Iterator for IterSteps<'a, B>
+    where B: Bus
+{
+    type Item = Result<StepInfo, CpuError>;
+
+    fn next(&mut self) -> Option<Result<StepInfo, CpuError>> {
+        if self.failed || self.cpu.is_idle() || self.cpu.is_halted() {
+            return None;
+        }
+
+        let result = step(self.cpu);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Executes one instruction, describing what was executed
+fn step<B>(cpu: &mut Cpu<B>) -> Result<StepInfo, CpuError>
+    where B: Bus
+{
+    let pc = cpu.registers.PC;
+    let byte = cpu.memory.read_byte(pc);
+    let cycles = cpu.step()?;
+
+    // Unknown opcodes can only get this far through the unknown opcode
+    // policy
+    let opcode = cpu.decode(byte).cloned().unwrap_or(OpCode {
+        code: byte,
+        mnemonic: "???",
+        length: unknown_opcode::unknown_opcode_length(byte),
+        time: cycles,
+        mode: AddressingMode::Unknown,
+    });
+
+    Ok(StepInfo {
+        pc,
+        opcode,
+        cycles,
+    })
+}
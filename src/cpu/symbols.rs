@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+/// How far past a symbol an address can be and still be shown relative to
+/// it, such as `MAIN_LOOP+3`
+const MAX_OFFSET: u16 = 0xFF;
+
+/// Names for addresses, used to show locations like `MAIN_LOOP+3` in
+/// place of raw addresses. `Assembler::symbols` returns the labels of
+/// assembled source as Symbols.
+///
+/// # Example
+/// ```
+/// use rs6502::Symbols;
+///
+/// let mut symbols = Symbols::new();
+/// symbols.insert("MAIN_LOOP", 0xC010);
+///
+/// assert_eq!(Some("MAIN_LOOP"), symbols.name(0xC010));
+/// assert_eq!("MAIN_LOOP+3", symbols.format_address(0xC013));
+/// assert_eq!("$C00F", symbols.format_address(0xC00F));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Default::default()
+    }
+
+    /// Names an address. An address keeps the first name it is given.
+    pub fn insert<S>(&mut self, name: S, addr: u16)
+        where S: Into<String>
+    {
+        self.names.entry(addr).or_insert_with(|| name.into());
+    }
+
+    /// Returns the name of an address, if it has one
+    pub fn name(&self, addr: u16) -> Option<&str> {
+        self.names.get(&addr).map(|name| &name[..])
+    }
+
+    /// Returns an address relative to the closest symbol at or below it,
+    /// such as `MAIN_LOOP+3`, or None if there is no symbol within a page
+    /// below it
+    pub fn locate(&self, addr: u16) -> Option<String> {
+        self.names.range(..=addr).next_back().and_then(|(&start, name)| match addr - start {
+            0 => Some(name.clone()),
+            offset if offset <= MAX_OFFSET => Some(format!("{}+{}", name, offset)),
+            _ => None,
+        })
+    }
+
+    /// Returns `locate` for the address, or the address in hex when it
+    /// isn't near a symbol
+    pub fn format_address(&self, addr: u16) -> String {
+        self.locate(addr).unwrap_or_else(|| format!("${:04X}", addr))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_addresses_within_a_page_of_a_symbol() {
+        let mut symbols = Symbols::new();
+        symbols.insert("BUFFER", 0x0200);
+        symbols.insert("START", 0xC000);
+        symbols.insert("ALIAS", 0xC000);
+
+        assert_eq!(Some("START".to_string()), symbols.locate(0xC000));
+        assert_eq!(Some("BUFFER+255".to_string()), symbols.locate(0x02FF));
+        assert_eq!(None, symbols.locate(0x0300));
+        assert_eq!(None, symbols.locate(0x0100));
+        assert_eq!(2, symbols.len());
+    }
+}
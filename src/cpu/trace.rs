@@ -0,0 +1,226 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+use ::opcodes::{AddressingMode, OpCode};
+use cpu::symbols::Symbols;
+
+/// A kind of instruction a trace can be narrowed down to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InstructionClass {
+    /// `LDA`, `LDX`, `LDY` and `LAX`
+    Loads,
+    /// `STA`, `STX`, `STY`, `STZ` and `SAX`
+    Stores,
+    /// Every conditional branch and `BRA`, whether taken or not
+    Branches,
+    /// Branches that were taken
+    TakenBranches,
+    /// `JMP`, `JSR`, `RTS`, `RTI` and `BRK`
+    Jumps,
+}
+
+impl InstructionClass {
+    pub fn matches(&self, line: &TraceLine) -> bool {
+        match *self {
+            InstructionClass::Loads => ["LDA", "LDX", "LDY", "LAX"].contains(&line.opcode.mnemonic),
+            InstructionClass::Stores => ["STA", "STX", "STY", "STZ", "SAX"].contains(&line.opcode.mnemonic),
+            InstructionClass::Branches => line.opcode.mode == AddressingMode::Relative,
+            InstructionClass::TakenBranches => line.opcode.mode == AddressingMode::Relative && line.branch_taken,
+            InstructionClass::Jumps => ["JMP", "JSR", "RTS", "RTI", "BRK"].contains(&line.opcode.mnemonic),
+        }
+    }
+}
+
+/// Decides which instructions a trace sink receives. An empty filter lets
+/// everything through. An address range narrows the trace to instructions
+/// in the range, and the classes narrow it to instructions of any of them.
+///
+/// # Example
+/// ```
+/// use rs6502::{InstructionClass, TraceFilter};
+///
+/// // Stores and taken branches in the $C000 page
+/// let filter = TraceFilter::new()
+///     .addresses(0xC000..=0xC0FF)
+///     .only(InstructionClass::Stores)
+///     .only(InstructionClass::TakenBranches);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceFilter {
+    addresses: Option<RangeInclusive<u16>>,
+    classes: Vec<InstructionClass>,
+}
+
+impl TraceFilter {
+    pub fn new() -> TraceFilter {
+        TraceFilter::default()
+    }
+
+    /// Only traces instructions whose address is in the range
+    pub fn addresses(mut self, range: RangeInclusive<u16>) -> TraceFilter {
+        self.addresses = Some(range);
+        self
+    }
+
+    /// Only traces instructions of this class, or of any other class
+    /// passed to `only`
+    pub fn only(mut self, class: InstructionClass) -> TraceFilter {
+        self.classes.push(class);
+        self
+    }
+
+    pub fn matches(&self, line: &TraceLine) -> bool {
+        if let Some(ref range) = self.addresses {
+            if !range.contains(&line.pc) {
+                return false;
+            }
+        }
+
+        self.classes.is_empty() || self.classes.iter().any(|class| class.matches(line))
+    }
+}
+
+/// An instruction the Cpu executed, with the registers as they were
+/// before it ran. Displays as a line of a trace log, which names the
+/// location and operand of the instruction when the Cpu has symbols.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceLine {
+    /// The address of the instruction
+    pub pc: u16,
+    pub opcode: OpCode,
+    /// The operand bytes, of which the opcode's length says how many are
+    /// used
+    pub operand: [u8; 2],
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    /// The status register
+    pub p: u8,
+    /// Whether the instruction was a branch that was taken
+    pub branch_taken: bool,
+    /// The symbols set with `Cpu::set_symbols`, if any
+    pub symbols: Option<Rc<Symbols>>,
+}
+
+impl TraceLine {
+    /// Formats the instruction in this crate's assembly syntax. Addresses
+    /// with a symbol are shown by name.
+    pub fn instruction(&self) -> String {
+        let byte = self.operand[0];
+        let word = byte as u16 | ((self.operand[1] as u16) << 8);
+        let zero_page = self.name(byte as u16).unwrap_or_else(|| format!("${:02X}", byte));
+        let absolute = self.name(word).unwrap_or_else(|| format!("${:04X}", word));
+
+        let operand = match self.opcode.mode {
+            AddressingMode::Immediate => format!(" #${:02X}", byte),
+            AddressingMode::Relative => {
+                let target = self.pc.wrapping_add(2).wrapping_add(byte as i8 as u16);
+                format!(" {}", self.name(target).unwrap_or_else(|| format!("${:04X}", target)))
+            }
+            AddressingMode::ZeroPage => format!(" {}", zero_page),
+            AddressingMode::ZeroPageX => format!(" {},X", zero_page),
+            AddressingMode::ZeroPageY => format!(" {},Y", zero_page),
+            AddressingMode::Absolute => format!(" {}", absolute),
+            AddressingMode::AbsoluteX => format!(" {},X", absolute),
+            AddressingMode::AbsoluteY => format!(" {},Y", absolute),
+            AddressingMode::Indirect => format!(" ({})", absolute),
+            AddressingMode::IndirectX => format!(" ({},X)", zero_page),
+            AddressingMode::IndirectY => format!(" ({}),Y", zero_page),
+            AddressingMode::ZeroPageIndirect => format!(" ({})", zero_page),
+            AddressingMode::AbsoluteIndexedIndirect => format!(" ({},X)", absolute),
+            _ => String::new(),
+        };
+
+        format!("{}{}", self.opcode.mnemonic, operand)
+    }
+
+    /// Returns where the instruction is relative to the closest symbol,
+    /// such as `MAIN_LOOP+3`, or None without symbols
+    pub fn location(&self) -> Option<String> {
+        self.symbols.as_ref().and_then(|symbols| symbols.locate(self.pc))
+    }
+
+    fn name(&self, addr: u16) -> Option<String> {
+        self.symbols.as_ref().and_then(|symbols| symbols.name(addr)).map(|name| name.to_string())
+    }
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bytes = format!("{:02X}", self.opcode.code);
+        for byte in self.operand.iter().take(self.opcode.length as usize - 1) {
+            bytes.push_str(&format!(" {:02X}", byte));
+        }
+
+        write!(f, "{:04X}  ", self.pc)?;
+        if self.symbols.is_some() {
+            write!(f, "{:<16}  ", self.location().unwrap_or_default())?;
+        }
+
+        write!(f,
+               "{:<8}  {:<14}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+               bytes,
+               self.instruction(),
+               self.a,
+               self.x,
+               self.y,
+               self.p,
+               self.s)
+    }
+}
+
+/// A sink registered with `Cpu::add_trace_sink`
+pub type TraceSink = Box<dyn FnMut(&TraceLine)>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(code: u8, operand: [u8; 2], branch_taken: bool) -> TraceLine {
+        TraceLine {
+            pc: 0xC010,
+            opcode: *OpCode::from_raw_byte(code).unwrap(),
+            operand,
+            a: 0x01,
+            x: 0x02,
+            y: 0x03,
+            s: 0xFD,
+            p: 0x24,
+            branch_taken,
+            symbols: None,
+        }
+    }
+
+    #[test]
+    fn formats_the_instruction_and_registers() {
+        assert_eq!("C010  8D 00 02  STA $0200       A:01 X:02 Y:03 P:24 SP:FD",
+                   line(0x8D, [0x00, 0x02], false).to_string());
+        assert_eq!("C010  D0 FB     BNE $C00D       A:01 X:02 Y:03 P:24 SP:FD",
+                   line(0xD0, [0xFB, 0x00], true).to_string());
+    }
+
+    #[test]
+    fn names_the_location_and_operand_from_symbols() {
+        let mut symbols = Symbols::new();
+        symbols.insert("MAIN_LOOP", 0xC00D);
+        symbols.insert("COUNT", 0x0200);
+        let symbols = Some(Rc::new(symbols));
+
+        assert_eq!("C010  MAIN_LOOP+3       8D 00 02  STA COUNT       A:01 X:02 Y:03 P:24 SP:FD",
+                   TraceLine { symbols: symbols.clone(), ..line(0x8D, [0x00, 0x02], false) }.to_string());
+        assert_eq!("C010  MAIN_LOOP+3       D0 FB     BNE MAIN_LOOP   A:01 X:02 Y:03 P:24 SP:FD",
+                   TraceLine { symbols, ..line(0xD0, [0xFB, 0x00], true) }.to_string());
+    }
+
+    #[test]
+    fn filters_by_address_and_class() {
+        let filter = TraceFilter::new().addresses(0xC000..=0xC0FF).only(InstructionClass::TakenBranches);
+
+        assert!(filter.matches(&line(0xD0, [0xFB, 0x00], true)));
+        assert!(!filter.matches(&line(0xD0, [0xFB, 0x00], false)));
+        assert!(!filter.matches(&line(0x8D, [0x00, 0x02], false)));
+        assert!(!filter.matches(&TraceLine { pc: 0xD000, ..line(0xD0, [0xFB, 0x00], true) }));
+    }
+}
@@ -0,0 +1,60 @@
+use cpu::cpu::{Cpu, CpuStepResult};
+use cpu::memory_bus::MemoryBus;
+
+/// A handler for opcodes the Cpu does not recognise. It is called with the
+/// Program Counter still pointing at the opcode byte and is responsible for
+/// moving it on. It returns the number of cycles taken.
+pub type UnknownOpcodeHandler<B = MemoryBus> = Box<dyn FnMut(&mut Cpu<B>, u8) -> CpuStepResult>;
+
+/// Determines what the Cpu does when it reaches a byte that is not a
+/// known opcode
+#[derive(Default)]
+pub enum UnknownOpcodePolicy<B = MemoryBus> {
+    /// Return `CpuError::unknown_opcode` from `step`
+    #[default]
+    Error,
+    /// Skip over the instruction as though it were a NOP, using the length
+    /// the instruction would have on a real 6502
+    Nop,
+    /// Stop executing instructions until the Cpu is reset
+    Halt,
+    /// Hand the opcode to a user supplied handler
+    Callback(UnknownOpcodeHandler<B>),
+}
+
+
+/// Returns the length of an undocumented opcode, which a real 6502 decodes
+/// using the same addressing mode as the documented opcodes in its column
+pub fn unknown_opcode_length(byte: u8) -> u8 {
+    let odd_row = byte & 0x10 == 0x10;
+
+    match byte & 0x0F {
+        0x00 if odd_row || byte >= 0x80 => 2,
+        0x00 if byte == 0x20 => 3,
+        0x00 => 1,
+        0x02 if !odd_row && byte >= 0x80 => 2,
+        0x02 | 0x08 | 0x0A => 1,
+        0x09 | 0x0B if odd_row => 3,
+        0x0C..=0x0F => 3,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undocumented_opcodes_match_their_column() {
+        // KIL, SLO (zp,X), NOP zp, NOP, NOP abs,X
+        assert_eq!(1, unknown_opcode_length(0x02));
+        assert_eq!(2, unknown_opcode_length(0x03));
+        assert_eq!(2, unknown_opcode_length(0x04));
+        assert_eq!(1, unknown_opcode_length(0x1A));
+        assert_eq!(3, unknown_opcode_length(0x1C));
+        // NOP #imm, ANC #imm, SLO abs,Y
+        assert_eq!(2, unknown_opcode_length(0x80));
+        assert_eq!(2, unknown_opcode_length(0x0B));
+        assert_eq!(3, unknown_opcode_length(0x1B));
+    }
+}
@@ -0,0 +1,206 @@
+use std;
+use std::fmt;
+
+use cpu::bus::Bus;
+use cpu::cpu::Cpu;
+
+/// Why an assertion passed to `Cpu::verify` did not hold
+#[derive(Debug, PartialEq)]
+pub struct VerifyError {
+    pub message: String,
+}
+
+impl VerifyError {
+    fn invalid_assertion(assertion: &str) -> VerifyError {
+        VerifyError::from(format!("Invalid assertion: '{}'", assertion))
+    }
+
+    fn invalid_value(value: &str) -> VerifyError {
+        VerifyError::from(format!("Invalid value: '{}'", value))
+    }
+
+    fn wrong_length(assertion: &str, expected: usize, actual: usize) -> VerifyError {
+        VerifyError::from(format!("'{}' compares {} values against {}", assertion, expected, actual))
+    }
+
+    fn failed(assertion: &str, actual: &[Value]) -> VerifyError {
+        let found: Vec<String> = actual.iter().map(|value| value.to_string()).collect();
+        VerifyError::from(format!("'{}' failed, found {}", assertion, found.join(" ")))
+    }
+}
+
+impl From<String> for VerifyError {
+    fn from(message: String) -> VerifyError {
+        VerifyError { message }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Value {
+    Byte(u8),
+    Word(u16),
+}
+
+impl Value {
+    fn to_u16(self) -> u16 {
+        match self {
+            Value::Byte(byte) => byte as u16,
+            Value::Word(word) => word,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Byte(byte) => write!(f, "${:02X}", byte),
+            Value::Word(word) => write!(f, "${:04X}", word),
+        }
+    }
+}
+
+/// Evaluates an assertion against the Cpu. See `Cpu::verify` for the syntax.
+pub fn verify<B>(cpu: &Cpu<B>, assertion: &str) -> Result<(), VerifyError>
+    where B: Bus
+{
+    let (lhs, equal, rhs) = if let Some(index) = assertion.find("==") {
+        (&assertion[..index], true, &assertion[index + 2..])
+    } else if let Some(index) = assertion.find("!=") {
+        (&assertion[..index], false, &assertion[index + 2..])
+    } else {
+        return Err(VerifyError::invalid_assertion(assertion));
+    };
+
+    let actual = evaluate(cpu, lhs.trim()).ok_or_else(|| VerifyError::invalid_assertion(assertion))?;
+    let expected = parse_values(rhs)?;
+
+    if expected.len() != actual.len() {
+        return Err(VerifyError::wrong_length(assertion, actual.len(), expected.len()));
+    }
+
+    let matches = actual.iter().zip(expected.iter()).all(|(actual, expected)| actual.to_u16() == *expected);
+    if matches == equal {
+        Ok(())
+    } else {
+        Err(VerifyError::failed(assertion, &actual))
+    }
+}
+
+/// Reads the values on the left hand side of an assertion: a register,
+/// a byte `[addr]`, a range of bytes `[start..end]` or a word `word[addr]`
+fn evaluate<B>(cpu: &Cpu<B>, lhs: &str) -> Option<Vec<Value>>
+    where B: Bus
+{
+    match &lhs.to_uppercase()[..] {
+        "A" => return Some(vec![Value::Byte(cpu.registers.A)]),
+        "X" => return Some(vec![Value::Byte(cpu.registers.X)]),
+        "Y" => return Some(vec![Value::Byte(cpu.registers.Y)]),
+        "SP" => return Some(vec![Value::Byte(cpu.registers.S)]),
+        "PC" => return Some(vec![Value::Word(cpu.registers.PC)]),
+        _ => {}
+    }
+
+    let (word, lhs) = if lhs.to_lowercase().starts_with("word") {
+        (true, lhs[4..].trim())
+    } else {
+        (false, lhs)
+    };
+
+    if !lhs.starts_with('[') || !lhs.ends_with(']') {
+        return None;
+    }
+    let inner = &lhs[1..lhs.len() - 1];
+
+    if let Some(index) = inner.find("..") {
+        let start = parse_number(inner[..index].trim()).ok()?;
+        let end = parse_number(inner[index + 2..].trim()).ok()?;
+        if word || end < start {
+            return None;
+        }
+
+        return Some((start..end).map(|addr| Value::Byte(cpu.memory.read_byte(addr))).collect());
+    }
+
+    let addr = parse_number(inner.trim()).ok()?;
+    if word {
+        Some(vec![Value::Word(cpu.memory.read_u16(addr))])
+    } else {
+        Some(vec![Value::Byte(cpu.memory.read_byte(addr))])
+    }
+}
+
+/// Parses a list of values separated by whitespace or commas, where
+/// `value*count` repeats a value
+fn parse_values(rhs: &str) -> Result<Vec<u16>, VerifyError> {
+    let mut values = Vec::new();
+
+    for item in rhs.split(|c: char| c == ',' || c.is_whitespace()).filter(|item| !item.is_empty()) {
+        if let Some(index) = item.find('*') {
+            let value = parse_number(&item[..index])?;
+            let count = item[index + 1..].parse::<usize>().map_err(|_| VerifyError::invalid_value(item))?;
+            values.extend(std::iter::repeat_n(value, count));
+        } else {
+            values.push(parse_number(item)?);
+        }
+    }
+
+    Ok(values)
+}
+
+/// Parses a number the way the assembler does: `$` for hexadecimal,
+/// `%` for binary and decimal otherwise
+pub fn parse_number(value: &str) -> Result<u16, VerifyError> {
+    let result = if let Some(hex) = value.strip_prefix('$') {
+        u16::from_str_radix(hex, 16)
+    } else if let Some(binary) = value.strip_prefix('%') {
+        u16::from_str_radix(binary, 2)
+    } else {
+        value.parse::<u16>()
+    };
+
+    result.map_err(|_| VerifyError::invalid_value(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.memory.write_bytes(0x0200, &[0x01, 0x02, 0x00, 0x00]);
+        cpu.memory.write_bytes(0xFFFC, &[0x00, 0xC0]);
+        cpu.registers.A = 0x10;
+
+        cpu
+    }
+
+    #[test]
+    fn compares_registers_bytes_and_words() {
+        let cpu = cpu();
+
+        assert_eq!(Ok(()), verify(&cpu, "A == $10"));
+        assert_eq!(Ok(()), verify(&cpu, "[$0201] == 2"));
+        assert_eq!(Ok(()), verify(&cpu, "word[$FFFC] == $C000"));
+        assert_eq!(Ok(()), verify(&cpu, "X != %1"));
+    }
+
+    #[test]
+    fn compares_ranges_with_repeated_values() {
+        let cpu = cpu();
+
+        assert_eq!(Ok(()), verify(&cpu, "[$0200..$0204] == $01, $02, 00*2"));
+        assert_eq!(Err(VerifyError::from("'[$0200..$0202] == 00*2' failed, found $01 $02".to_string())),
+                   verify(&cpu, "[$0200..$0202] == 00*2"));
+    }
+
+    #[test]
+    fn rejects_malformed_assertions() {
+        let cpu = cpu();
+
+        assert_eq!(Err(VerifyError::invalid_assertion("[$0200")), verify(&cpu, "[$0200"));
+        assert_eq!(Err(VerifyError::invalid_assertion("Q == 1")), verify(&cpu, "Q == 1"));
+        assert_eq!(Err(VerifyError::invalid_value("$GG")), verify(&cpu, "A == $GG"));
+        assert_eq!(Err(VerifyError::wrong_length("[$0200..$0202] == 1", 2, 1)),
+                   verify(&cpu, "[$0200..$0202] == 1"));
+    }
+}
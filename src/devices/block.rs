@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+use devices::Device;
+
+/// The number of bytes in each sector
+pub const SECTOR_SIZE: usize = 256;
+
+/// Reads the sector selected by the sector registers into memory
+/// at the buffer pointer
+pub const BLOCK_COMMAND_READ: u8 = 0x01;
+/// Writes a sector's worth of memory at the buffer pointer into
+/// the sector selected by the sector registers
+pub const BLOCK_COMMAND_WRITE: u8 = 0x02;
+
+pub const BLOCK_STATUS_OK: u8 = 0x00;
+pub const BLOCK_STATUS_ERROR: u8 = 0x01;
+
+/// Somewhere a `BlockDevice` can keep its sectors
+pub trait BlockStorage {
+    fn read_sector(&mut self, sector: u16, buf: &mut [u8]) -> io::Result<()>;
+    fn write_sector(&mut self, sector: u16, buf: &[u8]) -> io::Result<()>;
+}
+
+/// Sectors past the end of the Vec read as zeros, and writing
+/// to them grows the Vec
+impl BlockStorage for Vec<u8> {
+    fn read_sector(&mut self, sector: u16, buf: &mut [u8]) -> io::Result<()> {
+        let start = sector as usize * SECTOR_SIZE;
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = self.get(start + i).cloned().unwrap_or(0);
+        }
+
+        Ok(())
+    }
+
+    fn write_sector(&mut self, sector: u16, buf: &[u8]) -> io::Result<()> {
+        let start = sector as usize * SECTOR_SIZE;
+        if self.len() < start + buf.len() {
+            self.resize(start + buf.len(), 0);
+        }
+        self[start..start + buf.len()].copy_from_slice(buf);
+
+        Ok(())
+    }
+}
+
+/// Shares the storage with the host, so it can be inspected while the
+/// device is mapped into memory
+impl BlockStorage for Rc<RefCell<Vec<u8>>> {
+    fn read_sector(&mut self, sector: u16, buf: &mut [u8]) -> io::Result<()> {
+        self.borrow_mut().read_sector(sector, buf)
+    }
+
+    fn write_sector(&mut self, sector: u16, buf: &[u8]) -> io::Result<()> {
+        self.borrow_mut().write_sector(sector, buf)
+    }
+}
+
+/// Sectors past the end of the file read as zeros
+impl BlockStorage for File {
+    fn read_sector(&mut self, sector: u16, buf: &mut [u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))?;
+
+        let mut read = 0;
+        while read < buf.len() {
+            match self.read(&mut buf[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        for b in &mut buf[read..] {
+            *b = 0;
+        }
+
+        Ok(())
+    }
+
+    fn write_sector(&mut self, sector: u16, buf: &[u8]) -> io::Result<()> {
+        self.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE as u64))?;
+        self.write_all(buf)
+    }
+}
+
+/// A memory-mapped disk that transfers 256 byte sectors between its
+/// storage and memory. It occupies 5 addresses:
+///
+/// | Offset | Register                                         |
+/// |--------|--------------------------------------------------|
+/// | 0      | Command (write) / status of the last command (read) |
+/// | 1, 2   | Sector number, low byte first                    |
+/// | 3, 4   | Buffer pointer, low byte first                   |
+///
+/// # Example
+/// ```
+/// use rs6502::{BlockDevice, Cpu};
+///
+/// let mut cpu = Cpu::new();
+/// cpu.memory.map_device(0xD000..=0xD004, BlockDevice::new(vec![0x42; 512]));
+///
+/// // Read sector 1 into $0200
+/// cpu.memory.write_bytes(0xD001, &[0x01, 0x00, 0x00, 0x02]);
+/// cpu.memory.write_byte(0xD000, 0x01);
+///
+/// assert_eq!(0x00, cpu.memory.read_byte(0xD000));
+/// assert_eq!(0x42, cpu.memory.read_byte(0x02FF));
+/// ```
+pub struct BlockDevice<S> {
+    storage: S,
+    sector: u16,
+    buffer: u16,
+    status: u8,
+    command: Option<u8>,
+}
+
+impl<S> BlockDevice<S>
+    where S: BlockStorage
+{
+    pub fn new(storage: S) -> BlockDevice<S> {
+        BlockDevice {
+            storage,
+            sector: 0,
+            buffer: 0,
+            status: BLOCK_STATUS_OK,
+            command: None,
+        }
+    }
+
+    fn run_command(&mut self, command: u8, ram: &mut [u8]) -> io::Result<()> {
+        let mut sector = [0; SECTOR_SIZE];
+        let buffer = self.buffer as usize;
+
+        match command {
+            BLOCK_COMMAND_READ => {
+                self.storage.read_sector(self.sector, &mut sector)?;
+                for (i, b) in sector.iter().enumerate() {
+                    ram[(buffer + i) % ram.len()] = *b;
+                }
+            }
+            BLOCK_COMMAND_WRITE => {
+                for (i, b) in sector.iter_mut().enumerate() {
+                    *b = ram[(buffer + i) % ram.len()];
+                }
+                self.storage.write_sector(self.sector, &sector)?;
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "unknown command")),
+        }
+
+        Ok(())
+    }
+}
+
+impl<S> Device for BlockDevice<S>
+    where S: BlockStorage
+{
+    fn read(&mut self, offset: u16) -> u8 {
+        match offset {
+            0 => self.status,
+            1 => self.sector as u8,
+            2 => (self.sector >> 8) as u8,
+            3 => self.buffer as u8,
+            4 => (self.buffer >> 8) as u8,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        match offset {
+            0 => self.command = Some(value),
+            1 => self.sector = (self.sector & 0xFF00) | value as u16,
+            2 => self.sector = (self.sector & 0x00FF) | ((value as u16) << 8),
+            3 => self.buffer = (self.buffer & 0xFF00) | value as u16,
+            4 => self.buffer = (self.buffer & 0x00FF) | ((value as u16) << 8),
+            _ => {}
+        }
+    }
+
+    fn transfer(&mut self, ram: &mut [u8]) {
+        if let Some(command) = self.command.take() {
+            self.status = match self.run_command(command, ram) {
+                Ok(()) => BLOCK_STATUS_OK,
+                Err(_) => BLOCK_STATUS_ERROR,
+            };
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Block device"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(device: &mut BlockDevice<Vec<u8>>, sector: u16, buffer: u16) {
+        device.write(1, sector as u8);
+        device.write(2, (sector >> 8) as u8);
+        device.write(3, buffer as u8);
+        device.write(4, (buffer >> 8) as u8);
+    }
+
+    #[test]
+    fn can_write_then_read_a_sector() {
+        let mut ram = vec![0; 0x10000];
+        let mut device = BlockDevice::new(Vec::new());
+        for i in 0..SECTOR_SIZE {
+            ram[0x0300 + i] = i as u8;
+        }
+
+        select(&mut device, 2, 0x0300);
+        device.write(0, BLOCK_COMMAND_WRITE);
+        device.transfer(&mut ram);
+        select(&mut device, 2, 0x0500);
+        device.write(0, BLOCK_COMMAND_READ);
+        device.transfer(&mut ram);
+
+        assert_eq!(BLOCK_STATUS_OK, device.read(0));
+        assert_eq!(3 * SECTOR_SIZE, device.storage.len());
+        assert_eq!(&ram[0x0300..0x0400], &ram[0x0500..0x0600]);
+    }
+
+    #[test]
+    fn reports_errors_for_unknown_commands() {
+        let mut ram = vec![0; 0x10000];
+        let mut device = BlockDevice::new(Vec::new());
+
+        device.write(0, 0x7F);
+        device.transfer(&mut ram);
+
+        assert_eq!(BLOCK_STATUS_ERROR, device.read(0));
+    }
+}
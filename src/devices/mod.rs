@@ -0,0 +1,36 @@
+mod block;
+mod rng;
+mod text;
+
+pub use self::block::{BlockDevice, BlockStorage, BLOCK_COMMAND_READ, BLOCK_COMMAND_WRITE,
+                      BLOCK_STATUS_ERROR, BLOCK_STATUS_OK, SECTOR_SIZE};
+pub use self::rng::RandomDevice;
+pub use self::text::{DirtyRegion, TextFramebuffer, TextFramebufferHandler, TEXT_COLUMNS, TEXT_ROWS};
+
+use cpu::MemoryAccess;
+
+/// A peripheral mapped into the address space with `MemoryBus::map_device`.
+/// Offsets are relative to the start of the range the device is mapped at.
+pub trait Device {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, value: u8);
+
+    /// Called after every write to the device, giving it direct access to
+    /// RAM for transferring blocks of memory
+    fn transfer(&mut self, _ram: &mut [u8]) {}
+
+    /// Called by `MemoryBus::end_frame` once per frame
+    fn end_frame(&mut self) {}
+
+    /// The extra cycles the Cpu waits for an access to the device to
+    /// finish, such as for a peripheral slower than RAM. By default the
+    /// device is as fast as RAM.
+    fn wait_states(&self, _offset: u16, _access: MemoryAccess) -> u8 {
+        0
+    }
+
+    /// The name the device is listed under in a memory map
+    fn name(&self) -> &str {
+        "Device"
+    }
+}
@@ -0,0 +1,78 @@
+use devices::Device;
+
+// Used in place of a zero seed, which xorshift can never leave
+const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// A pseudo-random number register, like `$FE` on Easy6502. Every read
+/// returns the next byte from a xorshift generator, so a given seed always
+/// produces the same sequence. Writes are ignored.
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, RandomDevice};
+///
+/// let mut cpu = Cpu::new();
+/// cpu.memory.map_device(0xFE..=0xFE, RandomDevice::new(1234));
+///
+/// let first = cpu.memory.read_byte(0xFE);
+///
+/// let mut other = Cpu::new();
+/// other.memory.map_device(0xFE..=0xFE, RandomDevice::new(1234));
+///
+/// assert_eq!(first, other.memory.read_byte(0xFE));
+/// ```
+pub struct RandomDevice {
+    state: u64,
+}
+
+impl RandomDevice {
+    pub fn new(seed: u64) -> RandomDevice {
+        RandomDevice { state: if seed == 0 { DEFAULT_SEED } else { seed } }
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+impl Device for RandomDevice {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.next_byte()
+    }
+
+    fn write(&mut self, _offset: u16, _value: u8) {}
+
+    fn name(&self) -> &str {
+        "Random number generator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequence(device: &mut RandomDevice) -> Vec<u8> {
+        (0..16).map(|_| device.read(0)).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        assert_eq!(sequence(&mut RandomDevice::new(42)), sequence(&mut RandomDevice::new(42)));
+    }
+
+    #[test]
+    fn different_seeds_give_different_sequences() {
+        assert!(sequence(&mut RandomDevice::new(1)) != sequence(&mut RandomDevice::new(2)));
+    }
+
+    #[test]
+    fn zero_seed_still_produces_values() {
+        let values = sequence(&mut RandomDevice::new(0));
+
+        assert!(values.iter().any(|&b| b != 0));
+    }
+}
@@ -0,0 +1,151 @@
+use devices::Device;
+
+pub const TEXT_COLUMNS: usize = 40;
+pub const TEXT_ROWS: usize = 25;
+
+/// A run of changed characters on a single row, from `start_column`
+/// to `end_column` inclusive
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DirtyRegion {
+    pub row: u8,
+    pub start_column: u8,
+    pub end_column: u8,
+}
+
+/// Receives the regions that changed during a frame along with the whole
+/// character matrix, one row after another
+pub type TextFramebufferHandler = Box<dyn FnMut(&[DirtyRegion], &[u8])>;
+
+/// A 40x25 character matrix occupying 1000 bytes of the address space.
+/// At the end of each frame the handler is told which regions changed, if
+/// any did.
+///
+/// # Example
+/// ```
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use rs6502::{Cpu, DirtyRegion, TextFramebuffer};
+///
+/// let changes = Rc::new(RefCell::new(Vec::new()));
+/// let handler_changes = changes.clone();
+///
+/// let mut cpu = Cpu::new();
+/// cpu.memory.map_device(0x0400..=0x07E7, TextFramebuffer::new(move |regions, _| {
+///     handler_changes.borrow_mut().extend_from_slice(regions);
+/// }));
+///
+/// cpu.memory.write_bytes(0x0429, b"HI");
+/// cpu.memory.end_frame();
+///
+/// assert_eq!(vec![DirtyRegion { row: 1, start_column: 1, end_column: 2 }], *changes.borrow());
+/// ```
+pub struct TextFramebuffer {
+    cells: Vec<u8>,
+    dirty: Vec<Option<(u8, u8)>>,
+    handler: TextFramebufferHandler,
+}
+
+impl TextFramebuffer {
+    pub fn new<F>(handler: F) -> TextFramebuffer
+        where F: FnMut(&[DirtyRegion], &[u8]) + 'static
+    {
+        TextFramebuffer {
+            cells: vec![0; TEXT_COLUMNS * TEXT_ROWS],
+            dirty: vec![None; TEXT_ROWS],
+            handler: Box::new(handler),
+        }
+    }
+}
+
+impl Device for TextFramebuffer {
+    fn read(&mut self, offset: u16) -> u8 {
+        self.cells.get(offset as usize).cloned().unwrap_or(0)
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        let offset = offset as usize;
+        if offset >= self.cells.len() || self.cells[offset] == value {
+            return;
+        }
+        self.cells[offset] = value;
+
+        let row = offset / TEXT_COLUMNS;
+        let column = (offset % TEXT_COLUMNS) as u8;
+        self.dirty[row] = Some(match self.dirty[row] {
+            Some((start, end)) => (start.min(column), end.max(column)),
+            None => (column, column),
+        });
+    }
+
+    fn end_frame(&mut self) {
+        let regions: Vec<DirtyRegion> = self.dirty
+            .iter()
+            .enumerate()
+            .filter_map(|(row, span)| {
+                span.map(|(start, end)| {
+                    DirtyRegion {
+                        row: row as u8,
+                        start_column: start,
+                        end_column: end,
+                    }
+                })
+            })
+            .collect();
+
+        if !regions.is_empty() {
+            (self.handler)(&regions, &self.cells);
+        }
+
+        for span in &mut self.dirty {
+            *span = None;
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Text framebuffer"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn framebuffer() -> (TextFramebuffer, Rc<RefCell<Vec<Vec<DirtyRegion>>>>) {
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let handler_frames = frames.clone();
+        let device = TextFramebuffer::new(move |regions, _| {
+            handler_frames.borrow_mut().push(regions.to_vec());
+        });
+
+        (device, frames)
+    }
+
+    #[test]
+    fn reports_changed_spans_per_row() {
+        let (mut device, frames) = framebuffer();
+
+        device.write(5, b'A');
+        device.write(2, b'B');
+        device.write(999, b'C');
+        device.end_frame();
+
+        assert_eq!(vec![vec![DirtyRegion { row: 0, start_column: 2, end_column: 5 },
+                             DirtyRegion { row: 24, start_column: 39, end_column: 39 }]],
+                   *frames.borrow());
+    }
+
+    #[test]
+    fn skips_frames_without_changes() {
+        let (mut device, frames) = framebuffer();
+
+        device.write(0, b'A');
+        device.end_frame();
+        device.write(0, b'A');
+        device.end_frame();
+
+        assert_eq!(1, frames.borrow().len());
+        assert_eq!(b'A', device.read(0));
+    }
+}
@@ -1,6 +1,16 @@
+use std::collections::BTreeMap;
+
 use byteorder::{ByteOrder, LittleEndian};
 
-use opcodes::{AddressingMode, OpCode};
+use opcodes::{AddressingMode, CpuVariant, OpCode};
+
+/// Undocumented opcodes that ca65 can't assemble back to the same byte,
+/// either because they share a mnemonic and addressing mode with another
+/// opcode or because ca65 spells them differently (`KIL` is `JAM`)
+const CA65_BYTE_OPCODES: [u8; 36] = [0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA, 0x82, 0x89, 0xC2, 0xE2, 0x44,
+                                     0x64, 0x34, 0x54, 0x74, 0xD4, 0xF4, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC,
+                                     0x2B, 0xEB, 0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92,
+                                     0xB2, 0xD2, 0xF2];
 
 pub struct Disassembler {
     /// Determines whether byte offsets are generated
@@ -15,6 +25,23 @@ pub struct Disassembler {
     /// in memory so that it can adjust its memory
     /// offsets
     code_offset: u16,
+
+    /// Determines whether the output is ca65 compatible
+    /// source rather than a listing
+    ca65_output: bool,
+
+    /// The processor the code is decoded for
+    variant: CpuVariant,
+
+    /// Determines whether the targets of `JSR`s are marked
+    /// with a header in the Assembly output
+    subroutine_headers: bool,
+}
+
+impl Default for Disassembler {
+    fn default() -> Disassembler {
+        Disassembler::new()
+    }
 }
 
 /// A 6502 instruction disassembler
@@ -42,6 +69,9 @@ impl Disassembler {
             disable_offsets: false,
             disable_opcodes: true,
             code_offset: 0,
+            ca65_output: false,
+            variant: CpuVariant::Nmos6502,
+            subroutine_headers: false,
         }
     }
 
@@ -69,6 +99,9 @@ impl Disassembler {
             disable_offsets: true,
             disable_opcodes: true,
             code_offset: 0,
+            ca65_output: false,
+            variant: CpuVariant::Nmos6502,
+            subroutine_headers: false,
         }
     }
 
@@ -96,6 +129,9 @@ impl Disassembler {
             disable_offsets: false,
             disable_opcodes: false,
             code_offset: 0,
+            ca65_output: false,
+            variant: CpuVariant::Nmos6502,
+            subroutine_headers: false,
         }
     }
 
@@ -104,15 +140,165 @@ impl Disassembler {
             disable_offsets: false,
             disable_opcodes: false,
             code_offset: offset,
+            ca65_output: false,
+            variant: CpuVariant::Nmos6502,
+            subroutine_headers: false,
         }
     }
 
+    /// Creates an instance of the Disassembler that outputs source
+    /// for the ca65 assembler, for code loaded at the given address.
+    /// Branch targets become unnamed labels, absolute operands in the
+    /// zero page are forced to stay absolute, unknown bytes become
+    /// `.byte` and the interrupt vectors become `.word` when the code
+    /// runs to the end of memory.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Disassembler;
+    ///
+    /// let dasm = Disassembler::with_ca65_output(0xC000);
+    ///
+    /// let code: Vec<u8> = vec![0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0xAD, 0x10, 0x00, 0x02];
+    /// let asm = dasm.disassemble(&code);
+    ///
+    /// assert_eq!(Disassembler::clean_asm("
+    ///
+    ///     .setcpu \"6502\"
+    ///     .org $C000
+    ///     LDX #$05
+    ///     :
+    ///     DEX
+    ///     BNE :-
+    ///     LDA a:$0010
+    ///     .byte $02
+    ///
+    /// "), Disassembler::clean_asm(asm));
+    /// ```
+    pub fn with_ca65_output(offset: u16) -> Disassembler {
+        Disassembler {
+            disable_offsets: true,
+            disable_opcodes: true,
+            code_offset: offset,
+            ca65_output: true,
+            variant: CpuVariant::Nmos6502,
+            subroutine_headers: false,
+        }
+    }
+
+    /// Sets the processor the code is decoded for. Defaults to the NMOS
+    /// 6502.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{CpuVariant, Disassembler};
+    ///
+    /// let mut dasm = Disassembler::with_code_only();
+    /// dasm.set_variant(CpuVariant::Cmos65C02);
+    ///
+    /// let asm = dasm.disassemble(&[0xB2, 0x10, 0x9C, 0x00, 0x44]);
+    ///
+    /// assert_eq!(Disassembler::clean_asm("
+    ///
+    ///     LDA ($10)
+    ///     STZ $4400
+    ///
+    /// "), Disassembler::clean_asm(asm));
+    /// ```
+    pub fn set_variant(&mut self, variant: CpuVariant) {
+        self.variant = variant;
+    }
+
+    /// Starts each subroutine called from the code with a header naming it
+    /// after its address and counting its callers, separated from the code
+    /// before it by a blank line. A subroutine is the target of a `JSR`
+    /// that lands on an instruction in the code.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Disassembler;
+    ///
+    /// let mut dasm = Disassembler::with_offset(0xC000);
+    /// dasm.enable_subroutine_headers();
+    ///
+    /// // JSR $C006, JSR $C006, RTS
+    /// let asm = dasm.disassemble(&[0x20, 0x06, 0xC0, 0x20, 0x06, 0xC0, 0x60]);
+    ///
+    /// assert_eq!(Disassembler::clean_asm("
+    ///
+    ///     C000 20 06 C0 JSR $C006
+    ///     C003 20 06 C0 JSR $C006
+    ///     ; ---- subroutine sub_C006 (called 2 times) ----
+    ///     C006 60       RTS
+    ///
+    /// "), Disassembler::clean_asm(asm));
+    /// ```
+    pub fn enable_subroutine_headers(&mut self) {
+        self.subroutine_headers = true;
+    }
+
+    pub fn disable_subroutine_headers(&mut self) {
+        self.subroutine_headers = false;
+    }
+
     pub fn disassemble(&self, raw: &[u8]) -> String {
-        self.disassemble_with_addresses(raw)
+        let asm = self.disassemble_with_addresses(raw)
             .into_iter()
             .map(|x: (String, u16)| x.0)
             .collect::<Vec<_>>()
-            .join("\n")
+            .join("\n");
+
+        if self.ca65_output {
+            // Undocumented opcodes need ca65's NMOS 6502 with illegal opcodes
+            // enabled
+            let cpu = if self.variant == CpuVariant::Cmos65C02 {
+                "65C02"
+            } else if self.ca65_instructions(raw)
+                .iter()
+                .any(|&(_, opcode)| opcode.is_some_and(|opcode| opcode.is_undocumented())) {
+                "6502X"
+            } else {
+                "6502"
+            };
+
+            format!(".setcpu \"{}\"\n.org ${:04X}\n\n{}", cpu, self.code_offset, asm)
+        } else {
+            asm
+        }
+    }
+
+    /// Disassembles `count` instructions from a full memory image, starting
+    /// at `pc`, with the addresses of the image. Nothing needs to be sliced
+    /// out first, so code can be decoded wherever the PC happens to be.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Disassembler;
+    ///
+    /// let mut memory = [0x00; 0x10000];
+    /// memory[0x0400..0x0406].copy_from_slice(&[0xA9, 0x20, 0x8D, 0x00, 0x44, 0x60]);
+    ///
+    /// let asm = Disassembler::new().disassemble_from(&memory, 0x0402, 2);
+    ///
+    /// assert_eq!(Disassembler::clean_asm("
+    ///
+    ///     0402 STA $4400
+    ///     0405 RTS
+    ///
+    /// "), Disassembler::clean_asm(asm));
+    /// ```
+    pub fn disassemble_from(&self, memory: &[u8], pc: u16, count: usize) -> String {
+        let start = pc as usize;
+        let mut end = start;
+        for _ in 0..count {
+            if end >= memory.len() {
+                break;
+            }
+            end += OpCode::decode(memory[end], self.variant).map_or(1, |opcode| opcode.length as usize);
+        }
+
+        let dasm = Disassembler { code_offset: pc, ..*self };
+        dasm.disassemble(&memory[start.min(memory.len())..end.min(memory.len())])
     }
 
     /// Accepts a slice of 6502 bytecodes and translates them
@@ -135,87 +321,19 @@ impl Disassembler {
     /// "), Disassembler::clean_asm(asm));
     /// ```
     pub fn disassemble_with_addresses(&self, raw: &[u8]) -> Vec<(String, u16)> {
-        let mut result = Vec::new();
-
-        let mut i: usize = 0;
-        while i < raw.len() {
-            if let Some(opcode) = OpCode::from_raw_byte(raw[i]) {
-
-                // Each branch returns the opcode output and the
-                // disassembled output
-                let val = match opcode.mode {
-                    AddressingMode::Immediate => {
-                        let imm = raw[i + 0x01];
-                        (format!("{:02X} {:02X}", opcode.code, imm), format!(" #${:02X}", imm))
-                    }
-                    AddressingMode::Indirect => {
-                        let b1 = raw[i + 0x01];
-                        let b2 = raw[i + 0x02];
+        if self.ca65_output {
+            return self.disassemble_ca65(raw);
+        }
 
-                        let addr = LittleEndian::read_u16(&[b1, b2]);
+        let instructions = self.instructions(raw);
+        let subroutines = self.subroutines(raw, &instructions);
 
-                        (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
-                         format!(" (${:04X})", self.code_offset + addr))
-                    }
-                    AddressingMode::Relative => {
-                        let b1 = raw[i + 0x01];
-                        let offset = b1 as i8;
-                        let addr = if offset < 0 {
-                            if i >= -offset as usize - 0x02 {
-                                i - (-offset as usize - 0x02) as usize
-                            } else {
-                                b1 as usize   // Failsafe for potential overflow when disassembling raw bytes .. just dump the byte
-                            }
-                        } else {
-                            i + (offset as usize) + 0x02
-                        };
+        let mut result = Vec::new();
+        for &(i, opcode) in &instructions {
+            let header = subroutines.get(&i).map_or(String::new(), |&calls| self.subroutine_header(i, calls));
 
-                        (format!("{:02X} {:02X}", opcode.code, b1),
-                         format!(" ${:04X}", self.code_offset as isize + addr as isize))
-                    }
-                    AddressingMode::ZeroPage => {
-                        let b1 = raw[i + 0x01];
-                        (format!("{:02X} {:02X}", opcode.code, b1), format!(" ${:02X}", b1))
-                    }
-                    AddressingMode::ZeroPageX => {
-                        let b1 = raw[i + 0x01];
-                        (format!("{:02X} {:02X}", opcode.code, b1), format!(" ${:02X},X", b1))
-                    }
-                    AddressingMode::ZeroPageY => {
-                        let b1 = raw[i + 0x01];
-                        (format!("{:02X} {:02X}", opcode.code, b1), format!(" ${:02X},Y", b1))
-                    }
-                    AddressingMode::Absolute => {
-                        let b1 = raw[i + 0x01];
-                        let b2 = raw[i + 0x02];
-                        let addr = LittleEndian::read_u16(&[b1, b2]);
-                        (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
-                         format!(" ${:04X}", addr))
-                    }
-                    AddressingMode::AbsoluteX => {
-                        let b1 = raw[i + 0x01];
-                        let b2 = raw[i + 0x02];
-                        let addr = LittleEndian::read_u16(&[b1, b2]);
-                        (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
-                         format!(" ${:04X},X", addr))
-                    }
-                    AddressingMode::AbsoluteY => {
-                        let b1 = raw[i + 0x01];
-                        let b2 = raw[i + 0x02];
-                        let addr = LittleEndian::read_u16(&[b1, b2]);
-                        (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
-                         format!(" ${:04X},Y", addr))
-                    }
-                    AddressingMode::IndirectX => {
-                        let b1 = raw[i + 0x01];
-                        (format!("{:02X} {:02X}", opcode.code, b1), format!(" (${:02X},X)", b1))
-                    }
-                    AddressingMode::IndirectY => {
-                        let b1 = raw[i + 0x01];
-                        (format!(" {:02X} {:02X}", opcode.code, b1), format!(" (${:02X}),Y", b1))
-                    }
-                    _ => (format!("{:02X}", opcode.code), "".into()),
-                };
+            if let Some(opcode) = opcode {
+                let val = self.format_operand(opcode, raw, i);
 
                 let opcode_text = if self.disable_offsets {
                     if self.disable_opcodes {
@@ -237,24 +355,289 @@ impl Disassembler {
                                 val.1)
                     }
                 };
-                result.push((opcode_text, i as u16));
-                i += opcode.length as usize;
+                result.push((header + &self.flag_cmos_only(opcode_text, raw[i]), i as u16));
             } else {
                 let opcode_text = if self.disable_offsets {
-                    format!("{:02X}\n", raw[i] as u8)
+                    format!("{:02X}\n", { raw[i] })
                 } else {
                     format!("{:04X} {:02X}\n",
                             i + self.code_offset as usize,
-                            raw[i] as u8)
+                            { raw[i] })
                 };
-                result.push((opcode_text, i as u16));
-                i += 0x01;
+                result.push((header + &self.flag_cmos_only(opcode_text, raw[i]), i as u16));
             }
         }
 
         result
     }
 
+    /// Notes a line of a listing for an NMOS part whose opcode byte is an
+    /// instruction the 65C02 added, which is most likely code written for
+    /// the wrong processor
+    fn flag_cmos_only(&self, line: String, byte: u8) -> String {
+        if self.variant == CpuVariant::Cmos65C02 {
+            return line;
+        }
+
+        match OpCode::decode(byte, CpuVariant::Cmos65C02).filter(|opcode| opcode.is_cmos_only()) {
+            Some(opcode) => format!("{} ; 65C02 only: {}\n", line.trim_end(), opcode.mnemonic),
+            None => line,
+        }
+    }
+
+    /// Works out where each instruction starts in a listing, and the
+    /// opcode there if it isn't dumped as a byte
+    fn instructions(&self, raw: &[u8]) -> Vec<(usize, Option<&'static OpCode>)> {
+        let mut instructions = Vec::new();
+        let mut i: usize = 0;
+        while i < raw.len() {
+            // An instruction cut short by the end of the code is dumped as bytes
+            match OpCode::decode(raw[i], self.variant).filter(|opcode| i + opcode.length as usize <= raw.len()) {
+                Some(opcode) => {
+                    instructions.push((i, Some(opcode)));
+                    i += opcode.length as usize;
+                }
+                None => {
+                    instructions.push((i, None));
+                    i += 0x01;
+                }
+            }
+        }
+
+        instructions
+    }
+
+    /// Counts the `JSR`s to each instruction, by offset, when subroutine
+    /// headers are enabled
+    fn subroutines(&self, raw: &[u8], instructions: &[(usize, Option<&'static OpCode>)]) -> BTreeMap<usize, usize> {
+        let mut subroutines = BTreeMap::new();
+        if !self.subroutine_headers {
+            return subroutines;
+        }
+
+        let calls = instructions.iter().filter(|&&(_, opcode)| opcode.is_some_and(|opcode| opcode.mnemonic == "JSR"));
+        for &(i, _) in calls {
+            let target = LittleEndian::read_u16(&raw[i + 0x01..]).wrapping_sub(self.code_offset) as usize;
+            if instructions.iter().any(|&(start, opcode)| start == target && opcode.is_some()) {
+                *subroutines.entry(target).or_insert(0) += 1;
+            }
+        }
+
+        subroutines
+    }
+
+    fn subroutine_header(&self, i: usize, calls: usize) -> String {
+        format!("\n; ---- subroutine sub_{:04X} (called {} {}) ----\n",
+                i + self.code_offset as usize,
+                calls,
+                if calls == 1 { "time" } else { "times" })
+    }
+
+    /// The offset the interrupt vectors start at in ca65 output. They are
+    /// only split out when the code runs right up to the end of memory.
+    fn ca65_code_end(&self, raw: &[u8]) -> usize {
+        if self.code_offset as usize + raw.len() == 0x10000 && raw.len() >= 6 {
+            raw.len() - 6
+        } else {
+            raw.len()
+        }
+    }
+
+    /// Works out where each instruction starts in ca65 output, and the
+    /// opcode there if it isn't output as a `.byte`
+    fn ca65_instructions(&self, raw: &[u8]) -> Vec<(usize, Option<&'static OpCode>)> {
+        let code_end = self.ca65_code_end(raw);
+
+        let mut instructions = Vec::new();
+        let mut i: usize = 0;
+        while i < code_end {
+            match OpCode::decode(raw[i], self.variant) {
+                Some(opcode) if i + opcode.length as usize <= code_end &&
+                                !(opcode.is_undocumented() && CA65_BYTE_OPCODES.contains(&opcode.code)) &&
+                                !(self.variant == CpuVariant::Cmos65C02 && opcode.is_cmos_nop()) => {
+                    instructions.push((i, Some(opcode)));
+                    i += opcode.length as usize;
+                }
+                _ => {
+                    instructions.push((i, None));
+                    i += 0x01;
+                }
+            }
+        }
+
+        instructions
+    }
+
+    fn disassemble_ca65(&self, raw: &[u8]) -> Vec<(String, u16)> {
+        let code_end = self.ca65_code_end(raw);
+
+        // Work out where each instruction starts first, so that
+        // branches can be pointed at unnamed labels
+        let instructions = self.ca65_instructions(raw);
+
+        let mut labels: Vec<usize> = instructions.iter()
+            .filter_map(|&(i, opcode)| match opcode {
+                Some(opcode) if opcode.mode == AddressingMode::Relative => branch_target(raw, i),
+                _ => None,
+            })
+            .filter(|target| instructions.iter().any(|&(i, _)| i == *target))
+            .collect();
+        labels.sort();
+        labels.dedup();
+
+        let subroutines = self.subroutines(raw, &instructions);
+
+        let mut result = Vec::new();
+        for &(i, opcode) in &instructions {
+            let header = subroutines.get(&i).map_or(String::new(), |&calls| self.subroutine_header(i, calls));
+            let label = if labels.contains(&i) { ":\n" } else { "" };
+
+            let text = match opcode {
+                Some(opcode) => {
+                    format!("{}{}    {}{}\n",
+                            header,
+                            label,
+                            opcode.mnemonic,
+                            self.format_ca65_operand(opcode, raw, i, &labels))
+                }
+                None => format!("{}{}    .byte ${:02X}\n", header, label, raw[i]),
+            };
+            result.push((text, i as u16));
+        }
+
+        for i in (code_end..raw.len()).step_by(2) {
+            let text = format!("    .word ${:04X}\n", LittleEndian::read_u16(&raw[i..]));
+            result.push((text, i as u16));
+        }
+
+        result
+    }
+
+    /// Formats the operand of the instruction at offset `i` in ca65 syntax
+    fn format_ca65_operand(&self, opcode: &OpCode, raw: &[u8], i: usize, labels: &[usize]) -> String {
+        match opcode.mode {
+            AddressingMode::Relative => {
+                match branch_target(raw, i) {
+                    Some(target) if labels.contains(&target) => {
+                        // Unnamed labels are referenced by counting them
+                        // backwards or forwards from the branch
+                        if target <= i {
+                            let count = labels.iter().filter(|&&l| l >= target && l <= i).count();
+                            format!(" :{}", "-".repeat(count))
+                        } else {
+                            let count = labels.iter().filter(|&&l| l > i && l <= target).count();
+                            format!(" :{}", "+".repeat(count))
+                        }
+                    }
+                    _ => {
+                        let target = (self.code_offset as isize + i as isize + 0x02 + raw[i + 0x01] as i8 as isize) as u16;
+                        format!(" ${:04X}", target)
+                    }
+                }
+            }
+            AddressingMode::Indirect => {
+                format!(" (${:04X})", LittleEndian::read_u16(&raw[i + 0x01..]))
+            }
+            AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY
+                if raw[i + 0x02] == 0x00 => {
+                // ca65 would otherwise shrink these to zero page instructions
+                format!(" a:{}", &self.format_operand(opcode, raw, i).1[1..])
+            }
+            _ => self.format_operand(opcode, raw, i).1,
+        }
+    }
+
+    /// Formats the bytes of the instruction at offset `i` alongside
+    /// its operand in this crate's assembly syntax
+    fn format_operand(&self, opcode: &OpCode, raw: &[u8], i: usize) -> (String, String) {
+        // Each branch returns the opcode output and the
+        // disassembled output
+        match opcode.mode {
+            AddressingMode::Immediate => {
+                let imm = raw[i + 0x01];
+                (format!("{:02X} {:02X}", opcode.code, imm), format!(" #${:02X}", imm))
+            }
+            AddressingMode::Indirect => {
+                let b1 = raw[i + 0x01];
+                let b2 = raw[i + 0x02];
+
+                let addr = LittleEndian::read_u16(&[b1, b2]);
+
+                (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
+                 format!(" (${:04X})", self.code_offset + addr))
+            }
+            AddressingMode::Relative => {
+                let b1 = raw[i + 0x01];
+                let offset = b1 as i8;
+                let addr = if offset < 0 {
+                    if i >= -offset as usize - 0x02 {
+                        i - (-offset as usize - 0x02)
+                    } else {
+                        b1 as usize   // Failsafe for potential overflow when disassembling raw bytes .. just dump the byte
+                    }
+                } else {
+                    i + (offset as usize) + 0x02
+                };
+
+                (format!("{:02X} {:02X}", opcode.code, b1),
+                 format!(" ${:04X}", self.code_offset as isize + addr as isize))
+            }
+            AddressingMode::ZeroPage => {
+                let b1 = raw[i + 0x01];
+                (format!("{:02X} {:02X}", opcode.code, b1), format!(" ${:02X}", b1))
+            }
+            AddressingMode::ZeroPageX => {
+                let b1 = raw[i + 0x01];
+                (format!("{:02X} {:02X}", opcode.code, b1), format!(" ${:02X},X", b1))
+            }
+            AddressingMode::ZeroPageY => {
+                let b1 = raw[i + 0x01];
+                (format!("{:02X} {:02X}", opcode.code, b1), format!(" ${:02X},Y", b1))
+            }
+            AddressingMode::Absolute => {
+                let b1 = raw[i + 0x01];
+                let b2 = raw[i + 0x02];
+                let addr = LittleEndian::read_u16(&[b1, b2]);
+                (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
+                 format!(" ${:04X}", addr))
+            }
+            AddressingMode::AbsoluteX => {
+                let b1 = raw[i + 0x01];
+                let b2 = raw[i + 0x02];
+                let addr = LittleEndian::read_u16(&[b1, b2]);
+                (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
+                 format!(" ${:04X},X", addr))
+            }
+            AddressingMode::AbsoluteY => {
+                let b1 = raw[i + 0x01];
+                let b2 = raw[i + 0x02];
+                let addr = LittleEndian::read_u16(&[b1, b2]);
+                (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
+                 format!(" ${:04X},Y", addr))
+            }
+            AddressingMode::IndirectX => {
+                let b1 = raw[i + 0x01];
+                (format!("{:02X} {:02X}", opcode.code, b1), format!(" (${:02X},X)", b1))
+            }
+            AddressingMode::IndirectY => {
+                let b1 = raw[i + 0x01];
+                (format!(" {:02X} {:02X}", opcode.code, b1), format!(" (${:02X}),Y", b1))
+            }
+            AddressingMode::ZeroPageIndirect => {
+                let b1 = raw[i + 0x01];
+                (format!("{:02X} {:02X}", opcode.code, b1), format!(" (${:02X})", b1))
+            }
+            AddressingMode::AbsoluteIndexedIndirect => {
+                let b1 = raw[i + 0x01];
+                let b2 = raw[i + 0x02];
+                let addr = LittleEndian::read_u16(&[b1, b2]);
+                (format!("{:02X} {:02X} {:02X}", opcode.code, b1, b2),
+                 format!(" (${:04X},X)", addr))
+            }
+            _ => (format!("{:02X}", opcode.code), "".into()),
+        }
+    }
+
     /// Returns a Vector of Strings where each entry
     /// is a non-empty line of assembly instructions, with
     /// all leading and trailing whitespace removed.
@@ -278,11 +661,22 @@ impl Disassembler {
             .lines()
             .map(|line| line.trim())
             .map(String::from)
-            .filter(|line| line.len() > 0)
+            .filter(|line| !line.is_empty())
             .collect()
     }
 }
 
+/// Returns the offset a branch at offset `i` jumps to, if it
+/// lands within the code
+fn branch_target(raw: &[u8], i: usize) -> Option<usize> {
+    let target = i as isize + 0x02 + raw[i + 0x01] as i8 as isize;
+
+    if target >= 0 && (target as usize) < raw.len() {
+        Some(target as usize)
+    } else {
+        None
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -303,6 +697,22 @@ mod tests {
                    Disassembler::clean_asm(asm));
     }
 
+    #[test]
+    fn disassemble_from_stops_at_the_end_of_memory() {
+        let mut memory = vec![0x00; 0x10000];
+        memory[0xFFFE] = 0xEA;
+        memory[0xFFFF] = 0xAD;
+        let asm = Disassembler::new().disassemble_from(&memory, 0xFFFE, 5);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            FFFE NOP
+            FFFF AD
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
     #[test]
     fn can_disassemble_indirect_jmp() {
         let dasm = Disassembler::new();
@@ -491,4 +901,138 @@ mod tests {
         "),
                    Disassembler::clean_asm(asm));
     }
+
+    #[test]
+    fn disassembles_undocumented_opcodes() {
+        let dasm = Disassembler::new();
+        let code: Vec<u8> = vec![0xA7, 0x10, 0x1A, 0xDF, 0x00, 0x44, 0x8B];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            0000 LAX $10
+            0002 NOP ; 65C02 only: INC
+            0003 DCP $4400,X
+            0006 8B
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
+    #[test]
+    fn flags_65c02_instructions_on_an_nmos_6502() {
+        let dasm = Disassembler::new();
+        let code: Vec<u8> = vec![0x64, 0x10, 0xDA, 0xEA];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            0000 NOP $10 ; 65C02 only: STZ
+            0002 NOP ; 65C02 only: PHX
+            0003 NOP
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
+    #[test]
+    fn ca65_output_selects_the_illegal_opcode_cpu() {
+        let dasm = Disassembler::with_ca65_output(0xC000);
+        let code: Vec<u8> = vec![0xA7, 0x10, 0x1A, 0x60];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            .setcpu \"6502X\"
+            .org $C000
+            LAX $10
+            .byte $1A
+            RTS
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
+    #[test]
+    fn ca65_output_counts_unnamed_labels_between_branches() {
+        let dasm = Disassembler::with_ca65_output(0xC000);
+        let code: Vec<u8> = vec![0xF0, 0x04, 0xD0, 0x00, 0xD0, 0xFA, 0xEA, 0x60];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            .setcpu \"6502\"
+            .org $C000
+            :
+            BEQ :++
+            BNE :+
+            :
+            BNE :--
+            :
+            NOP
+            RTS
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
+    #[test]
+    fn ca65_output_keeps_branches_outside_the_code_as_addresses() {
+        let dasm = Disassembler::with_ca65_output(0xC000);
+        let code: Vec<u8> = vec![0xD0, 0x10, 0x6C, 0x00, 0x44];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            .setcpu \"6502\"
+            .org $C000
+            BNE $C012
+            JMP ($4400)
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
+    #[test]
+    fn ca65_output_marks_subroutines_called_within_the_code() {
+        let mut dasm = Disassembler::with_ca65_output(0xC000);
+        dasm.enable_subroutine_headers();
+        // JSR $C006, JSR $D000, BNE back to the JSR target, RTS
+        let code: Vec<u8> = vec![0x20, 0x06, 0xC0, 0x20, 0x00, 0xD0, 0xD0, 0xFE, 0x60];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            .setcpu \"6502\"
+            .org $C000
+            JSR $C006
+            JSR $D000
+            ; ---- subroutine sub_C006 (called 1 time) ----
+            :
+            BNE :-
+            RTS
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
+
+    #[test]
+    fn ca65_output_emits_vectors_as_words() {
+        let dasm = Disassembler::with_ca65_output(0xFFF8);
+        let code: Vec<u8> = vec![0x40, 0x20, 0xF8, 0xFF, 0x00, 0xC0, 0xF8, 0xFF];
+        let asm = dasm.disassemble(&code);
+
+        assert_eq!(Disassembler::clean_asm("
+
+            .setcpu \"6502\"
+            .org $FFF8
+            RTI
+            .byte $20
+            .word $FFF8
+            .word $C000
+            .word $FFF8
+
+        "),
+                   Disassembler::clean_asm(asm));
+    }
 }
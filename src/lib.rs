@@ -1,11 +1,42 @@
 extern crate byteorder;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 mod assembler;
+mod control_flow;
+mod convert;
 mod disassembler;
 mod cpu;
+mod devices;
+mod machine;
+mod monitor;
 mod opcodes;
+mod patch;
 
-pub use assembler::{Assembler, CodeSegment};
-pub use cpu::{Cpu, CpuError, CpuStepResult};
+pub mod selftest;
+pub mod testing;
+
+pub use assembler::{Assembler, AssemblerError, AssemblerOptions, AssemblerWarning, ChecksumAlgorithm,
+                    CodeSegment, CrossReference, DirectiveOutput, Encoder, Expansion, INesHeader,
+                    ImmediateBase, Label, LexerToken, Mirroring, Optimization, RelocatableCode, Span, Spanned,
+                    Statement, parse_statements, relocate, tokenize};
+pub use cpu::{AddressProfile, Bus, BusCycle, CallStack, CostModel, Costs, Coverage, Cpu, CpuError,
+              CpuErrorKind, CpuEvent, CpuEventListener, CpuSnapshot, CpuStepResult, Cycle, CycleHook,
+              DecodedInstruction, EventQueue, ExecuteError, ExitCodeSource, ExitCondition, ExternalEvent,
+              HostCall, InstructionClass, InstructionCount, InstructionStatistics, InterruptKind,
+              InterruptSourceStatistics, InterruptStatistics, IterSteps, Limit, LimitGuard, MemoryAccess,
+              MemoryBus, MemoryMap, MemoryRegion, OpcodeProfile, Operand, ProfileReport, Profiler, Protection,
+              RegionKind, Registers, RomWritePolicy, RunLimits, SelfModifyingCodeHandler,
+              SelfModifyingCodePolicy, StackFrame, StackFrameKind, StackGuard, StackViolation,
+              StackViolationKind, StatusFlags, StepHook, StepInfo, Steps, StopReason, Symbols, TraceFilter,
+              TraceLine, TraceSink, UnknownOpcodeHandler, UnknownOpcodePolicy, VerifyError};
+pub use control_flow::{find_unreachable_code, UnreachableRegion};
+pub use convert::{convert, Conversion, ConversionIssue, Dialect};
+pub use devices::{BlockDevice, BlockStorage, Device, DirtyRegion, RandomDevice, TextFramebuffer,
+                  TextFramebufferHandler, BLOCK_COMMAND_READ, BLOCK_COMMAND_WRITE, BLOCK_STATUS_ERROR,
+                  BLOCK_STATUS_OK, SECTOR_SIZE, TEXT_COLUMNS, TEXT_ROWS};
 pub use disassembler::Disassembler;
-pub use opcodes::OpCode;
+pub use machine::{Frame, FrameInterrupt, Machine};
+pub use monitor::{Monitor, MonitorError};
+pub use opcodes::{AddressingMode, CpuVariant, OpCode};
+pub use patch::{ByteChange, Patch, PatchError};
@@ -0,0 +1,151 @@
+use cpu::{Bus, Cpu, CpuError, MemoryBus};
+
+/// The interrupt a `Machine` raises at the end of every frame, like the
+/// vertical blank interrupt of a video chip
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FrameInterrupt {
+    Irq,
+    Nmi,
+}
+
+/// What happened during a call to `Machine::run_frame`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Frame {
+    /// The number of cycles executed during the frame
+    pub cycles: u32,
+    /// The number of instructions executed during the frame
+    pub instructions: u32,
+}
+
+/// A Cpu driven one frame at a time, for front-ends that present output at
+/// a fixed rate. Each frame runs the Cpu for a budget of cycles, tells the
+/// bus the frame has ended so its devices can hand their output to the
+/// host, then raises the frame interrupt if there is one.
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, FrameInterrupt, Machine};
+///
+/// let mut cpu = Cpu::new();
+/// // INX, JMP $C001, with an NMI handler at $D000 that increments Y
+/// cpu.load(&[0xE8, 0x4C, 0x01, 0xC0], None).unwrap();
+/// cpu.load(&[0xC8, 0x40], 0xD000).unwrap();
+/// cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+/// cpu.reset();
+///
+/// let mut machine = Machine::new(cpu, 100);
+/// machine.set_frame_interrupt(FrameInterrupt::Nmi);
+///
+/// let frame = machine.run_frame().unwrap();
+///
+/// assert!(frame.cycles >= 100);
+/// assert_eq!(0x01, machine.cpu.registers.X);
+///
+/// // The NMI raised at the end of the frame is taken as the next one starts
+/// machine.run_frame().unwrap();
+/// assert_eq!(0x01, machine.cpu.registers.Y);
+/// ```
+pub struct Machine<B = MemoryBus> {
+    pub cpu: Cpu<B>,
+    cycles_per_frame: u32,
+    frame_interrupt: Option<FrameInterrupt>,
+    overrun: u32,
+}
+
+impl<B> Machine<B>
+    where B: Bus
+{
+    pub fn new(cpu: Cpu<B>, cycles_per_frame: u32) -> Machine<B> {
+        Machine {
+            cpu,
+            cycles_per_frame,
+            frame_interrupt: None,
+            overrun: 0,
+        }
+    }
+
+    /// A Machine clocked at `clock_hz` that presents `frames_per_second`
+    /// frames, e.g. `Machine::with_frame_rate(cpu, 1_022_727, 60)` for an
+    /// NTSC Apple II
+    pub fn with_frame_rate(cpu: Cpu<B>, clock_hz: u32, frames_per_second: u32) -> Machine<B> {
+        Machine::new(cpu, clock_hz / frames_per_second)
+    }
+
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    /// Raises the given interrupt at the end of every frame
+    pub fn set_frame_interrupt(&mut self, interrupt: FrameInterrupt) {
+        self.frame_interrupt = Some(interrupt);
+    }
+
+    pub fn clear_frame_interrupt(&mut self) {
+        self.frame_interrupt = None;
+    }
+
+    /// Runs the Cpu for one frame's budget of cycles. An instruction that
+    /// runs past the end of the budget is finished, and the extra cycles
+    /// are taken from the next frame. A Cpu that is idle or halted ends the
+    /// frame early. Devices are notified and the frame interrupt raised
+    /// only if the frame completes without an error.
+    pub fn run_frame(&mut self) -> Result<Frame, CpuError> {
+        let budget = self.cycles_per_frame.saturating_sub(self.overrun);
+        let mut frame = Frame {
+            cycles: 0,
+            instructions: 0,
+        };
+
+        while frame.cycles < budget && !self.cpu.is_idle() && !self.cpu.is_halted() {
+            frame.cycles += self.cpu.step()? as u32;
+            frame.instructions += 1;
+        }
+        self.overrun = frame.cycles.saturating_sub(budget);
+
+        self.cpu.memory.end_frame();
+
+        match self.frame_interrupt {
+            Some(FrameInterrupt::Irq) => self.cpu.irq(),
+            Some(FrameInterrupt::Nmi) => self.cpu.nmi(),
+            None => {}
+        }
+
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine(code: &[u8], cycles_per_frame: u32) -> Machine {
+        let mut cpu = Cpu::new();
+        cpu.load(code, None).unwrap();
+        cpu.reset();
+
+        Machine::new(cpu, cycles_per_frame)
+    }
+
+    #[test]
+    fn carries_overrun_into_the_next_frame() {
+        // NOP, JMP $C000 - 5 cycles per loop
+        let mut machine = machine(&[0xEA, 0x4C, 0x00, 0xC0], 6);
+
+        assert_eq!(Frame { cycles: 7, instructions: 3 }, machine.run_frame().unwrap());
+        assert_eq!(Frame { cycles: 5, instructions: 2 }, machine.run_frame().unwrap());
+    }
+
+    #[test]
+    fn idle_cpu_ends_the_frame_early() {
+        // JMP $C000
+        let mut machine = machine(&[0x4C, 0x00, 0xC0], 1000);
+        machine.cpu.enable_idle_detection();
+
+        assert_eq!(Frame { cycles: 3, instructions: 1 }, machine.run_frame().unwrap());
+    }
+
+    #[test]
+    fn frame_rate_divides_the_clock() {
+        assert_eq!(17045, Machine::with_frame_rate(Cpu::new(), 1_022_727, 60).cycles_per_frame());
+    }
+}
@@ -0,0 +1,655 @@
+use std::fmt::Write;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use assembler::Assembler;
+use convert::{self, Dialect};
+use cpu::{self, Cpu, CpuError, CpuErrorKind, RunLimits};
+
+/// How many instructions `run` and `step` may execute when the Cpu has
+/// no run limits of its own, so a script can't hang on a program that
+/// never stops
+const RUN_INSTRUCTION_LIMIT: u64 = 1_000_000;
+
+/// The exit status of a script with a failing command
+const EXIT_FAILURE: i32 = 1;
+
+/// The exit status of a script stopped by a run limit
+const EXIT_LIMIT_REACHED: i32 = 2;
+
+/// Why a monitor command failed, and the line of the script it was on
+#[derive(Debug, PartialEq)]
+pub struct MonitorError {
+    pub message: String,
+    pub line: u32,
+    exit_status: i32,
+}
+
+impl MonitorError {
+    /// The status to exit a scripted session with: 2 if a run limit was
+    /// reached, or 1 for any other failure
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, Monitor};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// // JMP $C000
+    /// cpu.load(&[0x4C, 0x00, 0xC0], None).unwrap();
+    ///
+    /// let mut monitor = Monitor::new(cpu);
+    /// let error = monitor.run_script("reset\nlimit 1000\nrun").unwrap_err();
+    ///
+    /// assert_eq!(3, error.line);
+    /// assert_eq!(2, error.exit_status());
+    /// ```
+    pub fn exit_status(&self) -> i32 {
+        self.exit_status
+    }
+
+    fn unknown_command(command: &str) -> MonitorError {
+        MonitorError::from(format!("Unknown command: '{}'", command))
+    }
+
+    fn missing_argument(command: &str) -> MonitorError {
+        MonitorError::from(format!("Missing argument for '{}'", command))
+    }
+
+    fn unknown_label(label: &str) -> MonitorError {
+        MonitorError::from(format!("Unknown label: '{}'", label))
+    }
+
+    fn not_assembled(file: &str) -> MonitorError {
+        MonitorError::from(format!("'{}' is not the assembled file", file))
+    }
+
+    fn no_instruction_on_line(line: &str, file: &str) -> MonitorError {
+        MonitorError::from(format!("No instruction on line {} of '{}'", line, file))
+    }
+
+    fn unknown_dialect(dialect: &str) -> MonitorError {
+        MonitorError::from(format!("Unknown dialect: '{}'. Expected dasm, acme or ca65", dialect))
+    }
+
+    fn invalid_value(value: &str) -> MonitorError {
+        MonitorError::from(format!("Invalid value: '{}'", value))
+    }
+
+    fn not_a_byte(value: &str) -> MonitorError {
+        MonitorError::from(format!("Not a byte: '{}'", value))
+    }
+
+    fn invalid_count(value: &str) -> MonitorError {
+        MonitorError::from(format!("Not a count of instructions: '{}'", value))
+    }
+
+    fn not_zero_page(value: &str) -> MonitorError {
+        MonitorError::from(format!("Not a zero page address: '{}'", value))
+    }
+}
+
+impl From<String> for MonitorError {
+    fn from(message: String) -> MonitorError {
+        MonitorError {
+            message,
+            line: 0,
+            exit_status: EXIT_FAILURE,
+        }
+    }
+}
+
+impl From<CpuError> for MonitorError {
+    fn from(error: CpuError) -> MonitorError {
+        let exit_status = match *error.kind() {
+            CpuErrorKind::LimitReached(_) => EXIT_LIMIT_REACHED,
+            _ => EXIT_FAILURE,
+        };
+
+        MonitorError { exit_status, ..MonitorError::from(error.to_string()) }
+    }
+}
+
+/// A command driven debugger around a Cpu. Commands can be executed one at
+/// a time, or a whole script of them can be run non-interactively:
+///
+/// - `load <file> [addr]` loads a binary file, at `$C000` by default
+/// - `asm <file>` assembles a source file and loads its segments, giving
+///   the Cpu its labels as symbols
+/// - `break <addr|label|file:line>` adds a Cpu breakpoint, which stops
+///   `run` before the instruction at an address, the address of a label
+///   or the first instruction on a line of the assembled file. Labels and
+///   lines are looked up again each time `asm` assembles the file, so the
+///   breakpoints follow the code as it is edited.
+/// - `reset` resets the Cpu
+/// - `run` runs until a breakpoint, a `BRK`, or the Cpu halts or goes idle
+/// - `step [count]` runs one instruction, or `count` of them
+/// - `limit <count>` sets how many instructions each `run` or `step` may
+///   execute before it fails, 1,000,000 unless the Cpu had run limits
+/// - `dump <start> <end>` writes a hex dump of the bytes from `start` to
+///   `end` inclusive to the output
+/// - `> <addr> <byte>...` writes bytes into memory from `addr` onwards,
+///   all in hexadecimal with or without a `$`, such as `> C000 A9 01 60`
+/// - `fill <start> <end> <byte>` writes a byte to every address from
+///   `start` to `end` inclusive
+/// - `move <start> <end> <dest>` copies the bytes from `start` to `end`
+///   inclusive to `dest`
+/// - `compare <start> <end> <other>` writes each address from `start` to
+///   `end` whose byte differs from the block at `other` to the output,
+///   such as `0201: 02 0301: 05`
+/// - `watch <addr|label>` writes the 16-bit word at a zero page address to
+///   the output after every `run` and `step`, such as `ZP $02: $1234`
+/// - `verify <assertion>` checks an assertion with `Cpu::verify`
+/// - `convert <file> <dasm|acme|ca65>` translates a source file from
+///   another assembler with `convert` and writes it to the output, followed
+///   by a `; line N: ...` comment for each line it couldn't translate
+///
+/// Memory is changed through the `MemoryBus` block operations, so the
+/// program sees the changes as soon as it runs on.
+///
+/// Blank lines and lines starting with `;` are ignored. A failing command
+/// stops the script with an error whose `exit_status` a command line tool
+/// can exit with.
+///
+/// # Example
+/// ```
+/// use rs6502::{Cpu, Monitor};
+///
+/// let mut cpu = Cpu::new();
+/// // LDX #$03, DEX, BNE -3, STX $0200
+/// cpu.load(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0x8E, 0x00, 0x02], None).unwrap();
+///
+/// let mut monitor = Monitor::new(cpu);
+/// monitor.run_script("
+///     reset
+///     break $C005
+///     run
+///     verify X == 0
+///     dump $C000 $C002
+/// ").unwrap();
+///
+/// assert_eq!("C000  A2 03 CA                                          |...|\n", monitor.output());
+/// ```
+pub struct Monitor {
+    pub cpu: Cpu,
+    assembler: Assembler,
+    source: Option<String>,
+    // Breakpoints given as labels or lines, and the addresses the monitor
+    // set on the Cpu for them, which it takes away again when they move.
+    // An address that is also a breakpoint in its own right isn't listed.
+    symbolic_breakpoints: Vec<String>,
+    symbol_addresses: Vec<u16>,
+    watches: Vec<u8>,
+    output: String,
+}
+
+impl Monitor {
+    pub fn new(mut cpu: Cpu) -> Monitor {
+        if cpu.limits().is_none() {
+            cpu.set_limits(RunLimits::new().max_instructions(RUN_INSTRUCTION_LIMIT));
+        }
+
+        Monitor {
+            cpu,
+            assembler: Assembler::new(),
+            source: None,
+            symbolic_breakpoints: Vec::new(),
+            symbol_addresses: Vec::new(),
+            watches: Vec::new(),
+            output: String::new(),
+        }
+    }
+
+    /// Returns everything written by the commands executed so far
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Executes each line of a script in turn, stopping at the first
+    /// command that fails
+    pub fn run_script(&mut self, script: &str) -> Result<(), MonitorError> {
+        for (index, line) in script.lines().enumerate() {
+            if let Err(mut error) = self.execute(line) {
+                error.line = index as u32 + 1;
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes a script from a file
+    pub fn run_script_file(&mut self, path: &str) -> Result<(), MonitorError> {
+        let script = read_file(path)?;
+        self.run_script(&String::from_utf8_lossy(&script))
+    }
+
+    /// Executes a single command
+    pub fn execute(&mut self, line: &str) -> Result<(), MonitorError> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) if !command.starts_with(';') => command.to_lowercase(),
+            _ => return Ok(()),
+        };
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| args.get(index).cloned().ok_or_else(|| MonitorError::missing_argument(&command));
+
+        match &command[..] {
+            "load" => {
+                let code = read_file(arg(0)?)?;
+                let addr = match args.get(1) {
+                    Some(addr) => Some(parse_number(addr)?),
+                    None => None,
+                };
+                self.cpu.load(&code, addr)?;
+            }
+            "asm" => {
+                // A new assembler, so that labels taken out of the source
+                // don't linger from the last time it was assembled
+                let mut assembler = Assembler::new();
+                let segments = assembler.assemble_file(arg(0)?, None)
+                    .map_err(|error| MonitorError::from(error.message))?;
+                for segment in segments {
+                    self.cpu.load(&segment.code, segment.address)?;
+                }
+                self.assembler = assembler;
+                self.cpu.set_symbols(self.assembler.symbols());
+                self.source = Some(args[0].to_string());
+                self.resolve_breakpoints_again()?;
+            }
+            "break" => {
+                let addr = self.resolve(arg(0)?)?;
+                if parse_number(args[0]).is_ok() {
+                    self.symbol_addresses.retain(|&symbol_addr| symbol_addr != addr);
+                    self.cpu.add_breakpoint(addr);
+                } else {
+                    self.symbolic_breakpoints.push(args[0].to_string());
+                    self.add_symbol_breakpoint(addr);
+                }
+            }
+            "reset" => self.cpu.reset(),
+            "run" => {
+                // Carry on past the breakpoint the last run stopped at
+                self.cpu.resume();
+                self.cpu.run_until_brk()?;
+                self.write_watches();
+            }
+            "step" => {
+                let count = match args.first() {
+                    Some(count) => parse_number(count)? as u32,
+                    None => 1,
+                };
+                self.cpu.resume();
+                self.cpu.step_n(count)?;
+                self.write_watches();
+            }
+            "watch" => {
+                let addr = self.resolve(arg(0)?)?;
+                if addr > 0xFF {
+                    return Err(MonitorError::not_zero_page(arg(0)?));
+                }
+                self.watches.push(addr as u8);
+            }
+            "limit" => {
+                let count = arg(0)?.parse().map_err(|_| MonitorError::invalid_count(args[0]))?;
+                self.cpu.set_limits(RunLimits::new().max_instructions(count));
+            }
+            "dump" => {
+                let start = parse_number(arg(0)?)?;
+                let end = parse_number(arg(1)?)?;
+                self.output.push_str(&self.cpu.hexdump(start..=end));
+            }
+            ">" => {
+                let addr = parse_hex(arg(0)?)?;
+                let bytes = args[1..].iter().map(|value| parse_hex(value).and_then(|byte| to_byte(byte, value)));
+                let bytes = bytes.collect::<Result<Vec<u8>, MonitorError>>()?;
+                self.cpu.memory.write_bytes(addr, &bytes);
+            }
+            "fill" => {
+                let start = parse_number(arg(0)?)?;
+                let end = parse_number(arg(1)?)?;
+                let byte = to_byte(parse_number(arg(2)?)?, args[2])?;
+                self.cpu.memory.fill(start..=end, byte);
+            }
+            "move" => {
+                let start = parse_number(arg(0)?)?;
+                let end = parse_number(arg(1)?)?;
+                let dest = parse_number(arg(2)?)?;
+                self.cpu.memory.move_block(start..=end, dest);
+            }
+            "compare" => {
+                let start = parse_number(arg(0)?)?;
+                let end = parse_number(arg(1)?)?;
+                let other = parse_number(arg(2)?)?;
+                for addr in self.cpu.memory.compare_block(start..=end, other) {
+                    let other_addr = other.wrapping_add(addr.wrapping_sub(start));
+                    writeln!(self.output,
+                             "{:04X}: {:02X} {:04X}: {:02X}",
+                             addr,
+                             self.cpu.memory.read_byte(addr),
+                             other_addr,
+                             self.cpu.memory.read_byte(other_addr))
+                        .unwrap();
+                }
+            }
+            "verify" => {
+                let assertion = line.trim_start()[command.len()..].trim();
+                self.cpu.verify(assertion).map_err(|error| MonitorError::from(error.message))?;
+            }
+            "convert" => {
+                let source = read_file(arg(0)?)?;
+                let dialect = Dialect::from_name(arg(1)?).ok_or_else(|| MonitorError::unknown_dialect(args[1]))?;
+                let conversion = convert::convert(&String::from_utf8_lossy(&source), dialect);
+                self.output.push_str(&conversion.source);
+                for issue in conversion.issues {
+                    writeln!(self.output, "; line {}: {}", issue.line, issue.message).unwrap();
+                }
+            }
+            _ => return Err(MonitorError::unknown_command(&command)),
+        }
+
+        Ok(())
+    }
+
+    /// Resolves an address, a label from the last assembled file or a
+    /// `file:line` of it
+    fn resolve(&self, value: &str) -> Result<u16, MonitorError> {
+        if let Ok(addr) = parse_number(value) {
+            return Ok(addr);
+        }
+
+        if let Some(index) = value.rfind(':') {
+            let (file, line) = (&value[..index], &value[index + 1..]);
+            if !self.is_source(file) {
+                return Err(MonitorError::not_assembled(file));
+            }
+
+            return line.parse()
+                .ok()
+                .and_then(|line| self.assembler.line_address(line))
+                .ok_or_else(|| MonitorError::no_instruction_on_line(line, file));
+        }
+
+        self.assembler.label_address(value).ok_or_else(|| MonitorError::unknown_label(value))
+    }
+
+    /// Returns true if the path names the last assembled file, either as
+    /// it was given to `asm` or by its file name alone
+    fn is_source(&self, file: &str) -> bool {
+        match self.source {
+            Some(ref source) => source == file || Path::new(source).file_name() == Some(file.as_ref()),
+            None => false,
+        }
+    }
+
+    /// Moves the breakpoints given as labels or lines to wherever they are
+    /// in the code just assembled. If any of them can't be found, none of
+    /// them move.
+    fn resolve_breakpoints_again(&mut self) -> Result<(), MonitorError> {
+        let addrs = self.symbolic_breakpoints
+            .iter()
+            .map(|value| self.resolve(value))
+            .collect::<Result<Vec<u16>, MonitorError>>()?;
+
+        for addr in self.symbol_addresses.drain(..) {
+            self.cpu.remove_breakpoint(addr);
+        }
+        for addr in addrs {
+            self.add_symbol_breakpoint(addr);
+        }
+
+        Ok(())
+    }
+
+    fn add_symbol_breakpoint(&mut self, addr: u16) {
+        if !self.cpu.breakpoints().contains(&addr) {
+            self.cpu.add_breakpoint(addr);
+            self.symbol_addresses.push(addr);
+        }
+    }
+
+    fn write_watches(&mut self) {
+        for &addr in &self.watches {
+            writeln!(self.output, "ZP ${:02X}: ${:04X}", addr, self.cpu.zp_word(addr)).unwrap();
+        }
+    }
+}
+
+fn parse_number(value: &str) -> Result<u16, MonitorError> {
+    cpu::parse_number(value).map_err(|error| MonitorError::from(error.message))
+}
+
+/// Parses a number in hexadecimal, as memory edits are written, with or
+/// without a leading `$`
+fn parse_hex(value: &str) -> Result<u16, MonitorError> {
+    let digits = value.strip_prefix('$').unwrap_or(value);
+    u16::from_str_radix(digits, 16).map_err(|_| MonitorError::invalid_value(value))
+}
+
+fn to_byte(value: u16, text: &str) -> Result<u8, MonitorError> {
+    if value > 0xFF {
+        return Err(MonitorError::not_a_byte(text));
+    }
+
+    Ok(value as u8)
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, MonitorError> {
+    let mut contents = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut contents))
+        .map_err(|error| MonitorError::from(format!("Unable to read '{}': {}", path, error)))?;
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::StopReason;
+
+    fn monitor(code: &[u8]) -> Monitor {
+        let mut cpu = Cpu::new();
+        cpu.load(code, None).unwrap();
+        cpu.reset();
+
+        Monitor::new(cpu)
+    }
+
+    fn error(message: &str, line: u32) -> MonitorError {
+        MonitorError { line, ..MonitorError::from(message.to_string()) }
+    }
+
+    #[test]
+    fn reports_the_failing_line() {
+        // LDA #$01
+        let mut monitor = monitor(&[0xA9, 0x01]);
+
+        let error = monitor.run_script("; check A\nstep\nverify A == 2\nstep").unwrap_err();
+
+        assert_eq!(3, error.line);
+        assert_eq!("'A == 2' failed, found $01", error.message);
+        assert_eq!(0xC002, monitor.cpu.registers.PC);
+    }
+
+    #[test]
+    fn run_stops_at_brk() {
+        // INX, INX, BRK
+        let mut monitor = monitor(&[0xE8, 0xE8, 0x00]);
+
+        monitor.execute("run").unwrap();
+
+        assert_eq!(0xC002, monitor.cpu.registers.PC);
+    }
+
+    #[test]
+    fn run_fails_when_a_spin_loop_reaches_the_limit() {
+        // JMP $C000
+        let mut monitor = monitor(&[0x4C, 0x00, 0xC0]);
+
+        let error = monitor.run_script("run").unwrap_err();
+
+        assert_eq!("Instructions limit reached at C000", error.message);
+        assert_eq!(2, error.exit_status());
+    }
+
+    #[test]
+    fn breakpoints_and_errors_come_from_the_cpu() {
+        // INX, unknown opcode
+        let mut monitor = monitor(&[0xE8, 0xFF]);
+
+        monitor.execute("break $C001").unwrap();
+        monitor.execute("run").unwrap();
+        let failure = monitor.run_script("run").unwrap_err();
+
+        assert_eq!(vec![0xC001], monitor.cpu.breakpoints());
+        assert_eq!(error("Unknown opcode FF at C001", 1), failure);
+        assert_eq!(1, failure.exit_status());
+    }
+
+    #[test]
+    fn edits_memory_the_running_program_sees() {
+        // LDX #$01, then nothing
+        let mut monitor = monitor(&[0xA2, 0x01]);
+
+        // LDA $0200, ADC #$01, BRK
+        monitor.run_script("
+            step
+            > C002 AD 00 02 69 01 00
+            fill $0200 $020F $41
+            run
+            verify A == $42
+        ").unwrap();
+
+        assert_eq!(Err(error("Not a byte: '100'", 1)), monitor.run_script("> C000 100"));
+    }
+
+    #[test]
+    fn moves_and_compares_blocks() {
+        let mut monitor = monitor(&[]);
+        // LDA #$05, BRK
+        monitor.cpu.memory.write_bytes(0x0300, &[0xA9, 0x05, 0x00]);
+
+        monitor.run_script("
+            move $0300 $0302 $C000
+            compare $0300 $0302 $C000
+            > 0301 07
+            compare $0300 $0302 $C000
+            run
+            verify A == 5
+        ").unwrap();
+
+        assert_eq!("0301: 07 C001: 05\n", monitor.output());
+    }
+
+    #[test]
+    fn breaks_on_labels_from_assembled_files() {
+        let path = ::std::env::temp_dir().join("rs6502_monitor_labels.asm");
+        ::std::fs::write(&path, "
+            .ORG $C000
+            LDX #$05
+            LOOP DEX
+            BNE LOOP
+        ").unwrap();
+
+        let mut monitor = Monitor::new(Cpu::new());
+        monitor.run_script(&format!("asm {}\nreset\nbreak LOOP\nrun\nrun\nverify X == 4",
+                                    path.display()))
+            .unwrap();
+
+        assert_eq!(Err(error("Unknown label: 'MISSING'", 1)),
+                   monitor.run_script("break MISSING"));
+    }
+
+    #[test]
+    fn breaks_on_lines_of_the_assembled_file() {
+        let path = ::std::env::temp_dir().join("rs6502_monitor_lines.asm");
+        ::std::fs::write(&path, ".ORG $C000\nLDX #$05\nLOOP DEX\nBNE LOOP\nBRK\n").unwrap();
+
+        let mut monitor = Monitor::new(Cpu::new());
+        monitor.run_script(&format!("asm {}\nreset\nbreak rs6502_monitor_lines.asm:4\nrun", path.display()))
+            .unwrap();
+
+        assert_eq!(Some(StopReason::Breakpoint(0xC003)), monitor.cpu.halted());
+        assert_eq!(Err(error("No instruction on line 9 of 'rs6502_monitor_lines.asm'", 1)),
+                   monitor.run_script("break rs6502_monitor_lines.asm:9"));
+        assert_eq!(Err(error("'other.asm' is not the assembled file", 1)),
+                   monitor.run_script("break other.asm:1"));
+    }
+
+    #[test]
+    fn breakpoints_follow_their_labels_and_lines_when_reassembled() {
+        let path = ::std::env::temp_dir().join("rs6502_monitor_reassemble.asm");
+        ::std::fs::write(&path, ".ORG $C000\nLDX #$05\nLOOP DEX\nNOP\nBNE LOOP\nBRK\n").unwrap();
+
+        let mut monitor = Monitor::new(Cpu::new());
+        monitor.run_script(&format!("asm {}\nbreak LOOP\nbreak {}:4", path.display(), path.display())).unwrap();
+        assert_eq!(vec![0xC002, 0xC003], monitor.cpu.breakpoints());
+
+        // Loading X from memory takes a byte more, moving the loop along
+        ::std::fs::write(&path, ".ORG $C000\nLDX $0200\nLOOP DEX\nNOP\nBNE LOOP\nBRK\n").unwrap();
+        monitor.run_script(&format!("asm {}\n> 0200 05\nreset\nrun\nverify X == 5", path.display())).unwrap();
+
+        assert_eq!(vec![0xC003, 0xC004], monitor.cpu.breakpoints());
+        assert_eq!(Some(StopReason::Breakpoint(0xC003)), monitor.cpu.halted());
+    }
+
+    #[test]
+    fn reassembly_only_moves_the_breakpoints_set_for_symbols() {
+        let path = ::std::env::temp_dir().join("rs6502_monitor_moves.asm");
+        ::std::fs::write(&path, ".ORG $C000\nLDX #$05\nLOOP DEX\nNOP\nBNE LOOP\nBRK\n").unwrap();
+
+        let mut monitor = Monitor::new(Cpu::new());
+        monitor.run_script(&format!("asm {}\nbreak LOOP\nbreak $C002\nbreak {}:4", path.display(), path.display()))
+            .unwrap();
+
+        ::std::fs::write(&path, ".ORG $C000\nLDX $0200\nLOOP DEX\nNOP\nBNE LOOP\nBRK\n").unwrap();
+        monitor.run_script(&format!("asm {}", path.display())).unwrap();
+        assert_eq!(vec![0xC002, 0xC003, 0xC004], monitor.cpu.breakpoints());
+
+        // Without LOOP none of the breakpoints can move
+        ::std::fs::write(&path, ".ORG $C000\nLDX #$05\nDEX\nBRK\n").unwrap();
+        assert_eq!(Err(error("Unknown label: 'LOOP'", 1)),
+                   monitor.run_script(&format!("asm {}", path.display())));
+        assert_eq!(vec![0xC002, 0xC003, 0xC004], monitor.cpu.breakpoints());
+    }
+
+    #[test]
+    fn writes_zero_page_watches_after_each_step() {
+        // LDA #$80, STA $FF
+        let mut monitor = monitor(&[0xA9, 0x80, 0x85, 0xFF]);
+        monitor.cpu.memory.write_byte(0x00, 0x12);
+
+        monitor.run_script("watch $FF\nstep 2").unwrap();
+
+        assert_eq!("ZP $FF: $1280\n", monitor.output());
+        assert_eq!(Err(error("Not a zero page address: '$0100'", 1)),
+                   monitor.run_script("watch $0100"));
+    }
+
+    #[test]
+    fn converts_source_from_other_dialects() {
+        let path = ::std::env::temp_dir().join("rs6502_monitor_convert.s");
+        ::std::fs::write(&path, "  lda #<data\n  rts\n").unwrap();
+
+        let mut monitor = Monitor::new(Cpu::new());
+        monitor.execute(&format!("convert {} ca65", path.display())).unwrap();
+
+        assert_eq!("; lda #<data\n    RTS\n; line 1: Unsupported '<': expressions aren't supported\n",
+                   monitor.output());
+        assert_eq!(Err(error("Unknown dialect: 'tass'. Expected dasm, acme or ca65", 1)),
+                   monitor.run_script(&format!("convert {} tass", path.display())));
+    }
+
+    #[test]
+    fn dumps_sixteen_bytes_per_line() {
+        let mut monitor = monitor(&[]);
+        monitor.cpu.memory.write_bytes(0x0200, &[0xAA; 17]);
+
+        monitor.execute("dump $0200 $0210").unwrap();
+
+        assert_eq!("0200  AA AA AA AA AA AA AA AA  AA AA AA AA AA AA AA AA  |................|\n\
+                    0210  AA                                                |.|\n",
+                   monitor.output());
+    }
+}
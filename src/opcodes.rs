@@ -1,4 +1,8 @@
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum AddressingMode {
     Unknown,
     Implied,
@@ -14,8 +18,46 @@ pub enum AddressingMode {
     Indirect,
     IndirectX,
     IndirectY,
+    ZeroPageIndirect,
+    AbsoluteIndexedIndirect,
+}
+
+/// The processor an opcode is decoded for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Default)]
+pub enum CpuVariant {
+    /// The original NMOS 6502
+    #[default]
+    Nmos6502,
+    /// The CMOS 65C02, which adds `BRA`, `PHX`/`PHY`/`PLX`/`PLY`, `STZ`,
+    /// `TRB`/`TSB`, `INC A`/`DEC A`, `BIT #imm`, `BIT zp,X`, `BIT abs,X`,
+    /// `JMP (abs,X)` and `(zp)` addressing for the ALU instructions, and
+    /// runs every byte it doesn't define as a `NOP`
+    Cmos65C02,
+    /// The Ricoh 2A03 used in the NES: an NMOS 6502 without decimal mode.
+    /// The decimal flag can still be set and cleared, but `ADC` and `SBC`
+    /// always operate in binary.
+    Ricoh2A03,
+}
+
+impl CpuVariant {
+    /// Returns true if `ADC` and `SBC` honour the decimal flag
+    pub fn has_decimal_mode(&self) -> bool {
+        *self != CpuVariant::Ricoh2A03
+    }
+}
+
+impl fmt::Display for CpuVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            CpuVariant::Nmos6502 => "NMOS 6502",
+            CpuVariant::Cmos65C02 => "65C02",
+            CpuVariant::Ricoh2A03 => "Ricoh 2A03",
+        })
+    }
 }
 
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct OpCode {
     pub code: u8,
@@ -26,15 +68,105 @@ pub struct OpCode {
 }
 
 impl OpCode {
-    pub fn from_raw_byte<'opcode>(byte: u8) -> Option<&'opcode OpCode> {
-        OpCodes.iter().find(|opcode| opcode.code == byte)
+    /// Looks up an opcode by its byte, including the stable undocumented
+    /// opcodes
+    pub fn from_raw_byte(byte: u8) -> Option<&'static OpCode> {
+        NMOS_DECODE_TABLE[byte as usize].as_ref()
+    }
+
+    /// Looks up an opcode by its byte on the given processor. The 65C02
+    /// replaces the undocumented opcodes of the NMOS 6502 with its own, and
+    /// `NOP`s for the bytes it doesn't define.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{CpuVariant, OpCode};
+    ///
+    /// assert_eq!("SLO", OpCode::decode(0x07, CpuVariant::Nmos6502).unwrap().mnemonic);
+    /// assert_eq!("STZ", OpCode::decode(0x64, CpuVariant::Cmos65C02).unwrap().mnemonic);
+    /// assert_eq!("NOP", OpCode::decode(0x07, CpuVariant::Cmos65C02).unwrap().mnemonic);
+    /// ```
+    pub fn decode(byte: u8, variant: CpuVariant) -> Option<&'static OpCode> {
+        match variant {
+            CpuVariant::Nmos6502 | CpuVariant::Ricoh2A03 => OpCode::from_raw_byte(byte),
+            CpuVariant::Cmos65C02 => CMOS_DECODE_TABLE[byte as usize].as_ref(),
+        }
+    }
+
+    /// Looks up an opcode like `decode`, but by searching the opcode lists
+    /// the way bytes were decoded before there were decode tables. The
+    /// dispatch benchmark measures against it.
+    #[cfg(feature = "reference-dispatch")]
+    pub fn search(byte: u8, variant: CpuVariant) -> Option<&'static OpCode> {
+        let lists: &[&'static [OpCode]] = match variant {
+            CpuVariant::Nmos6502 | CpuVariant::Ricoh2A03 => &[&OPCODES, &UNDOCUMENTED_OPCODES],
+            CpuVariant::Cmos65C02 => &[&CMOS_OPCODES, &OPCODES, &CMOS_NOPS],
+        };
+
+        lists.iter().flat_map(|list| list.iter()).find(|opcode| opcode.code == byte)
+    }
+
+    /// Returns true for the undocumented opcodes, such as `LAX` or the
+    /// multi-byte `NOP`s, that only work on an NMOS 6502
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::OpCode;
+    ///
+    /// assert!(OpCode::from_raw_byte(0xA7).unwrap().is_undocumented());
+    /// assert!(!OpCode::from_raw_byte(0xA5).unwrap().is_undocumented());
+    /// ```
+    pub fn is_undocumented(&self) -> bool {
+        UNDOCUMENTED_OPCODES.contains(self)
+    }
+
+    /// Returns true for the instructions and addressing modes the 65C02
+    /// added, such as `STZ` or `LDA ($10)`
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{AddressingMode, OpCode};
+    ///
+    /// assert!(OpCode::from_mnemonic("STZ").unwrap().is_cmos_only());
+    /// assert!(OpCode::from_mnemonic_and_addressing_mode("LDA", AddressingMode::ZeroPageIndirect)
+    ///     .unwrap()
+    ///     .is_cmos_only());
+    /// assert!(!OpCode::from_mnemonic("LDA").unwrap().is_cmos_only());
+    /// ```
+    pub fn is_cmos_only(&self) -> bool {
+        CMOS_OPCODES.contains(self) && !OPCODES.iter().any(|opcode| opcode.code == self.code)
+    }
+
+    /// Returns true for the `NOP`s the 65C02 runs in place of the bytes it
+    /// leaves undefined, which assemblers have no mnemonic for
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{CpuVariant, OpCode};
+    ///
+    /// assert!(OpCode::decode(0x5C, CpuVariant::Cmos65C02).unwrap().is_cmos_nop());
+    /// assert!(!OpCode::decode(0xEA, CpuVariant::Cmos65C02).unwrap().is_cmos_nop());
+    /// ```
+    pub fn is_cmos_nop(&self) -> bool {
+        CMOS_NOPS.contains(self)
+    }
+
+    /// Returns true if the opcode runs on the given processor: the NMOS
+    /// parts lack the 65C02 additions, and the 65C02 lacks the undocumented
+    /// opcodes
+    pub fn is_available_on(&self, variant: CpuVariant) -> bool {
+        match variant {
+            CpuVariant::Nmos6502 | CpuVariant::Ricoh2A03 => !self.is_cmos_only(),
+            CpuVariant::Cmos65C02 => !self.is_undocumented(),
+        }
     }
 
     pub fn from_mnemonic<S>(input: S) -> Option<OpCode>
         where S: Into<String>
     {
         let input = input.into();
-        OpCodes.iter()
+        OPCODES.iter()
+            .chain(CMOS_OPCODES.iter())
             .find(|opcode| opcode.mnemonic == input.to_uppercase())
             .cloned()
     }
@@ -42,15 +174,52 @@ impl OpCode {
     pub fn from_mnemonic_and_addressing_mode<S>(input: S, mode: AddressingMode) -> Option<OpCode>
         where S: Into<String>
     {
-        let input = input.into();
-        OpCodes.iter()
-            .find(|opcode| opcode.mnemonic == input.to_uppercase() && opcode.mode == mode)
-            .cloned()
+        let input = input.into().to_uppercase();
+        mnemonic_table().get(&(&input[..], mode)).cloned()
     }
 }
 
+/// The opcodes the NMOS 6502 decodes, indexed by byte
+static NMOS_DECODE_TABLE: [Option<OpCode>; 256] = decode_table(&[&UNDOCUMENTED_OPCODES, &OPCODES]);
+
+/// The opcodes the 65C02 decodes, indexed by byte
+static CMOS_DECODE_TABLE: [Option<OpCode>; 256] = decode_table(&[&CMOS_NOPS, &OPCODES, &CMOS_OPCODES]);
+
+/// Builds a decode table from lists of opcodes. Where two lists have an
+/// opcode for the same byte, the later list wins.
+const fn decode_table(lists: &[&[OpCode]]) -> [Option<OpCode>; 256] {
+    let mut table = [None; 256];
+
+    let mut list = 0;
+    while list < lists.len() {
+        let mut i = 0;
+        while i < lists[list].len() {
+            let opcode = lists[list][i];
+            table[opcode.code as usize] = Some(opcode);
+            i += 1;
+        }
+        list += 1;
+    }
+
+    table
+}
+
+/// The documented and 65C02 opcodes by mnemonic and addressing mode, for
+/// the assembler. The NMOS timing wins where both processors have the
+/// same instruction.
+fn mnemonic_table() -> &'static HashMap<(&'static str, AddressingMode), OpCode> {
+    static TABLE: OnceLock<HashMap<(&'static str, AddressingMode), OpCode>> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        CMOS_OPCODES.iter()
+            .chain(OPCODES.iter())
+            .map(|opcode| ((opcode.mnemonic, opcode.mode), *opcode))
+            .collect()
+    })
+}
+
 // List of OpCodes. Source: http://www.6502.org/tutorials/6502opcodes.html#ADC
-static OpCodes: [OpCode; 151] = [OpCode {
+static OPCODES: [OpCode; 151] = [OpCode {
                                      code: 0x69,
                                      mnemonic: "ADC",
                                      length: 2,
@@ -1107,3 +1276,1456 @@ static OpCodes: [OpCode; 151] = [OpCode {
                                      time: 4,
                                      mode: AddressingMode::Absolute,
                                  }];
+
+// The stable undocumented opcodes of the NMOS 6502. Source:
+// http://www.oxyron.de/html/opcodes02.html
+static UNDOCUMENTED_OPCODES: [OpCode; 97] = [OpCode {
+                                                 code: 0xA7,
+                                                 mnemonic: "LAX",
+                                                 length: 2,
+                                                 time: 3,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0xB7,
+                                                 mnemonic: "LAX",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageY,
+                                             },
+                                             OpCode {
+                                                 code: 0xAF,
+                                                 mnemonic: "LAX",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0xBF,
+                                                 mnemonic: "LAX",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0xA3,
+                                                 mnemonic: "LAX",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0xB3,
+                                                 mnemonic: "LAX",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0x87,
+                                                 mnemonic: "SAX",
+                                                 length: 2,
+                                                 time: 3,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x97,
+                                                 mnemonic: "SAX",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageY,
+                                             },
+                                             OpCode {
+                                                 code: 0x8F,
+                                                 mnemonic: "SAX",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0x83,
+                                                 mnemonic: "SAX",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0xC7,
+                                                 mnemonic: "DCP",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0xD7,
+                                                 mnemonic: "DCP",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0xCF,
+                                                 mnemonic: "DCP",
+                                                 length: 3,
+                                                 time: 6,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0xDF,
+                                                 mnemonic: "DCP",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0xDB,
+                                                 mnemonic: "DCP",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0xC3,
+                                                 mnemonic: "DCP",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0xD3,
+                                                 mnemonic: "DCP",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0xE7,
+                                                 mnemonic: "ISC",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0xF7,
+                                                 mnemonic: "ISC",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0xEF,
+                                                 mnemonic: "ISC",
+                                                 length: 3,
+                                                 time: 6,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0xFF,
+                                                 mnemonic: "ISC",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0xFB,
+                                                 mnemonic: "ISC",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0xE3,
+                                                 mnemonic: "ISC",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0xF3,
+                                                 mnemonic: "ISC",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0x07,
+                                                 mnemonic: "SLO",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x17,
+                                                 mnemonic: "SLO",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x0F,
+                                                 mnemonic: "SLO",
+                                                 length: 3,
+                                                 time: 6,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0x1F,
+                                                 mnemonic: "SLO",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x1B,
+                                                 mnemonic: "SLO",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0x03,
+                                                 mnemonic: "SLO",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0x13,
+                                                 mnemonic: "SLO",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0x27,
+                                                 mnemonic: "RLA",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x37,
+                                                 mnemonic: "RLA",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x2F,
+                                                 mnemonic: "RLA",
+                                                 length: 3,
+                                                 time: 6,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0x3F,
+                                                 mnemonic: "RLA",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x3B,
+                                                 mnemonic: "RLA",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0x23,
+                                                 mnemonic: "RLA",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0x33,
+                                                 mnemonic: "RLA",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0x47,
+                                                 mnemonic: "SRE",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x57,
+                                                 mnemonic: "SRE",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x4F,
+                                                 mnemonic: "SRE",
+                                                 length: 3,
+                                                 time: 6,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0x5F,
+                                                 mnemonic: "SRE",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x5B,
+                                                 mnemonic: "SRE",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0x43,
+                                                 mnemonic: "SRE",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0x53,
+                                                 mnemonic: "SRE",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0x67,
+                                                 mnemonic: "RRA",
+                                                 length: 2,
+                                                 time: 5,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x77,
+                                                 mnemonic: "RRA",
+                                                 length: 2,
+                                                 time: 6,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x6F,
+                                                 mnemonic: "RRA",
+                                                 length: 3,
+                                                 time: 6,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0x7F,
+                                                 mnemonic: "RRA",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x7B,
+                                                 mnemonic: "RRA",
+                                                 length: 3,
+                                                 time: 7,
+                                                 mode: AddressingMode::AbsoluteY,
+                                             },
+                                             OpCode {
+                                                 code: 0x63,
+                                                 mnemonic: "RRA",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectX,
+                                             },
+                                             OpCode {
+                                                 code: 0x73,
+                                                 mnemonic: "RRA",
+                                                 length: 2,
+                                                 time: 8,
+                                                 mode: AddressingMode::IndirectY,
+                                             },
+                                             OpCode {
+                                                 code: 0x0B,
+                                                 mnemonic: "ANC",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x2B,
+                                                 mnemonic: "ANC",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x4B,
+                                                 mnemonic: "ALR",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x6B,
+                                                 mnemonic: "ARR",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0xCB,
+                                                 mnemonic: "AXS",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0xEB,
+                                                 mnemonic: "SBC",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x1A,
+                                                 mnemonic: "NOP",
+                                                 length: 1,
+                                                 time: 2,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x3A,
+                                                 mnemonic: "NOP",
+                                                 length: 1,
+                                                 time: 2,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x5A,
+                                                 mnemonic: "NOP",
+                                                 length: 1,
+                                                 time: 2,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x7A,
+                                                 mnemonic: "NOP",
+                                                 length: 1,
+                                                 time: 2,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0xDA,
+                                                 mnemonic: "NOP",
+                                                 length: 1,
+                                                 time: 2,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0xFA,
+                                                 mnemonic: "NOP",
+                                                 length: 1,
+                                                 time: 2,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x80,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x82,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x89,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0xC2,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0xE2,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 2,
+                                                 mode: AddressingMode::Immediate,
+                                             },
+                                             OpCode {
+                                                 code: 0x04,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 3,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x44,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 3,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x64,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 3,
+                                                 mode: AddressingMode::ZeroPage,
+                                             },
+                                             OpCode {
+                                                 code: 0x14,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x34,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x54,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x74,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0xD4,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0xF4,
+                                                 mnemonic: "NOP",
+                                                 length: 2,
+                                                 time: 4,
+                                                 mode: AddressingMode::ZeroPageX,
+                                             },
+                                             OpCode {
+                                                 code: 0x0C,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::Absolute,
+                                             },
+                                             OpCode {
+                                                 code: 0x1C,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x3C,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x5C,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x7C,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0xDC,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0xFC,
+                                                 mnemonic: "NOP",
+                                                 length: 3,
+                                                 time: 4,
+                                                 mode: AddressingMode::AbsoluteX,
+                                             },
+                                             OpCode {
+                                                 code: 0x02,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x12,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x22,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x32,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x42,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x52,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x62,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x72,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0x92,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0xB2,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0xD2,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             },
+                                             OpCode {
+                                                 code: 0xF2,
+                                                 mnemonic: "KIL",
+                                                 length: 1,
+                                                 time: 0,
+                                                 mode: AddressingMode::Implied,
+                                             }];
+
+// The opcodes the CMOS 65C02 adds to the instruction set. JMP ($0000)
+// is listed again as it takes an extra cycle on the 65C02. Source:
+// http://6502.org/tutorials/65c02opcodes.html
+static CMOS_OPCODES: [OpCode; 28] = [OpCode {
+                                         code: 0x80,
+                                         mnemonic: "BRA",
+                                         length: 2,
+                                         time: 2,
+                                         mode: AddressingMode::Relative,
+                                     },
+                                     OpCode {
+                                         code: 0xDA,
+                                         mnemonic: "PHX",
+                                         length: 1,
+                                         time: 3,
+                                         mode: AddressingMode::Implied,
+                                     },
+                                     OpCode {
+                                         code: 0x5A,
+                                         mnemonic: "PHY",
+                                         length: 1,
+                                         time: 3,
+                                         mode: AddressingMode::Implied,
+                                     },
+                                     OpCode {
+                                         code: 0xFA,
+                                         mnemonic: "PLX",
+                                         length: 1,
+                                         time: 4,
+                                         mode: AddressingMode::Implied,
+                                     },
+                                     OpCode {
+                                         code: 0x7A,
+                                         mnemonic: "PLY",
+                                         length: 1,
+                                         time: 4,
+                                         mode: AddressingMode::Implied,
+                                     },
+                                     OpCode {
+                                         code: 0x64,
+                                         mnemonic: "STZ",
+                                         length: 2,
+                                         time: 3,
+                                         mode: AddressingMode::ZeroPage,
+                                     },
+                                     OpCode {
+                                         code: 0x74,
+                                         mnemonic: "STZ",
+                                         length: 2,
+                                         time: 4,
+                                         mode: AddressingMode::ZeroPageX,
+                                     },
+                                     OpCode {
+                                         code: 0x9C,
+                                         mnemonic: "STZ",
+                                         length: 3,
+                                         time: 4,
+                                         mode: AddressingMode::Absolute,
+                                     },
+                                     OpCode {
+                                         code: 0x9E,
+                                         mnemonic: "STZ",
+                                         length: 3,
+                                         time: 5,
+                                         mode: AddressingMode::AbsoluteX,
+                                     },
+                                     OpCode {
+                                         code: 0x14,
+                                         mnemonic: "TRB",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPage,
+                                     },
+                                     OpCode {
+                                         code: 0x1C,
+                                         mnemonic: "TRB",
+                                         length: 3,
+                                         time: 6,
+                                         mode: AddressingMode::Absolute,
+                                     },
+                                     OpCode {
+                                         code: 0x04,
+                                         mnemonic: "TSB",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPage,
+                                     },
+                                     OpCode {
+                                         code: 0x0C,
+                                         mnemonic: "TSB",
+                                         length: 3,
+                                         time: 6,
+                                         mode: AddressingMode::Absolute,
+                                     },
+                                     OpCode {
+                                         code: 0x72,
+                                         mnemonic: "ADC",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0x32,
+                                         mnemonic: "AND",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0xD2,
+                                         mnemonic: "CMP",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0x52,
+                                         mnemonic: "EOR",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0xB2,
+                                         mnemonic: "LDA",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0x12,
+                                         mnemonic: "ORA",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0xF2,
+                                         mnemonic: "SBC",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0x92,
+                                         mnemonic: "STA",
+                                         length: 2,
+                                         time: 5,
+                                         mode: AddressingMode::ZeroPageIndirect,
+                                     },
+                                     OpCode {
+                                         code: 0x6C,
+                                         mnemonic: "JMP",
+                                         length: 3,
+                                         time: 6,
+                                         mode: AddressingMode::Indirect,
+                                     },
+                                     OpCode {
+                                         code: 0x1A,
+                                         mnemonic: "INC",
+                                         length: 1,
+                                         time: 2,
+                                         mode: AddressingMode::Accumulator,
+                                     },
+                                     OpCode {
+                                         code: 0x3A,
+                                         mnemonic: "DEC",
+                                         length: 1,
+                                         time: 2,
+                                         mode: AddressingMode::Accumulator,
+                                     },
+                                     OpCode {
+                                         code: 0x89,
+                                         mnemonic: "BIT",
+                                         length: 2,
+                                         time: 2,
+                                         mode: AddressingMode::Immediate,
+                                     },
+                                     OpCode {
+                                         code: 0x34,
+                                         mnemonic: "BIT",
+                                         length: 2,
+                                         time: 4,
+                                         mode: AddressingMode::ZeroPageX,
+                                     },
+                                     OpCode {
+                                         code: 0x3C,
+                                         mnemonic: "BIT",
+                                         length: 3,
+                                         time: 4,
+                                         mode: AddressingMode::AbsoluteX,
+                                     },
+                                     OpCode {
+                                         code: 0x7C,
+                                         mnemonic: "JMP",
+                                         length: 3,
+                                         time: 6,
+                                         mode: AddressingMode::AbsoluteIndexedIndirect,
+                                     }];
+
+// The bytes the 65C02 leaves undefined. Unlike the NMOS 6502 it runs every
+// one of them as a NOP of a fixed length and timing, so they aren't in the
+// assembler's tables. The bit instructions Rockwell and WDC put at $x7 and
+// $xF aren't emulated; those bytes are the one-cycle NOPs of the original
+// 65C02. Source: http://6502.org/tutorials/65c02opcodes.html
+static CMOS_NOPS: [OpCode; 78] = [OpCode {
+                                      code: 0x02,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0x22,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0x42,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0x62,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0x82,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0xC2,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0xE2,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 2,
+                                      mode: AddressingMode::Immediate,
+                                  },
+                                  OpCode {
+                                      code: 0x44,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 3,
+                                      mode: AddressingMode::ZeroPage,
+                                  },
+                                  OpCode {
+                                      code: 0x54,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 4,
+                                      mode: AddressingMode::ZeroPageX,
+                                  },
+                                  OpCode {
+                                      code: 0xD4,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 4,
+                                      mode: AddressingMode::ZeroPageX,
+                                  },
+                                  OpCode {
+                                      code: 0xF4,
+                                      mnemonic: "NOP",
+                                      length: 2,
+                                      time: 4,
+                                      mode: AddressingMode::ZeroPageX,
+                                  },
+                                  OpCode {
+                                      code: 0x5C,
+                                      mnemonic: "NOP",
+                                      length: 3,
+                                      time: 8,
+                                      mode: AddressingMode::Absolute,
+                                  },
+                                  OpCode {
+                                      code: 0xDC,
+                                      mnemonic: "NOP",
+                                      length: 3,
+                                      time: 4,
+                                      mode: AddressingMode::Absolute,
+                                  },
+                                  OpCode {
+                                      code: 0xFC,
+                                      mnemonic: "NOP",
+                                      length: 3,
+                                      time: 4,
+                                      mode: AddressingMode::Absolute,
+                                  },
+                                  OpCode {
+                                      code: 0x03,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x13,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x23,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x33,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x43,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x53,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x63,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x73,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x83,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x93,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xA3,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xB3,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xC3,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xD3,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xE3,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xF3,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x07,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x17,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x27,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x37,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x47,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x57,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x67,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x77,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x87,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x97,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xA7,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xB7,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xC7,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xD7,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xE7,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xF7,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x0B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x1B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x2B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x3B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x4B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x5B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x6B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x7B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x8B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x9B,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xAB,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xBB,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xCB,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xDB,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xEB,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xFB,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x0F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x1F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x2F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x3F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x4F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x5F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x6F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x7F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x8F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0x9F,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xAF,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xBF,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xCF,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xDF,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xEF,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  },
+                                  OpCode {
+                                      code: 0xFF,
+                                      mnemonic: "NOP",
+                                      length: 1,
+                                      time: 1,
+                                      mode: AddressingMode::Implied,
+                                  }];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_tables_match_the_opcode_lists() {
+        for byte in 0..=0xFF {
+            let nmos = OPCODES.iter().chain(UNDOCUMENTED_OPCODES.iter()).find(|opcode| opcode.code == byte);
+            let cmos = CMOS_OPCODES.iter()
+                .chain(OPCODES.iter())
+                .chain(CMOS_NOPS.iter())
+                .find(|opcode| opcode.code == byte);
+
+            assert_eq!(nmos, OpCode::decode(byte, CpuVariant::Nmos6502));
+            assert_eq!(cmos, OpCode::decode(byte, CpuVariant::Cmos65C02));
+        }
+    }
+}
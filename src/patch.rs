@@ -0,0 +1,247 @@
+use std::fmt;
+
+use assembler::CodeSegment;
+use cpu::Bus;
+
+const MEMORY_SIZE: usize = 0x10000;
+
+#[derive(Debug, PartialEq)]
+pub struct PatchError {
+    pub message: String,
+}
+
+impl PatchError {
+    fn invalid_line(line: usize, text: &str) -> PatchError {
+        PatchError::from(format!("Invalid patch on line {}: '{}'", line, text))
+    }
+
+    fn mismatch(change: &ByteChange, found: u8) -> PatchError {
+        PatchError::from(format!("Expected ${:02X} at ${:04X} but found ${:02X}",
+                                 change.original,
+                                 change.address,
+                                 found))
+    }
+
+    fn out_of_range(address: u16) -> PatchError {
+        PatchError::from(format!("Address ${:04X} is outside of the image", address))
+    }
+}
+
+impl From<String> for PatchError {
+    fn from(message: String) -> PatchError {
+        PatchError { message }
+    }
+}
+
+/// A byte that differs between two images
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ByteChange {
+    pub address: u16,
+    pub original: u8,
+    pub new: u8,
+}
+
+/// A list of byte changes that turns one image into another. As text, a
+/// patch is one change per line of the form `C003 EA 60` (address,
+/// original byte, new byte), and lines starting with `;` are comments.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Patch {
+    pub changes: Vec<ByteChange>,
+}
+
+impl Patch {
+    /// Compares two assembled images byte by byte. Addresses that an image
+    /// does not cover are treated as `$00`, as they are in a new `MemoryBus`.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{ByteChange, CodeSegment, Patch};
+    ///
+    /// let original = vec![CodeSegment { address: 0xC000, code: vec![0xA9, 0x01, 0x60] }];
+    /// let modified = vec![CodeSegment { address: 0xC000, code: vec![0xA9, 0x02, 0x60] }];
+    ///
+    /// let patch = Patch::diff(&original, &modified);
+    ///
+    /// assert_eq!(vec![ByteChange { address: 0xC001, original: 0x01, new: 0x02 }], patch.changes);
+    /// ```
+    pub fn diff(original: &[CodeSegment], modified: &[CodeSegment]) -> Patch {
+        let original = flatten(original);
+        let modified = flatten(modified);
+
+        let changes = (0..MEMORY_SIZE)
+            .filter(|&addr| original[addr] != modified[addr])
+            .map(|addr| {
+                ByteChange {
+                    address: addr as u16,
+                    original: original[addr],
+                    new: modified[addr],
+                }
+            })
+            .collect();
+
+        Patch { changes }
+    }
+
+    /// Parses a patch from its text form
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::Patch;
+    ///
+    /// let patch = Patch::parse("
+    ///     ; Skip the copy protection check
+    ///     C010 20 EA
+    ///     C011 00 EA
+    ///     C012 D0 EA
+    /// ").unwrap();
+    ///
+    /// assert_eq!(3, patch.changes.len());
+    /// ```
+    pub fn parse(text: &str) -> Result<Patch, PatchError> {
+        let mut changes = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            let values: Vec<&str> = line.split_whitespace().collect();
+            if values.len() != 3 {
+                return Err(PatchError::invalid_line(index + 1, line));
+            }
+
+            match (u16::from_str_radix(values[0], 16),
+                   u8::from_str_radix(values[1], 16),
+                   u8::from_str_radix(values[2], 16)) {
+                (Ok(address), Ok(original), Ok(new)) => {
+                    changes.push(ByteChange {
+                        address,
+                        original,
+                        new,
+                    })
+                }
+                _ => return Err(PatchError::invalid_line(index + 1, line)),
+            }
+        }
+
+        Ok(Patch { changes })
+    }
+
+    /// Applies the patch to memory, after checking that every byte it
+    /// changes still holds its original value. Nothing is written if any
+    /// byte differs.
+    ///
+    /// # Example
+    /// ```
+    /// use rs6502::{Cpu, Patch};
+    ///
+    /// let mut cpu = Cpu::new();
+    /// cpu.memory.write_bytes(0xC000, &[0xA9, 0x01]);
+    ///
+    /// Patch::parse("C001 01 FF").unwrap().apply(&mut cpu.memory).unwrap();
+    ///
+    /// assert_eq!(0xFF, cpu.memory.read_byte(0xC001));
+    /// assert!(Patch::parse("C001 01 FF").unwrap().apply(&mut cpu.memory).is_err());
+    /// ```
+    pub fn apply<B>(&self, bus: &mut B) -> Result<(), PatchError>
+        where B: Bus
+    {
+        for change in &self.changes {
+            let found = bus.read_byte(change.address);
+            if found != change.original {
+                return Err(PatchError::mismatch(change, found));
+            }
+        }
+
+        for change in &self.changes {
+            bus.write_byte(change.address, change.new);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the patch to a raw image, such as a ROM file, that is
+    /// loaded at `base`. Like `apply`, nothing is written if any original
+    /// byte differs.
+    pub fn apply_to_image(&self, image: &mut [u8], base: u16) -> Result<(), PatchError> {
+        for change in &self.changes {
+            let offset = change.address.wrapping_sub(base) as usize;
+            match image.get(offset) {
+                Some(&found) if found != change.original => return Err(PatchError::mismatch(change, found)),
+                Some(_) => {}
+                None => return Err(PatchError::out_of_range(change.address)),
+            }
+        }
+
+        for change in &self.changes {
+            image[change.address.wrapping_sub(base) as usize] = change.new;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{:04X} {:02X} {:02X}", change.address, change.original, change.new)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn flatten(segments: &[CodeSegment]) -> Vec<u8> {
+    let mut memory = vec![0x00; MEMORY_SIZE];
+    for segment in segments {
+        for (offset, byte) in segment.code.iter().enumerate() {
+            memory[(segment.address as usize + offset) % MEMORY_SIZE] = *byte;
+        }
+    }
+
+    memory
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let original = vec![CodeSegment { address: 0xC000, code: vec![0xEA, 0xEA] }];
+        let modified = vec![CodeSegment { address: 0xC000, code: vec![0xEA, 0x60, 0x00] }];
+
+        let patch = Patch::diff(&original, &modified);
+
+        assert_eq!("C001 EA 60\n", patch.to_string());
+        assert_eq!(patch, Patch::parse(&patch.to_string()).unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(PatchError::from(String::from("Invalid patch on line 2: 'C000 EA'")),
+                   Patch::parse("C000 EA 60\nC000 EA").unwrap_err());
+        assert!(Patch::parse("C000 EA 160").is_err());
+    }
+
+    #[test]
+    fn applies_to_an_image_at_a_base_address() {
+        let mut image = vec![0xEA, 0xEA, 0xEA];
+        let patch = Patch::parse("E002 EA 60").unwrap();
+
+        patch.apply_to_image(&mut image, 0xE000).unwrap();
+
+        assert_eq!(vec![0xEA, 0xEA, 0x60], image);
+        assert!(Patch::parse("E003 EA 60").unwrap().apply_to_image(&mut image, 0xE000).is_err());
+    }
+
+    #[test]
+    fn writes_nothing_when_an_original_byte_differs() {
+        let mut image = vec![0xEA, 0xEA];
+        let patch = Patch::parse("0000 EA 60\n0001 00 60").unwrap();
+
+        assert!(patch.apply_to_image(&mut image, 0x0000).is_err());
+        assert_eq!(vec![0xEA, 0xEA], image);
+    }
+}
@@ -0,0 +1,365 @@
+//! A built-in battery of known-answer instruction tests, for checking at
+//! runtime that a Cpu configuration behaves as documented
+
+use std::fmt;
+
+use ::opcodes::CpuVariant;
+use cpu::{Cpu, CpuError};
+
+const ORIGIN: u16 = 0xC000;
+
+/// The registers, flags, cycles and memory a test checks
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct State {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    /// The status register, laid out as `NV-BDIZC`
+    pub p: u8,
+    pub pc: u16,
+    /// The cycles the test's steps took
+    pub cycles: u64,
+    /// The bytes checked, by address
+    pub memory: Vec<(u16, u8)>,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "A=${:02X} X=${:02X} Y=${:02X} S=${:02X} P=${:02X} PC=${:04X} cycles={}",
+               self.a,
+               self.x,
+               self.y,
+               self.s,
+               self.p,
+               self.pc,
+               self.cycles)?;
+        for &(addr, value) in &self.memory {
+            write!(f, " [${:04X}]=${:02X}", addr, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A test that gave the wrong answer, or failed to run
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub name: &'static str,
+    pub expected: State,
+    pub actual: Result<State, CpuError>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.actual {
+            Ok(ref actual) => write!(f, "{}: expected {}, got {}", self.name, self.expected, actual),
+            Err(ref error) => write!(f, "{}: expected {}, got {:?}", self.name, self.expected, error),
+        }
+    }
+}
+
+/// What a self-test run found
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub variant: CpuVariant,
+    /// The number of tests run
+    pub tests: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Returns true if every test passed
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f,
+                 "{:?}: {} of {} tests passed",
+                 self.variant,
+                 self.tests - self.failures.len(),
+                 self.tests)?;
+        for failure in &self.failures {
+            writeln!(f, "  {}", failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the self-test against a default Cpu
+///
+/// # Example
+/// ```
+/// let report = rs6502::selftest::run();
+///
+/// assert!(report.is_ok(), "{}", report);
+/// ```
+pub fn run() -> Report {
+    run_with(Cpu::new)
+}
+
+/// Runs the self-test against Cpus made by `make_cpu`, such as one set to
+/// the 65C02 variant. Each test gets a new Cpu, which is reset and run from
+/// `$C000`. Tests whose answer depends on the variant expect the answer
+/// for the variant of the Cpu.
+///
+/// # Example
+/// ```
+/// use rs6502::{selftest, Cpu, CpuVariant};
+///
+/// let report = selftest::run_with(|| {
+///     let mut cpu = Cpu::new();
+///     cpu.set_variant(CpuVariant::Cmos65C02);
+///     cpu
+/// });
+///
+/// assert_eq!(CpuVariant::Cmos65C02, report.variant);
+/// assert!(report.is_ok(), "{}", report);
+/// ```
+pub fn run_with<F>(make_cpu: F) -> Report
+    where F: Fn() -> Cpu
+{
+    let variant = make_cpu().variant();
+    let mut report = Report {
+        variant,
+        tests: 0,
+        failures: Vec::new(),
+    };
+
+    for case in CASES {
+        let expected = (case.expected)(variant);
+        let actual = run_case(make_cpu(), case, &expected);
+        report.tests += 1;
+        if actual.as_ref() != Ok(&expected) {
+            report.failures.push(Failure {
+                name: case.name,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    report
+}
+
+struct Case {
+    name: &'static str,
+    code: &'static [u8],
+    setup: fn(&mut Cpu),
+    steps: u32,
+    expected: fn(CpuVariant) -> State,
+}
+
+fn run_case(mut cpu: Cpu, case: &Case, expected: &State) -> Result<State, CpuError> {
+    cpu.load(case.code, ORIGIN)?;
+    cpu.reset();
+    cpu.registers.PC = ORIGIN;
+    (case.setup)(&mut cpu);
+
+    let cycles = cpu.cycles();
+    cpu.step_n(case.steps)?;
+
+    Ok(State {
+        a: cpu.registers.A,
+        x: cpu.registers.X,
+        y: cpu.registers.Y,
+        s: cpu.registers.S,
+        p: cpu.flags.to_u8(),
+        pc: cpu.registers.PC,
+        cycles: cpu.cycles() - cycles,
+        memory: expected.memory.iter().map(|&(addr, _)| (addr, cpu.memory.read_byte(addr))).collect(),
+    })
+}
+
+/// The state after a reset, with the interrupt flag and bit 5 set
+fn state(pc: u16, cycles: u64) -> State {
+    State {
+        a: 0x00,
+        x: 0x00,
+        y: 0x00,
+        s: 0xFF,
+        p: 0x24,
+        pc,
+        cycles,
+        memory: Vec::new(),
+    }
+}
+
+fn no_setup(_: &mut Cpu) {}
+
+static CASES: &[Case] = &[Case {
+                              name: "ADC sets overflow when two positives make a negative",
+                              // CLC, LDA #$50, ADC #$50
+                              code: &[0x18, 0xA9, 0x50, 0x69, 0x50],
+                              setup: no_setup,
+                              steps: 3,
+                              expected: |_| State { a: 0xA0, p: 0xE4, ..state(0xC005, 6) },
+                          },
+                          Case {
+                              name: "ADC adds in decimal mode",
+                              // SED, CLC, LDA #$19, ADC #$28
+                              code: &[0xF8, 0x18, 0xA9, 0x19, 0x69, 0x28],
+                              setup: no_setup,
+                              steps: 4,
+                              expected: |_| State { a: 0x47, p: 0x2C, ..state(0xC006, 8) },
+                          },
+                          Case {
+                              name: "SBC borrows below zero",
+                              // SEC, LDA #$00, SBC #$01
+                              code: &[0x38, 0xA9, 0x00, 0xE9, 0x01],
+                              setup: no_setup,
+                              steps: 3,
+                              expected: |_| State { a: 0xFF, p: 0xA4, ..state(0xC005, 6) },
+                          },
+                          Case {
+                              name: "INX wraps to zero",
+                              // LDX #$FF, INX
+                              code: &[0xA2, 0xFF, 0xE8],
+                              setup: no_setup,
+                              steps: 2,
+                              expected: |_| State { p: 0x26, ..state(0xC003, 4) },
+                          },
+                          Case {
+                              name: "Zero page indexing wraps within the zero page",
+                              // LDX #$01, LDA $FF,X
+                              code: &[0xA2, 0x01, 0xB5, 0xFF],
+                              setup: |cpu| {
+                                  cpu.memory.write_byte(0x0000, 0x42);
+                                  cpu.memory.write_byte(0x0100, 0x99);
+                              },
+                              steps: 2,
+                              expected: |_| State { a: 0x42, x: 0x01, ..state(0xC004, 6) },
+                          },
+                          Case {
+                              name: "JMP indirect through a pointer at the end of a page",
+                              // JMP ($02FF)
+                              code: &[0x6C, 0xFF, 0x02],
+                              setup: |cpu| {
+                                  cpu.memory.write_byte(0x02FF, 0x10);
+                                  cpu.memory.write_byte(0x0200, 0xC0);
+                                  cpu.memory.write_byte(0x0300, 0xD0);
+                              },
+                              steps: 1,
+                              expected: |variant| if variant == CpuVariant::Cmos65C02 {
+                                  state(0xD010, 6)
+                              } else {
+                                  state(0xC010, 5)
+                              },
+                          },
+                          Case {
+                              name: "PHA and PLA round trip through the stack",
+                              // LDA #$42, PHA, LDA #$00, PLA
+                              code: &[0xA9, 0x42, 0x48, 0xA9, 0x00, 0x68],
+                              setup: no_setup,
+                              steps: 4,
+                              expected: |_| State { a: 0x42, memory: vec![(0x01FF, 0x42)], ..state(0xC006, 11) },
+                          },
+                          Case {
+                              name: "PHP pushes the status with the B flag and bit 5 set",
+                              // PHP
+                              code: &[0x08],
+                              setup: no_setup,
+                              steps: 1,
+                              expected: |_| State { s: 0xFE, memory: vec![(0x01FF, 0x34)], ..state(0xC001, 3) },
+                          },
+                          Case {
+                              name: "JSR pushes the address of its last byte and RTS returns after it",
+                              // JSR $C004, BRK, RTS
+                              code: &[0x20, 0x04, 0xC0, 0x00, 0x60],
+                              setup: no_setup,
+                              steps: 2,
+                              expected: |_| {
+                                  State { memory: vec![(0x01FF, 0xC0), (0x01FE, 0x02)], ..state(0xC003, 12) }
+                              },
+                          },
+                          Case {
+                              name: "BRK pushes its address plus 2 and the status, and enters the IRQ handler",
+                              // SED, BRK
+                              code: &[0xF8, 0x00],
+                              setup: |cpu| cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]),
+                              steps: 2,
+                              expected: |variant| {
+                                  // The 65C02 also clears the decimal flag
+                                  let p = if variant == CpuVariant::Cmos65C02 { 0x24 } else { 0x2C };
+                                  State {
+                                      s: 0xFC,
+                                      p,
+                                      memory: vec![(0x01FF, 0xC0), (0x01FE, 0x03), (0x01FD, 0x3C)],
+                                      ..state(0xD000, 9)
+                                  }
+                              },
+                          },
+                          Case {
+                              name: "An NMI is taken even with interrupts disabled",
+                              // NOP
+                              code: &[0xEA],
+                              setup: |cpu| {
+                                  cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD1]);
+                                  cpu.nmi();
+                              },
+                              steps: 1,
+                              expected: |_| State {
+                                  s: 0xFC,
+                                  memory: vec![(0x01FF, 0xC0), (0x01FE, 0x00), (0x01FD, 0x24)],
+                                  ..state(0xD100, 7)
+                              },
+                          },
+                          Case {
+                              name: "An asserted IRQ is taken once interrupts are enabled",
+                              // CLI, NOP
+                              code: &[0x58, 0xEA],
+                              setup: |cpu| {
+                                  cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+                                  cpu.assert_irq();
+                              },
+                              steps: 2,
+                              expected: |_| State {
+                                  s: 0xFC,
+                                  memory: vec![(0x01FF, 0xC0), (0x01FE, 0x01), (0x01FD, 0x20)],
+                                  ..state(0xD000, 9)
+                              },
+                          },
+                          Case {
+                              name: "BIT copies bits 7 and 6 and tests against A",
+                              // LDA #$01, BIT $10
+                              code: &[0xA9, 0x01, 0x24, 0x10],
+                              setup: |cpu| cpu.memory.write_byte(0x0010, 0xC0),
+                              steps: 2,
+                              expected: |_| State { a: 0x01, p: 0xE6, ..state(0xC004, 5) },
+                          },
+                          Case {
+                              name: "ROR rotates the carry into bit 7",
+                              // SEC, LDA #$01, ROR A
+                              code: &[0x38, 0xA9, 0x01, 0x6A],
+                              setup: no_setup,
+                              steps: 3,
+                              expected: |_| State { a: 0x80, p: 0xA5, ..state(0xC004, 6) },
+                          }];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_expected_and_actual_state_of_failures() {
+        // A breakpoint stops every test before its first instruction,
+        // other than the one whose NMI is taken first
+        let report = run_with(|| {
+            let mut cpu = Cpu::new();
+            cpu.add_breakpoint(ORIGIN);
+            cpu
+        });
+
+        assert_eq!(CASES.len(), report.tests);
+        assert_eq!(CASES.len() - 1, report.failures.len());
+        assert_eq!(Ok(state(ORIGIN, 0)), report.failures[0].actual);
+        assert!(report.to_string()
+            .contains("ADC sets overflow when two positives make a negative: expected A=$A0 X=$00 Y=$00 S=$FF \
+                       P=$E4 PC=$C005 cycles=6, got A=$00"));
+    }
+}
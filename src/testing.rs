@@ -0,0 +1,246 @@
+//! Helpers for unit testing 6502 assembly routines
+
+use std::fmt::Write;
+
+use assembler::Assembler;
+use cpu::{Cpu, StatusFlags};
+
+// Where routines without an .ORG directive are assembled and loaded
+const DEFAULT_ORIGIN: u16 = 0xC000;
+const DEFAULT_INSTRUCTION_LIMIT: u32 = 100_000;
+
+/// Assembles a routine, sets up the Cpu and runs the routine until it
+/// reaches a `BRK`. Panics if the source doesn't assemble, the Cpu returns
+/// an error or the routine doesn't finish within the instruction limit.
+///
+/// # Example
+/// ```
+/// use rs6502::testing::AsmTest;
+///
+/// AsmTest::new("
+///         LDX #$00
+///     LOOP
+///         LDA $0200,X
+///         ADC #$01
+///         STA $0300,X
+///         INX
+///         CPX #$03
+///         BNE LOOP
+/// ")
+///     .memory(0x0200, &[0x01, 0x02, 0x03])
+///     .run()
+///     .assert_x(0x03)
+///     .assert_memory(0x0300, &[0x02, 0x03, 0x04]);
+/// ```
+pub struct AsmTest {
+    source: String,
+    registers: (Option<u8>, Option<u8>, Option<u8>),
+    status: Option<u8>,
+    memory: Vec<(u16, Vec<u8>)>,
+    instruction_limit: u32,
+}
+
+impl AsmTest {
+    pub fn new<S>(source: S) -> AsmTest
+        where S: Into<String>
+    {
+        AsmTest {
+            source: source.into(),
+            registers: (None, None, None),
+            status: None,
+            memory: Vec::new(),
+            instruction_limit: DEFAULT_INSTRUCTION_LIMIT,
+        }
+    }
+
+    pub fn a(mut self, value: u8) -> AsmTest {
+        self.registers.0 = Some(value);
+        self
+    }
+
+    pub fn x(mut self, value: u8) -> AsmTest {
+        self.registers.1 = Some(value);
+        self
+    }
+
+    pub fn y(mut self, value: u8) -> AsmTest {
+        self.registers.2 = Some(value);
+        self
+    }
+
+    /// Sets the status register from a byte, laid out as `NV-BDIZC`
+    pub fn status(mut self, value: u8) -> AsmTest {
+        self.status = Some(value);
+        self
+    }
+
+    /// Writes bytes into memory after the routine is loaded
+    pub fn memory(mut self, addr: u16, bytes: &[u8]) -> AsmTest {
+        self.memory.push((addr, bytes.to_vec()));
+        self
+    }
+
+    /// The most instructions the routine may execute before the test fails
+    pub fn instruction_limit(mut self, limit: u32) -> AsmTest {
+        self.instruction_limit = limit;
+        self
+    }
+
+    pub fn run(self) -> AsmTestResult {
+        let mut assembler = Assembler::new();
+        let segments = match assembler.assemble_string(self.source, DEFAULT_ORIGIN) {
+            Ok(segments) => segments,
+            Err(error) => panic!("Failed to assemble test source: {}", error.message),
+        };
+
+        let mut cpu = Cpu::new();
+        for segment in &segments {
+            cpu.load(&segment.code[..], segment.address).unwrap();
+        }
+        cpu.reset();
+
+        let (a, x, y) = self.registers;
+        cpu.registers.A = a.unwrap_or(cpu.registers.A);
+        cpu.registers.X = x.unwrap_or(cpu.registers.X);
+        cpu.registers.Y = y.unwrap_or(cpu.registers.Y);
+        if let Some(status) = self.status {
+            cpu.flags = StatusFlags::from(status);
+        }
+        for (addr, bytes) in self.memory {
+            cpu.memory.write_bytes(addr, &bytes);
+        }
+
+        let mut result = AsmTestResult {
+            cpu,
+            cycles: 0,
+            instructions: 0,
+        };
+
+        while !result.cpu.is_idle() && !result.cpu.is_halted() {
+            let pc = result.cpu.registers.PC;
+            if result.cpu.memory.read_byte(pc) == 0x00 {
+                break;
+            }
+            if result.instructions == self.instruction_limit {
+                panic!("Routine did not reach a BRK within {} instructions; PC is ${:04X}",
+                       self.instruction_limit,
+                       pc);
+            }
+
+            match result.cpu.step() {
+                Ok(cycles) => result.cycles += cycles as u64,
+                Err(error) => panic!("Cpu error at ${:04X}: {:?}", pc, error),
+            }
+            result.instructions += 1;
+        }
+
+        result
+    }
+}
+
+/// The state of the Cpu after an `AsmTest` has run. The assertions panic
+/// with a description of every difference found.
+pub struct AsmTestResult {
+    pub cpu: Cpu,
+    /// The cycles taken by the routine, not counting the final `BRK`
+    pub cycles: u64,
+    pub instructions: u32,
+}
+
+impl AsmTestResult {
+    pub fn assert_a(&self, expected: u8) -> &AsmTestResult {
+        assert_register("A", expected, self.cpu.registers.A);
+        self
+    }
+
+    pub fn assert_x(&self, expected: u8) -> &AsmTestResult {
+        assert_register("X", expected, self.cpu.registers.X);
+        self
+    }
+
+    pub fn assert_y(&self, expected: u8) -> &AsmTestResult {
+        assert_register("Y", expected, self.cpu.registers.Y);
+        self
+    }
+
+    /// Compares the status register, laid out as `NV-BDIZC`. Bits 4 and 5
+    /// are ignored since neither exist in the real register.
+    pub fn assert_status(&self, expected: u8) -> &AsmTestResult {
+        let actual = self.cpu.flags.to_u8();
+        if (expected ^ actual) & 0xCF != 0 {
+            panic!("Status register differs\n      NV-BDIZC\n  expected {:08b}\n     found {:08b}",
+                   expected,
+                   actual);
+        }
+        self
+    }
+
+    pub fn assert_memory(&self, addr: u16, expected: &[u8]) -> &AsmTestResult {
+        let mut differences = String::new();
+        for (offset, expected) in expected.iter().enumerate() {
+            let addr = addr.wrapping_add(offset as u16);
+            let actual = self.cpu.memory.read_byte(addr);
+            if actual != *expected {
+                writeln!(differences,
+                         "  ${:04X}: expected ${:02X}, found ${:02X}",
+                         addr,
+                         expected,
+                         actual)
+                    .unwrap();
+            }
+        }
+
+        if !differences.is_empty() {
+            panic!("Memory differs from ${:04X}\n{}", addr, differences);
+        }
+        self
+    }
+
+    pub fn assert_cycles(&self, expected: u64) -> &AsmTestResult {
+        if self.cycles != expected {
+            panic!("Routine took {} cycles, expected {}", self.cycles, expected);
+        }
+        self
+    }
+}
+
+fn assert_register(name: &str, expected: u8, actual: u8) {
+    if actual != expected {
+        panic!("Register {}: expected ${:02X}, found ${:02X}", name, expected, actual);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_cycles_up_to_the_brk() {
+        let result = AsmTest::new("
+            LDA #$01
+            ADC #$01
+        ")
+            .status(0x01)
+            .run();
+
+        result.assert_a(0x03).assert_status(0x00).assert_cycles(4);
+        assert_eq!(2, result.instructions);
+    }
+
+    #[test]
+    #[should_panic(expected = "$0201: expected $05, found $00")]
+    fn memory_differences_are_listed() {
+        AsmTest::new("LDA #$04\nSTA $0200").run().assert_memory(0x0200, &[0x04, 0x05]);
+    }
+
+    #[test]
+    #[should_panic(expected = "within 10 instructions")]
+    fn runaway_routines_fail() {
+        AsmTest::new("
+        LOOP
+            JMP LOOP
+        ")
+            .instruction_limit(10)
+            .run();
+    }
+}
@@ -72,4 +72,74 @@ fn INTEGRATION_ASSEMBLY_can_assemble_disassemble_random_memory_segments() {
     ")
                    .join("\n"),
                clean_disassembled);
-}
\ No newline at end of file
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn INTEGRATION_ASSEMBLY_can_assemble_disassemble_65c02_opcodes() {
+    let asm = "
+        LDA ($10)
+        STA ($12)
+        STZ $4400,X
+        TSB $20
+        PHX
+        PLY
+    END BRA END
+    ";
+
+    let mut assembler = rs6502::Assembler::new();
+    let mut disassembler = rs6502::Disassembler::with_code_only();
+    disassembler.set_variant(rs6502::CpuVariant::Cmos65C02);
+
+    let segments = assembler.assemble_string(asm, None).unwrap();
+    let disassembled = rs6502::Disassembler::clean_asm(disassembler.disassemble(&segments[0].code));
+
+    assert_eq!(vec![0xB2, 0x10, 0x92, 0x12, 0x9E, 0x00, 0x44, 0x04, 0x20, 0xDA, 0x7A, 0x80, 0xFE],
+               segments[0].code);
+    assert_eq!(rs6502::Disassembler::clean_asm("
+        LDA ($10)
+        STA ($12)
+        STZ $4400,X
+        TSB $20
+        PHX
+        PLY
+        BRA $000B
+    "),
+               disassembled);
+}
+
+#[test]
+#[allow(non_snake_case)]
+fn INTEGRATION_ASSEMBLY_can_assemble_disassemble_65c02_inc_a_bit_and_indexed_jmp() {
+    let asm = "
+        INC
+        DEC
+        BIT #$01
+        BIT $10,X
+        BIT $1234,X
+        JMP ($1234,X)
+        JMP (TABLE,X)
+    TABLE
+    ";
+
+    let mut assembler = rs6502::Assembler::new();
+    assembler.set_variant(rs6502::CpuVariant::Cmos65C02);
+    let mut disassembler = rs6502::Disassembler::with_code_only();
+    disassembler.set_variant(rs6502::CpuVariant::Cmos65C02);
+
+    let segments = assembler.assemble_string(asm, None).unwrap();
+    let disassembled = rs6502::Disassembler::clean_asm(disassembler.disassemble(&segments[0].code));
+
+    assert_eq!(vec![0x1A, 0x3A, 0x89, 0x01, 0x34, 0x10, 0x3C, 0x34, 0x12, 0x7C, 0x34, 0x12, 0x7C, 0x0F, 0x00],
+               segments[0].code);
+    assert_eq!(rs6502::Disassembler::clean_asm("
+        INC
+        DEC
+        BIT #$01
+        BIT $10,X
+        BIT $1234,X
+        JMP ($1234,X)
+        JMP ($000F,X)
+    "),
+               disassembled);
+}
@@ -1,3 +1,6 @@
+#![allow(unused_must_use, unused_variables, clippy::assertions_on_constants, clippy::bool_assert_comparison,
+         clippy::useless_vec)]
+
 extern crate rs6502;
 
 #[cfg(test)]
@@ -157,6 +160,24 @@ mod tests {
             assert_eq!(0x05, cpu.registers.A);
         }
 
+        #[test]
+        fn the_2a03_ignores_the_decimal_flag() {
+            let code = vec![0xF8, 0xA9, 0x05, 0x69, 0x05, 0x38, 0xA9, 0x10, 0xE9, 0x01];
+            let mut cpu = Cpu::new();
+            cpu.set_variant(CpuVariant::Ricoh2A03);
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            cpu.step_n(3);
+
+            assert_eq!(true, cpu.flags.decimal);
+            assert_eq!(0x0A, cpu.registers.A);
+
+            cpu.step_n(3);
+
+            assert_eq!(0x0F, cpu.registers.A);
+        }
+
         #[test]
         fn sta_can_store_bytes_in_memory() {
             let code = vec![0xA9, 0x20, 0x8D, 0x00, 0x20];
@@ -659,6 +680,48 @@ mod tests {
             assert_eq!(0x0007, cpu.registers.PC);
         }
 
+        #[test]
+        fn jmp_indirect_wraps_within_the_page_of_the_pointer() {
+            // JMP ($10FF)
+            let code = vec![0x6C, 0xFF, 0x10];
+
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None).unwrap();
+            cpu.memory.write_bytes(0x10FF, &[0x34, 0x12]);
+            cpu.memory.write_bytes(0x1000, &[0x56]);
+            cpu.reset();
+
+            cpu.step().unwrap();
+            assert_eq!(0x5634, cpu.registers.PC);
+
+            let mut cpu = Cpu::new_65c02();
+            cpu.load(&code[..], None).unwrap();
+            cpu.memory.write_bytes(0x10FF, &[0x34, 0x12]);
+            cpu.memory.write_bytes(0x1000, &[0x56]);
+            cpu.reset();
+
+            cpu.step().unwrap();
+            assert_eq!(0x1234, cpu.registers.PC);
+        }
+
+        #[test]
+        fn indirect_pointers_wrap_within_the_zero_page() {
+            // LDX #$01, LDA ($FE,X), LDY #$01, LDA ($FF),Y
+            let code = vec![0xA2, 0x01, 0xA1, 0xFE, 0xA0, 0x01, 0xB1, 0xFF];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None).unwrap();
+            cpu.memory.write_bytes(0xFF, &[0x00]);
+            cpu.memory.write_bytes(0x00, &[0x02]);
+            cpu.memory.write_bytes(0x0200, &[0x42, 0x43]);
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(0x42, cpu.registers.A);
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(0x43, cpu.registers.A);
+        }
+
         #[test]
         fn dex_wraps() {
             let code = vec![0xA2, 0x00, 0xCA];
@@ -731,5 +794,2009 @@ mod tests {
 
             assert_eq!(0x00, cpu.memory[0x85]);
         }
+
+        #[test]
+        fn statistics_are_disabled_by_default() {
+            let cpu = Cpu::new();
+
+            assert!(cpu.statistics().is_none());
+        }
+
+        #[test]
+        fn can_count_executed_instructions() {
+            let code = vec![0xA9, 0x01, 0xA9, 0x02, 0xA5, 0x10, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.enable_statistics();
+
+            cpu.step_n(4);
+
+            let statistics = cpu.statistics().unwrap();
+            assert_eq!(4, statistics.total());
+            assert_eq!(3, statistics.count_mnemonic("LDA"));
+            assert_eq!(2, statistics.count("LDA", AddressingMode::Immediate));
+            assert_eq!(1, statistics.count("LDA", AddressingMode::ZeroPage));
+            assert_eq!(1, statistics.count("INX", AddressingMode::Implied));
+        }
+
+        #[test]
+        fn statistics_report_is_sorted_by_count() {
+            let code = vec![0xE8, 0xA9, 0x01, 0xA9, 0x02];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.enable_statistics();
+
+            cpu.step_n(3);
+
+            let report = cpu.statistics().unwrap().report();
+            assert_eq!(InstructionCount {
+                           mnemonic: "LDA",
+                           mode: AddressingMode::Immediate,
+                           count: 2,
+                       },
+                       report[0]);
+            assert_eq!("INX", report[1].mnemonic);
+        }
+
+        #[test]
+        fn cost_model_defaults_to_cycles_per_address() {
+            // LDX #$02, DEX, BNE -3
+            let code = vec![0xA2, 0x02, 0xCA, 0xD0, 0xFD];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.set_cost_model(CostModel::new());
+
+            cpu.step_n(5);
+
+            let costs = cpu.costs().unwrap();
+            assert_eq!(2.0 + 2.0 * 2.0 + 3.0 + 2.0, costs.total());
+            assert_eq!(vec![(0xC000, 2.0), (0xC002, 4.0), (0xC003, 5.0)], costs.by_address());
+
+            cpu.clear_costs();
+            assert_eq!(0.0, cpu.costs().unwrap().total());
+        }
+
+        #[test]
+        fn steps_yields_executed_instructions() {
+            let code = vec![0xA9, 0x01, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            let steps: Vec<StepInfo> = cpu.steps().take(2).collect();
+
+            assert_eq!(0xC000, steps[0].pc);
+            assert_eq!("LDA", steps[0].opcode.mnemonic);
+            assert_eq!(2, steps[0].cycles);
+            assert_eq!(0xC002, steps[1].pc);
+            assert_eq!("INX", steps[1].opcode.mnemonic);
+        }
+
+        #[test]
+        fn steps_stops_on_error() {
+            let code = vec![0xE8, 0xC3];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            let mut steps = cpu.steps();
+            let count = steps.by_ref().count();
+
+            assert_eq!(1, count);
+            assert_eq!(Some(&CpuError::unknown_opcode(0xC001, 0xC3)), steps.error());
+        }
+
+        #[test]
+        fn iter_steps_ends_at_a_breakpoint_and_works_with_adapters() {
+            // LDA #$01, INX, INX, NOP
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xA9, 0x01, 0xE8, 0xE8, 0xEA], None);
+            cpu.reset();
+            cpu.add_breakpoint(0xC004);
+
+            let pcs: Result<Vec<u16>, CpuError> = cpu.iter_steps()
+                .filter(|step| step.as_ref().map_or(true, |step| step.opcode.mnemonic == "INX"))
+                .map(|step| step.map(|step| step.pc))
+                .collect();
+
+            assert_eq!(Ok(vec![0xC002, 0xC003]), pcs);
+            assert!(cpu.is_halted());
+            assert_eq!(0, cpu.iter_steps().count());
+        }
+
+        #[test]
+        fn idle_detection_stops_on_jump_to_self() {
+            let code = vec![0xE8, 0x4C, 0x01, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.enable_idle_detection();
+
+            let cycles = cpu.step_n(100).unwrap();
+
+            assert_eq!(true, cpu.is_idle());
+            assert_eq!(5, cycles);
+            assert_eq!(0x01, cpu.registers.X);
+            assert_eq!(0xC001, cpu.registers.PC);
+        }
+
+        #[test]
+        fn idle_detection_stops_on_branch_to_self() {
+            let code = vec![0xA2, 0x01, 0xD0, 0xFE];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.enable_idle_detection();
+
+            let count = cpu.steps().count();
+
+            assert_eq!(2, count);
+            assert_eq!(true, cpu.is_idle());
+            assert_eq!(0xC002, cpu.registers.PC);
+        }
+
+        #[test]
+        fn idle_cpu_resumes_on_interrupt() {
+            let code = vec![0x58, 0x4C, 0x01, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.enable_idle_detection();
+
+            cpu.step_n(10).unwrap();
+            assert_eq!(true, cpu.is_idle());
+
+            cpu.irq();
+
+            assert_eq!(false, cpu.is_idle());
+            assert_eq!(0xD000, cpu.registers.PC);
+        }
+
+        #[test]
+        fn held_irq_line_reenters_the_handler_until_released() {
+            // CLI, JMP $C001, then an IRQ handler of INX, RTI
+            let code = vec![0x58, 0x4C, 0x01, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_bytes(0xD000, &[0xE8, 0x40]);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.enable_idle_detection();
+
+            cpu.step_n(3).unwrap();
+            assert_eq!(true, cpu.is_idle());
+
+            cpu.assert_irq();
+            assert_eq!(Ok(7), cpu.step());
+            assert_eq!(false, cpu.is_idle());
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(Ok(7), cpu.step());
+            cpu.step_n(2).unwrap();
+            assert_eq!(2, cpu.registers.X);
+
+            cpu.release_irq();
+            cpu.step_n(2).unwrap();
+            assert_eq!(2, cpu.registers.X);
+            assert_eq!(0xC001, cpu.registers.PC);
+        }
+
+        #[test]
+        fn scheduled_events_wake_an_idle_cpu_at_their_cycle() {
+            // CLI, JMP $C001, then an IRQ handler of INX, RTI
+            let code = vec![0x58, 0x4C, 0x01, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_bytes(0xD000, &[0xE8, 0x40]);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.enable_idle_detection();
+            cpu.schedule(1010, ExternalEvent::ReleaseIrq);
+            cpu.schedule(1000, ExternalEvent::AssertIrq);
+
+            cpu.step_n(3).unwrap();
+            assert_eq!(true, cpu.is_idle());
+            assert_eq!(5, cpu.cycles());
+
+            assert_eq!(Ok(7), cpu.step());
+            assert_eq!(1007, cpu.cycles());
+            assert_eq!(1, cpu.event_queue().len());
+
+            // INX, RTI, then the line is released before it is checked again
+            cpu.step_n(3).unwrap();
+            assert_eq!(1, cpu.registers.X);
+            assert_eq!(true, cpu.is_idle());
+            assert!(cpu.event_queue().is_empty());
+        }
+
+        #[test]
+        fn does_not_idle_without_idle_detection() {
+            let code = vec![0x4C, 0x00, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            let cycles = cpu.step_n(10).unwrap();
+
+            assert_eq!(false, cpu.is_idle());
+            assert_eq!(30, cycles);
+        }
+
+        #[test]
+        fn unknown_opcodes_are_errors_by_default() {
+            let code = vec![0x02];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(Err(CpuError::unknown_opcode(0xC000, 0x02)), cpu.step());
+        }
+
+        #[test]
+        fn can_skip_unknown_opcodes_as_nops() {
+            let code = vec![0x04, 0x10, 0x1A, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.set_unknown_opcode_policy(UnknownOpcodePolicy::Nop);
+
+            let steps: Vec<StepInfo> = cpu.steps().take(3).collect();
+
+            assert_eq!(0x01, cpu.registers.X);
+            assert_eq!("???", steps[0].opcode.mnemonic);
+            assert_eq!(2, steps[0].opcode.length);
+            assert_eq!(0xC002, steps[1].pc);
+            assert_eq!("INX", steps[2].opcode.mnemonic);
+        }
+
+        #[test]
+        fn can_halt_on_unknown_opcodes() {
+            let code = vec![0xE8, 0x02, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.set_unknown_opcode_policy(UnknownOpcodePolicy::Halt);
+
+            cpu.step_n(10).unwrap();
+
+            assert_eq!(true, cpu.is_halted());
+            assert_eq!(Some(StopReason::UnknownOpcode { pc: 0xC001, opcode: 0x02 }), cpu.halted());
+            assert_eq!(0x01, cpu.registers.X);
+            assert_eq!(0xC001, cpu.registers.PC);
+
+            cpu.reset();
+            assert_eq!(false, cpu.is_halted());
+        }
+
+        #[test]
+        fn stops_at_a_breakpoint_on_every_pass_of_a_loop() {
+            // LDX #$03, DEX, BNE -3, INY
+            let code = vec![0xA2, 0x03, 0xCA, 0xD0, 0xFD, 0xC8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.add_breakpoint(0xC002);
+
+            let mut stops = Vec::new();
+            for _ in 0..3 {
+                cpu.run().unwrap();
+                stops.push((cpu.halted(), cpu.registers.X));
+                cpu.resume();
+            }
+            cpu.remove_breakpoint(0xC002);
+            cpu.step_n(3).unwrap();
+
+            assert_eq!(vec![(Some(StopReason::Breakpoint(0xC002)), 3),
+                            (Some(StopReason::Breakpoint(0xC002)), 2),
+                            (Some(StopReason::Breakpoint(0xC002)), 1)],
+                       stops);
+            assert_eq!(0x00, cpu.registers.X);
+            assert_eq!(0x01, cpu.registers.Y);
+            assert!(cpu.breakpoints().is_empty());
+        }
+
+        #[test]
+        fn can_handle_unknown_opcodes_with_a_callback() {
+            // An extension opcode that loads its operand into X
+            let code = vec![0x02, 0x2A, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.set_unknown_opcode_policy(UnknownOpcodePolicy::Callback(Box::new(|cpu, opcode| {
+                assert_eq!(0x02, opcode);
+                let pc = cpu.registers.PC;
+                cpu.registers.X = cpu.memory.read_byte(pc + 1);
+                cpu.registers.PC += 2;
+                Ok(4)
+            })));
+
+            let cycles = cpu.step_n(2).unwrap();
+
+            assert_eq!(6, cycles);
+            assert_eq!(0x2B, cpu.registers.X);
+        }
+
+        #[test]
+        fn can_call_host_functions() {
+            // Host call $01 twice, then host call $02
+            let code = vec![0x02, 0x01, 0x02, 0x01, 0x02, 0x02];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.enable_host_calls(0x02);
+            cpu.register_host_call(0x01, |cpu| cpu.registers.X += 1);
+            cpu.register_host_call(0x02, |cpu| {
+                let x = cpu.registers.X;
+                cpu.memory.write_byte(0x0200, x);
+            });
+
+            let cycles = cpu.step_n(3).unwrap();
+
+            assert_eq!(6, cycles);
+            assert_eq!(0x02, cpu.memory.read_byte(0x0200));
+            assert_eq!(0xC006, cpu.registers.PC);
+        }
+
+        #[test]
+        fn host_calls_are_counted_like_other_instructions() {
+            // Host call $01, then INX
+            let code = [0x02, 0x01, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None).unwrap();
+            cpu.reset();
+            cpu.enable_host_calls(0x02);
+            cpu.set_host_call_cycles(12);
+            cpu.register_host_call(0x01, |cpu| cpu.registers.A = 0x42);
+            cpu.enable_statistics();
+            cpu.enable_coverage();
+            cpu.enable_history(10);
+            let steps = ::std::rc::Rc::new(::std::cell::RefCell::new(Vec::new()));
+            let recorded = steps.clone();
+            cpu.set_post_step_hook(move |_, instruction| recorded.borrow_mut().push(instruction.opcode.mnemonic));
+
+            assert_eq!(Ok(12), cpu.step());
+            assert_eq!(Ok(2), cpu.step());
+
+            assert_eq!(vec!["HCL", "INX"], *steps.borrow());
+            assert_eq!(1, cpu.statistics().unwrap().count("HCL", AddressingMode::Immediate));
+            assert!(cpu.coverage().unwrap().executed(0xC001));
+
+            assert_eq!(2, cpu.step_back(2));
+            assert_eq!(0x00, cpu.registers.A);
+            assert_eq!(0xC000, cpu.registers.PC);
+        }
+
+        #[test]
+        fn errors_on_unregistered_host_calls() {
+            let code = vec![0x02, 0x07];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.enable_host_calls(0x02);
+
+            assert_eq!(Err(CpuError::unknown_host_call(0xC000, 0x07)), cpu.step());
+        }
+
+        #[test]
+        fn loads_code_into_memory_the_bus_marks_as_rom() {
+            let mut cpu = Cpu::new();
+            cpu.memory.mark_rom(0xC000..=0xFFFF);
+
+            cpu.load(&[0xA9, 0x42, 0x8D, 0x00, 0xC0], None).unwrap();
+            cpu.reset();
+            cpu.step_n(2).unwrap();
+
+            assert_eq!(0x42, cpu.registers.A);
+            assert_eq!(0xA9, cpu.memory.read_byte(0xC000));
+            assert_eq!(1, cpu.memory.rom_write_count());
+        }
+
+        #[test]
+        fn cpu_protection_is_checked_before_the_bus_rom() {
+            let mut cpu = Cpu::new();
+            cpu.memory.mark_rom(0xC000..=0xFFFF);
+            cpu.protect(0xC000..=0xFFFF, Protection::ReadOnly);
+
+            // STA $C000
+            cpu.load(&[0x8D, 0x00, 0xC0], None).unwrap();
+            cpu.reset();
+
+            assert_eq!(Err(CpuError::memory_protection(0xC000, 0xC000, MemoryAccess::Write)), cpu.step());
+            assert_eq!(0, cpu.memory.rom_write_count());
+
+            cpu.clear_protection();
+            cpu.reset();
+            cpu.step().unwrap();
+
+            assert_eq!(0x8D, cpu.memory.read_byte(0xC000));
+            assert_eq!(1, cpu.memory.rom_write_count());
+        }
+
+        #[test]
+        fn blocks_writes_to_read_only_memory() {
+            let code = vec![0xA9, 0x42, 0x8D, 0x00, 0x20];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.protect(0x2000..=0x2FFF, Protection::ReadOnly);
+
+            let result = cpu.step_n(2);
+
+            assert_eq!(Err(CpuError::memory_protection(0xC002, 0x2000, MemoryAccess::Write)), result);
+            assert_eq!(0x00, cpu.memory.read_byte(0x2000));
+        }
+
+        #[test]
+        fn faults_on_reads_from_write_only_memory() {
+            let code = vec![0xA5, 0x80];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.protect(0x0080..=0x0080, Protection::WriteOnly);
+
+            let error = cpu.step().unwrap_err();
+
+            assert_eq!(0x0080, error.addr());
+            assert_eq!(&CpuErrorKind::MemoryProtection {
+                           pc: 0xC000,
+                           access: MemoryAccess::Read,
+                       },
+                       error.kind());
+        }
+
+        #[test]
+        fn faults_on_executing_no_execute_memory() {
+            let code = vec![0x4C, 0x00, 0x02];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.protect(0x0000..=0x07FF, Protection::NoExecute);
+
+            cpu.step().unwrap();
+
+            assert_eq!(Err(CpuError::memory_protection(0x0200, 0x0200, MemoryAccess::Execute)), cpu.step());
+        }
+
+        #[test]
+        fn can_clear_memory_protection() {
+            let code = vec![0x8D, 0x00, 0x20];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.protect(0x2000..=0x2FFF, Protection::ReadOnly);
+            cpu.clear_protection();
+
+            assert_eq!(Ok(4), cpu.step());
+        }
+
+        #[test]
+        fn ignores_self_modifying_code_by_default() {
+            let code = vec![0xA9, 0xE8, 0x8D, 0x05, 0xC0, 0xEA];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.mark_code(0xC000..=0xC005);
+
+            cpu.step_n(3).unwrap();
+
+            assert_eq!(0x01, cpu.registers.X);
+        }
+
+        #[test]
+        fn can_report_self_modifying_code_to_a_callback() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            let code = vec![0xA9, 0xE8, 0x8D, 0x05, 0xC0, 0xEA, 0x8D, 0x00, 0x02];
+            let writes = Rc::new(RefCell::new(Vec::new()));
+            let recorded = writes.clone();
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.mark_code(0xC000..=0xC008);
+            cpu.set_self_modifying_code_policy(SelfModifyingCodePolicy::Callback(Box::new(move |pc, addr| {
+                recorded.borrow_mut().push((pc, addr));
+            })));
+
+            cpu.step_n(4).unwrap();
+
+            assert_eq!(vec![(0xC002, 0xC005)], *writes.borrow());
+            assert_eq!(0x01, cpu.registers.X);
+        }
+
+        #[test]
+        fn can_error_on_self_modifying_code() {
+            let code = vec![0xEE, 0x05, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.mark_code(0xC000..=0xC0FF);
+            cpu.set_self_modifying_code_policy(SelfModifyingCodePolicy::Error);
+
+            let error = cpu.step().unwrap_err();
+
+            assert_eq!(0xC005, error.addr());
+            assert_eq!(&CpuErrorKind::SelfModifyingCode { pc: 0xC000 }, error.kind());
+        }
+
+        fn record_events(cpu: &mut Cpu) -> ::std::rc::Rc<::std::cell::RefCell<Vec<CpuEvent>>> {
+            let events = ::std::rc::Rc::new(::std::cell::RefCell::new(Vec::new()));
+            let recorded = events.clone();
+            cpu.add_event_listener(move |event| recorded.borrow_mut().push(*event));
+
+            events
+        }
+
+        #[test]
+        fn raises_events_for_memory_and_stack_access() {
+            let code = vec![0xA5, 0x10, 0x48, 0x68];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_byte(0x10, 0x42);
+            cpu.reset();
+            let events = record_events(&mut cpu);
+
+            cpu.step_n(3).unwrap();
+
+            let events = events.borrow();
+            assert_eq!(CpuEvent::MemoryRead { addr: 0x0010, value: 0x42 }, events[1]);
+            assert_eq!(CpuEvent::InstructionRetired {
+                           pc: 0xC000,
+                           opcode: *OpCode::from_raw_byte(0xA5).unwrap(),
+                           cycles: 3,
+                       },
+                       events[2]);
+            assert_eq!(CpuEvent::StackPush { addr: 0x01FF, value: 0x42 }, events[3]);
+            assert_eq!(CpuEvent::StackPop { addr: 0x01FF, value: 0x42 }, events[5]);
+            assert_eq!(7, events.len());
+        }
+
+        #[test]
+        fn raises_events_for_writes_and_interrupts() {
+            let code = vec![0x58, 0x85, 0x20];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.reset();
+            let events = record_events(&mut cpu);
+
+            cpu.step_n(2).unwrap();
+            cpu.irq();
+
+            let events = events.borrow();
+            assert!(events.contains(&CpuEvent::MemoryWrite { addr: 0x0020, value: 0x00 }));
+            assert_eq!(&[CpuEvent::StackPush { addr: 0x01FF, value: 0xC0 },
+                         CpuEvent::StackPush { addr: 0x01FE, value: 0x03 },
+                         CpuEvent::StackPush { addr: 0x01FD, value: 0x20 },
+                         CpuEvent::InterruptTaken {
+                             kind: InterruptKind::Irq,
+                             return_addr: 0xC003,
+                             handler: 0xD000,
+                         }],
+                       &events[events.len() - 4..]);
+        }
+
+        #[test]
+        fn stops_raising_events_once_listeners_are_cleared() {
+            let code = vec![0xE8, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            let events = record_events(&mut cpu);
+
+            cpu.step().unwrap();
+            cpu.clear_event_listeners();
+            cpu.step().unwrap();
+
+            assert_eq!(1, events.borrow().len());
+        }
+
+        #[test]
+        fn step_hooks_run_either_side_of_each_instruction() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            // INX, INX
+            let code = vec![0xE8, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let before = seen.clone();
+            cpu.set_pre_step_hook(move |cpu, instruction| {
+                before.borrow_mut().push(("pre", instruction.pc, cpu.registers.X))
+            });
+            let after = seen.clone();
+            cpu.set_post_step_hook(move |cpu, instruction| {
+                after.borrow_mut().push(("post", instruction.pc, cpu.registers.X))
+            });
+
+            cpu.step_n(2).unwrap();
+            cpu.clear_step_hooks();
+            cpu.reset();
+            cpu.step().unwrap();
+
+            assert_eq!(vec![("pre", 0xC000, 0), ("post", 0xC000, 1), ("pre", 0xC001, 1), ("post", 0xC001, 2)],
+                       *seen.borrow());
+        }
+
+        #[test]
+        fn each_trace_sink_has_its_own_filter() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            // LDX #$02, JSR $D000, DEX, BNE -6 with INY, RTS at $D000
+            let code = vec![0xA2, 0x02, 0x20, 0x00, 0xD0, 0xCA, 0xD0, 0xFA];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_bytes(0xD000, &[0xC8, 0x60]);
+            cpu.reset();
+
+            let subroutine = Rc::new(RefCell::new(Vec::new()));
+            let lines = subroutine.clone();
+            cpu.add_trace_sink(TraceFilter::new().addresses(0xD000..=0xDFFF),
+                               move |line| lines.borrow_mut().push(line.pc));
+            let branches = Rc::new(RefCell::new(Vec::new()));
+            let lines = branches.clone();
+            cpu.add_trace_sink(TraceFilter::new().only(InstructionClass::TakenBranches),
+                               move |line| lines.borrow_mut().push(line.pc));
+
+            cpu.step_n(11).unwrap();
+
+            assert_eq!(vec![0xD000, 0xD001, 0xD000, 0xD001], *subroutine.borrow());
+            assert_eq!(vec![0xC006], *branches.borrow());
+        }
+
+        #[test]
+        fn trace_lines_name_locations_and_operands_from_symbols() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            let mut assembler = Assembler::new();
+            let segments = assembler
+                .assemble_string("
+                    .ORG $C000
+                    MAIN_LOOP INC COUNT
+                              NOP
+                              JMP MAIN_LOOP
+                    .ORG $0200
+                    COUNT
+                    .BYTE #$00
+                ", None)
+                .unwrap();
+
+            let mut cpu = Cpu::new();
+            for segment in segments {
+                cpu.load(&segment.code, segment.address).unwrap();
+            }
+            cpu.reset();
+            cpu.set_symbols(assembler.symbols());
+
+            let log = Rc::new(RefCell::new(Vec::new()));
+            let lines = log.clone();
+            cpu.add_trace_sink(TraceFilter::new(), move |line| lines.borrow_mut().push(line.instruction()));
+            cpu.step_n(3).unwrap();
+
+            assert_eq!(vec!["INC COUNT", "NOP", "JMP MAIN_LOOP"], *log.borrow());
+            assert_eq!(Some("MAIN_LOOP+3".into()), cpu.symbols().and_then(|symbols| symbols.locate(0xC003)));
+        }
+
+        #[test]
+        fn stack_guard_reports_the_calls_that_wrapped_the_stack() {
+            // JSR $D000 at $C000; at $D000: LDA #$00, PHA, JSR $D000
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x20, 0x00, 0xD0], None).unwrap();
+            cpu.memory.write_bytes(0xD000, &[0xA9, 0x00, 0x48, 0x20, 0x00, 0xD0]);
+            cpu.reset();
+            cpu.set_stack_guard(StackGuard::new());
+
+            let error = cpu.step_n(1000).unwrap_err();
+
+            match *error.kind() {
+                CpuErrorKind::StackViolation(ref violation) => {
+                    assert_eq!(StackViolationKind::Overflow, violation.kind);
+                    assert_eq!(0xFE, violation.stack_pointer);
+                    assert_eq!(StackFrame {
+                                   kind: StackFrameKind::Call,
+                                   from: 0xC000,
+                                   to: 0xD000,
+                                   stack_pointer: 0xFF,
+                               },
+                               violation.backtrace[0]);
+                    assert!(violation.backtrace[1..].iter().all(|frame| frame.from == 0xD003));
+                }
+                _ => panic!("expected a stack violation, got {:?}", error),
+            }
+        }
+
+        #[test]
+        fn stack_guard_forgets_calls_that_have_returned() {
+            // JSR $D000, JMP $C000 with RTS at $D000
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x20, 0x00, 0xD0, 0x4C, 0x00, 0xC0], None).unwrap();
+            cpu.memory.write_bytes(0xD000, &[0x60]);
+            cpu.reset();
+            cpu.set_stack_guard(StackGuard::new().max_depth(2));
+
+            cpu.step_n(300).unwrap();
+
+            assert_eq!(0xFF, cpu.registers.S);
+        }
+
+        #[test]
+        fn run_for_cycles_stops_early_when_the_cpu_goes_idle() {
+            // INX, JMP $C001
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xE8, 0x4C, 0x01, 0xC0], None).unwrap();
+            cpu.reset();
+            cpu.enable_idle_detection();
+
+            assert_eq!(0, cpu.run_for_cycles(1000).unwrap());
+            assert_eq!(1, cpu.registers.X);
+            assert!(cpu.is_idle());
+        }
+
+        #[test]
+        fn irq_latency_includes_the_time_interrupts_were_disabled() {
+            // SEI, NOP, NOP, CLI, NOP with an IRQ handler of RTI
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x78, 0xEA, 0xEA, 0x58, 0xEA], None).unwrap();
+            cpu.memory.write_bytes(0xD000, &[0x40]);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.enable_interrupt_statistics();
+
+            cpu.step().unwrap();
+            cpu.assert_irq();
+            cpu.step_n(4).unwrap();
+            cpu.release_irq();
+            cpu.step().unwrap();
+
+            let irq = cpu.interrupt_statistics().unwrap().source(InterruptKind::Irq);
+            assert_eq!(1, irq.count);
+            // NOP, NOP and CLI, then entering the handler
+            assert_eq!(13, irq.max_latency);
+            assert_eq!(6, irq.handler_cycles);
+            assert_eq!(0xC004, cpu.registers.PC);
+        }
+
+        #[test]
+        fn step_until_gives_up_at_the_instruction_limit() {
+            // INX, JMP $C000
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xE8, 0x4C, 0x00, 0xC0], None).unwrap();
+            cpu.reset();
+            cpu.set_limits(RunLimits::new().max_instructions(10));
+
+            let error = cpu.step_until(|cpu| cpu.registers.X == 0xFF).unwrap_err();
+
+            assert_eq!(&CpuErrorKind::LimitReached(Limit::Instructions), error.kind());
+            assert_eq!(5, cpu.registers.X);
+        }
+
+        #[test]
+        fn run_until_brk_reports_breakpoints_and_jams() {
+            // INX, INX, KIL
+            let mut cpu = Cpu::new();
+            cpu.enable_illegal_opcodes();
+            cpu.load(&[0xE8, 0xE8, 0x02], None).unwrap();
+            cpu.reset();
+            cpu.add_breakpoint(0xC001);
+
+            assert_eq!(StopReason::Breakpoint(0xC001), cpu.run_until_brk().unwrap());
+
+            cpu.resume();
+            assert_eq!(StopReason::Jammed { pc: 0xC002, opcode: 0x02 }, cpu.run_until_brk().unwrap());
+            assert_eq!(2, cpu.registers.X);
+        }
+
+        #[test]
+        fn snapshots_keep_pending_interrupts() {
+            // NOP, NOP with an NMI handler of INX, RTI
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xEA, 0xEA], None).unwrap();
+            cpu.memory.write_bytes(0xD000, &[0xE8, 0x40]);
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.step().unwrap();
+            cpu.nmi();
+
+            let mut snapshot = cpu.snapshot();
+            cpu.reset();
+            cpu.restore(&snapshot).unwrap();
+            cpu.step_n(2).unwrap();
+
+            assert_eq!(1, cpu.registers.X);
+            assert_eq!(11, cpu.cycles());
+
+            snapshot.memory.truncate(0x8000);
+            assert_eq!(&CpuErrorKind::InvalidSnapshot, cpu.restore(&snapshot).unwrap_err().kind());
+        }
+
+        #[test]
+        fn word_operands_and_indexing_wrap_at_the_top_of_memory() {
+            // LDA $FFFF,X at $FFFD, then INX at $0000
+            let mut cpu = Cpu::new();
+            cpu.memory.write_bytes(0xFFFD, &[0xBD, 0xFF, 0xFF, 0xE8, 0x42]);
+            cpu.registers.PC = 0xFFFD;
+            cpu.registers.X = 0x02;
+
+            cpu.step_n(2).unwrap();
+
+            assert_eq!(0x42, cpu.registers.A);
+            assert_eq!(0x03, cpu.registers.X);
+            assert_eq!(0x0001, cpu.registers.PC);
+        }
+
+        #[test]
+        fn step_back_undoes_pushes_and_interrupts_within_the_history_depth() {
+            // LDA #$42, JSR $C006, BRK, PHA with an NMI handler of RTI
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xA9, 0x42, 0x20, 0x06, 0xC0, 0x00, 0x48], None).unwrap();
+            cpu.memory.write_bytes(0xD000, &[0x40]);
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.enable_history(3);
+
+            cpu.step_n(3).unwrap();
+            cpu.nmi();
+            cpu.step().unwrap();
+            assert_eq!(0xD000, cpu.registers.PC);
+            assert_eq!(3, cpu.history_len());
+
+            assert_eq!(3, cpu.step_back(5));
+            assert_eq!(0xC002, cpu.registers.PC);
+            assert_eq!(0xFF, cpu.registers.S);
+            assert_eq!(0x42, cpu.registers.A);
+            assert_eq!(2, cpu.cycles());
+            assert!((0x01FB..0x0200).all(|addr| cpu.memory.read_byte(addr) == 0));
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(0x42, cpu.memory.read_byte(0x01FD));
+            assert_eq!(1, cpu.step_back(1));
+            assert_eq!(0x00, cpu.memory.read_byte(0x01FD));
+        }
+
+        #[test]
+        fn history_skips_steps_that_run_nothing() {
+            // INX, INX, then a breakpoint on INX and an unknown opcode
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xE8, 0xE8, 0xE8, 0x02], None).unwrap();
+            cpu.reset();
+            cpu.enable_history(10);
+            cpu.add_breakpoint(0xC002);
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(Ok(0), cpu.step());
+            assert_eq!(Ok(0), cpu.step());
+            cpu.resume();
+            cpu.step().unwrap();
+            assert!(cpu.step().is_err());
+            assert_eq!(3, cpu.history_len());
+
+            assert_eq!(2, cpu.step_back(2));
+            assert_eq!(0xC001, cpu.registers.PC);
+            assert_eq!(0x01, cpu.registers.X);
+        }
+
+        #[test]
+        fn history_leaves_mapped_devices_alone() {
+            fn run(history: bool) -> (u8, u8) {
+                // STA $FE, LDA $FE, STA $10
+                let mut cpu = Cpu::new();
+                cpu.load(&[0x8D, 0xFE, 0x00, 0xAD, 0xFE, 0x00, 0x85, 0x10], None).unwrap();
+                cpu.memory.map_device(0xFE..=0xFE, RandomDevice::new(1234));
+                cpu.reset();
+                if history {
+                    cpu.enable_history(10);
+                }
+
+                cpu.step_n(3).unwrap();
+                let value = cpu.registers.A;
+                cpu.step_back(3);
+                (value, cpu.memory.read_byte(0xFE))
+            }
+
+            // Recording the write to $FE must not take a number from the
+            // generator, and undoing it must not write to the device
+            assert_eq!(run(false), run(true));
+        }
+
+        #[test]
+        fn profiles_subroutines_by_address_until_cleared() {
+            // JSR $C006, JSR $C006, INX, RTS
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x20, 0x06, 0xC0, 0x20, 0x06, 0xC0, 0xE8, 0x60], None).unwrap();
+            cpu.reset();
+            cpu.set_profiler(Profiler::by_address());
+
+            cpu.step_n(6).unwrap();
+
+            let report = cpu.profiler().unwrap().report();
+            assert_eq!(vec![(0xC007, 2, 12), (0xC000, 1, 6), (0xC003, 1, 6), (0xC006, 2, 4)],
+                       report.addresses.iter().map(|p| (p.addr, p.count, p.cycles)).collect::<Vec<_>>());
+            assert_eq!(28, report.cycles);
+
+            cpu.clear_profile();
+            assert_eq!(ProfileReport::default(), cpu.profiler().unwrap().report());
+        }
+
+        #[test]
+        fn coverage_separates_code_from_pointers_and_the_stack() {
+            // JSR $C004, BRK, LDA ($10),Y, RTS
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x20, 0x04, 0xC0, 0x00, 0xB1, 0x10, 0x60], None).unwrap();
+            cpu.memory.write_bytes(0x10, &[0x00, 0x02]);
+            cpu.reset();
+            cpu.enable_coverage();
+
+            cpu.step_n(3).unwrap();
+
+            let coverage = cpu.coverage().unwrap();
+            assert_eq!(vec![0xC000..=0xC002, 0xC004..=0xC006], coverage.executed_ranges());
+            assert_eq!(vec![0x0010..=0x0011, 0x01FE..=0x0200], coverage.data_ranges());
+            assert!(coverage.written(0x01FF) && coverage.read(0x01FF));
+            assert!(!coverage.written(0x0200));
+        }
+
+        #[test]
+        fn call_stack_follows_nested_calls_and_interrupts() {
+            // JSR $C004, BRK, JSR $C008, RTS, NOP, RTS with an NMI handler of RTI
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x20, 0x04, 0xC0, 0x00, 0x20, 0x08, 0xC0, 0x60, 0xEA, 0x60], None).unwrap();
+            cpu.memory.write_bytes(0xD000, &[0x40]);
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+            cpu.reset();
+            cpu.enable_call_stack();
+
+            cpu.step_n(2).unwrap();
+            cpu.nmi();
+            cpu.step().unwrap();
+
+            let frames = cpu.call_stack().unwrap().frames();
+            assert_eq!(vec![(StackFrameKind::Call, 0xC000, 0xC004),
+                            (StackFrameKind::Call, 0xC004, 0xC008),
+                            (StackFrameKind::Interrupt(InterruptKind::Nmi), 0xC008, 0xD000)],
+                       frames.iter().map(|frame| (frame.kind, frame.from, frame.to)).collect::<Vec<_>>());
+            assert_eq!(0xC008, frames[2].return_address());
+
+            cpu.step_n(4).unwrap();
+
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert!(cpu.call_stack().unwrap().frames().is_empty());
+            assert!(cpu.call_stack().unwrap().unbalanced_returns().is_empty());
+        }
+
+        #[test]
+        fn wait_states_are_added_to_instructions_and_interrupts() {
+            struct SlowPort;
+
+            impl Device for SlowPort {
+                fn read(&mut self, _offset: u16) -> u8 {
+                    0x42
+                }
+
+                fn write(&mut self, _offset: u16, _value: u8) {}
+
+                fn wait_states(&self, _offset: u16, access: MemoryAccess) -> u8 {
+                    if access == MemoryAccess::Read { 2 } else { 1 }
+                }
+            }
+
+            // LDA $D000, STA $D001, PHA with an NMI vector in slow ROM
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xAD, 0x00, 0xD0, 0x8D, 0x01, 0xD0, 0x48], None).unwrap();
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xE0]);
+            cpu.memory.map_device(0xD000..=0xD00F, SlowPort);
+            cpu.memory.set_wait_states(0xFFFA..=0xFFFF, 1);
+            cpu.reset();
+
+            assert_eq!(6, cpu.step().unwrap());
+            assert_eq!(5, cpu.step().unwrap());
+            assert_eq!(3, cpu.step().unwrap());
+            cpu.nmi();
+            assert_eq!(9, cpu.step().unwrap());
+            assert_eq!(23, cpu.cycles());
+        }
+
+        fn record_cycles(cpu: &mut Cpu) -> ::std::rc::Rc<::std::cell::RefCell<Vec<Cycle>>> {
+            let cycles = ::std::rc::Rc::new(::std::cell::RefCell::new(Vec::new()));
+            let recorded = cycles.clone();
+            cpu.add_cycle_hook(move |cycle| recorded.borrow_mut().push(*cycle));
+
+            cycles
+        }
+
+        fn bus_cycle(access: MemoryAccess, addr: u16, value: u8) -> Option<BusCycle> {
+            Some(BusCycle { access, addr, value })
+        }
+
+        #[test]
+        fn calls_cycle_hooks_for_every_cycle_of_an_instruction() {
+            // JSR $D000
+            let code = vec![0x20, 0x00, 0xD0];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            let cycles = record_cycles(&mut cpu);
+
+            assert_eq!(Ok(6), cpu.step());
+
+            let bus: Vec<_> = cycles.borrow().iter().map(|cycle| cycle.bus).collect();
+            assert_eq!(vec![bus_cycle(MemoryAccess::Execute, 0xC000, 0x20),
+                            bus_cycle(MemoryAccess::Read, 0xC001, 0x00),
+                            bus_cycle(MemoryAccess::Read, 0xC002, 0xD0),
+                            None,
+                            bus_cycle(MemoryAccess::Write, 0x01FF, 0xC0),
+                            bus_cycle(MemoryAccess::Write, 0x01FE, 0x02)],
+                       bus);
+            assert!(cycles.borrow().iter().enumerate().all(|(index, cycle)| cycle.index as usize == index));
+        }
+
+        #[test]
+        fn read_modify_write_instructions_make_dummy_accesses() {
+            // LDX #$10, INC $20F8,X
+            let code = vec![0xA2, 0x10, 0xFE, 0xF8, 0x20];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.memory.write_byte(0x2108, 0x41);
+            cpu.reset();
+            cpu.step().unwrap();
+            let cycles = record_cycles(&mut cpu);
+
+            assert_eq!(Ok(7), cpu.step());
+
+            let bus: Vec<_> = cycles.borrow().iter().map(|cycle| cycle.bus).collect();
+            assert_eq!(vec![bus_cycle(MemoryAccess::Execute, 0xC002, 0xFE),
+                            bus_cycle(MemoryAccess::Read, 0xC003, 0xF8),
+                            bus_cycle(MemoryAccess::Read, 0xC004, 0x20),
+                            bus_cycle(MemoryAccess::Read, 0x2008, 0x00),
+                            bus_cycle(MemoryAccess::Read, 0x2108, 0x41),
+                            bus_cycle(MemoryAccess::Write, 0x2108, 0x41),
+                            bus_cycle(MemoryAccess::Write, 0x2108, 0x42)],
+                       bus);
+        }
+
+        #[test]
+        fn dispatch_matches_the_opcode_tables_for_every_byte() {
+            // The rules `step` followed when it compared mnemonics, before
+            // they were worked out once when the dispatch table is built,
+            // along with the 65C02's BIT abs,X
+            fn page_crossing_penalty(opcode: &OpCode) -> bool {
+                ["ADC", "AND", "BIT", "CMP", "EOR", "LAX", "LDA", "LDX", "LDY", "NOP", "ORA", "SBC"]
+                    .contains(&opcode.mnemonic) &&
+                [AddressingMode::AbsoluteX, AddressingMode::AbsoluteY, AddressingMode::IndirectY].contains(&opcode.mode)
+            }
+            fn indexed_dummy_read(opcode: &OpCode, variant: CpuVariant) -> bool {
+                ["ASL", "DCP", "DEC", "INC", "ISC", "LSR", "RLA", "ROL", "ROR", "RRA", "SLO", "SRE"]
+                    .contains(&opcode.mnemonic) && opcode.mode == AddressingMode::AbsoluteX &&
+                variant != CpuVariant::Cmos65C02
+            }
+
+            let variants = [CpuVariant::Nmos6502, CpuVariant::Cmos65C02, CpuVariant::Ricoh2A03];
+            let settings = [(false, false), (false, true), (true, false), (true, true)];
+
+            for &variant in &variants {
+                for &(illegal_opcodes, unofficial_nops) in &settings {
+                    for byte in 0..=0xFFu8 {
+                        let expected = OpCode::decode(byte, variant).filter(|opcode| {
+                            variant == CpuVariant::Cmos65C02 || illegal_opcodes || !opcode.is_undocumented() ||
+                            (unofficial_nops && opcode.mnemonic == "NOP")
+                        });
+
+                        // The operand $10FF, and a pointer at $FF to $10FF,
+                        // cross a page with an index of 1
+                        let mut cpu = Cpu::new();
+                        cpu.set_variant(variant);
+                        if illegal_opcodes {
+                            cpu.enable_illegal_opcodes();
+                        }
+                        if unofficial_nops {
+                            cpu.enable_unofficial_nops();
+                        }
+                        cpu.load(&[byte, 0xFF, 0x10][..], None).unwrap();
+                        cpu.memory.write_bytes(0x0000, &[0x10]);
+                        cpu.memory.write_bytes(0x00FF, &[0xFF]);
+                        cpu.reset();
+                        cpu.registers.X = 1;
+                        cpu.registers.Y = 1;
+                        let cycles = record_cycles(&mut cpu);
+
+                        let context = format!("{:02X} on {} with illegal opcodes {} and unofficial NOPs {}",
+                                              byte,
+                                              variant,
+                                              illegal_opcodes,
+                                              unofficial_nops);
+                        assert_eq!(expected.map(|opcode| opcode.code), cpu.decode(byte).map(|opcode| opcode.code),
+                                   "{}",
+                                   context);
+
+                        let opcode = match expected {
+                            Some(opcode) => opcode,
+                            None => {
+                                assert_eq!(Err(CpuError::unknown_opcode(0xC000, byte)), cpu.step(), "{}", context);
+                                continue;
+                            }
+                        };
+                        let result = cpu.step();
+                        if opcode.mode == AddressingMode::Relative || opcode.mnemonic.starts_with("BB") ||
+                           opcode.mnemonic == "KIL" {
+                            continue;
+                        }
+
+                        let penalty = if page_crossing_penalty(opcode) { 1 } else { 0 };
+                        assert_eq!(Ok(opcode.time + penalty), result, "{}", context);
+
+                        let unfixed_read = cycles.borrow().iter().any(|cycle| {
+                            cycle.bus.is_some_and(|bus| bus.access == MemoryAccess::Read && bus.addr == 0x1000)
+                        });
+                        if opcode.mode == AddressingMode::AbsoluteX {
+                            assert_eq!(indexed_dummy_read(opcode, variant), unfixed_read, "{}", context);
+                        }
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn calls_cycle_hooks_for_interrupts() {
+            let mut cpu = Cpu::new();
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+            cpu.reset();
+            let cycles = record_cycles(&mut cpu);
+
+            cpu.nmi();
+            assert_eq!(0, cycles.borrow().len());
+            assert_eq!(Ok(7), cpu.step());
+
+            let cycles = cycles.borrow();
+            assert_eq!(7, cycles.len());
+            assert!(cycles.iter().all(|cycle| cycle.pc == 0x0000));
+            assert_eq!(None, cycles[1].bus);
+            assert_eq!(bus_cycle(MemoryAccess::Write, 0x01FF, 0x00), cycles[2].bus);
+            assert_eq!(bus_cycle(MemoryAccess::Read, 0xFFFB, 0xD0), cycles[6].bus);
+        }
+
+        #[test]
+        fn random_device_is_reproducible_for_a_seed() {
+            // LDA $FE, STA $0200, LDA $FE, STA $0201
+            let code = vec![0xA5, 0xFE, 0x8D, 0x00, 0x02, 0xA5, 0xFE, 0x8D, 0x01, 0x02];
+            let run = |seed| {
+                let mut cpu = Cpu::new();
+                cpu.memory.map_device(0xFE..=0xFE, RandomDevice::new(seed));
+                cpu.load(&code[..], None);
+                cpu.reset();
+                cpu.step_n(4).unwrap();
+
+                (cpu.memory[0x0200], cpu.memory[0x0201])
+            };
+
+            assert_eq!(run(7), run(7));
+            assert!(run(7) != run(8));
+        }
+
+        #[test]
+        fn guest_can_persist_sectors_to_block_device() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            // Fill $0300 with $AB, then write it to sector 1 from $0300
+            let code = vec![0xA9, 0xAB, 0xA2, 0x00, 0x9D, 0x00, 0x03, 0xE8, 0xD0, 0xFA,
+                            0xA9, 0x01, 0x8D, 0x01, 0xD0, 0xA9, 0x00, 0x8D, 0x02, 0xD0,
+                            0x8D, 0x03, 0xD0, 0xA9, 0x03, 0x8D, 0x04, 0xD0,
+                            0xA9, 0x02, 0x8D, 0x00, 0xD0, 0xAD, 0x00, 0xD0];
+            let disk = Rc::new(RefCell::new(Vec::new()));
+            let mut cpu = Cpu::new();
+            cpu.memory.map_device(0xD000..=0xD004, BlockDevice::new(disk.clone()));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            cpu.step_n(2 + 256 * 3 + 10).unwrap();
+
+            assert_eq!(BLOCK_STATUS_OK, cpu.registers.A);
+            assert_eq!(2 * SECTOR_SIZE, disk.borrow().len());
+            assert_eq!(&[0xAB; SECTOR_SIZE][..], &disk.borrow()[SECTOR_SIZE..]);
+        }
+
+        #[test]
+        fn text_framebuffer_reports_guest_writes_once_per_frame() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            // LDA #$41, STA $0400, STA $0427, STA $07E7
+            let code = vec![0xA9, 0x41, 0x8D, 0x00, 0x04, 0x8D, 0x27, 0x04, 0x8D, 0xE7, 0x07];
+            let frames = Rc::new(RefCell::new(Vec::new()));
+            let handler_frames = frames.clone();
+            let mut cpu = Cpu::new();
+            cpu.memory.map_device(0x0400..=0x07E7,
+                                  TextFramebuffer::new(move |regions, cells| {
+                                      assert_eq!(TEXT_COLUMNS * TEXT_ROWS, cells.len());
+                                      handler_frames.borrow_mut().push(regions.to_vec());
+                                  }));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            cpu.step_n(4).unwrap();
+            cpu.memory.end_frame();
+            cpu.memory.end_frame();
+
+            assert_eq!(vec![vec![DirtyRegion { row: 0, start_column: 0, end_column: 39 },
+                                 DirtyRegion { row: 24, start_column: 39, end_column: 39 }]],
+                       *frames.borrow());
+            assert_eq!(0x41, cpu.memory.read_byte(0x0427));
+        }
+
+        #[test]
+        fn program_exits_at_trap_address_with_accumulator() {
+            // LDA #$07, JMP $C000 - the trap stops it before looping
+            let code = vec![0xA9, 0x07, 0x4C, 0x00, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.set_exit_condition(ExitCondition::Trap(0xC002));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(Some(0x07), cpu.run().unwrap());
+            assert_eq!(0xC002, cpu.registers.PC);
+            assert!(cpu.is_halted());
+        }
+
+        #[test]
+        fn program_exits_on_brk_marker_with_code_from_memory() {
+            // LDA #$2A, STA $10, BRK $01 exits, whereas BRK $00 would not
+            let code = vec![0xA9, 0x2A, 0x85, 0x10, 0x00, 0x01];
+            let mut cpu = Cpu::new();
+            cpu.set_exit_condition(ExitCondition::Brk(0x01));
+            cpu.set_exit_code_source(ExitCodeSource::Memory(0x10));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(Some(0x2A), cpu.run().unwrap());
+            assert_eq!(0xC004, cpu.registers.PC);
+        }
+
+        #[test]
+        fn exit_code_is_cleared_by_reset() {
+            // LDA #$03, STA $FFF0
+            let code = vec![0xA9, 0x03, 0x8D, 0xF0, 0xFF];
+            let mut cpu = Cpu::new();
+            cpu.set_exit_condition(ExitCondition::PortWrite(0xFFF0));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            cpu.step_n(10).unwrap();
+            assert_eq!(Some(0x03), cpu.exit_code());
+
+            cpu.reset();
+            assert_eq!(None, cpu.exit_code());
+        }
+
+        #[test]
+        fn step_n_reports_cycle_limit() {
+            // INX, JMP $C000
+            let code = vec![0xE8, 0x4C, 0x00, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.set_limits(RunLimits::new().max_cycles(10));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            let error = cpu.step_n(100).unwrap_err();
+
+            assert_eq!(&CpuErrorKind::LimitReached(Limit::Cycles), error.kind());
+            assert_eq!(0x02, cpu.registers.X);
+        }
+
+        #[test]
+        fn step_n_within_limits_is_unaffected() {
+            // INX, JMP $C000
+            let code = vec![0xE8, 0x4C, 0x00, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.set_limits(RunLimits::new().max_instructions(10));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(Ok(25), cpu.step_n(10));
+            assert_eq!(Ok(25), cpu.step_n(10));
+            assert_eq!(0x0A, cpu.registers.X);
+        }
+
+        #[test]
+        fn run_stops_at_wall_clock_deadline() {
+            use std::time::Duration;
+
+            // JMP $C000
+            let code = vec![0x4C, 0x00, 0xC0];
+            let mut cpu = Cpu::new();
+            cpu.set_limits(RunLimits::new().timeout(Duration::from_millis(10)));
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(&CpuErrorKind::LimitReached(Limit::Time), cpu.run().unwrap_err().kind());
+        }
+
+        #[test]
+        fn step_over_runs_nested_subroutines() {
+            // JSR $C004, BRK, JSR $C008, INX, RTS, INY, RTS
+            let code = vec![0x20, 0x04, 0xC0, 0x00, 0x20, 0x09, 0xC0, 0xE8, 0x60, 0xC8, 0x60];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(Ok(6 + 6 + 2 + 6 + 2 + 6), cpu.step_over());
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert_eq!(0x01, cpu.registers.X);
+            assert_eq!(0x01, cpu.registers.Y);
+            assert_eq!(0xFF, cpu.registers.S);
+        }
+
+        #[test]
+        fn step_over_single_steps_other_instructions() {
+            // INX, INX
+            let code = vec![0xE8, 0xE8];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            assert_eq!(Ok(2), cpu.step_over());
+            assert_eq!(0xC001, cpu.registers.PC);
+        }
+
+        #[test]
+        fn step_out_ignores_returns_from_nested_calls() {
+            // JSR $C004, BRK, PHA, JSR $C00A, PLA, RTS, INY, RTS
+            let code = vec![0x20, 0x04, 0xC0, 0x00, 0x48, 0x20, 0x0A, 0xC0, 0x68, 0x60, 0xC8,
+                            0x60];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            cpu.step_out().unwrap();
+
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert_eq!(0x01, cpu.registers.Y);
+            assert_eq!(0xFF, cpu.registers.S);
+        }
+
+        #[test]
+        fn run_to_stops_at_an_earlier_breakpoint_and_keeps_it() {
+            // INX, INX, INX, INX
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xE8, 0xE8, 0xE8, 0xE8], None);
+            cpu.reset();
+            cpu.add_breakpoint(0xC001);
+
+            assert_eq!(Ok(StopReason::Breakpoint(0xC001)), cpu.run_to(0xC003));
+            assert_eq!(Some(StopReason::Breakpoint(0xC001)), cpu.halted());
+            assert_eq!(vec![0xC001], cpu.breakpoints());
+
+            cpu.resume();
+            assert_eq!(Ok(StopReason::Breakpoint(0xC003)), cpu.run_to(0xC003));
+            assert_eq!(0x03, cpu.registers.X);
+            assert_eq!(None, cpu.halted());
+        }
+
+        #[test]
+        fn run_to_goes_round_a_loop_and_removes_the_cursor_at_a_limit() {
+            // INX, JMP $C000
+            let mut cpu = Cpu::new();
+            cpu.load(&[0xE8, 0x4C, 0x00, 0xC0], None);
+            cpu.reset();
+
+            assert_eq!(Ok(StopReason::Breakpoint(0xC000)), cpu.run_to(0xC000));
+            assert_eq!(0x01, cpu.registers.X);
+
+            cpu.set_limits(RunLimits::new().max_instructions(10));
+            let error = cpu.run_to(0xD000).unwrap_err();
+
+            assert_eq!(&CpuErrorKind::LimitReached(Limit::Instructions), error.kind());
+            assert!(cpu.breakpoints().is_empty());
+        }
+
+        #[test]
+        fn custom_bus_sees_stack_and_vector_accesses() {
+            use std::cell::RefCell;
+            use std::rc::Rc;
+
+            struct LoggingBus {
+                ram: Vec<u8>,
+                writes: Rc<RefCell<Vec<u16>>>,
+            }
+
+            impl Bus for LoggingBus {
+                fn read_byte(&self, addr: u16) -> u8 {
+                    self.ram[addr as usize]
+                }
+
+                fn write_byte(&mut self, addr: u16, byte: u8) {
+                    self.writes.borrow_mut().push(addr);
+                    self.ram[addr as usize] = byte;
+                }
+            }
+
+            let writes = Rc::new(RefCell::new(Vec::new()));
+            let mut cpu = Cpu::with_bus(LoggingBus {
+                ram: vec![0; 0x10000],
+                writes: writes.clone(),
+            });
+            // JSR $C004, BRK, RTS
+            cpu.load(&[0x20, 0x04, 0xC0, 0x00, 0x60], None).unwrap();
+            writes.borrow_mut().clear();
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+
+            assert_eq!(vec![0x01FF, 0x01FE], *writes.borrow());
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert_eq!(0xC0, cpu.memory.ram[0x01FF]);
+        }
+
+        #[test]
+        fn indexed_reads_take_a_cycle_longer_across_pages() {
+            // LDX #$01, LDA $20FF,X, LDA $2000,X, STA $20FF,X
+            let code = vec![0xA2, 0x01, 0xBD, 0xFF, 0x20, 0xBD, 0x00, 0x20, 0x9D, 0xFF, 0x20];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.step();
+
+            assert_eq!(Ok(5), cpu.step());
+            assert_eq!(Ok(4), cpu.step());
+            assert_eq!(Ok(5), cpu.step());
+        }
+
+        #[test]
+        fn indirect_indexed_reads_take_a_cycle_longer_across_pages() {
+            // LDY #$10, LDA ($20),Y
+            let code = vec![0xA0, 0x10, 0xB1, 0x20];
+            let mut cpu = Cpu::new();
+            cpu.memory.write_bytes(0x20, &[0xF8, 0x30]);
+            cpu.load(&code[..], None);
+            cpu.reset();
+            cpu.step();
+
+            assert_eq!(Ok(6), cpu.step());
+        }
+
+        #[test]
+        fn taken_branches_cost_extra_cycles() {
+            // BCC +0, BCS +2, BCC to the next page
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x90, 0x00, 0xB0, 0x02], None);
+            cpu.load(&[0x90, 0x02], 0xC1FC);
+            cpu.reset();
+
+            assert_eq!(Ok(3), cpu.step());
+            assert_eq!(Ok(2), cpu.step());
+
+            cpu.registers.PC = 0xC1FC;
+            assert_eq!(Ok(4), cpu.step());
+            assert_eq!(0xC200, cpu.registers.PC);
+        }
+
+        #[test]
+        fn power_on_reset_boots_from_the_reset_vector() {
+            let mut cpu = Cpu::new();
+            // LDA #$42, BRK
+            cpu.memory.write_bytes(0xE000, &[0xA9, 0x42, 0x00]);
+            cpu.memory.write_bytes(0xFFFC, &[0x00, 0xE0]);
+            cpu.registers.X = 0x33;
+            cpu.registers.S = 0xFD;
+
+            assert_eq!(7, cpu.power_on_reset());
+            cpu.step().unwrap();
+
+            assert_eq!(0x42, cpu.registers.A);
+            assert_eq!(0x33, cpu.registers.X);
+            assert_eq!(0xFA, cpu.registers.S);
+            assert!(cpu.flags.interrupt_disabled);
+        }
+
+        #[test]
+        fn cpu_over_a_memory_image_boots_from_its_reset_vector() {
+            let mut image = [0x00; 0x10000];
+            // INX, JMP $0800
+            image[0x0800..0x0804].copy_from_slice(&[0xE8, 0x4C, 0x00, 0x08]);
+            image[0xFFFC] = 0x00;
+            image[0xFFFD] = 0x08;
+
+            let mut cpu = Cpu::with_memory(&image);
+            cpu.power_on_reset();
+            cpu.step_n(4).unwrap();
+
+            assert_eq!(0x02, cpu.registers.X);
+            assert_eq!(0x0800, cpu.registers.PC);
+        }
+
+        #[test]
+        fn power_on_reset_wraps_the_stack_pointer() {
+            let mut cpu = Cpu::new();
+            cpu.registers.S = 0x01;
+
+            cpu.power_on_reset();
+
+            assert_eq!(0xFE, cpu.registers.S);
+        }
+
+        #[test]
+        fn brk_enters_the_irq_handler_even_when_interrupts_are_disabled() {
+            let mut cpu = Cpu::new();
+            // BRK, a padding byte, then INX where the handler returns to
+            cpu.load(&[0x00, 0xFF, 0xE8], None).unwrap();
+            // RTI
+            cpu.load(&[0x40], 0x2000).unwrap();
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0x20]);
+            cpu.reset();
+            cpu.flags.interrupt_disabled = true;
+
+            cpu.step().unwrap();
+
+            assert_eq!(0x2000, cpu.registers.PC);
+            assert!(cpu.flags.interrupt_disabled);
+            assert_eq!(0xC0, cpu.memory.read_byte(0x01FF));
+            assert_eq!(0x02, cpu.memory.read_byte(0x01FE));
+            assert_eq!(0x10, cpu.memory.read_byte(0x01FD) & 0x10);
+
+            cpu.step_n(2).unwrap();
+
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert_eq!(0x01, cpu.registers.X);
+        }
+
+        #[test]
+        fn jsr_pushes_the_address_of_its_last_byte() {
+            let mut cpu = Cpu::new();
+            // JSR $C004, BRK, RTS
+            cpu.load(&[0x20, 0x04, 0xC0, 0x00, 0x60], None).unwrap();
+            cpu.reset();
+
+            cpu.step().unwrap();
+
+            assert_eq!(0xC0, cpu.memory.read_byte(0x01FF));
+            assert_eq!(0x02, cpu.memory.read_byte(0x01FE));
+
+            cpu.step().unwrap();
+
+            assert_eq!(0xC003, cpu.registers.PC);
+        }
+
+        #[test]
+        fn rts_jumps_to_one_past_a_pushed_address() {
+            let mut cpu = Cpu::new();
+            // The "RTS trick": LDA #$C0, PHA, LDA #$07, PHA, RTS, BRK, BRK, INX
+            cpu.load(&[0xA9, 0xC0, 0x48, 0xA9, 0x07, 0x48, 0x60, 0x00, 0xE8], None).unwrap();
+            cpu.reset();
+
+            cpu.step_n(6).unwrap();
+
+            assert_eq!(0x01, cpu.registers.X);
+        }
+
+        #[test]
+        fn tsx_and_txs_transfer_the_stack_pointer() {
+            let mut cpu = Cpu::new();
+            // TSX, LDX #$00, TXS, PHA, TSX
+            cpu.load(&[0xBA, 0xA2, 0x00, 0x9A, 0x48, 0xBA], None).unwrap();
+            cpu.reset();
+
+            cpu.step().unwrap();
+
+            assert_eq!(0xFF, cpu.registers.X);
+            assert!(cpu.flags.sign);
+
+            cpu.step_n(4).unwrap();
+
+            // Pushing with the pointer at $00 wraps to the top of the page
+            assert_eq!(0xFF, cpu.registers.S);
+            assert_eq!(0xFF, cpu.registers.X);
+            assert_eq!(0x00, cpu.registers.A);
+        }
+
+        #[test]
+        fn illegal_opcodes_are_unknown_unless_enabled() {
+            let mut cpu = Cpu::new();
+            // LAX $10
+            cpu.load(&[0xA7, 0x10], None).unwrap();
+            cpu.memory.write_byte(0x10, 0x8F);
+            cpu.reset();
+
+            assert_eq!(Err(CpuError::unknown_opcode(0xC000, 0xA7)), cpu.step());
+
+            cpu.enable_illegal_opcodes();
+
+            assert_eq!(Ok(3), cpu.step());
+            assert_eq!(0x8F, cpu.registers.A);
+            assert_eq!(0x8F, cpu.registers.X);
+            assert!(cpu.flags.sign);
+        }
+
+        #[test]
+        fn sax_stores_a_and_x() {
+            let mut cpu = Cpu::new();
+            cpu.enable_illegal_opcodes();
+            // LDA #$F0, LDX #$8F, SAX $20
+            cpu.load(&[0xA9, 0xF0, 0xA2, 0x8F, 0x87, 0x20], None).unwrap();
+            cpu.reset();
+
+            cpu.step_n(3).unwrap();
+
+            assert_eq!(0x80, cpu.memory.read_byte(0x20));
+            assert_eq!(0xF0, cpu.registers.A);
+            assert_eq!(0x8F, cpu.registers.X);
+        }
+
+        #[test]
+        fn illegal_read_modify_write_opcodes() {
+            // Each program modifies $10 and then combines it with A
+            let programs: Vec<(Vec<u8>, u8, u8, u8, bool)> = vec![
+                // LDA #$40, DCP $10
+                (vec![0xA9, 0x40, 0xC7, 0x10], 0x41, 0x40, 0x40, true),
+                // SEC, LDA #$10, ISC $10
+                (vec![0x38, 0xA9, 0x10, 0xE7, 0x10], 0x04, 0x05, 0x0B, true),
+                // LDA #$01, SLO $10
+                (vec![0xA9, 0x01, 0x07, 0x10], 0x81, 0x02, 0x03, true),
+                // SEC, LDA #$FF, RLA $10
+                (vec![0x38, 0xA9, 0xFF, 0x27, 0x10], 0x40, 0x81, 0x81, false),
+                // LDA #$0F, SRE $10
+                (vec![0xA9, 0x0F, 0x47, 0x10], 0x03, 0x01, 0x0E, true),
+                // CLC, LDA #$10, RRA $10
+                (vec![0x18, 0xA9, 0x10, 0x67, 0x10], 0x03, 0x01, 0x12, false),
+            ];
+
+            for (code, original, memory, a, carry) in programs {
+                let mut cpu = Cpu::new();
+                cpu.enable_illegal_opcodes();
+                cpu.load(&code[..], None).unwrap();
+                cpu.memory.write_byte(0x10, original);
+                cpu.reset();
+
+                let mut cycles = 0;
+                while cpu.registers.PC < 0xC000 + code.len() as u16 {
+                    cycles = cpu.step().unwrap();
+                }
+
+                assert_eq!(5, cycles);
+                assert_eq!(memory, cpu.memory.read_byte(0x10));
+                assert_eq!(a, cpu.registers.A);
+                assert_eq!(carry, cpu.flags.carry);
+            }
+        }
+
+        #[test]
+        fn illegal_immediate_opcodes() {
+            let mut cpu = Cpu::new();
+            cpu.enable_illegal_opcodes();
+            // LDA #$F0, ANC #$80, LDA #$FF, ALR #$03
+            cpu.load(&[0xA9, 0xF0, 0x0B, 0x80, 0xA9, 0xFF, 0x4B, 0x03], None).unwrap();
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(0x80, cpu.registers.A);
+            assert!(cpu.flags.carry);
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(0x01, cpu.registers.A);
+            assert!(cpu.flags.carry);
+
+            let mut cpu = Cpu::new();
+            cpu.enable_illegal_opcodes();
+            // CLC, LDA #$FF, ARR #$80, LDX #$F3, LDA #$0F, AXS #$02
+            cpu.load(&[0x18, 0xA9, 0xFF, 0x6B, 0x80, 0xA2, 0xF3, 0xA9, 0x0F, 0xCB, 0x02], None).unwrap();
+            cpu.reset();
+
+            cpu.step_n(3).unwrap();
+            assert_eq!(0x40, cpu.registers.A);
+            assert!(cpu.flags.carry);
+            assert!(cpu.flags.overflow);
+
+            cpu.step_n(3).unwrap();
+            assert_eq!(0x01, cpu.registers.X);
+            assert!(cpu.flags.carry);
+        }
+
+        #[test]
+        fn illegal_nops_skip_their_operands() {
+            let mut cpu = Cpu::new();
+            cpu.enable_illegal_opcodes();
+            // LDX #$01, NOP $10, NOP, NOP #$FF, NOP $C0FF,X, INX
+            cpu.load(&[0xA2, 0x01, 0x04, 0x10, 0x1A, 0x80, 0xFF, 0x1C, 0xFF, 0xC0, 0xE8], None).unwrap();
+            cpu.reset();
+
+            let cycles: Vec<u8> = cpu.steps().take(6).map(|step| step.cycles).collect();
+
+            // NOP abs,X takes an extra cycle when it crosses a page
+            assert_eq!(vec![2, 3, 2, 2, 5, 2], cycles);
+            assert_eq!(0x02, cpu.registers.X);
+        }
+
+        #[test]
+        fn kil_jams_the_cpu() {
+            let mut cpu = Cpu::new();
+            cpu.enable_illegal_opcodes();
+            // INX, KIL, INX
+            cpu.load(&[0xE8, 0x12, 0xE8], None).unwrap();
+            cpu.reset();
+
+            let count = cpu.steps().count();
+
+            assert_eq!(2, count);
+            assert_eq!(Some(StopReason::Jammed { pc: 0xC001, opcode: 0x12 }), cpu.halted());
+            assert_eq!(None, cpu.exit_code());
+            assert_eq!(0x01, cpu.registers.X);
+            assert_eq!(0xC001, cpu.registers.PC);
+
+            cpu.reset();
+            assert_eq!(None, cpu.halted());
+        }
+
+        #[test]
+        fn unofficial_nops_run_without_the_other_illegal_opcodes() {
+            // LDX #$01, NOP #$FF, NOP $44FF,X, NOP $10,X, LAX $10
+            let code = vec![0xA2, 0x01, 0x80, 0xFF, 0x1C, 0xFF, 0x44, 0x14, 0x10, 0xA7, 0x10];
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None).unwrap();
+            cpu.reset();
+            cpu.enable_unofficial_nops();
+
+            cpu.step().unwrap();
+            assert_eq!(Ok(2), cpu.step());
+            assert_eq!(Ok(5), cpu.step());
+            assert_eq!(Ok(4), cpu.step());
+            assert_eq!(0xC009, cpu.registers.PC);
+            assert_eq!(Err(CpuError::unknown_opcode(0xC009, 0xA7)), cpu.step());
+
+            cpu.disable_unofficial_nops();
+            cpu.reset();
+            cpu.step().unwrap();
+            assert_eq!(Err(CpuError::unknown_opcode(0xC002, 0x80)), cpu.step());
+        }
+
+        #[test]
+        fn cmos_opcodes_depend_on_the_variant() {
+            // STZ $10, JMP ($0020)
+            let code = vec![0x64, 0x10, 0x6C, 0x20, 0x00];
+
+            let mut cpu = Cpu::new();
+            cpu.load(&code[..], None).unwrap();
+            cpu.reset();
+
+            assert_eq!(Err(CpuError::unknown_opcode(0xC000, 0x64)), cpu.step());
+
+            let mut cpu = Cpu::new_65c02();
+            cpu.load(&code[..], None).unwrap();
+            cpu.memory.write_bytes(0x10, &[0xFF]);
+            cpu.memory.write_bytes(0x20, &[0x00, 0xD0]);
+            cpu.reset();
+
+            assert_eq!(Ok(3), cpu.step());
+            assert_eq!(0x00, cpu.memory.read_byte(0x10));
+            // JMP ($0000) takes an extra cycle on the 65C02
+            assert_eq!(Ok(6), cpu.step());
+            assert_eq!(0xD000, cpu.registers.PC);
+        }
+
+        #[test]
+        fn tsb_and_trb_set_the_zero_flag_from_a_and_memory() {
+            let mut cpu = Cpu::new_65c02();
+            // LDA #$0C, TSB $10, TRB $10
+            cpu.load(&[0xA9, 0x0C, 0x04, 0x10, 0x14, 0x10], None).unwrap();
+            cpu.memory.write_bytes(0x10, &[0x03]);
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(0x0F, cpu.memory.read_byte(0x10));
+            assert!(cpu.flags.zero);
+
+            cpu.step().unwrap();
+            assert_eq!(0x03, cpu.memory.read_byte(0x10));
+            assert!(!cpu.flags.zero);
+        }
+
+        #[test]
+        fn inc_and_dec_work_on_the_accumulator_on_the_65c02() {
+            let mut cpu = Cpu::new_65c02();
+            // LDA #$FF, INC A, DEC A
+            cpu.load(&[0xA9, 0xFF, 0x1A, 0x3A], None).unwrap();
+            cpu.reset();
+
+            cpu.step().unwrap();
+            assert_eq!(Ok(2), cpu.step());
+            assert_eq!(0x00, cpu.registers.A);
+            assert!(cpu.flags.zero);
+
+            assert_eq!(Ok(2), cpu.step());
+            assert_eq!(0xFF, cpu.registers.A);
+            assert!(cpu.flags.sign);
+            assert!(!cpu.flags.zero);
+        }
+
+        #[test]
+        fn bit_immediate_only_sets_the_zero_flag() {
+            let mut cpu = Cpu::new_65c02();
+            // LDA #$01, BIT #$C0, BIT #$C1
+            cpu.load(&[0xA9, 0x01, 0x89, 0xC0, 0x89, 0xC1], None).unwrap();
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            assert!(cpu.flags.zero);
+            assert!(!cpu.flags.sign);
+            assert!(!cpu.flags.overflow);
+
+            cpu.step().unwrap();
+            assert!(!cpu.flags.zero);
+        }
+
+        #[test]
+        fn bit_indexed_reads_from_the_indexed_address() {
+            let mut cpu = Cpu::new_65c02();
+            // LDX #$02, LDA #$01, BIT $10,X, BIT $20FF,X
+            cpu.load(&[0xA2, 0x02, 0xA9, 0x01, 0x34, 0x10, 0x3C, 0xFF, 0x20], None).unwrap();
+            cpu.memory.write_bytes(0x12, &[0xC1]);
+            cpu.memory.write_bytes(0x2101, &[0x40]);
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            assert_eq!(Ok(4), cpu.step());
+            assert!(cpu.flags.sign);
+            assert!(cpu.flags.overflow);
+            assert!(!cpu.flags.zero);
+
+            // Crossing a page costs a cycle
+            assert_eq!(Ok(5), cpu.step());
+            assert!(!cpu.flags.sign);
+            assert!(cpu.flags.overflow);
+            assert!(cpu.flags.zero);
+        }
+
+        #[test]
+        fn jmp_absolute_indexed_indirect_jumps_through_a_table() {
+            let mut cpu = Cpu::new_65c02();
+            // LDX #$04, JMP ($0200,X)
+            cpu.load(&[0xA2, 0x04, 0x7C, 0x00, 0x02], None).unwrap();
+            cpu.memory.write_bytes(0x0204, &[0x34, 0x12]);
+            cpu.reset();
+
+            cpu.step().unwrap();
+            assert_eq!(Ok(6), cpu.step());
+            assert_eq!(0x1234, cpu.registers.PC);
+        }
+
+        #[test]
+        fn undefined_65c02_opcodes_are_nops() {
+            let mut cpu = Cpu::new_65c02();
+            // $03, $02 $FF, $5C $00 $00, $DC $00 $00
+            cpu.load(&[0x03, 0x02, 0xFF, 0x5C, 0x00, 0x00, 0xDC, 0x00, 0x00], None).unwrap();
+            cpu.reset();
+
+            assert_eq!(Ok(1), cpu.step());
+            assert_eq!(0xC001, cpu.registers.PC);
+            assert_eq!(Ok(2), cpu.step());
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert_eq!(Ok(8), cpu.step());
+            assert_eq!(0xC006, cpu.registers.PC);
+            assert_eq!(Ok(4), cpu.step());
+            assert_eq!(0xC009, cpu.registers.PC);
+        }
+
+        #[test]
+        fn nmi_during_irq_handler_returns_through_both() {
+            let mut cpu = Cpu::new();
+            // CLI, SEC, NOP
+            cpu.load(&[0x58, 0x38, 0xEA], None).unwrap();
+            // IRQ handler: CLC, RTI. NMI handler: LDX #$07, RTI
+            cpu.memory.write_bytes(0xD000, &[0x18, 0x40]);
+            cpu.memory.write_bytes(0xE000, &[0xA2, 0x07, 0x40]);
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xE0]);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            cpu.irq();
+
+            // The pushed status has bit 5 set and B clear
+            assert_eq!(0xD000, cpu.registers.PC);
+            assert_eq!(&[0x21, 0x02, 0xC0], &cpu.memory[0x01FD..0x0200]);
+            assert!(cpu.flags.interrupt_disabled);
+
+            cpu.step().unwrap();
+            cpu.nmi();
+
+            assert_eq!(Ok(7), cpu.step());
+            assert_eq!(0xE000, cpu.registers.PC);
+            assert_eq!(&[0x24, 0x01, 0xD0], &cpu.memory[0x01FA..0x01FD]);
+
+            cpu.step_n(2).unwrap();
+
+            assert_eq!(0xD001, cpu.registers.PC);
+            assert_eq!(0x07, cpu.registers.X);
+            assert!(cpu.flags.interrupt_disabled);
+            assert!(!cpu.flags.carry);
+
+            cpu.step().unwrap();
+
+            assert_eq!(0xC002, cpu.registers.PC);
+            assert_eq!(0xFF, cpu.registers.S);
+            assert!(!cpu.flags.interrupt_disabled);
+            assert!(cpu.flags.carry);
+        }
+
+        #[test]
+        fn nmi_signalled_during_an_instruction_is_taken_after_it() {
+            let mut cpu = Cpu::new();
+            // CLI, host call $01, NOP
+            cpu.load(&[0x58, 0x02, 0x01, 0xEA], None).unwrap();
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xE0]);
+            cpu.memory.write_bytes(0xFFFE, &[0x00, 0xD0]);
+            cpu.enable_host_calls(0x02);
+            cpu.register_host_call(0x01, |cpu| cpu.nmi());
+            cpu.reset();
+
+            cpu.step_n(2).unwrap();
+            cpu.assert_irq();
+
+            assert_eq!(0xC003, cpu.registers.PC);
+            assert!(cpu.nmi_pending());
+
+            // The NMI is taken before the IRQ
+            assert_eq!(Ok(7), cpu.step());
+            assert_eq!(0xE000, cpu.registers.PC);
+            assert!(!cpu.nmi_pending());
+        }
+
+        #[test]
+        fn held_nmi_line_is_only_taken_once() {
+            let mut cpu = Cpu::new();
+            // NOP, NOP, NOP, with an NMI handler of RTI
+            cpu.load(&[0xEA, 0xEA, 0xEA], None).unwrap();
+            cpu.memory.write_bytes(0xE000, &[0x40]);
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xE0]);
+            cpu.reset();
+
+            cpu.assert_nmi();
+            cpu.step_n(2).unwrap();
+            cpu.assert_nmi();
+            cpu.step().unwrap();
+
+            assert_eq!(0xC001, cpu.registers.PC);
+
+            cpu.release_nmi();
+            cpu.assert_nmi();
+            cpu.step().unwrap();
+
+            assert_eq!(0xE000, cpu.registers.PC);
+        }
+
+        #[test]
+        fn interrupts_jump_through_their_vectors_even_when_zero() {
+            let mut cpu = Cpu::new();
+            cpu.load(&[0x58], None).unwrap();
+            cpu.reset();
+
+            cpu.step().unwrap();
+            cpu.irq();
+
+            assert_eq!(0x0000, cpu.registers.PC);
+            assert_eq!(0xFC, cpu.registers.S);
+        }
+
+        #[test]
+        fn the_65c02_clears_decimal_mode_on_interrupts() {
+            let mut cpu = Cpu::new_65c02();
+            // SED
+            cpu.load(&[0xF8], None).unwrap();
+            cpu.memory.write_bytes(0xFFFA, &[0x00, 0xD0]);
+            cpu.reset();
+
+            cpu.step().unwrap();
+            cpu.nmi();
+            cpu.step().unwrap();
+
+            assert!(!cpu.flags.decimal);
+            assert_eq!(0x2C, cpu.memory.read_byte(0x01FD));
+        }
    }
-}
\ No newline at end of file
+}
@@ -1,3 +1,4 @@
+#![allow(non_snake_case, unused_must_use, clippy::bool_assert_comparison)]
 
 extern crate rs6502;
 
@@ -894,8 +895,8 @@ fn INTEGRATION_CPU_cant_interrupt_when_disabled() {
     // Attempt an interrupt
     cpu.irq();
 
-    // Execute the rest:
-    cpu.step_n(50);
+    // Execute the rest, stopping short of the BRK that follows it
+    cpu.step_n(3);
 
     assert_eq!(0x30, cpu.registers.A);
 }
@@ -941,4 +942,213 @@ fn INTEGRATION_CPU_can_interrupt_when_not_disabled() {
     cpu.step_n(50);
 
     assert_eq!(0xA0, cpu.registers.A);
-}
\ No newline at end of file
+}
+
+#[test]
+fn INTEGRATION_CPU_asm_test_runs_subroutines_to_brk() {
+    use rs6502::testing::AsmTest;
+
+    let asm = "
+        LDA #$05
+        JSR DOUBLE
+        STA $0200
+        BRK
+
+    DOUBLE
+        ASL
+        RTS
+    ";
+
+    AsmTest::new(asm)
+        .run()
+        .assert_a(0x0A)
+        .assert_memory(0x0200, &[0x0A])
+        .assert_cycles(2 + 6 + 2 + 6 + 4);
+}
+
+#[test]
+fn INTEGRATION_CPU_execute_runs_program_at_origin() {
+    let asm = "
+        JSR STORE
+        BRK
+    STORE
+        LDA #$42
+        STA $0200
+        RTS
+    ";
+
+    let cpu = rs6502::Cpu::execute(asm, 0x2000).unwrap();
+
+    assert_eq!(0x42, cpu.memory[0x0200]);
+    assert_eq!(0x2003, cpu.registers.PC);
+}
+
+#[test]
+fn INTEGRATION_CPU_execute_reports_assembler_and_limit_errors() {
+    match rs6502::Cpu::execute("JMP MISSING", None) {
+        Err(rs6502::ExecuteError::Assembler(_)) => {}
+        _ => panic!("expected an assembler error"),
+    }
+
+    match rs6502::Cpu::execute("LOOP\nJMP LOOP", None) {
+        Err(rs6502::ExecuteError::Cpu(error)) => {
+            assert_eq!(&rs6502::CpuErrorKind::LimitReached(rs6502::Limit::Instructions), error.kind())
+        }
+        _ => panic!("expected a limit error"),
+    }
+}
+
+#[test]
+fn INTEGRATION_CPU_std_memcpy_copies_whole_and_partial_pages() {
+    use rs6502::testing::AsmTest;
+
+    let asm = "
+        JMP MAIN
+    .INCLUDE <std/memcpy.inc>
+    MAIN
+        LDA #$00
+        STA MEMCPY_SRC
+        LDA #$30
+        STA MEMCPY_SRC_HI
+        LDA #$00
+        STA MEMCPY_DST
+        LDA #$40
+        STA MEMCPY_DST_HI
+        LDA #$02
+        STA MEMCPY_LEN
+        LDA #$01
+        STA MEMCPY_LEN_HI
+        JSR MEMCPY
+        BRK
+    ";
+
+    let source: Vec<u8> = (0..0x103).map(|i| i as u8 ^ 0x5A).collect();
+    let mut expected = source[..0x102].to_vec();
+    expected.push(0x00);
+
+    AsmTest::new(asm)
+        .memory(0x3000, &source)
+        .run()
+        .assert_memory(0x4000, &expected);
+}
+
+#[test]
+fn INTEGRATION_CPU_std_memset_fills_memory() {
+    use rs6502::testing::AsmTest;
+
+    let asm = "
+        JMP MAIN
+    .INCLUDE <std/memset.inc>
+    MAIN
+        LDA #$00
+        STA MEMSET_DST
+        LDA #$40
+        STA MEMSET_DST_HI
+        LDA #$05
+        STA MEMSET_LEN
+        LDA #$00
+        STA MEMSET_LEN_HI
+        LDA #$AA
+        JSR MEMSET
+        BRK
+    ";
+
+    AsmTest::new(asm)
+        .run()
+        .assert_memory(0x4000, &[0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0x00]);
+}
+
+#[test]
+fn INTEGRATION_CPU_std_math16_adds_subtracts_and_compares() {
+    use rs6502::testing::AsmTest;
+
+    let asm = "
+        JMP MAIN
+    .INCLUDE <std/math16.inc>
+    MAIN
+        LDA #$F0
+        STA MATH16_A
+        LDA #$12
+        STA MATH16_A_HI
+        LDA #$20
+        STA MATH16_B
+        LDA #$01
+        STA MATH16_B_HI
+        JSR ADD16
+        JSR CMP16
+        PHP
+        JSR SUB16
+        JSR SUB16
+        BRK
+    ";
+
+    // $12F0 + $0120 = $1410, - $0120 - $0120 = $11D0
+    let result = AsmTest::new(asm).run();
+    result.assert_memory(0x00FA, &[0xD0, 0x11]);
+
+    // $1410 compared with $0120 sets the carry but not zero
+    assert_eq!(0x01, result.cpu.memory[0x01FF] & 0x03);
+}
+
+#[test]
+fn INTEGRATION_CPU_std_bcd_converts_both_ways() {
+    use rs6502::testing::AsmTest;
+
+    let asm = "
+        JMP MAIN
+    .INCLUDE <std/bcd.inc>
+    MAIN
+        LDA #$4B
+        JSR BIN_TO_BCD
+        STA $0200
+        JSR BCD_TO_BIN
+        STA $0201
+        BRK
+    ";
+
+    AsmTest::new(asm)
+        .run()
+        .assert_memory(0x0200, &[0x75, 0x4B]);
+}
+
+#[test]
+fn INTEGRATION_CPU_runs_65c02_instructions() {
+    let asm = "
+        PTR = $10
+            LDA #$00
+            STA PTR
+            LDA #$02
+            STA $11
+            LDA #$FF
+            STA $0200
+            STA $0201
+            STZ $0200
+            LDA #$0F
+            TRB $0201
+            LDX #$03
+        LOOP
+            PHX
+            DEX
+            BNE LOOP
+            PLY
+            LDA (PTR)
+            BRA DONE
+            LDA #$AA
+        DONE
+            BRK
+    ";
+
+    let mut cpu = rs6502::Cpu::new_65c02();
+    let mut assembler = rs6502::Assembler::new();
+
+    let segments = assembler.assemble_string(asm, None).unwrap();
+    cpu.load(&segments[0].code[..], None);
+    cpu.reset();
+
+    cpu.step_n(23);
+
+    assert_eq!(0x00, cpu.memory[0x0200]);
+    assert_eq!(0xF0, cpu.memory[0x0201]);
+    assert_eq!(0x01, cpu.registers.Y);
+    assert_eq!(0x00, cpu.registers.A);
+}